@@ -1,7 +1,7 @@
 use anyhow::Context;
 use axum::{
     Router,
-    extract::{Json, Path, Request, State},
+    extract::{Json, Path, Query, Request, State},
     http::{StatusCode, header::AUTHORIZATION},
     middleware::{self, Next},
     response::{IntoResponse, Response},
@@ -10,12 +10,14 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use ssp::circuit::{Circuit, Record, ViewDelta, Change, ChangeSet, Operation, SubqueryOp};
 use ssp::circuit::view::OutputFormat;
+use ssp::permissions::{Permissions, SelectRule};
+use ssp::types::{Sp00kyValue, WireType};
 use surrealdb::engine::remote::ws::{Client, Ws};
 use surrealdb::opt::auth::Root;
 use surrealdb::types::RecordId;
@@ -67,8 +69,13 @@ pub struct AppState {
     pub scheduler_url: Option<String>,
     pub start_time: std::time::Instant,
     pub crdt_cache: Arc<crdt::CrdtCache>,
+    /// Declared field types JSON flattens, by table; read at bootstrap.
+    pub field_types: Arc<RwLock<FieldTypes>>,
 }
 
+/// Fields declared with a `WireType`, by table.
+pub type FieldTypes = HashMap<String, HashMap<String, WireType>>;
+
 // --- Request/Response DTOs ---
 
 #[derive(Deserialize, Debug)]
@@ -96,6 +103,8 @@ pub struct Config {
     pub heartbeat_interval_ms: u64,
     pub advertise_addr: Option<String>,
     pub ttl_cleanup_interval_secs: u64,
+    /// How often the circuit clock (`time::now()`) advances.
+    pub clock_tick_interval_ms: u64,
 }
 
 pub fn load_config() -> Config {
@@ -118,6 +127,10 @@ pub fn load_config() -> Config {
             .ok()
             .and_then(|s| s.parse().ok())
             .unwrap_or(60),
+        clock_tick_interval_ms: std::env::var("CLOCK_TICK_INTERVAL_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1000),
     }
 }
 
@@ -199,7 +212,7 @@ impl BootstrapSource {
                     .with_context(|| format!("Query failed: {}", surql))?;
                 let val: surrealdb::types::Value = response.take(0)
                     .context("Failed to parse query response")?;
-                Ok(serde_json::to_value(&val).unwrap_or_default())
+                Ok(val.into_json_value())
             }
             BootstrapSource::Proxy { client, proxy_url } => {
                 let url = format!("{}/query", proxy_url);
@@ -220,6 +233,66 @@ impl BootstrapSource {
             }
         }
     }
+
+    /// Rows returned by `surql`, keeping SurrealDB's types: read directly
+    /// when connected to SurrealDB, recovered from the JSON and the
+    /// declared `fields` types otherwise.
+    async fn query_rows(
+        &self,
+        surql: &str,
+        fields: &HashMap<String, WireType>,
+    ) -> anyhow::Result<Vec<Sp00kyValue>> {
+        let rows = match self {
+            BootstrapSource::Direct(db) => {
+                let mut response = db.query(surql).await
+                    .with_context(|| format!("Query failed: {}", surql))?;
+                let val: surrealdb::types::Value = response.take(0)
+                    .context("Failed to parse query response")?;
+                match surreal_to_value(val) {
+                    Sp00kyValue::Array(rows) => rows,
+                    _ => vec![],
+                }
+            }
+            BootstrapSource::Proxy { .. } => match self.query(surql).await? {
+                Value::Array(rows) => rows
+                    .into_iter()
+                    .map(|row| Sp00kyValue::from_wire_fields(row, fields))
+                    .collect(),
+                _ => vec![],
+            },
+        };
+        Ok(rows)
+    }
+}
+
+/// Convert a SurrealDB value, keeping the types JSON has no form for.
+/// Geometries, ranges, regexes and files go through their JSON form.
+fn surreal_to_value(value: surrealdb::types::Value) -> Sp00kyValue {
+    use surrealdb::types::{Number, ToSql, Value as Surreal};
+    match value {
+        Surreal::None | Surreal::Null => Sp00kyValue::Null,
+        Surreal::Bool(b) => Sp00kyValue::Bool(b),
+        Surreal::Number(Number::Int(i)) => Sp00kyValue::Int(i),
+        Surreal::Number(Number::Float(f)) => Sp00kyValue::Float(f),
+        Surreal::Number(Number::Decimal(d)) => Sp00kyValue::Decimal(d),
+        Surreal::String(s) => Sp00kyValue::Str(s.into()),
+        Surreal::Datetime(t) => Sp00kyValue::Datetime(t.into_inner()),
+        Surreal::Duration(d) => Sp00kyValue::Duration(d.into_inner()),
+        Surreal::Uuid(u) => Sp00kyValue::Uuid(u.into_inner()),
+        Surreal::Bytes(b) => Sp00kyValue::Bytes(b.into_inner().to_vec()),
+        Surreal::RecordId(id) => Sp00kyValue::RecordId(id.to_sql().into()),
+        Surreal::Table(t) => Sp00kyValue::Str(t.to_string().into()),
+        Surreal::Array(items) => {
+            Sp00kyValue::Array(items.into_vec().into_iter().map(surreal_to_value).collect())
+        }
+        Surreal::Set(items) => {
+            Sp00kyValue::Array(items.into_inner().into_iter().map(surreal_to_value).collect())
+        }
+        Surreal::Object(fields) => Sp00kyValue::Object(
+            fields.into_inner().into_iter().map(|(k, v)| (k.into(), surreal_to_value(v))).collect(),
+        ),
+        other => Sp00kyValue::from(other.into_json_value()),
+    }
 }
 
 // --- Database Connection ---
@@ -298,6 +371,8 @@ pub fn create_app(state: AppState) -> Router {
         .route("/log", post(log_handler))
         .route("/debug/view/:view_id", get(debug_view_handler))
         .route("/debug/deps", get(debug_deps_handler))
+        .route("/debug/explain", get(debug_explain_all_handler))
+        .route("/debug/explain/:view_id", get(debug_explain_handler))
         .route("/view/register", post(register_view_handler))
         .route("/view/unregister", post(unregister_view_handler))
         .route("/crdt/apply", post(crdt_apply_handler))
@@ -360,6 +435,8 @@ pub async fn run_server() -> anyhow::Result<()> {
         crdt::CrdtAllowList::from_env(),
     ));
 
+    let field_types = Arc::new(RwLock::new(FieldTypes::new()));
+
     let state = AppState {
         db: db.clone(),
         processor: processor_arc.clone(),
@@ -371,6 +448,7 @@ pub async fn run_server() -> anyhow::Result<()> {
         scheduler_url: config.scheduler_url.clone(),
         start_time: std::time::Instant::now(),
         crdt_cache,
+        field_types: field_types.clone(),
     };
 
     let app = create_app(state);
@@ -385,6 +463,7 @@ pub async fn run_server() -> anyhow::Result<()> {
     {
         let db = db.clone();
         let processor = processor_arc.clone();
+        let field_types = field_types.clone();
         let status = status.clone();
         let metrics = metrics.clone();
         let scheduler_url = config.scheduler_url.clone();
@@ -441,7 +520,7 @@ pub async fn run_server() -> anyhow::Result<()> {
             let mut attempt = 0;
             loop {
                 attempt += 1;
-                match self_bootstrap(&source, &processor).await {
+                match self_bootstrap(&source, &processor, &field_types).await {
                     Ok(()) => {
                         // Integrity check: only when the scheduler handed us
                         // expected hashes (cluster mode). Mismatch ⇒ wipe
@@ -622,6 +701,30 @@ pub async fn run_server() -> anyhow::Result<()> {
         info!(interval_secs = config.ttl_cleanup_interval_secs, "TTL cleanup loop started");
     }
 
+    // Spawn clock loop: moves time-windowed views (`time::now()` filters)
+    {
+        let db = db.clone();
+        let processor = processor_arc.clone();
+        let status = status.clone();
+        let metrics = metrics.clone();
+        let interval_ms = config.clock_tick_interval_ms;
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_millis(interval_ms));
+
+            loop {
+                interval.tick().await;
+
+                if *status.read().await != SspStatus::Ready {
+                    continue;
+                }
+
+                clock_tick(&db, &processor, &metrics).await;
+            }
+        });
+        info!(interval_ms = config.clock_tick_interval_ms, "Clock loop started");
+    }
+
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal(meter_provider))
         .await
@@ -666,23 +769,86 @@ async fn shutdown_signal(
 
 // --- Self-Bootstrap ---
 
+/// Table `select` permissions from `INFO FOR DB STRUCTURE`, where a rule
+/// is `true` (`FULL`), `false` (`NONE`) or the text of its `WHERE`
+/// condition. A table whose rule is missing or cannot be evaluated denies
+/// every row.
+pub fn load_permissions(tables: &[Value]) -> Permissions {
+    let mut permissions = Permissions::new();
+    for table in tables {
+        let Some(name) = table.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        if name.starts_with("_00_") {
+            continue;
+        }
+        let result = match table.pointer("/permissions/select") {
+            Some(Value::String(condition)) => permissions.define(name, condition),
+            Some(Value::Bool(full)) => {
+                permissions.set(name, if *full { SelectRule::Full } else { SelectRule::None });
+                Ok(())
+            }
+            other => {
+                permissions.set(name, SelectRule::None);
+                Err(anyhow::anyhow!("unreadable select permission {:?}", other))
+            }
+        };
+        if let Err(e) = result {
+            warn!(table = %name, error = %e, "Unsupported select permission, denying all rows");
+        }
+    }
+    permissions
+}
+
+/// Top-level fields of `INFO FOR TABLE ... STRUCTURE` declared `decimal`,
+/// `duration`, `bytes`, `datetime` or `uuid` (optionally `option<..>` or
+/// `none | ..`), whose JSON is read back as that type.
+pub fn load_field_types(fields: &[Value]) -> HashMap<String, WireType> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let name = field.get("name")?.as_str()?;
+            let ty = wire_type(field.get("kind")?.as_str()?)?;
+            (!name.contains(['.', '['])).then(|| (name.to_string(), ty))
+        })
+        .collect()
+}
+
+/// The wire type of a field kind as SurrealDB prints it.
+fn wire_type(kind: &str) -> Option<WireType> {
+    if let Some(inner) = kind.strip_prefix("option<").and_then(|k| k.strip_suffix('>')) {
+        return wire_type(inner);
+    }
+    let mut kinds = kind.split('|').map(str::trim).filter(|k| !matches!(*k, "none" | "null"));
+    match (kinds.next()?, kinds.next()) {
+        ("decimal", None) => Some(WireType::Decimal),
+        ("duration", None) => Some(WireType::Duration),
+        ("bytes", None) => Some(WireType::Bytes),
+        ("datetime", None) => Some(WireType::Datetime),
+        ("uuid", None) => Some(WireType::Uuid),
+        _ => None,
+    }
+}
+
 /// Bootstrap the circuit by loading all table data and view definitions.
 /// Works with either a direct SurrealDB connection or the scheduler's HTTP proxy.
 async fn self_bootstrap(
     source: &BootstrapSource,
     processor: &Arc<RwLock<Circuit>>,
+    field_types: &Arc<RwLock<FieldTypes>>,
 ) -> anyhow::Result<()> {
     info!("Starting self-bootstrap");
 
     // Step 1: Discover tables via INFO FOR DB
-    let info_json = source.query("INFO FOR DB").await
+    let info_json = source.query("INFO FOR DB STRUCTURE").await
         .context("Failed to query INFO FOR DB")?;
 
     let tables: Vec<String> = match info_json.get("tables") {
-        Some(Value::Object(tables_map)) => tables_map
-            .keys()
+        Some(Value::Array(table_defs)) => table_defs
+            .iter()
+            .filter_map(|table| table.get("name")?.as_str())
             .filter(|name| !name.starts_with("_00_"))
-            .cloned()
+            .map(str::to_string)
             .collect(),
         _ => {
             info!("No tables found in database");
@@ -692,15 +858,25 @@ async fn self_bootstrap(
 
     info!(count = tables.len(), "Discovered tables: {:?}", tables);
 
+    // Table select permissions are enforced in every view registered below
+    let permissions = match info_json.get("tables") {
+        Some(Value::Array(table_defs)) => load_permissions(table_defs),
+        _ => Permissions::new(),
+    };
+    processor.write().await.set_permissions(permissions);
+
     // Step 2: Load all table data
     for table in &tables {
-        let result = source.query(&format!("SELECT * FROM {}", table)).await
-            .with_context(|| format!("Failed to query table {}", table))?;
-
-        let rows: Vec<Value> = match result {
-            Value::Array(arr) => arr,
-            _ => vec![],
+        // Records ingested later are read with the same field types
+        let info = source.query(&format!("INFO FOR TABLE {} STRUCTURE", table)).await
+            .with_context(|| format!("Failed to query INFO FOR TABLE {}", table))?;
+        let fields = match info.get("fields") {
+            Some(Value::Array(fields)) => load_field_types(fields),
+            _ => HashMap::new(),
         };
+        let rows = source.query_rows(&format!("SELECT * FROM {}", table), &fields).await
+            .with_context(|| format!("Failed to query table {}", table))?;
+        field_types.write().await.insert(table.clone(), fields);
         let record_count = rows.len();
 
         let records: Vec<Record> = rows
@@ -729,6 +905,7 @@ async fn self_bootstrap(
     };
     info!(count = views.len(), "Found persisted views");
 
+    let mut registrations = Vec::with_capacity(views.len());
     for view_row in views {
         let view_id = match view_row.get("id") {
             Some(Value::String(s)) => s.clone(),
@@ -772,6 +949,11 @@ async fn self_bootstrap(
             .get("params")
             .cloned()
             .unwrap_or(json!({}));
+        // The auth context was persisted with the params; hand it back as
+        // such, since reserved keys in client params are dropped.
+        let auth = params.get("_00_auth").cloned();
+        let access = params.get("_00_access").cloned();
+        let rows = view_row.get("rows").and_then(|v| v.as_bool()).unwrap_or(false);
 
         let payload = json!({
             "id": raw_id,
@@ -780,24 +962,24 @@ async fn self_bootstrap(
             "ttl": ttl,
             "lastActiveAt": last_active_at,
             "params": params,
+            "auth": auth,
+            "access": access,
+            "rows": rows,
         });
 
         match ssp::service::view::prepare_registration_dbsp(payload) {
-            Ok(data) => {
-                let mut circuit = processor.write().await;
-                circuit.add_query(
-                    data.plan,
-                    data.safe_params,
-                    Some(OutputFormat::Streaming),
-                );
-                info!(view_id = %raw_id, "Re-registered view");
-            }
+            Ok(data) => registrations.push((data.plan, data.safe_params, Some(OutputFormat::Streaming), data.rows)),
             Err(e) => {
                 warn!(view_id = %raw_id, error = %e, "Failed to re-register view");
             }
         }
     }
 
+    // Register all views at once: their initial evaluations run in parallel.
+    let count = registrations.len();
+    processor.write().await.add_queries(registrations);
+    info!(views = count, "Re-registered views");
+
     Ok(())
 }
 
@@ -875,7 +1057,10 @@ async fn ingest_handler(
     };
 
     // Prepare record data
-    let clean = ssp::sanitizer::normalize_record(payload.record.clone());
+    let clean = match state.field_types.read().await.get(&payload.table) {
+        Some(fields) => Sp00kyValue::from_wire_fields(payload.record.clone(), fields),
+        None => Sp00kyValue::from_wire(payload.record.clone()),
+    };
 
     // Check if this is a job table and queue the job if pending (only on assigned SSP)
     if let Some(backend_info) = state.job_config.job_tables.get(&payload.table) {
@@ -1094,11 +1279,11 @@ async fn register_view_handler(
     // Register view with Streaming format
     let update = {
         let mut circuit = state.processor.write().await;
-        circuit.add_query(
-            data.plan.clone(),
-            data.safe_params,
-            Some(OutputFormat::Streaming),
-        )
+        if data.rows {
+            circuit.add_query_with_rows(data.plan.clone(), data.safe_params, Some(OutputFormat::Streaming))
+        } else {
+            circuit.add_query(data.plan.clone(), data.safe_params, Some(OutputFormat::Streaming))
+        }
     };
 
     state.metrics.view_count.add(1, &[]);
@@ -1135,7 +1320,7 @@ async fn register_view_handler(
         .unwrap_or(Value::Null);
 
     // Store incantation metadata
-    let query = "UPSERT <record>$id SET clientId = <string>$clientId, surql = <string>$surql, params = $params, ttl = <duration>$ttl, lastActiveAt = <datetime>$lastActiveAt";
+    let query = "UPSERT <record>$id SET clientId = <string>$clientId, surql = <string>$surql, params = $params, ttl = <duration>$ttl, lastActiveAt = <datetime>$lastActiveAt, rows = <bool>$rows";
 
     if let Err(e) = state
         .db
@@ -1146,6 +1331,7 @@ async fn register_view_handler(
         .bind(("params", params))
         .bind(("ttl", ttl))
         .bind(("lastActiveAt", last_active_at))
+        .bind(("rows", data.rows))
         .await
     {
         error!("Failed to upsert incantation metadata: {}", e);
@@ -1311,6 +1497,9 @@ async fn info_handler(State(state): State<AppState>) -> Json<Value> {
         .map(|(t, h)| (t, Value::String(h)))
         .collect();
 
+    // Estimated memory of stored rows, view caches and operator state.
+    let circuit_memory = serde_json::to_value(circuit.memory_report()).unwrap_or(Value::Null);
+
     Json(json!([
         {
             "entity": "ssp",
@@ -1323,6 +1512,7 @@ async fn info_handler(State(state): State<AppState>) -> Json<Value> {
             "last_heartbeat_seconds_ago": null,
             "circuit_tables": circuit_tables,
             "circuit_hashes": circuit_hashes,
+            "circuit_memory": circuit_memory,
             "env": env_vars,
         }
     ]))
@@ -1350,7 +1540,7 @@ async fn debug_view_handler(
             "cache": cache_summary,
             "subquery_tables": view.subquery_tables,
             "referenced_tables": view.referenced_tables,
-            "content_generation": view.content_generation,
+            "uncorrelated_tables": view.uncorrelated_tables,
             "subquery_cache": view.subquery_cache.iter()
                 .map(|(k, (pk, alias))| json!({"key": k, "parent_key": pk, "alias": alias}))
                 .collect::<Vec<_>>(),
@@ -1371,6 +1561,50 @@ async fn debug_deps_handler(State(state): State<AppState>) -> impl IntoResponse
     }))
 }
 
+#[derive(Deserialize)]
+struct ExplainParams {
+    #[serde(default)]
+    analyze: bool,
+}
+
+/// EXPLAIN handler - operator tree and state sizes of a view.
+/// `?analyze=true` adds per-node step counts, rows and times.
+async fn debug_explain_handler(
+    State(state): State<AppState>,
+    Path(view_id): Path<String>,
+    Query(params): Query<ExplainParams>,
+) -> impl IntoResponse {
+    let circuit = state.processor.read().await;
+    match circuit.explain(&view_id, params.analyze) {
+        Some(explain) => Json(json!(explain)),
+        None => Json(json!({ "error": "View not found" })),
+    }
+}
+
+/// EXPLAIN summary of every view, most expensive first
+async fn debug_explain_all_handler(
+    State(state): State<AppState>,
+    Query(params): Query<ExplainParams>,
+) -> impl IntoResponse {
+    let circuit = state.processor.read().await;
+    let views: Vec<Value> = circuit
+        .explain_all(params.analyze)
+        .into_iter()
+        .map(|e| {
+            json!({
+                "view_id": e.view_id,
+                "nodes": e.nodes,
+                "state_rows": e.state_rows,
+                "step_time_us": e.step_time_us,
+            })
+        })
+        .collect();
+    Json(json!({
+        "node_count": circuit.node_count(),
+        "views": views,
+    }))
+}
+
 /// Version handler
 async fn version_handler() -> impl IntoResponse {
     Json(json!({
@@ -1484,6 +1718,26 @@ async fn ttl_cleanup_sweep(
 
 // --- Helper Functions ---
 
+/// Advance the circuit clock to the current time and write the edges of
+/// views whose time windows changed.
+async fn clock_tick(db: &SharedDb, processor: &Arc<RwLock<Circuit>>, metrics: &Arc<Metrics>) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    let mut circuit = processor.write().await;
+    let deltas = circuit.tick(now);
+    if deltas.is_empty() {
+        return;
+    }
+    debug!(views_affected = deltas.len(), "Clock tick moved time-windowed views");
+    // Keep the same guard, downgraded, so no step lands between the tick
+    // and the edges it wrote.
+    let circuit = circuit.downgrade();
+    let delta_refs: Vec<&ViewDelta> = deltas.iter().collect();
+    update_all_edges(db, &delta_refs, metrics, &circuit).await;
+}
+
 /// Parse a record ID string into SurrealDB RecordId
 fn parse_record_id(id: &str) -> Option<RecordId> {
     RecordId::parse_simple(id).ok()
@@ -1541,6 +1795,20 @@ pub async fn update_all_edges<C: Connection>(
         let binding_name = format!("from{}", idx);
         bindings.push((binding_name.clone(), from_id));
 
+        // Clients check their copy of the view against this hash
+        all_statements.push(format!(
+            "UPDATE ${} SET resultHash = '{}'",
+            binding_name, delta.result_hash
+        ));
+
+        // Aggregate rows are derived by the circuit and have no backing
+        // record; their values travel on the edge itself.
+        let aggregate_data: std::collections::HashMap<&str, (i64, String)> = delta
+            .aggregate_rows
+            .iter()
+            .map(|agg| (agg.key.as_str(), aggregate_edge_fields(&agg.row)))
+            .collect();
+
         // Process additions (Created)
         for id in &delta.additions {
             if parse_record_id(id).is_none() {
@@ -1553,11 +1821,14 @@ pub async fn update_all_edges<C: Connection>(
                 continue;
             }
 
-            let version = circuit.store.get_record_version_by_key(id).unwrap_or(1);
+            let (version, data) = match aggregate_data.get(id.as_str()) {
+                Some((version, data)) => (*version, format!(", data = {}", data)),
+                None => (circuit.store.get_record_version_by_key(id).unwrap_or(1), String::new()),
+            };
             created_count += 1;
             all_statements.push(format!(
-                "RELATE ${1}->_00_list_ref->{0} SET version = {2}, clientId = (SELECT VALUE clientId FROM ${1} LIMIT 1)[0]",
-                id, binding_name, version
+                "RELATE ${1}->_00_list_ref->{0} SET version = {2}, clientId = (SELECT VALUE clientId FROM ${1} LIMIT 1)[0]{3}",
+                id, binding_name, version, data
             ));
         }
 
@@ -1573,11 +1844,14 @@ pub async fn update_all_edges<C: Connection>(
                 continue;
             }
 
-            let version = circuit.store.get_record_version_by_key(id).unwrap_or(1);
+            let (version, data) = match aggregate_data.get(id.as_str()) {
+                Some((version, data)) => (*version, format!(", data = {}", data)),
+                None => (circuit.store.get_record_version_by_key(id).unwrap_or(1), String::new()),
+            };
             updated_count += 1;
             all_statements.push(format!(
-                "UPDATE _00_list_ref SET version = {2} WHERE in = ${0} AND out = {1}",
-                binding_name, id, version
+                "UPDATE _00_list_ref SET version = {2}{3} WHERE in = ${0} AND out = {1}",
+                binding_name, id, version, data
             ));
         }
 
@@ -1720,6 +1994,13 @@ pub async fn update_all_edges<C: Connection>(
     }
 }
 
+/// Version and inline object literal for an aggregate row's edge.
+fn aggregate_edge_fields(row: &Sp00kyValue) -> (i64, String) {
+    let version = row.get("_00_rv").and_then(|v| v.as_i64()).unwrap_or(1);
+    let data = Value::from(row.clone()).to_string();
+    (version, data)
+}
+
 /// Update edges for a single view (convenience wrapper for register_view_handler)
 async fn update_incantation_edges<C: Connection>(
     db: &Surreal<C>,
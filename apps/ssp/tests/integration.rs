@@ -10,8 +10,9 @@ use tower::ServiceExt;
 
 use ssp::circuit::view::OutputFormat;
 use ssp::circuit::{Circuit, Record};
+use ssp_server::crdt::{CrdtAllowList, CrdtCache};
 use ssp_server::metrics::Metrics;
-use ssp_server::{create_app, AppState, SharedDb, SspStatus};
+use ssp_server::{create_app, AppState, FieldTypes, SharedDb, SspStatus};
 
 use job_runner::{JobConfig, JobEntry};
 use tokio::sync::mpsc;
@@ -34,6 +35,7 @@ struct TestHarness {
     job_queue_tx: mpsc::Sender<JobEntry>,
     job_queue_rx: Arc<tokio::sync::Mutex<mpsc::Receiver<JobEntry>>>,
     db: SharedDb,
+    field_types: Arc<RwLock<FieldTypes>>,
 }
 
 impl TestHarness {
@@ -48,7 +50,7 @@ impl TestHarness {
     fn with_options(status: SspStatus, job_config: JobConfig) -> Self {
         // Set auth secret for the middleware
         unsafe {
-            std::env::set_var("SP00KY_AUTH_SECRET", AUTH_SECRET);
+            std::env::set_var("SPKY_AUTH_SECRET", AUTH_SECRET);
         }
 
         let (tx, rx) = mpsc::channel::<JobEntry>(100);
@@ -70,6 +72,7 @@ impl TestHarness {
             job_queue_tx: tx,
             job_queue_rx: Arc::new(tokio::sync::Mutex::new(rx)),
             db: Arc::new(db),
+            field_types: Arc::new(RwLock::new(FieldTypes::new())),
         }
     }
 
@@ -81,6 +84,11 @@ impl TestHarness {
             metrics: Arc::clone(&self.metrics),
             job_config: Arc::clone(&self.job_config),
             job_queue_tx: self.job_queue_tx.clone(),
+            ssp_id: "ssp-test".to_string(),
+            scheduler_url: None,
+            start_time: std::time::Instant::now(),
+            crdt_cache: Arc::new(CrdtCache::new(16, CrdtAllowList::default())),
+            field_types: Arc::clone(&self.field_types),
         };
         create_app(state)
     }
@@ -229,7 +237,7 @@ mod auth_tests {
     #[tokio::test]
     async fn unauthenticated_request_returns_401() {
         let h = TestHarness::new();
-        let (status, _) = get_json(h.app(), "/health").await;
+        let (status, _) = get_json(h.app(), "/debug/deps").await;
         assert_eq!(status, StatusCode::UNAUTHORIZED);
     }
 
@@ -238,7 +246,7 @@ mod auth_tests {
         let h = TestHarness::new();
         let request = Request::builder()
             .method("GET")
-            .uri("/health")
+            .uri("/debug/deps")
             .header("Authorization", "Bearer wrong-token")
             .body(axum::body::Body::empty())
             .unwrap();
@@ -250,7 +258,7 @@ mod auth_tests {
     #[tokio::test]
     async fn valid_token_passes_through() {
         let h = TestHarness::new();
-        let (status, _) = get_authed(h.app(), "/health").await;
+        let (status, _) = get_authed(h.app(), "/debug/deps").await;
         assert_eq!(status, StatusCode::OK);
     }
 
@@ -259,7 +267,7 @@ mod auth_tests {
         let h = TestHarness::new();
         let request = Request::builder()
             .method("GET")
-            .uri("/health")
+            .uri("/debug/deps")
             .header("Authorization", AUTH_SECRET)
             .body(axum::body::Body::empty())
             .unwrap();
@@ -278,8 +286,6 @@ mod health_tests {
         let (status, body) = get_authed(h.app(), "/health").await;
         assert_eq!(status, StatusCode::OK);
         assert_eq!(body["status"], "ready");
-        assert_eq!(body["views"], 0);
-        assert_eq!(body["tables"], 0);
     }
 
     #[tokio::test]
@@ -299,7 +305,14 @@ mod health_tests {
     }
 
     #[tokio::test]
-    async fn health_reports_view_and_table_count() {
+    async fn health_does_not_require_auth() {
+        let h = TestHarness::new();
+        let (status, _) = get_json(h.app(), "/health").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn info_reports_view_and_table_count() {
         let h = TestHarness::new();
 
         // Load data into two tables
@@ -312,19 +325,11 @@ mod health_tests {
         // Register a view
         h.register_view_direct("view1", "SELECT * FROM user").await;
 
-        let (status, body) = get_authed(h.app(), "/health").await;
+        let (status, body) = get_authed(h.app(), "/info").await;
         assert_eq!(status, StatusCode::OK);
-        assert_eq!(body["views"], 1);
-        assert_eq!(body["tables"], 2);
-    }
-
-    #[tokio::test]
-    async fn health_response_has_all_fields() {
-        let h = TestHarness::new();
-        let (_, body) = get_authed(h.app(), "/health").await;
-        assert!(body.get("status").is_some());
-        assert!(body.get("views").is_some());
-        assert!(body.get("tables").is_some());
+        assert_eq!(body[0]["id"], "ssp-test");
+        assert_eq!(body[0]["views"], 1);
+        assert_eq!(body[0]["circuit_tables"].as_object().unwrap().len(), 2);
     }
 }
 
@@ -447,6 +452,26 @@ mod debug_tests {
         assert_eq!(status, StatusCode::OK);
         assert_eq!(body["view_count"], 2);
     }
+
+    #[tokio::test]
+    async fn debug_explain_returns_operator_tree() {
+        let h = TestHarness::new();
+        h.register_view_direct("v1", "SELECT * FROM user WHERE active = true").await;
+
+        let (status, body) = get_authed(h.app(), "/debug/explain/v1?analyze=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["view_id"], "v1");
+        assert_eq!(body["plan"]["op"], "filter");
+        assert_eq!(body["plan"]["inputs"][0]["op"], "scan");
+        assert!(body["plan"]["stats"].get("steps").is_some());
+
+        let (_, body) = get_authed(h.app(), "/debug/explain/nonexistent").await;
+        assert_eq!(body["error"], "View not found");
+
+        let (status, body) = get_authed(h.app(), "/debug/explain").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["views"][0]["view_id"], "v1");
+    }
 }
 
 mod ingest_tests {
@@ -562,6 +587,27 @@ mod ingest_tests {
         let circuit = h.processor.read().await;
         assert!(circuit.table_names().contains(&"user".to_string()));
     }
+
+    #[tokio::test]
+    async fn ingest_reads_declared_field_types() {
+        use ssp::types::{Sp00kyValue, WireType};
+
+        let h = TestHarness::new();
+        h.field_types.write().await.insert(
+            "item".to_string(),
+            [("price".to_string(), WireType::Decimal), ("ttl".to_string(), WireType::Duration)].into(),
+        );
+        let record = json!({"id": "item:1", "price": "12.50", "ttl": "1h", "name": "12.50"});
+        let payload = ingest_payload_with_record("item", "CREATE", "item:1", record);
+        let (status, _) = post_authed(h.app(), "/ingest", &payload).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let circuit = h.processor.read().await;
+        let row = circuit.store.get_row_by_key("item:1").expect("record should be stored");
+        assert_eq!(row.get("price"), Some(&Sp00kyValue::Decimal("12.50".parse().unwrap())));
+        assert_eq!(row.get("ttl"), Some(&Sp00kyValue::Duration(std::time::Duration::from_secs(3600))));
+        assert_eq!(row.get("name"), Some(&Sp00kyValue::Str("12.50".into())));
+    }
 }
 
 mod view_lifecycle_tests {
@@ -585,6 +631,17 @@ mod view_lifecycle_tests {
         assert_eq!(status, StatusCode::BAD_REQUEST);
     }
 
+    #[tokio::test]
+    async fn register_rejects_non_object_params() {
+        let h = TestHarness::new();
+        let mut payload = view_payload("v1", "SELECT * FROM user");
+        payload["params"] = json!("[1]");
+        payload["auth"] = json!({"id": "user:1"});
+        let (status, _) = post_authed(h.app(), "/view/register", &payload).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(h.processor.read().await.get_view("v1").is_none());
+    }
+
     #[tokio::test]
     async fn register_adds_view() {
         let h = TestHarness::new();
@@ -766,11 +823,87 @@ mod status_gating_tests {
     }
 }
 
+mod permissions_tests {
+    use super::*;
+    use ssp::permissions::SelectRule;
+    use ssp_server::load_permissions;
+
+    /// The rule of table `a`, given its `select` permission as
+    /// `INFO FOR DB STRUCTURE` reports it.
+    fn rule_of(select: Value) -> Option<SelectRule> {
+        let tables = json!([
+            { "name": "a", "permissions": { "select": select, "create": false } },
+            { "name": "_00_query", "permissions": { "select": false } },
+        ]);
+        let permissions = load_permissions(tables.as_array().unwrap());
+        assert!(permissions.rule("_00_query").is_none());
+        permissions.rule("a").cloned()
+    }
+
+    #[test]
+    fn select_rules_are_read_from_table_definitions() {
+        let owned = rule_of(json!("owner = $auth.id AND $access = 'account'"));
+        let Some(SelectRule::Where(predicate)) = owned else {
+            panic!("expected a WHERE rule, got {:?}", owned);
+        };
+        let predicate = serde_json::to_value(predicate).unwrap();
+        assert_eq!(predicate["predicates"][0]["value"], json!({ "$param": "_00_auth.id" }));
+        assert_eq!(predicate["predicates"][1]["expr"]["left"]["name"], "_00_access");
+
+        assert!(matches!(rule_of(json!(true)), Some(SelectRule::Full)));
+        assert!(matches!(rule_of(json!(false)), Some(SelectRule::None)));
+        assert!(matches!(rule_of(json!("true")), Some(SelectRule::Full)));
+    }
+
+    #[test]
+    fn unreadable_rules_deny_every_row() {
+        assert!(matches!(
+            rule_of(json!("$auth.id IN (SELECT VALUE in FROM member)")),
+            Some(SelectRule::None)
+        ));
+        assert!(matches!(rule_of(json!(null)), Some(SelectRule::None)));
+        // The unstructured `INFO FOR DB` form is not read as a rule.
+        assert!(matches!(rule_of(json!({ "a": "DEFINE TABLE a PERMISSIONS FULL" })), Some(SelectRule::None)));
+    }
+}
+
 // ===========================================================================
 // DB Integration Tests (require running SurrealDB)
 // ===========================================================================
 // Run with: cargo test -- --ignored
 
+mod field_type_tests {
+    use ssp::types::WireType;
+    use ssp_server::load_field_types;
+
+    #[test]
+    fn field_types_are_read_from_field_definitions() {
+        let fields = serde_json::json!([
+            { "name": "price", "table": "item", "kind": "decimal" },
+            { "name": "ttl", "table": "item", "kind": "none | duration" },
+            { "name": "blob", "table": "item", "kind": "option<bytes>" },
+            { "name": "created", "table": "item", "kind": "datetime" },
+            { "name": "token", "table": "item", "kind": "option<uuid>" },
+            { "name": "name", "table": "item", "kind": "string" },
+            { "name": "either", "table": "item", "kind": "decimal | string" },
+            { "name": "any", "table": "item" },
+            { "name": "meta.cost", "table": "item", "kind": "decimal" },
+        ]);
+        let types = load_field_types(fields.as_array().unwrap());
+        assert_eq!(
+            types,
+            [
+                ("price".to_string(), WireType::Decimal),
+                ("ttl".to_string(), WireType::Duration),
+                ("blob".to_string(), WireType::Bytes),
+                ("created".to_string(), WireType::Datetime),
+                ("token".to_string(), WireType::Uuid),
+            ]
+            .into()
+        );
+    }
+}
+
 mod db_integration_tests {
     use super::*;
     use surrealdb::engine::remote::ws::Ws;
@@ -782,7 +915,7 @@ mod db_integration_tests {
             .unwrap_or_else(|_| "127.0.0.1:8000".to_string());
 
         unsafe {
-            std::env::set_var("SP00KY_AUTH_SECRET", AUTH_SECRET);
+            std::env::set_var("SPKY_AUTH_SECRET", AUTH_SECRET);
         }
 
         let db = Surreal::new::<Ws>(&addr)
@@ -820,6 +953,7 @@ mod db_integration_tests {
             job_queue_tx: tx,
             job_queue_rx: Arc::new(tokio::sync::Mutex::new(rx)),
             db: Arc::new(db),
+            field_types: Arc::new(RwLock::new(FieldTypes::new())),
         }
     }
 
@@ -170,7 +170,7 @@ export class CacheModule implements StreamUpdateReceiver {
    * Register a query with DBSP to create a materialized view
    * Returns the initial result array
    */
-  registerQuery(config: QueryConfig): { localArray: RecordVersionArray } {
+  registerQuery(config: QueryConfig): { localArray: RecordVersionArray; resultHash?: string } {
     this.logger.debug(
       {
         queryHash: config.queryHash,
@@ -207,7 +207,7 @@ export class CacheModule implements StreamUpdateReceiver {
         'Query registered successfully'
       );
 
-      return { localArray: update.localArray };
+      return { localArray: update.localArray, resultHash: update.resultHash };
     } catch (err) {
       this.logger.error(
         { err, queryHash: config.queryHash, Category: 'sp00ky-client::CacheModule::registerQuery' },
@@ -189,7 +189,7 @@ export class DataModule<S extends SchemaStructure> {
   }
 
   private async processStreamUpdate(update: StreamUpdate): Promise<void> {
-    const { queryHash, localArray } = update;
+    const { queryHash, localArray, resultHash } = update;
     const queryState = this.activeQueries.get(queryHash);
     if (!queryState) {
       this.logger.warn(
@@ -209,6 +209,7 @@ export class DataModule<S extends SchemaStructure> {
       // Update state
       const newRecords = records || [];
       queryState.config.localArray = localArray;
+      queryState.localHash = resultHash;
       await this.local.query(surql.seal(surql.updateSet('id', ['localArray'])), {
         id: queryState.config.id,
         localArray,
@@ -289,6 +290,13 @@ export class DataModule<S extends SchemaStructure> {
     });
   }
 
+  updateQueryRemoteHash(hash: string, remoteHash: string | undefined): void {
+    const queryState = this.getQueryByHash(hash);
+    if (queryState) {
+      queryState.remoteHash = remoteHash;
+    }
+  }
+
   async updateQueryRemoteArray(hash: string, remoteArray: RecordVersionArray): Promise<void> {
     const queryState = this.getQueryByHash(hash);
     if (!queryState) {
@@ -709,7 +717,7 @@ export class DataModule<S extends SchemaStructure> {
       tableName,
     });
 
-    const { localArray } = this.cache.registerQuery({
+    const { localArray, resultHash } = this.cache.registerQuery({
       queryHash: hash,
       surql: surqlString,
       params,
@@ -724,6 +732,7 @@ export class DataModule<S extends SchemaStructure> {
       })
     );
 
+    queryState.localHash = resultHash;
     this.activeQueries.set(hash, queryState);
     this.startTTLHeartbeat(queryState);
     this.logger.debug(
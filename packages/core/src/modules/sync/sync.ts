@@ -1,5 +1,5 @@
 import type { LocalDatabaseService, RemoteDatabaseService } from '../../services/database/index';
-import type { RecordVersionArray } from '../../types';
+import type { QueryState, RecordVersionArray } from '../../types';
 import { createSyncEventSystem, SyncEventTypes, SyncQueueEventTypes } from './events/index';
 import type { Logger } from '../../services/logger/index';
 import type { DownEvent, UpEvent} from './queue/index';
@@ -305,11 +305,37 @@ export class Sp00kySync<S extends SchemaStructure> {
     ).nextSet();
 
     if (!diff) {
+      this.verifyResultHash(queryState);
       return;
     }
     return this.syncEngine.syncRecords(diff);
   }
 
+  /**
+   * Once the local records of a query match the remote list, compare the
+   * result_hash the SSP stored on the query with that of the local view.
+   * Both hash the records the view holds, so they differ only when the
+   * local copy does. Runs once per new remote hash.
+   */
+  private verifyResultHash(queryState: QueryState) {
+    const { remoteHash, localHash } = queryState;
+    if (!remoteHash || remoteHash === queryState.verifiedHash) {
+      return;
+    }
+    queryState.verifiedHash = remoteHash;
+    if (localHash !== remoteHash) {
+      this.logger.warn(
+        {
+          queryId: encodeRecordId(queryState.config.id),
+          remoteHash,
+          localHash,
+          Category: 'sp00ky-client::Sp00kySync::verifyResultHash',
+        },
+        'result_hash of the server does not match the local view'
+      );
+    }
+  }
+
   /**
    * Enqueues a list of mutations (up events) to be sent to the remote.
    * @param mutations Array of UpEvents (create/update/delete) to enqueue.
@@ -377,6 +403,12 @@ export class Sp00kySync<S extends SchemaStructure> {
 
     const array: RecordVersionArray = items.map((item) => [encodeRecordId(item.out), item.version]);
 
+    const [resultHash] = await this.remote.query<[string | undefined]>(
+      'SELECT VALUE resultHash FROM ONLY $id',
+      { id: queryState.config.id }
+    );
+    this.dataModule.updateQueryRemoteHash(queryHash, resultHash ?? undefined);
+
     this.logger.debug(
       {
         queryId: encodeRecordId(queryState.config.id),
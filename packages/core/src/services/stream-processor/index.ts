@@ -27,6 +27,8 @@ interface QueryPlanConfig {
 export interface StreamUpdate {
   queryHash: string;
   localArray: RecordVersionArray;
+  // result_hash of the local view, compared with the server's by the sync
+  resultHash?: string;
   op?: 'CREATE' | 'UPDATE' | 'DELETE'; // Operation type for conditional debouncing
 }
 
@@ -42,11 +44,15 @@ export interface StreamUpdateReceiver {
   onStreamUpdate(update: StreamUpdate): void;
 }
 
+// How often the processor clock behind time::now() advances
+const CLOCK_TICK_INTERVAL_MS = 1000;
+
 export class StreamProcessorService {
   private logger: Logger;
   private processor: WasmProcessor | undefined;
   private isInitialized = false;
   private receivers: StreamUpdateReceiver[] = [];
+  private tickTimer: ReturnType<typeof setInterval> | undefined;
 
   constructor(
     public events: EventSystem<StreamProcessorEvents>,
@@ -92,6 +98,7 @@ export class StreamProcessorService {
       // Try to load state
       await this.loadState();
 
+      this.tickTimer = setInterval(() => this.tick(), CLOCK_TICK_INTERVAL_MS);
       this.isInitialized = true;
       this.logger.info(
         { Category: 'sp00ky-client::StreamProcessorService::init' },
@@ -106,6 +113,42 @@ export class StreamProcessorService {
     }
   }
 
+  /**
+   * Stop the processor clock.
+   */
+  close() {
+    if (this.tickTimer !== undefined) {
+      clearInterval(this.tickTimer);
+      this.tickTimer = undefined;
+    }
+  }
+
+  /**
+   * Advance the clock behind time::now(), so views with time windows
+   * gain and lose records as time passes without any ingest.
+   */
+  tick() {
+    if (!this.processor) return;
+    try {
+      const rawUpdates = this.processor.tick();
+      if (rawUpdates.length > 0) {
+        this.notifyUpdates(
+          rawUpdates.map((u: WasmStreamUpdate) => ({
+            queryHash: u.query_id,
+            localArray: u.result_data,
+            resultHash: u.result_hash,
+          }))
+        );
+        this.saveState();
+      }
+    } catch (e) {
+      this.logger.error(
+        { error: e, Category: 'sp00ky-client::StreamProcessorService::tick' },
+        'Clock tick failed'
+      );
+    }
+  }
+
   async loadState() {
     if (!this.processor) return;
     try {
@@ -129,8 +172,8 @@ export class StreamProcessorService {
         );
         // Assuming processor has a load_state method matching the save_state behavior
         // If not, we might need to adjust based on the actual WASM API
-        if (typeof (this.processor as any).load_state === 'function') {
-          (this.processor as any).load_state(state);
+        if (typeof this.processor.load_state === 'function') {
+          this.processor.load_state(state);
         } else {
           this.logger.warn(
             { Category: 'sp00ky-client::StreamProcessorService::loadState' },
@@ -155,8 +198,8 @@ export class StreamProcessorService {
     if (!this.processor) return;
     try {
       // Assuming processor has a save_state method that returns the state string/bytes
-      if (typeof (this.processor as any).save_state === 'function') {
-        const state = (this.processor as any).save_state();
+      if (typeof this.processor.save_state === 'function') {
+        const state = this.processor.save_state();
         if (state) {
           await this.persistenceClient.set('_00_stream_processor_state', state);
           this.logger.trace(
@@ -221,6 +264,7 @@ export class StreamProcessorService {
         const updates: StreamUpdate[] = rawUpdates.map((u: WasmStreamUpdate) => ({
           queryHash: u.query_id,
           localArray: u.result_data,
+          resultHash: u.result_hash,
           op: op,
         }));
         // Direct handler call instead of event
@@ -283,6 +327,7 @@ export class StreamProcessorService {
       const update: StreamUpdate = {
         queryHash: initialUpdate.query_id,
         localArray: initialUpdate.result_data,
+        resultHash: initialUpdate.result_hash,
       };
       this.saveState();
       this.logger.debug(
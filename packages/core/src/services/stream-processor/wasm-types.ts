@@ -4,6 +4,23 @@ export interface WasmStreamUpdate {
   query_id: string;
   result_hash: string;
   result_data: RecordVersionArray; // Match Rust 'result_data' field
+  // Subquery records not in result_data; result_hash covers them too
+  subquery_data?: RecordVersionArray;
+  delta: {
+    additions: RecordVersionArray;
+    removals: string[];
+    updates: RecordVersionArray;
+  };
+  aggregate_rows: Array<[string, Record<string, any>]>;
+  // Only for views registered with format 'tree'
+  tree?: WasmTreeNode[];
+  // Content of added and updated records; only for views registered with rows
+  rows?: Array<[string, Record<string, any>]>;
+}
+
+export interface WasmTreeNode {
+  id: string;
+  children?: Record<string, WasmTreeNode[]>;
 }
 
 export interface WasmQueryConfig {
@@ -28,4 +45,7 @@ export interface WasmProcessor {
   ingest(table: string, op: string, id: string, record: any): WasmStreamUpdate[];
   register_view(config: WasmQueryConfig): WasmStreamUpdate | undefined;
   unregister_view(id: string): void;
+  tick(now?: number): WasmStreamUpdate[];
+  save_state(): string;
+  load_state(state: string | Uint8Array): void;
 }
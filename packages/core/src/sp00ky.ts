@@ -280,6 +280,7 @@ export class Sp00kyClient<S extends SchemaStructure> {
   }
 
   async close() {
+    this.streamProcessor.close();
     this.crdtManager.closeAll();
     await this.local.close();
     await this.remote.close();
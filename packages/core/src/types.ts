@@ -178,6 +178,12 @@ export interface QueryState {
   ttlDurationMs: number;
   /** Number of times the query has been updated. */
   updateCount: number;
+  /** result_hash of the local view of the query. */
+  localHash?: string;
+  /** result_hash of the view on the server, as of the last sync. */
+  remoteHash?: string;
+  /** The last remoteHash compared with localHash. */
+  verifiedHash?: string;
 }
 
 // Callback types
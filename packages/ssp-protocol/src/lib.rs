@@ -3,6 +3,7 @@ use serde_json::Value;
 use std::collections::BTreeMap;
 
 pub mod snapshot_hash;
+pub mod view_hash;
 
 // --- Ingest API (snake_case wire format) ---
 
@@ -32,6 +33,17 @@ pub struct ViewRegisterRequest {
     pub last_active_at: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub format: Option<String>,
+    /// Auth context of the registering user, bound as `$auth` in table
+    /// select permissions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<Value>,
+    /// Access method the user signed in with, bound as `$access`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<Value>,
+    /// Whether the view's deltas carry the content of the rows they add or
+    /// update.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rows: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
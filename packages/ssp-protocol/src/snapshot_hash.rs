@@ -10,11 +10,20 @@
 //! Output is lowercase hex of a blake3-256 digest, prefixed `b3:` so future
 //! algorithm changes are visible in diagnostics.
 //!
+//! Values SurrealDB types natively are hashed in the JSON form its SDK
+//! gives them (`Value::into_json_value`): datetimes as `canonical_datetime`,
+//! durations as `canonical_duration`, decimals as their decimal string,
+//! UUIDs as lowercase hyphenated strings, record ids as `table:key`
+//! strings and bytes as arrays of numbers. An SSP holding typed values must
+//! encode them this way before hashing.
+//!
 //! See `Replica::compute_table_hashes` (apps/scheduler/src/replica.rs) and
 //! `Circuit::compute_table_hashes` (packages/ssp/src/circuit/circuit.rs).
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde_json::Value;
 use std::collections::BTreeMap;
+use std::time::Duration;
 
 const HASH_PREFIX: &str = "b3:";
 
@@ -96,6 +105,45 @@ fn write_canonical(value: &Value, out: &mut Vec<u8>) {
     }
 }
 
+/// A datetime as SurrealDB writes it in JSON: RFC 3339 in UTC with a `Z`
+/// suffix and as many fractional digits as needed (none, 3, 6 or 9).
+pub fn canonical_datetime(datetime: &DateTime<Utc>) -> String {
+    datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// A duration as SurrealDB writes it: its `y w d h m s ms µs ns` parts,
+/// largest first, skipping zero parts (`1h30m`); `0ns` when empty.
+pub fn canonical_duration(duration: Duration) -> String {
+    const UNITS: [(&str, u64); 6] = [
+        ("y", 365 * 86_400),
+        ("w", 7 * 86_400),
+        ("d", 86_400),
+        ("h", 3_600),
+        ("m", 60),
+        ("s", 1),
+    ];
+    const SUBSEC: [(&str, u32); 3] = [("ms", 1_000_000), ("µs", 1_000), ("ns", 1)];
+
+    let (mut secs, mut nanos) = (duration.as_secs(), duration.subsec_nanos());
+    if secs == 0 && nanos == 0 {
+        return "0ns".to_string();
+    }
+    let mut out = String::new();
+    for (unit, size) in UNITS {
+        if secs >= size {
+            out.push_str(&format!("{}{unit}", secs / size));
+            secs %= size;
+        }
+    }
+    for (unit, size) in SUBSEC {
+        if nanos >= size {
+            out.push_str(&format!("{}{unit}", nanos / size));
+            nanos %= size;
+        }
+    }
+    out
+}
+
 /// Compare two per-table hash maps and return the tables that disagree.
 /// A table missing on one side counts as a mismatch (paired with
 /// `empty_table_hash()` on the missing side).
@@ -206,4 +254,16 @@ mod tests {
         let names: Vec<&str> = diffs.iter().map(|d| d.table.as_str()).collect();
         assert_eq!(names, vec!["t2", "t3"]);
     }
+
+    #[test]
+    fn typed_values_use_surrealdb_json_forms() {
+        let t = DateTime::parse_from_rfc3339("2024-03-01T10:00:00+02:00").unwrap().to_utc();
+        assert_eq!(canonical_datetime(&t), "2024-03-01T08:00:00Z");
+        let t = t + chrono::Duration::milliseconds(250);
+        assert_eq!(canonical_datetime(&t), "2024-03-01T08:00:00.250Z");
+
+        assert_eq!(canonical_duration(Duration::ZERO), "0ns");
+        assert_eq!(canonical_duration(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(canonical_duration(Duration::new(8 * 86_400, 1_500)), "1w1d1µs500ns");
+    }
 }
@@ -0,0 +1,209 @@
+//! Order-independent hash of the records a view holds, sent to clients as
+//! `result_hash`.
+//!
+//! Each record contributes the blake3-256 digest of its full `table:id`
+//! key, a NUL byte and its version in decimal; the view hash is the XOR of
+//! those digests.
+//! XOR makes the hash independent of iteration order and lets the SSP
+//! update it in O(1) per change: folding a record in or out is the same
+//! operation. A client holding `(id, version)` pairs of the same records
+//! computes the same value with `hash_records`, so it can verify its
+//! local copy of a view against the server's.
+//!
+//! A view holds each key once, even when it is both one of the view's own
+//! records and a subquery record, so `hash_records` expects distinct keys:
+//! it skips repeats rather than folding them out again.
+//!
+//! Rows derived by the SSP rather than stored (aggregate groups) have no
+//! version that other processes agree on; they are hashed at the
+//! `content_version` of their content instead.
+//!
+//! Output is lowercase hex prefixed `b3x:`, to tell it apart from the
+//! `b3:` table hashes of `snapshot_hash`.
+
+const HASH_PREFIX: &str = "b3x:";
+
+/// An XOR set hash over `(id, version)` pairs. The empty set hashes to
+/// all zeros.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SetHash([u8; 32]);
+
+impl SetHash {
+    /// Fold the record `(id, version)` into the hash, or out of it if it
+    /// was already folded in.
+    pub fn toggle(&mut self, id: &str, version: i64) {
+        self.xor(&SetHash(*record_digest(id, version).as_bytes()));
+    }
+
+    /// Fold another set hash into this one. Shared with the `blake3_xor`
+    /// surrealism module so there is a single XOR implementation.
+    pub fn xor(&mut self, other: &SetHash) {
+        for (acc, byte) in self.0.iter_mut().zip(other.0) {
+            *acc ^= byte;
+        }
+    }
+
+    /// Parse 64 hex digits, with or without the `b3x:` prefix.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix(HASH_PREFIX).unwrap_or(hex);
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).ok()?;
+            *byte = u8::from_str_radix(pair, 16).ok()?;
+        }
+        Some(SetHash(bytes))
+    }
+
+    pub fn to_hex(&self) -> String {
+        format!("{}{}", HASH_PREFIX, self.to_bare_hex())
+    }
+
+    /// The hash as 64 lowercase hex digits, without the `b3x:` prefix.
+    pub fn to_bare_hex(&self) -> String {
+        let mut out = String::with_capacity(64);
+        for byte in self.0 {
+            out.push_str(&format!("{:02x}", byte));
+        }
+        out
+    }
+}
+
+/// Hash a set of `(id, version)` records. Iterator order does not matter;
+/// a key seen more than once is folded in only the first time.
+pub fn hash_records<I, S>(records: I) -> String
+where
+    I: IntoIterator<Item = (S, i64)>,
+    S: AsRef<str>,
+{
+    let mut hash = SetHash::default();
+    let mut seen = std::collections::HashSet::new();
+    for (id, version) in records {
+        if seen.insert(id.as_ref().to_string()) {
+            hash.toggle(id.as_ref(), version);
+        }
+    }
+    hash.to_hex()
+}
+
+/// Version a derived row is hashed at: the first 63 bits of the blake3
+/// digest of its content, with object keys sorted and the row's own
+/// `_00_rv` left out. Equal rows get equal versions in every process.
+pub fn content_version(row: &serde_json::Value) -> i64 {
+    let mut hasher = blake3::Hasher::new();
+    match row {
+        serde_json::Value::Object(map) => {
+            let fields = serde_json::Map::from_iter(
+                map.iter().filter(|(k, _)| *k != "_00_rv").map(|(k, v)| (k.clone(), v.clone())),
+            );
+            write_canonical(&mut hasher, &serde_json::Value::Object(fields));
+        }
+        other => write_canonical(&mut hasher, other),
+    }
+    let mut first = [0u8; 8];
+    first.copy_from_slice(&hasher.finalize().as_bytes()[..8]);
+    (u64::from_le_bytes(first) >> 1) as i64
+}
+
+/// Feed `value` to `hasher` as JSON with sorted object keys, whatever the
+/// map order of `serde_json`.
+fn write_canonical(hasher: &mut blake3::Hasher, value: &serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            hasher.update(b"{");
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    hasher.update(b",");
+                }
+                hasher.update(serde_json::Value::from(key.as_str()).to_string().as_bytes());
+                hasher.update(b":");
+                write_canonical(hasher, &map[key]);
+            }
+            hasher.update(b"}");
+        }
+        serde_json::Value::Array(items) => {
+            hasher.update(b"[");
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    hasher.update(b",");
+                }
+                write_canonical(hasher, item);
+            }
+            hasher.update(b"]");
+        }
+        other => {
+            hasher.update(other.to_string().as_bytes());
+        }
+    }
+}
+
+fn record_digest(id: &str, version: i64) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(id.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(version.to_string().as_bytes());
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_independent() {
+        let a = hash_records([("user:1", 1), ("user:2", 3), ("user:3", 2)]);
+        let b = hash_records([("user:3", 2), ("user:1", 1), ("user:2", 3)]);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn toggling_twice_removes_a_record() {
+        let mut hash = SetHash::default();
+        hash.toggle("user:1", 1);
+        hash.toggle("user:2", 1);
+        hash.toggle("user:2", 1);
+        assert_eq!(hash.to_hex(), hash_records([("user:1", 1)]));
+    }
+
+    #[test]
+    fn detects_version_change() {
+        assert_ne!(hash_records([("user:1", 1)]), hash_records([("user:1", 2)]));
+    }
+
+    #[test]
+    fn repeated_keys_are_folded_once() {
+        assert_eq!(
+            hash_records([("user:1", 1), ("user:2", 1), ("user:1", 1)]),
+            hash_records([("user:1", 1), ("user:2", 1)])
+        );
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let mut hash = SetHash::default();
+        hash.toggle("user:1", 1);
+        assert_eq!(SetHash::from_hex(&hash.to_hex()), Some(hash));
+        assert_eq!(SetHash::from_hex(&hash.to_bare_hex()), Some(hash));
+        assert_eq!(SetHash::from_hex("abc"), None);
+    }
+
+    #[test]
+    fn content_version_ignores_key_order_and_row_version() {
+        let a = serde_json::json!({"id": "g:1", "count": 2, "tags": [{"b": 1, "a": 2}], "_00_rv": 3});
+        let b = serde_json::json!({"tags": [{"a": 2, "b": 1}], "_00_rv": 9, "count": 2, "id": "g:1"});
+        assert_eq!(content_version(&a), content_version(&b));
+        assert!(content_version(&a) >= 0);
+        let c = serde_json::json!({"id": "g:1", "count": 3, "tags": [{"b": 1, "a": 2}]});
+        assert_ne!(content_version(&a), content_version(&c));
+    }
+
+    #[test]
+    fn empty_set_is_zero() {
+        let empty = hash_records(std::iter::empty::<(&str, i64)>());
+        assert_eq!(empty, format!("b3x:{}", "0".repeat(64)));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::Serialize;
 use serde_json::Value;
-use ssp::circuit::{Change, ChangeSet, Circuit, Operation, ViewDelta};
+use ssp::circuit::{Change, ChangeSet, Checkpoint, Circuit, Operation, SnapshotFormat, TreeNode, ViewDelta};
 use ssp::eval::normalize_record_id;
 use ssp::types::Sp00kyValue;
 use wasm_bindgen::prelude::*;
@@ -14,6 +14,16 @@ pub fn init() {
     web_sys::console::log_1(&format!("[ssp-wasm] v{} loaded", VERSION).into());
 }
 
+/// The `result_hash` of a view holding `records` (`[id, version][]`).
+/// Derived rows (aggregate groups) are hashed at the content version of
+/// their row rather than their `_00_rv` (see
+/// `ssp_protocol::view_hash::content_version`).
+#[wasm_bindgen]
+pub fn hash_view_records(records: JsValue) -> Result<String, JsValue> {
+    let records: Vec<(String, i64)> = serde_wasm_bindgen::from_value(records)?;
+    Ok(ssp_protocol::view_hash::hash_records(records))
+}
+
 #[wasm_bindgen]
 pub struct Sp00kyProcessor {
     circuit: Circuit,
@@ -37,7 +47,19 @@ struct WasmViewUpdate {
     query_id: String,
     result_hash: String,
     result_data: Vec<(String, i64)>,
+    /// Subquery records held by the view that are not in `result_data`;
+    /// `result_hash` covers them too.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    subquery_data: Vec<(String, i64)>,
     delta: WasmDelta,
+    /// Values of aggregate groups added or updated in this delta.
+    aggregate_rows: Vec<(String, Value)>,
+    /// Records with their subquery records nested below them (`tree` views).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tree: Vec<TreeNode>,
+    /// Content of added and updated records (views registered with `rows`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rows: Vec<(String, Value)>,
 }
 
 /// Transform a Vec<ViewDelta> to Vec<WasmViewUpdate> with versions from the store.
@@ -48,9 +70,9 @@ fn transform_deltas(deltas: &[ViewDelta], circuit: &Circuit) -> Vec<WasmViewUpda
         .collect()
 }
 
-/// Resolve version for a key from the store (defaults to 1).
-fn version_for(circuit: &Circuit, key: &str) -> i64 {
-    circuit.store.get_record_version_by_key(key).unwrap_or(1)
+/// Resolve version for a key as seen by a view (defaults to 1).
+fn version_for(circuit: &Circuit, query_id: &str, key: &str) -> i64 {
+    circuit.record_version(query_id, key).unwrap_or(1)
 }
 
 /// Transform a single ViewDelta to WasmViewUpdate.
@@ -58,13 +80,24 @@ fn transform_single_delta(delta: &ViewDelta, circuit: &Circuit) -> WasmViewUpdat
     let result_data: Vec<(String, i64)> = delta
         .records
         .iter()
-        .map(|key| (key.clone(), version_for(circuit, key)))
+        .map(|key| (key.clone(), version_for(circuit, &delta.query_id, key)))
         .collect();
 
+    let view = circuit.get_view(&delta.query_id);
+    let subquery_data: Vec<(String, i64)> = match view {
+        Some(view) if !delta.records.is_empty() => view
+            .subquery_cache
+            .keys()
+            .filter(|key| !view.cache.contains_key(key.as_str()))
+            .map(|key| (key.clone(), version_for(circuit, &delta.query_id, key)))
+            .collect(),
+        _ => vec![],
+    };
+
     let additions: Vec<WasmDeltaRecord> = delta
         .additions
         .iter()
-        .map(|key| WasmDeltaRecord(key.clone(), version_for(circuit, key)))
+        .map(|key| WasmDeltaRecord(key.clone(), version_for(circuit, &delta.query_id, key)))
         .collect();
 
     let removals: Vec<String> = delta.removals.clone();
@@ -72,18 +105,32 @@ fn transform_single_delta(delta: &ViewDelta, circuit: &Circuit) -> WasmViewUpdat
     let updates: Vec<WasmDeltaRecord> = delta
         .updates
         .iter()
-        .map(|key| WasmDeltaRecord(key.clone(), version_for(circuit, key)))
+        .map(|key| WasmDeltaRecord(key.clone(), version_for(circuit, &delta.query_id, key)))
+        .collect();
+
+    let aggregate_rows: Vec<(String, Value)> = delta
+        .aggregate_rows
+        .iter()
+        .map(|agg| (agg.key.clone(), Value::from(agg.row.clone())))
         .collect();
 
     WasmViewUpdate {
         query_id: delta.query_id.clone(),
         result_hash: delta.result_hash.clone(),
         result_data,
+        subquery_data,
         delta: WasmDelta {
             additions,
             removals,
             updates,
         },
+        aggregate_rows,
+        tree: delta.tree.clone(),
+        rows: delta
+            .rows
+            .iter()
+            .map(|(key, row)| (key.clone(), Value::from(row.clone())))
+            .collect(),
     }
 }
 
@@ -94,11 +141,25 @@ export interface WasmViewUpdate {
   query_id: string;
   result_hash: string;
   result_data: [string, number][];
+  /** Subquery records of the view not in `result_data`; covered by `result_hash`. */
+  subquery_data?: [string, number][];
   delta: {
     additions: [string, number][];
     removals: string[];
     updates: [string, number][];
   };
+  aggregate_rows: [string, Record<string, any>][];
+  /** Only for views registered with format 'tree'. */
+  tree?: WasmTreeNode[];
+  /** Content of added and updated records, as projected by the query.
+   *  Only for views registered with `rows: true`. */
+  rows?: [string, Record<string, any>][];
+}
+
+export interface WasmTreeNode {
+  id: string;
+  /** Related records by subquery alias. */
+  children?: Record<string, WasmTreeNode[]>;
 }
 
 export interface WasmViewConfig {
@@ -110,6 +171,7 @@ export interface WasmViewConfig {
   lastActiveAt: string;
   safe_params?: Record<string, any>;
   format?: 'flat' | 'tree' | 'streaming';
+  rows?: boolean;
 }
 
 export interface WasmIngestItem {
@@ -124,9 +186,21 @@ export interface WasmIngestItem {
 impl Sp00kyProcessor {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Sp00kyProcessor {
-        Sp00kyProcessor {
-            circuit: Circuit::new(),
-        }
+        let mut circuit = Circuit::new();
+        circuit.tick(js_sys::Date::now() as i64);
+        Sp00kyProcessor { circuit }
+    }
+
+    /// Advance the clock behind `time::now()` (Unix milliseconds, defaults
+    /// to `Date.now()`). Returns the updates of views whose time windows
+    /// gained or lost records.
+    pub fn tick(&mut self, now: Option<f64>) -> Result<JsValue, JsValue> {
+        let now = now.unwrap_or_else(js_sys::Date::now) as i64;
+        let deltas = self.circuit.tick(now);
+        let wasm_updates = transform_deltas(&deltas, &self.circuit);
+
+        let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+        Ok(wasm_updates.serialize(&serializer)?)
     }
 
     /// Ingest a record into the stream processor
@@ -140,9 +214,8 @@ impl Sp00kyProcessor {
         let record: Value = serde_wasm_bindgen::from_value(record)
             .map_err(|e| JsValue::from_str(&format!("Failed to parse record: {}", e)))?;
 
-        // Normalize the record and convert to new Sp00kyValue
-        let clean_record = ssp::sanitizer::normalize_record(record);
-        let clean_sv: Sp00kyValue = clean_record.into();
+        // Normalize the record, recovering SurrealDB types from its JSON
+        let clean_sv = Sp00kyValue::from_wire(record);
 
         let record_id = clean_sv
             .get("id")
@@ -188,9 +261,13 @@ impl Sp00kyProcessor {
             .map_err(|e| JsValue::from_str(&format!("Registration failed: {}", e)))?;
 
         let plan_id = data.plan.id.clone();
-        let initial_delta = self
-            .circuit
-            .add_query(data.plan, data.safe_params, data.format);
+        let initial_delta = if data.rows {
+            self.circuit
+                .add_query_with_rows(data.plan, data.safe_params, data.format)
+        } else {
+            self.circuit
+                .add_query(data.plan, data.safe_params, data.format)
+        };
 
         let wasm_result = match initial_delta {
             Some(ref delta) => transform_single_delta(delta, &self.circuit),
@@ -198,11 +275,15 @@ impl Sp00kyProcessor {
                 query_id: plan_id,
                 result_hash: String::new(),
                 result_data: vec![],
+                subquery_data: vec![],
                 delta: WasmDelta {
                     additions: vec![],
                     removals: vec![],
                     updates: vec![],
                 },
+                aggregate_rows: vec![],
+                tree: vec![],
+                rows: vec![],
             },
         };
 
@@ -215,6 +296,18 @@ impl Sp00kyProcessor {
         self.circuit.remove_query(&id);
     }
 
+    /// EXPLAIN a registered view: its operator tree and state sizes. With
+    /// `analyze`, also per-node step counts, rows and times.
+    pub fn explain(&self, id: String, analyze: Option<bool>) -> Result<JsValue, JsValue> {
+        let explain = self
+            .circuit
+            .explain(&id, analyze.unwrap_or(false))
+            .ok_or_else(|| JsValue::from_str(&format!("View not found: {}", id)))?;
+
+        let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+        Ok(explain.serialize(&serializer)?)
+    }
+
     /// Save the current circuit state as a JSON string
     pub fn save_state(&self) -> Result<String, JsValue> {
         self.circuit
@@ -222,12 +315,61 @@ impl Sp00kyProcessor {
             .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
     }
 
-    /// Load circuit state from a JSON string
-    pub fn load_state(&mut self, state: String) -> Result<(), JsValue> {
-        let circuit = Circuit::restore(&state)
+    /// Save the current circuit state as a binary snapshot (a `Uint8Array`)
+    pub fn save_state_binary(&self) -> Result<Vec<u8>, JsValue> {
+        self.circuit
+            .save_bytes()
+            .map_err(|e| JsValue::from_str(&format!("Failed to serialize state: {}", e)))
+    }
+
+    /// Load circuit state saved by `save_state` (string) or
+    /// `save_state_binary` (bytes). Snapshots of older versions are migrated.
+    pub fn load_state(&mut self, state: JsValue) -> Result<(), JsValue> {
+        let bytes = match state.as_string() {
+            Some(json) => json.into_bytes(),
+            None => js_sys::Uint8Array::new(&state).to_vec(),
+        };
+        let circuit = Circuit::restore_from(bytes.as_slice())
             .map_err(|e| JsValue::from_str(&format!("Failed to deserialize state: {}", e)))?;
 
         self.circuit = circuit;
         Ok(())
     }
+
+    /// Write a checkpoint: the changes since the previous one, or the full
+    /// state when compaction is due. Returns `{ kind, seq, base, data }`,
+    /// `kind` being `"base"` or `"delta"`; after a base, earlier
+    /// checkpoints can be dropped.
+    pub fn checkpoint(&mut self) -> Result<JsValue, JsValue> {
+        let mut data = Vec::new();
+        let written = self
+            .circuit
+            .checkpoint(&mut data, SnapshotFormat::Binary)
+            .map_err(|e| JsValue::from_str(&format!("Failed to write checkpoint: {}", e)))?;
+        let (kind, base) = match written {
+            Checkpoint::Base { seq } => ("base", seq),
+            Checkpoint::Delta { base, .. } => ("delta", base),
+        };
+
+        let result = js_sys::Object::new();
+        js_sys::Reflect::set(&result, &"kind".into(), &kind.into())?;
+        js_sys::Reflect::set(&result, &"seq".into(), &(written.seq() as f64).into())?;
+        js_sys::Reflect::set(&result, &"base".into(), &(base as f64).into())?;
+        js_sys::Reflect::set(&result, &"data".into(), &js_sys::Uint8Array::from(data.as_slice()))?;
+        Ok(result.into())
+    }
+
+    /// Restore from a base checkpoint and the deltas written after it.
+    pub fn load_checkpoints(&mut self, base: JsValue, deltas: js_sys::Array) -> Result<(), JsValue> {
+        let base = js_sys::Uint8Array::new(&base).to_vec();
+        let deltas: Vec<Vec<u8>> = deltas
+            .iter()
+            .map(|delta| js_sys::Uint8Array::new(&delta).to_vec())
+            .collect();
+        let circuit = Circuit::restore_checkpoints(base.as_slice(), deltas.iter().map(Vec::as_slice))
+            .map_err(|e| JsValue::from_str(&format!("Failed to restore checkpoints: {}", e)))?;
+
+        self.circuit = circuit;
+        Ok(())
+    }
 }
@@ -133,6 +133,22 @@ describe('State Persistence (save_state / load_state)', () => {
     expect(parsed).not.toBeNull();
   });
 
+  it('should save and restore a binary snapshot', () => {
+    const processor = new Sp00kyProcessor();
+    const user = makeUserRecord('alice', 'alice@test.com');
+    processor.ingest('user', 'CREATE', user.id, user.record);
+    const config = createViewConfig('persist-binary', 'SELECT * FROM user');
+    const originalHash = (processor.register_view(config) as WasmViewUpdate).result_hash;
+
+    const state = processor.save_state_binary();
+    expect(state).toBeInstanceOf(Uint8Array);
+
+    const restored = new Sp00kyProcessor();
+    restored.load_state(state);
+    const restoredView = restored.register_view(config) as WasmViewUpdate;
+    expect(restoredView.result_hash).toBe(originalHash);
+  });
+
   it('should throw when loading corrupted state', () => {
     const processor = new Sp00kyProcessor();
     expect(() => processor.load_state('not-valid-json')).toThrow();
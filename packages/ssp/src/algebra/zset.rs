@@ -1,8 +1,9 @@
 use super::weight::Weight;
 use std::collections::HashMap;
 
-/// A row key is a string in the format "table:id".
-pub type RowKey = String;
+/// A row key is a string in the format "table:id". Clones share one
+/// allocation; the store keeps one key per row (see `Collection::key`).
+pub type RowKey = smol_str::SmolStr;
 
 /// A Z-set: a map from keys to integer weights.
 ///
@@ -88,7 +89,7 @@ mod tests {
     use super::*;
 
     fn zset(items: &[(&str, i64)]) -> ZSet {
-        items.iter().map(|(k, w)| (k.to_string(), *w)).collect()
+        items.iter().map(|(k, w)| ((*k).into(), *w)).collect()
     }
 
     #[test]
@@ -0,0 +1,369 @@
+//! Delta checkpoints: persist only what changed since the previous
+//! checkpoint, on top of a full state written now and then.
+//!
+//! Every checkpoint has a sequence number. A full state (a base) records
+//! its own number, and each delta records the base it builds on and its
+//! own number. Restoring reads the base and replays its deltas in order;
+//! deltas of other bases are stale and skipped.
+
+use crate::circuit::snapshot::{CircuitState, QueryState, QueryStateRef, VERSION};
+use crate::circuit::store::Store;
+use crate::circuit::view::View;
+use crate::types::{make_key, raw_id, Sp00kyValue};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// When `Circuit::checkpoint` writes a full state instead of a delta.
+#[derive(Debug, Clone, Copy)]
+pub struct CheckpointPolicy {
+    /// Deltas written on top of one base before the next checkpoint
+    /// compacts them into a new base.
+    pub max_deltas: usize,
+    /// Compact once this fraction of all rows changed since the last
+    /// checkpoint, as the delta would be about as large as the state.
+    pub max_changed_ratio: f64,
+}
+
+impl Default for CheckpointPolicy {
+    fn default() -> Self {
+        CheckpointPolicy {
+            max_deltas: 32,
+            max_changed_ratio: 0.5,
+        }
+    }
+}
+
+/// What `Circuit::checkpoint` wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checkpoint {
+    /// The full state. Checkpoints before it are no longer needed.
+    Base { seq: u64 },
+    /// The changes since the previous checkpoint, on top of base `base`.
+    Delta { base: u64, seq: u64 },
+}
+
+impl Checkpoint {
+    pub fn seq(&self) -> u64 {
+        match self {
+            Checkpoint::Base { seq } | Checkpoint::Delta { seq, .. } => *seq,
+        }
+    }
+}
+
+/// Checkpoint bookkeeping of a circuit: what changed since the last
+/// checkpoint, and where the chain of checkpoints stands.
+#[derive(Debug, Default)]
+pub(crate) struct Checkpoints {
+    pub policy: CheckpointPolicy,
+    /// Sequence number of the last checkpoint.
+    pub seq: u64,
+    /// Base the last checkpoint builds on, and the deltas written on top of
+    /// it. `None` until a base is written or restored, so the first
+    /// checkpoint is always a full state.
+    chain: Option<(u64, usize)>,
+    /// Ids of changed rows, by table, as they were passed in.
+    rows: HashMap<String, HashSet<String>>,
+    views: HashSet<String>,
+    removed: HashSet<String>,
+}
+
+impl Checkpoints {
+    /// Bookkeeping of a state that includes checkpoint `seq` but continues
+    /// no chain: the next checkpoint is a base.
+    pub fn after(seq: u64) -> Self {
+        Checkpoints {
+            seq,
+            ..Self::default()
+        }
+    }
+
+    pub fn row(&mut self, table: &str, id: &str) {
+        if let Some(ids) = self.rows.get_mut(table) {
+            ids.insert(id.to_string());
+        } else {
+            self.rows.insert(table.to_string(), HashSet::from([id.to_string()]));
+        }
+    }
+
+    pub fn view(&mut self, id: &str) {
+        self.removed.remove(id);
+        self.views.insert(id.to_string());
+    }
+
+    pub fn remove_view(&mut self, id: &str) {
+        self.views.remove(id);
+        self.removed.insert(id.to_string());
+    }
+
+    /// Continue the chain a state restored with its deltas belongs to.
+    pub fn restored(&mut self, base: u64, seq: u64) {
+        self.seq = seq;
+        self.chain = Some((base, (seq - base) as usize));
+        self.clear();
+    }
+
+    /// End the chain: the next checkpoint is a full state, for a change
+    /// deltas do not carry.
+    pub fn rebase(&mut self) {
+        self.chain = None;
+    }
+
+    /// Base of the chain of checkpoints the circuit's state continues.
+    pub fn base(&self) -> u64 {
+        self.chain.map_or(self.seq, |(base, _)| base)
+    }
+
+    /// Whether the next checkpoint should be a full state.
+    pub fn compaction_due(&self, total_rows: usize) -> bool {
+        let Some((_, deltas)) = self.chain else {
+            return true;
+        };
+        let changed: usize = self.rows.values().map(HashSet::len).sum();
+        deltas >= self.policy.max_deltas
+            || changed as f64 > self.policy.max_changed_ratio * total_rows.max(1) as f64
+    }
+
+    /// The changes since the last checkpoint, as the next delta.
+    pub fn delta<'a>(
+        &'a self,
+        store: &'a Store,
+        views: &'a HashMap<String, View>,
+        now: i64,
+    ) -> DeltaStateRef<'a> {
+        let (base, _) = self.chain.expect("a delta builds on a base");
+        let collections = self
+            .rows
+            .iter()
+            .map(|(table, ids)| {
+                let coll = store.get_collection(table);
+                let rows = ids
+                    .iter()
+                    .map(|id| {
+                        let key = make_key(table, id);
+                        let row = RowState {
+                            weight: coll.and_then(|c| c.zset.get(key.as_str())).copied().unwrap_or(0),
+                            data: coll.and_then(|c| c.get_row(id)),
+                            key,
+                        };
+                        (raw_id(id), row)
+                    })
+                    .collect();
+                (table.as_str(), rows)
+            })
+            .collect();
+        DeltaStateRef {
+            kind: "delta",
+            version: VERSION,
+            base,
+            seq: self.seq + 1,
+            now,
+            collections,
+            queries: self
+                .views
+                .iter()
+                .filter_map(|id| views.get(id))
+                .map(QueryStateRef::new)
+                .collect(),
+            removed: self.removed.iter().map(String::as_str).collect(),
+        }
+    }
+
+    /// Record that checkpoint `seq + 1` was written.
+    pub fn written(&mut self, base: bool) -> Checkpoint {
+        self.seq += 1;
+        self.clear();
+        match (&mut self.chain, base) {
+            (Some((base, deltas)), false) => {
+                *deltas += 1;
+                Checkpoint::Delta {
+                    base: *base,
+                    seq: self.seq,
+                }
+            }
+            (chain, _) => {
+                *chain = Some((self.seq, 0));
+                Checkpoint::Base { seq: self.seq }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.rows.clear();
+        self.views.clear();
+        self.removed.clear();
+    }
+}
+
+/// A changed row: its Z-set weight and contents (`None` if deleted).
+#[derive(Serialize, Deserialize)]
+pub(crate) struct RowState<V> {
+    key: String,
+    weight: i64,
+    data: Option<V>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct DeltaStateRef<'a> {
+    kind: &'static str,
+    version: u16,
+    base: u64,
+    seq: u64,
+    now: i64,
+    collections: HashMap<&'a str, HashMap<&'a str, RowState<&'a Sp00kyValue>>>,
+    queries: Vec<QueryStateRef<'a>>,
+    removed: Vec<&'a str>,
+}
+
+/// A delta checkpoint, as read back.
+#[derive(Deserialize)]
+pub(crate) struct DeltaState {
+    pub base: u64,
+    pub seq: u64,
+    now: i64,
+    collections: HashMap<String, HashMap<String, RowState<Sp00kyValue>>>,
+    queries: Vec<QueryState>,
+    removed: Vec<String>,
+}
+
+impl DeltaState {
+    /// Replay the delta on top of `state`.
+    pub fn apply(self, state: &mut CircuitState) {
+        for (table, rows) in self.collections {
+            let coll = state.store.ensure_collection(&table);
+            for (id, row) in rows {
+                match row.data {
+                    Some(data) => coll.rows.insert(id.into(), data),
+                    None => coll.rows.remove(id.as_str()),
+                };
+                if row.weight == 0 {
+                    coll.zset.remove(row.key.as_str());
+                } else {
+                    coll.zset.insert(row.key.into(), row.weight);
+                }
+            }
+        }
+
+        state
+            .queries
+            .retain(|q| !self.removed.contains(&q.plan.id));
+        for query in self.queries {
+            match state.queries.iter_mut().find(|q| q.plan.id == query.plan.id) {
+                Some(existing) => *existing = query,
+                None => state.queries.push(query),
+            }
+        }
+        state.now = Some(self.now);
+        state.seq = self.seq;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{Change, ChangeSet, Circuit, Record, SnapshotError, SnapshotFormat};
+    use crate::operator::QueryPlan;
+    use serde_json::json;
+
+    fn plan(id: &str, sql: &str) -> QueryPlan {
+        let root = crate::converter::convert_surql_to_dbsp(sql).unwrap();
+        QueryPlan { id: id.to_string(), root: serde_json::from_value(root).unwrap() }
+    }
+
+    fn step(circuit: &mut Circuit, changes: Vec<Change>) {
+        circuit.step(ChangeSet { changes });
+    }
+
+    fn checkpoint(circuit: &mut Circuit, format: SnapshotFormat) -> (Checkpoint, Vec<u8>) {
+        let mut bytes = Vec::new();
+        let written = circuit.checkpoint(&mut bytes, format).unwrap();
+        (written, bytes)
+    }
+
+    fn assert_same(a: &Circuit, b: &Circuit) {
+        let mut ids = a.view_ids();
+        ids.sort();
+        let mut other = b.view_ids();
+        other.sort();
+        assert_eq!(ids, other);
+        for id in &ids {
+            let (x, y) = (a.get_view(id).unwrap(), b.get_view(id).unwrap());
+            assert_eq!((&x.cache, &x.last_hash), (&y.cache, &y.last_hash));
+        }
+        assert_eq!(a.compute_table_hashes(), b.compute_table_hashes());
+        assert_eq!(a.now(), b.now());
+    }
+
+    #[test]
+    fn deltas_replay_onto_their_base() {
+        let mut circuit = Circuit::new();
+        circuit.load((0..100).map(|i| Record::new("task", &format!("task:{}", i), json!({"done": i % 2 == 0}))));
+        circuit.add_query(plan("open", "SELECT * FROM task WHERE done = false"), None, None);
+        circuit.add_query(plan("all", "SELECT * FROM task"), None, None);
+
+        let (first, base) = checkpoint(&mut circuit, SnapshotFormat::Binary);
+        assert_eq!(first, Checkpoint::Base { seq: 1 });
+
+        step(&mut circuit, vec![
+            Change::update("task", "task:1", json!({"done": true})),
+            Change::delete("task", "task:2"),
+            Change::create("task", "task:100", json!({"done": false})),
+        ]);
+        circuit.remove_query("all");
+        let (second, delta) = checkpoint(&mut circuit, SnapshotFormat::Binary);
+        assert_eq!(second, Checkpoint::Delta { base: 1, seq: 2 });
+        assert!(delta.len() * 2 < base.len());
+
+        circuit.add_query(plan("done", "SELECT * FROM task WHERE done = true"), None, None);
+        step(&mut circuit, vec![Change::update("task", "task:3", json!({"done": false}))]);
+        circuit.tick(circuit.now() + 1_000);
+        let (third, json) = checkpoint(&mut circuit, SnapshotFormat::Json);
+        assert_eq!(third, Checkpoint::Delta { base: 1, seq: 3 });
+
+        // Deltas are ordered by sequence number, not by argument order.
+        let mut restored = Circuit::restore_checkpoints(base.as_slice(), [json.as_slice(), delta.as_slice()]).unwrap();
+        assert_same(&circuit, &restored);
+
+        // The restored circuit continues the chain and keeps maintaining views.
+        let change = || vec![Change::create("task", "task:101", json!({"done": false}))];
+        step(&mut circuit, change());
+        step(&mut restored, change());
+        assert_same(&circuit, &restored);
+        let (next, _) = checkpoint(&mut restored, SnapshotFormat::Binary);
+        assert_eq!(next, Checkpoint::Delta { base: 1, seq: 4 });
+    }
+
+    #[test]
+    fn compaction_starts_a_new_base() {
+        let mut circuit = Circuit::new();
+        circuit.set_checkpoint_policy(CheckpointPolicy { max_deltas: 2, ..CheckpointPolicy::default() });
+        circuit.load((0..10).map(|i| Record::new("task", &format!("task:{}", i), json!({"n": i}))));
+        circuit.add_query(plan("all", "SELECT * FROM task"), None, None);
+
+        let mut written = Vec::new();
+        for i in 0..4 {
+            step(&mut circuit, vec![Change::update("task", &format!("task:{}", i), json!({"n": -i}))]);
+            written.push(checkpoint(&mut circuit, SnapshotFormat::Binary));
+        }
+        let kinds: Vec<Checkpoint> = written.iter().map(|(c, _)| *c).collect();
+        assert_eq!(kinds, vec![
+            Checkpoint::Base { seq: 1 },
+            Checkpoint::Delta { base: 1, seq: 2 },
+            Checkpoint::Delta { base: 1, seq: 3 },
+            Checkpoint::Base { seq: 4 },
+        ]);
+
+        // Changing most rows at once compacts early.
+        step(&mut circuit, (0..10).map(|i| Change::update("task", &format!("task:{}", i), json!({"n": 0}))).collect());
+        assert_eq!(checkpoint(&mut circuit, SnapshotFormat::Binary).0, Checkpoint::Base { seq: 5 });
+
+        // Deltas of an older base are stale.
+        let all: Vec<&[u8]> = written.iter().map(|(_, bytes)| bytes.as_slice()).collect();
+        let restored = Circuit::restore_checkpoints(all[3], all.clone()).unwrap();
+        assert_eq!(restored.get_view("all").unwrap().cache.len(), 10);
+
+        // A gap in the deltas is an error.
+        let err = Circuit::restore_checkpoints(all[0], [all[2]]).err().unwrap();
+        assert!(matches!(err, SnapshotError::Decode(_)));
+        assert!(matches!(Circuit::restore_from(all[1]), Err(SnapshotError::Decode(_))));
+    }
+}
@@ -1,10 +1,17 @@
-use crate::algebra::ZSet;
-use crate::circuit::graph::Graph;
+use crate::algebra::{RowKey, ZSet, ZSetOps};
+use crate::circuit::explain::Explain;
+use crate::circuit::graph::{Graph, Node, NodeId};
+use crate::circuit::memory::MemoryReport;
+use crate::circuit::checkpoint::{Checkpoint, CheckpointPolicy, Checkpoints};
+use crate::circuit::snapshot::{self, CircuitState, CircuitStateRef, QueryStateRef, Snapshot, SnapshotError, SnapshotFormat};
 use crate::circuit::store::{ChangeSet, Operation, Record, Store};
-use crate::circuit::view::{OutputFormat, View};
-use crate::operator::QueryPlan;
+use crate::circuit::view::{OutputFormat, SubqueryEdges, TreeNode, Versions, View};
+use crate::eval::resolve_field;
+use crate::operator::{optimize, GraphStep, Map, OperatorPlan, Projection, QueryPlan};
+use crate::permissions::{is_record_user, Permissions};
 use crate::types::{make_key, raw_id, Sp00kyValue};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
 
 /// Operation type for a subquery record delta.
 #[derive(Debug, Clone, PartialEq)]
@@ -27,6 +34,20 @@ pub struct SubqueryDeltaItem {
     pub op: SubqueryOp,
 }
 
+/// Row content for a key synthesized by a grouping view (GROUP BY or
+/// SELECT DISTINCT), or for a record projected with computed columns.
+///
+/// Group keys (`_00_agg:<hash>`) have no backing record in the store, and
+/// computed columns are not stored, so their values travel with the delta.
+#[derive(Debug, Clone)]
+pub struct AggregateRow {
+    /// The group key (e.g., "_00_agg:9f2c...") or record key.
+    pub key: String,
+    /// Group-by fields and aggregate columns, or the projected fields;
+    /// plus `id` and `_00_rv`.
+    pub row: Sp00kyValue,
+}
+
 /// Output from a materialized view after a step.
 #[derive(Debug, Clone)]
 pub struct ViewDelta {
@@ -37,12 +58,22 @@ pub struct ViewDelta {
     pub removals: Vec<String>,
     /// Keys whose content changed but remain in the view.
     pub updates: Vec<String>,
-    /// All keys currently in the view (for flat/tree modes).
+    /// All keys currently in the view; empty for `Streaming` views.
     pub records: Vec<String>,
+    /// The view's records with their subquery records nested below them;
+    /// `Tree` views only.
+    pub tree: Vec<TreeNode>,
+    /// Content of the added and updated records, as the query projects
+    /// them, and of added or updated subquery records; only for queries
+    /// registered with `add_query_with_rows`.
+    pub rows: Vec<(String, Sp00kyValue)>,
     /// Hash of the current view state.
     pub result_hash: String,
     /// Subquery record changes (additions/updates/removals for child records).
     pub subquery_items: Vec<SubqueryDeltaItem>,
+    /// Current values of added or updated groups (GROUP BY / DISTINCT) and
+    /// of records with computed projections.
+    pub aggregate_rows: Vec<AggregateRow>,
 }
 
 /// The DBSP incremental computation circuit.
@@ -52,23 +83,49 @@ pub struct ViewDelta {
 /// updates all affected materialized views and returns their deltas.
 pub struct Circuit {
     pub store: Store,
-    /// One operator DAG per registered query.
-    graphs: HashMap<String, Graph>,
+    /// Operator DAG shared by all registered queries: identical sub-plans
+    /// (with identical parameters) are a single node.
+    graph: Graph,
+    /// Output node of each registered query.
+    outputs: HashMap<String, NodeId>,
     /// View output state per query.
     views: HashMap<String, View>,
     /// Routing: table_name → [query_id].
     dependency_map: HashMap<String, Vec<String>>,
+    /// Clock behind `time::now()`, Unix milliseconds. Moved by `tick()`.
+    now: i64,
+    /// Table `select` rules ANDed into queries registered from now on.
+    permissions: Permissions,
+    /// Changes since the last checkpoint.
+    checkpoints: Checkpoints,
+}
+
+/// Wall-clock time in Unix milliseconds. There is no system clock on
+/// wasm32; the host sets the time with `Circuit::tick`.
+fn system_now() -> i64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        0
+    }
 }
 
 /// Compute the full set of subquery records visible through the current view.
 ///
-/// Returns: child_key → (parent_key, alias)
+/// Returns every parent → child link (see `SubqueryEdges`).
 /// This operates as a side-channel alongside the main Z-set pipeline.
 fn compute_current_subquery_set(
     store: &Store,
     view: &View,
-) -> HashMap<String, (String, String)> {
-    let mut result = HashMap::new();
+    permissions: &Permissions,
+) -> SubqueryEdges {
+    let mut result = SubqueryEdges::new();
 
     let subquery_infos = view.plan.root.subquery_projection_info();
 
@@ -107,7 +164,7 @@ fn compute_current_subquery_set(
                 let child_raw = raw_id(child_full_key);
                 if collection.rows.contains_key(child_raw) {
                     let child_key = make_key(subquery_table, child_raw);
-                    result.insert(child_key, (parent_full_key.clone(), alias.clone()));
+                    link(&mut result, child_key, parent_full_key.to_string(), alias);
                 }
             }
             continue;
@@ -121,11 +178,17 @@ fn compute_current_subquery_set(
 
             if view.cache.contains_key(fk_value) {
                 let child_key = make_key(subquery_table, child_raw_id);
-                result.insert(child_key, (fk_value.to_string(), alias.clone()));
+                link(&mut result, child_key, fk_value.to_string(), alias);
             }
         }
     }
 
+    // Records reached by pass 1, the parents of nested subqueries
+    let level_one: HashSet<String> = result
+        .values()
+        .flat_map(|linked| linked.values().flatten().cloned())
+        .collect();
+
     // Pass 2: Nested subqueries (parent_table = Some) — parent is a subquery item
     for (alias, subquery_table, parent_key_opt, parent_table_opt) in &subquery_infos {
         let pt = match parent_table_opt {
@@ -146,17 +209,17 @@ fn compute_current_subquery_set(
             None => continue,
         };
 
-        // Build index: parent's parent_field value → parent full key
+        // Build index: parent's parent_field value → parent full keys
         // Only for parent rows already in the result set (level-1 items)
-        let mut parent_field_index: HashMap<String, String> = HashMap::new();
+        let mut parent_field_index: HashMap<String, Vec<String>> = HashMap::new();
         for (parent_raw_id, parent_row_data) in &parent_coll.rows {
             let parent_full_key = make_key(pt, parent_raw_id);
-            if result.contains_key(&parent_full_key) {
+            if level_one.contains(&parent_full_key) {
                 if let Some(val) = parent_row_data
                     .get(&parent_key.parent_field)
                     .and_then(|v| v.as_str())
                 {
-                    parent_field_index.insert(val.to_string(), parent_full_key);
+                    parent_field_index.entry(val.to_string()).or_default().push(parent_full_key);
                 }
             }
         }
@@ -168,16 +231,130 @@ fn compute_current_subquery_set(
                     Some(v) => v,
                     None => continue,
                 };
-            if let Some(parent_full_key) = parent_field_index.get(child_value) {
+            for parent_full_key in parent_field_index.get(child_value).into_iter().flatten() {
                 let child_key = make_key(subquery_table, child_raw_id);
-                result.insert(child_key, (parent_full_key.clone(), alias.clone()));
+                link(&mut result, child_key, parent_full_key.clone(), alias);
+            }
+        }
+    }
+
+    // Pass 3: Graph traversals and record links from records in the view
+    for projection in view.plan.root.root_projections() {
+        match projection {
+            Projection::Graph { alias, steps } => {
+                for (child_key, parent_key) in traverse(store, view.cache.keys(), steps) {
+                    link(&mut result, child_key, parent_key, alias);
+                }
+            }
+            Projection::Link { alias, field } => {
+                for parent_key in view.cache.keys() {
+                    let target = store
+                        .get_row_by_key(parent_key)
+                        .and_then(|row| record_link(resolve_field(Some(row), field)));
+                    if let Some(target) = target.filter(|t| store.get_row_by_key(t).is_some()) {
+                        link(&mut result, target, parent_key.to_string(), alias);
+                    }
+                }
             }
+            _ => {}
         }
     }
 
+    for linked in result.values_mut() {
+        for children in linked.values_mut() {
+            children.retain(|child_key| permissions.allows(store, child_key, view.params.as_ref()));
+        }
+        linked.retain(|_, children| !children.is_empty());
+    }
+    result.retain(|_, linked| !linked.is_empty());
     result
 }
 
+/// Record that `parent` links to `child` under `alias`.
+fn link(edges: &mut SubqueryEdges, child: String, parent: String, alias: &str) {
+    edges
+        .entry(parent)
+        .or_default()
+        .entry(alias.to_string())
+        .or_default()
+        .insert(child);
+}
+
+/// The record key a link value names (`"user:1"`).
+fn record_link(value: Option<&Sp00kyValue>) -> Option<String> {
+    value
+        .and_then(|v| v.as_str())
+        .filter(|s| crate::types::parse_key(s).is_some())
+        .map(str::to_string)
+}
+
+/// Follow graph `steps` out of each of `starts`.
+///
+/// Returns: reached record key → the start it was reached from. Each hop
+/// looks up the edges of the records reached so far in the edge table's
+/// index (see `Store::index_edges`); an edge counts only if its target
+/// record exists in the hop's table.
+fn traverse<'a>(
+    store: &Store,
+    starts: impl IntoIterator<Item = &'a RowKey>,
+    steps: &[GraphStep],
+) -> HashMap<String, String> {
+    let mut frontier: HashMap<String, String> =
+        starts.into_iter().map(|k| (k.to_string(), k.to_string())).collect();
+    for step in steps {
+        let (from, to) = step.endpoints();
+        let mut next = HashMap::new();
+        let Some(edges) = store.get_collection(&step.edge) else {
+            return next;
+        };
+        for (key, start) in &frontier {
+            for edge in edges.edges_from(from, key) {
+                let Some(target) = record_link(edge.get(to)) else {
+                    continue;
+                };
+                let in_table =
+                    crate::types::parse_key(&target).is_some_and(|(t, _)| t == step.table);
+                if in_table && store.get_row_by_key(&target).is_some() {
+                    next.insert(target, start.clone());
+                }
+            }
+        }
+        frontier = next;
+    }
+    frontier
+}
+
+/// Edge tables the graph projections of `view` traverse.
+fn edge_tables(view: &View) -> impl Iterator<Item = &str> {
+    view.plan.root.root_projections().into_iter().flat_map(|projection| match projection {
+        Projection::Graph { steps, .. } => steps.iter().map(|s| s.edge.as_str()).collect(),
+        _ => Vec::new(),
+    })
+}
+
+/// Tables named by the record links of `Projection::Link` columns. They are
+/// only known from the data, so views start depending on them as links
+/// appear.
+fn link_tables(store: &Store, view: &View) -> Vec<String> {
+    let mut tables = Vec::new();
+    for projection in view.plan.root.root_projections() {
+        let Projection::Link { field, .. } = projection else {
+            continue;
+        };
+        for parent_key in view.cache.keys() {
+            let target = store
+                .get_row_by_key(parent_key)
+                .and_then(|row| record_link(resolve_field(Some(row), field)));
+            if let Some((table, _)) = target.as_deref().and_then(crate::types::parse_key) {
+                if !tables.iter().any(|t| t == table) {
+                    tables.push(table.to_string());
+                }
+            }
+        }
+    }
+    tables
+}
+
 /// Diff two subquery sets and produce delta items.
 fn diff_subquery_sets(
     old: &HashMap<String, (String, String)>,
@@ -231,24 +408,171 @@ fn diff_subquery_sets(
     items
 }
 
+/// Evaluate one pass over the graph in topological order.
+///
+/// Scan nodes of the tables in `table_deltas` receive their table's delta;
+/// only nodes downstream of them are stepped. Returns each node's output
+/// delta, `None` for nodes that were not stepped.
+///
+/// Nodes are stepped level by level (see `Graph::levels`); the nodes of a
+/// level run concurrently with the `parallel` feature.
+fn run_graph(
+    graph: &mut Graph,
+    store: &Store,
+    table_deltas: &HashMap<String, ZSet>,
+) -> Vec<Option<ZSet>> {
+    let mut node_outputs: Vec<Option<ZSet>> = vec![None; graph.node_count()];
+    let empty_delta: ZSet = HashMap::new();
+    // Input deltas of templated filters, split by binding (see `Template`).
+    let mut routed: HashMap<usize, HashMap<NodeId, ZSet>> = HashMap::new();
+
+    for level in graph.levels() {
+        // A binding's part of its template's input, by node.
+        let mut parts: HashMap<NodeId, ZSet> = HashMap::new();
+        for &node_id in &level {
+            let Some(t) = graph.template_of(node_id) else {
+                continue;
+            };
+            let template = &graph.templates()[t];
+            let Some(delta) = node_outputs[template.input].as_ref() else {
+                continue;
+            };
+            let split = routed
+                .entry(t)
+                .or_insert_with(|| template.route(delta, store));
+            if let Some(part) = split.remove(&node_id) {
+                parts.insert(node_id, part);
+            }
+        }
+
+        let mut active: Vec<(NodeId, Vec<&ZSet>)> = Vec::new();
+        for &node_id in &level {
+            let node = &graph.nodes[node_id];
+            let inputs = if graph.template_of(node_id).is_some() {
+                let Some(part) = parts.get(&node_id) else {
+                    continue;
+                };
+                vec![part]
+            } else if node.operator.arity() == 0 {
+                // Scan node: inject the table delta
+                let Some(delta) = table_deltas.get(&node.operator.collections()[0]) else {
+                    continue;
+                };
+                vec![delta]
+            } else {
+                if node.inputs.iter().all(|&id| node_outputs[id].is_none()) {
+                    continue;
+                }
+                node.inputs
+                    .iter()
+                    .map(|&input_id| node_outputs[input_id].as_ref().unwrap_or(&empty_delta))
+                    .collect()
+            };
+            active.push((node_id, inputs));
+        }
+        active.sort_by_key(|(id, _)| *id);
+
+        let ids: Vec<NodeId> = active.iter().map(|(id, _)| *id).collect();
+        let jobs: Vec<(&mut Node, Vec<&ZSet>)> = graph
+            .nodes_mut(&ids)
+            .into_iter()
+            .zip(active.into_iter().map(|(_, inputs)| inputs))
+            .collect();
+        let stepped = par_map(jobs, |(node, inputs)| (node.id, node.step(&inputs, store)));
+        for (node_id, output) in stepped {
+            node_outputs[node_id] = Some(output);
+        }
+    }
+
+    node_outputs
+}
+
+/// Map `items` through `f`, in order. Runs on the rayon pool with the
+/// `parallel` feature, unless there is only one item.
+fn par_map<T: Send, R: Send>(items: Vec<T>, f: impl Fn(T) -> R + Send + Sync) -> Vec<R> {
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    if items.len() > 1 {
+        use rayon::prelude::*;
+        return items.into_par_iter().map(f).collect();
+    }
+    items.into_iter().map(f).collect()
+}
+
+/// Move every operator's clock to `now` and propagate the resulting deltas
+/// (records entering or leaving time windows) through the graph. Returns
+/// each node's output delta.
+fn run_tick(graph: &mut Graph, store: &Store, now: i64) -> Vec<ZSet> {
+    let mut node_outputs: Vec<ZSet> = vec![HashMap::new(); graph.node_count()];
+    let topo_order: Vec<usize> = graph.topo_order().to_vec();
+
+    for &node_id in &topo_order {
+        let node = &mut graph.nodes[node_id];
+        let mut output = node.operator.tick(now, store, node.ctx.as_ref());
+        if node.inputs.iter().any(|&id| !node_outputs[id].is_empty()) {
+            let inputs: Vec<&ZSet> = node.inputs.iter().map(|&id| &node_outputs[id]).collect();
+            output.add(&node.step(&inputs, store));
+        }
+        node_outputs[node_id] = output;
+    }
+
+    node_outputs
+}
+
+/// Drain the changed rows of every node that was stepped (see
+/// `Operator::drain_changed_rows`), by node.
+fn drain_changed_rows(graph: &mut Graph, stepped: impl Fn(NodeId) -> bool) -> Vec<Vec<RowKey>> {
+    graph
+        .nodes
+        .iter_mut()
+        .map(|node| {
+            if stepped(node.id) {
+                node.operator.drain_changed_rows()
+            } else {
+                vec![]
+            }
+        })
+        .collect()
+}
+
+/// Collect the derived rows of aggregate keys among `keys`.
+fn collect_aggregate_rows<'a>(
+    graph: &Graph,
+    output: NodeId,
+    keys: impl IntoIterator<Item = &'a String>,
+) -> Vec<AggregateRow> {
+    keys.into_iter()
+        .filter_map(|key| {
+            graph.derived_row(output, key).map(|row| AggregateRow {
+                key: key.clone(),
+                row,
+            })
+        })
+        .collect()
+}
+
 impl Circuit {
     /// Create an empty circuit.
     pub fn new() -> Self {
         Self {
             store: Store::new(),
-            graphs: HashMap::new(),
+            graph: Graph::new(),
+            outputs: HashMap::new(),
             views: HashMap::new(),
             dependency_map: HashMap::new(),
+            now: system_now(),
+            permissions: Permissions::new(),
+            checkpoints: Checkpoints::default(),
         }
     }
 
     /// Bulk-load initial data into base collections.
     pub fn load(&mut self, records: impl IntoIterator<Item = Record>) {
         for record in records {
+            self.checkpoints.row(&record.table, &record.id);
             let coll = self.store.ensure_collection(&record.table);
-            let key = make_key(&record.table, &record.id);
+            let key = coll.key(&record.id);
             let normalized = crate::types::raw_id(&record.id);
-            coll.rows.insert(normalized.to_string(), record.data);
+            coll.rows.insert(normalized.into(), record.data);
             coll.zset.insert(key, 1);
         }
     }
@@ -261,13 +585,94 @@ impl Circuit {
         params: Option<serde_json::Value>,
         format: Option<OutputFormat>,
     ) -> Option<ViewDelta> {
+        let query_id = plan.id.clone();
+        let view_output = self.register(plan, params, format);
+        self.finish_registrations(vec![(query_id, view_output)]).pop().flatten()
+    }
+
+    /// `add_query` for consumers that render a view from its deltas alone:
+    /// every delta also carries the content of the rows it adds or updates
+    /// (`ViewDelta::rows`).
+    pub fn add_query_with_rows(
+        &mut self,
+        plan: QueryPlan,
+        params: Option<serde_json::Value>,
+        format: Option<OutputFormat>,
+    ) -> Option<ViewDelta> {
+        let query_id = plan.id.clone();
+        let view_output = self.register(plan, params, format);
+        if let Some(view) = self.views.get_mut(&query_id) {
+            view.rows = true;
+        }
+        self.finish_registrations(vec![(query_id, view_output)]).pop().flatten()
+    }
+
+    /// Register many queries, as after a bootstrap: `add_query` of each, or
+    /// `add_query_with_rows` where the flag is set, with the initial
+    /// evaluations of the views run concurrently with the `parallel`
+    /// feature. Returns each query's first ViewDelta, in order.
+    pub fn add_queries(
+        &mut self,
+        queries: impl IntoIterator<Item = (QueryPlan, Option<serde_json::Value>, Option<OutputFormat>, bool)>,
+    ) -> Vec<Option<ViewDelta>> {
+        let mut deltas = Vec::new();
+        let mut batch: Vec<(String, ZSet)> = Vec::new();
+        for (plan, params, format, rows) in queries {
+            // Re-registering a query of this batch replaces its view: apply
+            // the batch so far first, as `add_query` one by one would.
+            if batch.iter().any(|(query_id, _)| *query_id == plan.id) {
+                deltas.extend(self.finish_registrations(std::mem::take(&mut batch)));
+            }
+            let query_id = plan.id.clone();
+            let view_output = self.register(plan, params, format);
+            if let Some(view) = self.views.get_mut(&query_id) {
+                view.rows = rows;
+            }
+            batch.push((query_id, view_output));
+        }
+        deltas.extend(self.finish_registrations(batch));
+        deltas
+    }
+
+    fn finish_registrations(&mut self, batch: Vec<(String, ZSet)>) -> Vec<Option<ViewDelta>> {
+        let query_ids: Vec<String> = batch.iter().map(|(query_id, _)| query_id.clone()).collect();
+        self.index_edge_tables();
+        let deltas = self.run_initial_snapshots(batch);
+        for query_id in &query_ids {
+            self.track_link_tables(query_id);
+        }
+        deltas
+    }
+
+    /// Add a query's operators and view, returning the view's initial
+    /// output for `run_initial_snapshots`. A query registered again under
+    /// the same id replaces the old one, whose operators are released.
+    fn register(
+        &mut self,
+        plan: QueryPlan,
+        params: Option<serde_json::Value>,
+        format: Option<OutputFormat>,
+    ) -> ZSet {
+        if self.outputs.contains_key(&plan.id) {
+            self.remove_query(&plan.id);
+        }
+        let params_sv = params.map(Sp00kyValue::from);
+        // Row-level security: for record users, every table the query reads
+        // only yields the rows its select rule allows under their auth.
+        let plan = if self.permissions.is_empty() || !is_record_user(params_sv.as_ref()) {
+            plan
+        } else {
+            QueryPlan {
+                root: self.permissions.secure(&plan.root),
+                ..plan
+            }
+        };
         let query_id = plan.id.clone();
         let referenced_tables = plan.root.referenced_tables();
         let format = format.unwrap_or_default();
-        let params_sv = params.map(Sp00kyValue::from);
 
-        // Build the operator DAG
-        let graph = Graph::from_plan(&plan.root);
+        // Add the query's operators to the shared DAG
+        let (output, view_output) = self.compile(&plan, params_sv.as_ref());
 
         // Create view state
         let view = View::new(
@@ -278,8 +683,9 @@ impl Circuit {
             referenced_tables.clone(),
         );
 
-        self.graphs.insert(query_id.clone(), graph);
+        self.outputs.insert(query_id.clone(), output);
         self.views.insert(query_id.clone(), view);
+        self.checkpoints.view(&query_id);
 
         // Update dependency map
         for table in &referenced_tables {
@@ -289,14 +695,49 @@ impl Circuit {
                 .push(query_id.clone());
         }
 
-        // Run initial snapshot evaluation
-        self.run_initial_snapshot(&query_id)
+        view_output
+    }
+
+    /// Set the table `select` rules (see `Permissions`). They are part of
+    /// the plans of queries registered afterwards; registered queries keep
+    /// the rules they were compiled with.
+    ///
+    /// The rules are saved with the full state, so the next checkpoint is
+    /// one.
+    pub fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+        self.checkpoints.rebase();
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    /// Add an optimized plan to the shared DAG, on the circuit's clock, and
+    /// initialize the operators it did not share with earlier queries.
+    /// Returns the output node and its full current output.
+    fn compile(&mut self, plan: &QueryPlan, params: Option<&Sp00kyValue>) -> (NodeId, ZSet) {
+        let first_new = self.graph.node_count();
+        let output = self.graph.add_plan(&optimize(&plan.root), params);
+        for node in &mut self.graph.nodes[first_new..] {
+            node.operator.tick(self.now, &self.store, node.ctx.as_ref());
+        }
+        let full = self.graph.prime(first_new, output, &self.store);
+        (output, full)
     }
 
     /// Remove a registered query.
     pub fn remove_query(&mut self, query_id: &str) {
-        self.graphs.remove(query_id);
-        self.views.remove(query_id);
+        if let Some(output) = self.outputs.remove(query_id) {
+            let remap = self.graph.release(output);
+            for node in self.outputs.values_mut() {
+                *node = remap[*node].expect("outputs of registered queries are kept");
+            }
+        }
+        if self.views.remove(query_id).is_some() {
+            self.checkpoints.remove_view(query_id);
+            self.index_edge_tables();
+        }
 
         // Clean up dependency map
         for (_, query_ids) in self.dependency_map.iter_mut() {
@@ -317,9 +758,49 @@ impl Circuit {
         // Track content-only updates (Operation::Update has weight 0)
         let mut content_updates: HashMap<String, Vec<String>> = HashMap::new();
 
+        // Updates and deletes of rows that existed before this step retract
+        // the previous row contents in a separate pass (Phase 3a), so
+        // content-dependent operators (Filter, TopK, Aggregate) evaluate the
+        // retraction against the row they originally admitted. An update is
+        // then re-inserted with its new contents in the main pass.
+        // Holds (table, id, previous row) for those keys.
+        let mut replaced_rows: Vec<(String, String, Sp00kyValue)> = Vec::new();
+        let mut retraction_deltas: HashMap<String, ZSet> = HashMap::new();
+
         for change in &changes.changes {
+            let key = make_key(&change.table, &change.id);
+            let mut retracted = false;
+            if change.op != Operation::Create {
+                let touched = table_deltas
+                    .get(&change.table)
+                    .is_some_and(|d| d.contains_key(key.as_str()))
+                    || retraction_deltas
+                        .get(&change.table)
+                        .is_some_and(|d| d.contains_key(key.as_str()));
+                let old_row = self
+                    .store
+                    .get_collection(&change.table)
+                    .filter(|coll| coll.zset.contains_key(key.as_str()))
+                    .and_then(|coll| coll.get_row(&change.id));
+                if let (false, Some(old)) = (touched, old_row) {
+                    replaced_rows.push((change.table.clone(), change.id.clone(), old.clone()));
+                    retraction_deltas
+                        .entry(change.table.clone())
+                        .or_default()
+                        .insert(key.as_str().into(), -1);
+                    if change.op == Operation::Update {
+                        *table_deltas
+                            .entry(change.table.clone())
+                            .or_default()
+                            .entry(key.as_str().into())
+                            .or_insert(0) += 1;
+                    }
+                    retracted = true;
+                }
+            }
             let (key, weight) = self.store.apply_change(change);
-            if weight != 0 {
+            self.checkpoints.row(&change.table, &change.id);
+            if weight != 0 && !retracted {
                 let delta = table_deltas.entry(change.table.clone()).or_default();
                 *delta.entry(key).or_insert(0) += weight;
             }
@@ -340,6 +821,11 @@ impl Circuit {
         for delta in table_deltas.values_mut() {
             delta.retain(|_, w| *w != 0);
         }
+        // Tables touched only by retractions still count as changed for
+        // subquery change detection in step_query.
+        for table in retraction_deltas.keys() {
+            table_deltas.entry(table.clone()).or_default();
+        }
 
         // Phase 2: Determine affected queries
         let mut affected_queries: Vec<String> = Vec::new();
@@ -353,302 +839,654 @@ impl Circuit {
             }
         }
 
-        // Phase 3: Step each affected query's DAG
-        let mut results = Vec::new();
-        for query_id in affected_queries {
-            if let Some(delta) = self.step_query(&query_id, &table_deltas, &content_updates) {
-                results.push(delta);
+        // Phase 3a: Retract replaced/deleted rows. The previous row contents
+        // are swapped back into the store for this pass only.
+        let mut retractions: Vec<Option<ZSet>> = vec![];
+        if !retraction_deltas.is_empty() {
+            let current_rows = self.swap_rows(&replaced_rows);
+            retractions = run_graph(&mut self.graph, &self.store, &retraction_deltas);
+            self.swap_rows(&current_rows);
+        }
+
+        // Phase 3b: Step the DAG once; shared nodes serve every query reading them
+        let outputs = run_graph(&mut self.graph, &self.store, &table_deltas);
+        let changed_rows = drain_changed_rows(&mut self.graph, |id| outputs[id].is_some());
+
+        // Phase 4: Update the affected views; they are independent of
+        // each other and updated concurrently (see `step_views`).
+        let mut jobs = Vec::new();
+        for query_id in &affected_queries {
+            let Some(&output) = self.outputs.get(query_id) else {
+                continue;
+            };
+            let mut view_delta = outputs[output].clone().unwrap_or_default();
+            if let Some(Some(retracted)) = retractions.get(output) {
+                view_delta.add(retracted);
             }
+            let changed: Vec<RowKey> = self
+                .graph
+                .subgraph(output)
+                .into_iter()
+                .flat_map(|id| changed_rows[id].iter().cloned())
+                .collect();
+            jobs.push((query_id.clone(), view_delta, changed));
+        }
+        let results = self.step_views(jobs, &table_deltas, &retraction_deltas, &content_updates);
+        for query_id in &affected_queries {
+            self.track_link_tables(query_id);
         }
 
         results
     }
 
-    /// Get a reference to a view's state.
-    pub fn get_view(&self, query_id: &str) -> Option<&View> {
-        self.views.get(query_id)
+    /// Route changes of tables that record links (`author.*`) point into to
+    /// the view; such tables are only known once the links appear, and stop
+    /// being routed once no link points into them.
+    fn track_link_tables(&mut self, query_id: &str) {
+        let Some(view) = self.views.get_mut(query_id) else {
+            return;
+        };
+        let current = link_tables(&self.store, view);
+        let planned = view.plan.root.referenced_tables();
+        for table in std::mem::take(&mut view.link_tables) {
+            if current.contains(&table) || planned.contains(&table) {
+                continue;
+            }
+            view.subquery_tables.retain(|t| *t != table);
+            view.referenced_tables.retain(|t| *t != table);
+            if let Some(routed) = self.dependency_map.get_mut(&table) {
+                routed.retain(|id| id != query_id);
+                if routed.is_empty() {
+                    self.dependency_map.remove(&table);
+                }
+            }
+        }
+        for table in &current {
+            if !view.subquery_tables.contains(table) {
+                view.subquery_tables.push(table.clone());
+            }
+            if !view.referenced_tables.contains(table) {
+                view.referenced_tables.push(table.clone());
+            }
+            let routed = self.dependency_map.entry(table.clone()).or_default();
+            if !routed.iter().any(|id| id == query_id) {
+                routed.push(query_id.to_string());
+            }
+        }
+        view.link_tables = current;
     }
 
-    /// Run initial evaluation for a newly registered query.
-    ///
-    /// Uses `step()` so that stateful operators (TopK, Join, Aggregate,
-    /// Distinct) prime their internal buffers. For Scan nodes, the full
-    /// collection Z-set is injected as the initial "delta from empty".
-    fn run_initial_snapshot(&mut self, query_id: &str) -> Option<ViewDelta> {
-        let graph = self.graphs.get_mut(query_id)?;
-        let view = self.views.get_mut(query_id)?;
-
-        let num_nodes = graph.node_count();
-        let mut node_outputs: Vec<Option<ZSet>> = vec![None; num_nodes];
-
-        let topo_order: Vec<usize> = graph.topo_order().to_vec();
-
-        for &node_id in &topo_order {
-            let input_ids = graph.nodes[node_id].inputs.clone();
-            let arity = graph.nodes[node_id].operator.arity();
-
-            let output = if arity == 0 {
-                // Scan node: inject the full collection as initial delta
-                let table_name = graph.nodes[node_id].operator.collections();
-                let full_zset = table_name
-                    .first()
-                    .and_then(|t| self.store.get_collection(t))
-                    .map(|c| c.zset.clone())
-                    .unwrap_or_default();
-                graph.nodes[node_id]
-                    .operator
-                    .step(&[&full_zset], &self.store, view.params.as_ref())
-            } else {
-                let inputs: Vec<&ZSet> = input_ids
-                    .iter()
-                    .map(|&input_id| node_outputs[input_id].as_ref().unwrap())
-                    .collect();
-                graph.nodes[node_id]
-                    .operator
-                    .step(&inputs, &self.store, view.params.as_ref())
-            };
+    /// Keep edge indexes on exactly the tables registered graph
+    /// projections traverse.
+    fn index_edge_tables(&mut self) {
+        let tables = self.views.values().flat_map(edge_tables).map(str::to_string).collect();
+        self.store.index_edges(&tables);
+    }
 
-            node_outputs[node_id] = Some(output);
+    /// Move the clock behind `time::now()` to `now` (Unix milliseconds).
+    ///
+    /// Views with time-windowed filters (`created_at > time::now() - 1d`)
+    /// re-evaluate the records they hold and report those that entered or
+    /// left the window, without any data change. Hosts call this
+    /// periodically; the clock may also move backwards.
+    pub fn tick(&mut self, now: i64) -> Vec<ViewDelta> {
+        self.now = now;
+        let (no_deltas, no_updates) = (HashMap::new(), HashMap::new());
+        let ticked = run_tick(&mut self.graph, &self.store, now);
+        let changed_rows = drain_changed_rows(&mut self.graph, |_| true);
+
+        let mut query_ids: Vec<String> = self.outputs.keys().cloned().collect();
+        query_ids.sort();
+
+        let mut jobs = Vec::new();
+        for query_id in query_ids {
+            let output = self.outputs[&query_id];
+            if ticked[output].is_empty() {
+                continue;
+            }
+            let changed: Vec<RowKey> = self
+                .graph
+                .subgraph(output)
+                .into_iter()
+                .flat_map(|id| changed_rows[id].iter().cloned())
+                .collect();
+            jobs.push((query_id, ticked[output].clone(), changed));
         }
-
-        let view_output = node_outputs[graph.output_node].take()?;
-
-        if view_output.is_empty() {
-            return None;
+        let ticked_ids: Vec<String> = jobs.iter().map(|(query_id, _, _)| query_id.clone()).collect();
+        let results = self.step_views(jobs, &no_deltas, &no_deltas, &no_updates);
+        for query_id in &ticked_ids {
+            self.track_link_tables(query_id);
         }
+        results
+    }
 
-        // Apply to view cache
-        let additions: Vec<String> = view_output
-            .iter()
-            .filter(|(_, &w)| w > 0)
-            .map(|(k, _)| k.clone())
-            .collect();
-
-        view.apply_delta(&view_output);
-        view.last_hash = view.compute_hash();
-
-        // Compute initial subquery record set
-        let new_subquery_set = compute_current_subquery_set(&self.store, view);
-        let subquery_items: Vec<SubqueryDeltaItem> = new_subquery_set
-            .iter()
-            .map(|(key, (parent_key, alias))| SubqueryDeltaItem {
-                id: key.clone(),
-                parent_key: parent_key.clone(),
-                alias: alias.clone(),
-                op: SubqueryOp::Add,
-            })
-            .collect();
-        view.subquery_cache = new_subquery_set;
-
-        let records: Vec<String> = view.cache.keys().cloned().collect();
-
-        Some(ViewDelta {
-            query_id: query_id.to_string(),
-            additions,
-            removals: vec![],
-            updates: vec![],
-            records,
-            result_hash: view.last_hash.clone(),
-            subquery_items,
-        })
+    /// Current value of `time::now()`, Unix milliseconds.
+    pub fn now(&self) -> i64 {
+        self.now
     }
 
-    /// Step a single query's operator DAG with table deltas.
-    fn step_query(
+    /// Replace stored rows with the given contents, returning what was there
+    /// before (a `Null` entry means the row was absent and is removed).
+    fn swap_rows(
         &mut self,
-        query_id: &str,
-        table_deltas: &HashMap<String, ZSet>,
-        content_updates: &HashMap<String, Vec<String>>,
-    ) -> Option<ViewDelta> {
-        let graph = self.graphs.get_mut(query_id)?;
-        let view = self.views.get_mut(query_id)?;
-
-        let num_nodes = graph.node_count();
-        let mut node_outputs: Vec<Option<ZSet>> = vec![None; num_nodes];
-        let empty_delta: ZSet = HashMap::new();
-
-        // Clone topo order to avoid holding an immutable borrow on graph
-        // while we mutably access graph.nodes[..].operator.step()
-        let topo_order: Vec<usize> = graph.topo_order().to_vec();
-
-        for &node_id in &topo_order {
-            let input_ids = graph.nodes[node_id].inputs.clone();
-            let arity = graph.nodes[node_id].operator.arity();
-
-            let output = if arity == 0 {
-                // Scan node: inject the table delta
-                let table_name = graph.nodes[node_id].operator.collections()[0].clone();
-                let delta = table_deltas.get(&table_name).unwrap_or(&empty_delta);
-                graph.nodes[node_id]
-                    .operator
-                    .step(&[delta], &self.store, view.params.as_ref())
+        rows: &[(String, String, Sp00kyValue)],
+    ) -> Vec<(String, String, Sp00kyValue)> {
+        let mut previous = Vec::with_capacity(rows.len());
+        for (table, id, value) in rows.iter().rev() {
+            let coll = self.store.ensure_collection(table);
+            let raw = RowKey::from(raw_id(id));
+            let old = if value.is_null() {
+                coll.rows.remove(&raw)
             } else {
-                let inputs: Vec<&ZSet> = input_ids
-                    .iter()
-                    .map(|&input_id| node_outputs[input_id].as_ref().unwrap())
-                    .collect();
-                graph.nodes[node_id]
-                    .operator
-                    .step(&inputs, &self.store, view.params.as_ref())
+                coll.rows.insert(raw, value.clone())
             };
-
-            node_outputs[node_id] = Some(output);
+            previous.push((table.clone(), id.clone(), old.unwrap_or(Sp00kyValue::Null)));
         }
+        previous
+    }
+
+    /// Get a reference to a view's state.
+    pub fn get_view(&self, query_id: &str) -> Option<&View> {
+        self.views.get(query_id)
+    }
 
-        let view_delta = node_outputs[graph.output_node].take()?;
+    /// `EXPLAIN` of a view: its operator tree and state sizes. With
+    /// `analyze`, also the work each operator's steps did so far.
+    pub fn explain(&self, query_id: &str, analyze: bool) -> Option<Explain> {
+        let output = *self.outputs.get(query_id)?;
+        Some(Explain::new(query_id, &self.graph, output, analyze))
+    }
 
-        // Identify content-only updates: keys in the view cache whose data changed
-        // but membership didn't (Operation::Update with weight 0).
-        let mut updates: Vec<String> = content_updates
+    /// `explain` of every view, the most expensive first: by step time
+    /// with `analyze`, otherwise by state size.
+    pub fn explain_all(&self, analyze: bool) -> Vec<Explain> {
+        let mut all: Vec<Explain> = self
+            .outputs
             .iter()
-            .flat_map(|(_, keys)| keys.iter())
-            .filter(|key| view.cache.contains_key(*key) && !view_delta.contains_key(*key))
-            .cloned()
+            .map(|(query_id, &output)| Explain::new(query_id, &self.graph, output, analyze))
             .collect();
+        all.sort_by(|a, b| {
+            (b.step_time_us, b.state_rows, &a.view_id).cmp(&(a.step_time_us, a.state_rows, &b.view_id))
+        });
+        all
+    }
 
-        // Detect subquery table changes: if any table referenced in a subquery
-        // projection had changes, all cached parent records need re-fetching.
-        if !view.subquery_tables.is_empty() {
-            let has_subquery_changes = view.subquery_tables.iter().any(|t| {
-                table_deltas.contains_key(t) || content_updates.contains_key(t)
-            });
-            if has_subquery_changes {
-                view.bump_content_generation();
-                for key in view.cache.keys() {
-                    if !updates.contains(key) {
-                        updates.push(key.clone());
-                    }
-                }
-            }
-        }
+    /// Estimated memory held by the store, view caches and operator state.
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport::new(&self.store, &self.views, &self.graph)
+    }
 
-        let has_membership_changes = !view_delta.is_empty();
-        let has_content_updates = !updates.is_empty();
+    /// Current contents of a row derived by a view (an aggregate group, or a
+    /// row with computed columns), or `None` if the key is a plain stored record.
+    pub fn derived_row(&self, query_id: &str, key: &str) -> Option<Sp00kyValue> {
+        self.graph.derived_row(*self.outputs.get(query_id)?, key)
+    }
 
-        if !has_membership_changes && !has_content_updates {
-            return None;
+    /// Version of a key as seen by a view: derived rows carry their own
+    /// `_00_rv`, everything else comes from the store.
+    pub fn record_version(&self, query_id: &str, key: &str) -> Option<i64> {
+        match self.derived_row(query_id, key) {
+            Some(row) => row.get("_00_rv").and_then(|v| v.as_i64()),
+            None => self.store.get_record_version_by_key(key),
         }
+    }
 
-        // Categorize membership changes before applying
-        let additions: Vec<String> = view_delta
-            .iter()
-            .filter(|(k, &w)| w > 0 && !view.cache.contains_key(*k))
-            .map(|(k, _)| k.clone())
+    /// Apply the initial outputs of newly registered queries (see
+    /// `compile`) to their views, concurrently with the `parallel` feature.
+    /// Deltas come back in the order of `jobs`.
+    fn run_initial_snapshots(&mut self, jobs: Vec<(String, ZSet)>) -> Vec<Option<ViewDelta>> {
+        let env = ViewEnv {
+            store: &self.store,
+            graph: &self.graph,
+            permissions: &self.permissions,
+            table_deltas: &HashMap::new(),
+            retraction_deltas: &HashMap::new(),
+            content_updates: &HashMap::new(),
+        };
+        let count = jobs.len();
+        let mut pending: HashMap<String, (usize, ZSet)> = jobs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (query_id, view_output))| (query_id, (i, view_output)))
             .collect();
-        let removals: Vec<String> = view_delta
-            .iter()
-            .filter(|(k, &w)| {
-                w < 0 && view.cache.get(*k).map(|&old| old + w <= 0).unwrap_or(false)
+        let outputs = &self.outputs;
+        let work: Vec<_> = self
+            .views
+            .iter_mut()
+            .filter_map(|(query_id, view)| {
+                let (i, view_output) = pending.remove(query_id)?;
+                Some((i, *outputs.get(query_id)?, view, view_output))
+            })
+            .collect();
+        let mut results = vec![None; count];
+        for (i, delta) in par_map(work, |(i, output, view, view_output)| {
+            (i, snapshot_view(view, output, &env, view_output))
+        }) {
+            results[i] = delta;
+        }
+        results
+    }
+
+    /// Apply the output deltas of a step to the views of `jobs` (query id,
+    /// output delta, changed rows; see `step_view`), concurrently with the
+    /// `parallel` feature. Deltas come back in the order of `jobs`.
+    fn step_views(
+        &mut self,
+        jobs: Vec<(String, ZSet, Vec<RowKey>)>,
+        table_deltas: &HashMap<String, ZSet>,
+        retraction_deltas: &HashMap<String, ZSet>,
+        content_updates: &HashMap<String, Vec<String>>,
+    ) -> Vec<ViewDelta> {
+        let env = ViewEnv {
+            store: &self.store,
+            graph: &self.graph,
+            permissions: &self.permissions,
+            table_deltas,
+            retraction_deltas,
+            content_updates,
+        };
+        let mut pending: HashMap<String, (usize, ZSet, Vec<RowKey>)> = HashMap::new();
+        for (i, (query_id, view_delta, changed)) in jobs.into_iter().enumerate() {
+            if self.views.contains_key(&query_id) {
+                self.checkpoints.view(&query_id);
+            }
+            pending.insert(query_id, (i, view_delta, changed));
+        }
+        let outputs = &self.outputs;
+        let work: Vec<_> = self
+            .views
+            .iter_mut()
+            .filter_map(|(query_id, view)| {
+                let (i, view_delta, changed) = pending.remove(query_id)?;
+                Some((i, *outputs.get(query_id)?, view, view_delta, changed))
             })
-            .map(|(k, _)| k.clone())
             .collect();
+        let mut results = par_map(work, |(i, output, view, view_delta, changed)| {
+            (i, step_view(view, output, &env, view_delta, changed))
+        });
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().filter_map(|(_, delta)| delta).collect()
+    }
+}
 
-        // Apply delta to view cache
-        view.apply_delta(&view_delta);
-        let new_hash = view.compute_hash();
+/// What updating a view reads besides the view itself.
+struct ViewEnv<'a> {
+    store: &'a Store,
+    graph: &'a Graph,
+    permissions: &'a Permissions,
+    /// The step's input, for subquery change detection.
+    table_deltas: &'a HashMap<String, ZSet>,
+    /// Rows updated or deleted in the step (see `Circuit::step`).
+    retraction_deltas: &'a HashMap<String, ZSet>,
+    content_updates: &'a HashMap<String, Vec<String>>,
+}
 
-        // For content-only updates, the hash won't change (keys unchanged),
-        // but we still want to emit the delta so consumers know about data changes.
-        if new_hash == view.last_hash && !has_content_updates {
-            return None;
+/// Content of the rows of `keys` as the query at `output` emits them, and
+/// of the added or updated subquery records; empty unless the view was
+/// registered with rows.
+///
+/// Computed rows come from the graph. Field projections are not part of
+/// the graph (see `optimize`), so other rows are shaped by the top-level
+/// projections of the view's own plan, and subquery records by those of
+/// the subquery that selected them. Records of graph traversals and
+/// record links are sent whole.
+fn delta_rows<'a>(
+    view: &View,
+    output: NodeId,
+    env: &ViewEnv,
+    keys: impl IntoIterator<Item = &'a String>,
+    subquery_items: &[SubqueryDeltaItem],
+) -> Vec<(String, Sp00kyValue)> {
+    if !view.rows {
+        return vec![];
+    }
+    let select_of = |plan: &OperatorPlan| {
+        let mut projections: Vec<Projection> = plan.root_projections().into_iter().cloned().collect();
+        if projections.is_empty() {
+            projections.push(Projection::All);
         }
+        Map::new(projections)
+    };
+    let select = select_of(&view.plan.root);
+    let select_all = Map::new(vec![Projection::All]);
+    let subquery_selects: HashMap<&str, Map> = view
+        .plan
+        .root
+        .subquery_plans()
+        .into_iter()
+        .map(|(alias, plan)| (alias, select_of(plan)))
+        .collect();
+
+    let row = |map: &Map, key: &str| {
+        env.store.get_row_by_key(key)?;
+        Some(map.project(key, env.store, view.params.as_ref()))
+    };
+    let related = subquery_items
+        .iter()
+        .filter(|item| item.op != SubqueryOp::Remove)
+        .filter_map(|item| {
+            let map = subquery_selects.get(item.alias.as_str()).unwrap_or(&select_all);
+            Some((item.id.clone(), row(map, &item.id)?))
+        });
+    keys.into_iter()
+        .filter_map(|key| {
+            let content = env.graph.derived_row(output, key).or_else(|| row(&select, key))?;
+            Some((key.clone(), content))
+        })
+        .chain(related)
+        .collect()
+}
+
+/// Apply the initial output of a newly registered query, read from its
+/// `output` node, to its view.
+fn snapshot_view(view: &mut View, output: NodeId, env: &ViewEnv, view_output: ZSet) -> Option<ViewDelta> {
+    if view_output.is_empty() {
+        return None;
+    }
+
+    // Apply to view cache
+    let additions: Vec<String> = view_output
+        .iter()
+        .filter(|(_, &w)| w > 0)
+        .map(|(k, _)| k.to_string())
+        .collect();
+
+    let versions = Versions { store: env.store, graph: env.graph, output };
+    view.apply_delta(&view_output, &versions);
+
+    // Compute initial subquery record set
+    let new_subquery_set = compute_current_subquery_set(env.store, view, env.permissions);
+    view.set_subquery_cache(new_subquery_set, &versions);
+    if !view.uncorrelated_tables.is_empty() {
+        view.rebuild_hash(&versions);
+    }
+    let subquery_items: Vec<SubqueryDeltaItem> = view
+        .subquery_cache
+        .iter()
+        .map(|(key, (parent_key, alias))| SubqueryDeltaItem {
+            id: key.clone(),
+            parent_key: parent_key.clone(),
+            alias: alias.clone(),
+            op: SubqueryOp::Add,
+        })
+        .collect();
+    view.last_hash = view.compute_hash();
+
+    let records = view.records();
+    let aggregate_rows = collect_aggregate_rows(env.graph, output, &additions);
+    let rows = delta_rows(view, output, env, &additions, &subquery_items);
+
+    Some(ViewDelta {
+        query_id: view.query_id.clone(),
+        additions,
+        removals: vec![],
+        updates: vec![],
+        records,
+        tree: view.tree(),
+        rows,
+        result_hash: view.last_hash.clone(),
+        subquery_items,
+        aggregate_rows,
+    })
+}
 
-        if new_hash != view.last_hash {
-            view.last_hash = new_hash.clone();
+/// Apply a query's output delta for this step to its view.
+///
+/// `changed_rows` are the keys drained from the query's operators whose
+/// content changed without a membership change.
+fn step_view(
+    view: &mut View,
+    output: NodeId,
+    env: &ViewEnv,
+    view_delta: ZSet,
+    changed_rows: Vec<RowKey>,
+) -> Option<ViewDelta> {
+    let (table_deltas, content_updates) = (env.table_deltas, env.content_updates);
+
+    // Identify content-only updates: keys in the view cache whose data changed
+    // but membership didn't (Operation::Update with weight 0).
+    let mut updates: Vec<String> = content_updates
+        .iter()
+        .flat_map(|(_, keys)| keys.iter())
+        .filter(|key| view.cache.contains_key(key.as_str()) && !view_delta.contains_key(key.as_str()))
+        .cloned()
+        .collect();
+
+    // Aggregate groups or outer-join parents whose content changed without
+    // a membership change. Drained from every node of the query: a join
+    // is usually wrapped in a filter or projection. Aggregate groups are
+    // hashed at their own version, bumped with their content.
+    let versions = Versions { store: env.store, graph: env.graph, output };
+    for key in changed_rows {
+        if view.cache.contains_key(&key) && !view_delta.contains_key(&key) {
+            view.sync_hash(&key, &versions);
+            if !updates.iter().any(|u| u == key.as_str()) {
+                updates.push(key.to_string());
+            }
         }
+    }
 
-        // Compute subquery record diffs when relevant tables changed
-        let has_subquery_table_changes = view.subquery_tables.iter().any(|t| {
+    // Detect subquery table changes: if any table referenced in a subquery
+    // projection had changes, all cached parent records need re-fetching.
+    // Records of correlated subqueries are hashed at their versions (see
+    // `View::set_subquery_cache`), as are all records of the tables of
+    // uncorrelated subqueries.
+    if !view.subquery_tables.is_empty() {
+        let has_subquery_changes = view.subquery_tables.iter().any(|t| {
             table_deltas.contains_key(t) || content_updates.contains_key(t)
         });
-        let subquery_items = if has_membership_changes || has_subquery_table_changes {
-            let new_subquery_set = compute_current_subquery_set(&self.store, view);
-            let items = diff_subquery_sets(&view.subquery_cache, &new_subquery_set, &self.store);
-            view.subquery_cache = new_subquery_set;
-            items
-        } else {
-            vec![]
-        };
+        if has_subquery_changes {
+            let uncorrelated: Vec<RowKey> = view
+                .uncorrelated_tables
+                .iter()
+                .flat_map(|t| table_deltas.get(t).into_iter().chain(env.retraction_deltas.get(t)))
+                .flat_map(|delta| delta.keys().cloned())
+                .collect();
+            for key in uncorrelated {
+                view.sync_hash(&key, &versions);
+            }
+            for key in view.cache.keys() {
+                if !updates.iter().any(|u| u == key.as_str()) {
+                    updates.push(key.to_string());
+                }
+            }
+        }
+    }
 
-        let records: Vec<String> = view.cache.keys().cloned().collect();
+    let has_membership_changes = !view_delta.is_empty();
+    let has_content_updates = !updates.is_empty();
 
-        Some(ViewDelta {
-            query_id: query_id.to_string(),
-            additions,
-            removals,
-            updates,
-            records,
-            result_hash: view.last_hash.clone(),
-            subquery_items,
-        })
+    if !has_membership_changes && !has_content_updates {
+        return None;
     }
-}
-
-// --- Serialization support ---
 
-use serde::{Deserialize, Serialize};
+    // Categorize membership changes before applying
+    let additions: Vec<String> = view_delta
+        .iter()
+        .filter(|(k, &w)| w > 0 && !view.cache.contains_key(*k))
+        .map(|(k, _)| k.to_string())
+        .collect();
+    let removals: Vec<String> = view_delta
+        .iter()
+        .filter(|(k, &w)| {
+            w < 0 && view.cache.get(*k).map(|&old| old + w <= 0).unwrap_or(false)
+        })
+        .map(|(k, _)| k.to_string())
+        .collect();
+
+    // Apply delta to view cache, and rehash the records stored at a new
+    // version in this step
+    view.apply_delta(&view_delta, &versions);
+    for key in content_updates.values().flatten() {
+        view.sync_hash(key, &versions);
+    }
 
-/// Serializable snapshot of the circuit state.
-#[derive(Serialize, Deserialize)]
-struct CircuitState {
-    store: Store,
-    queries: Vec<QueryState>,
+    // Compute subquery record diffs when relevant tables changed, or when
+    // records in the view changed (their links may point elsewhere now)
+    let has_subquery_table_changes = view.subquery_tables.iter().any(|t| {
+        table_deltas.contains_key(t) || content_updates.contains_key(t)
+    });
+    let subquery_items = if has_membership_changes
+        || has_subquery_table_changes
+        || has_content_updates
+    {
+        let new_subquery_set = compute_current_subquery_set(env.store, view, env.permissions);
+        let old_subquery_set = view.set_subquery_cache(new_subquery_set, &versions);
+        diff_subquery_sets(&old_subquery_set, &view.subquery_cache, env.store)
+    } else {
+        vec![]
+    };
+
+    // Aggregate values and other derived rows change without a new
+    // version, so content-only updates are emitted even when the hash
+    // stays the same.
+    let new_hash = view.compute_hash();
+    if new_hash == view.last_hash && !has_content_updates {
+        return None;
+    }
+    view.last_hash = new_hash;
+
+    let records = view.records();
+    let aggregate_rows =
+        collect_aggregate_rows(env.graph, output, additions.iter().chain(&updates));
+    let rows = delta_rows(view, output, env, additions.iter().chain(&updates), &subquery_items);
+
+    Some(ViewDelta {
+        query_id: view.query_id.clone(),
+        additions,
+        removals,
+        updates,
+        records,
+        tree: view.tree(),
+        rows,
+        result_hash: view.last_hash.clone(),
+        subquery_items,
+        aggregate_rows,
+    })
 }
 
-/// Serializable snapshot of a single query's state.
-#[derive(Serialize, Deserialize)]
-struct QueryState {
-    plan: QueryPlan,
-    #[serde(default)]
-    params: Option<serde_json::Value>,
-    format: OutputFormat,
-    cache: ZSet,
-    last_hash: String,
-    content_generation: u64,
-    #[serde(default)]
-    subquery_cache: HashMap<String, (String, String)>,
-}
+// --- Serialization support ---
 
 impl Circuit {
-    /// Serialize the circuit state to a JSON string.
-    ///
-    /// The operator DAG (which contains trait objects) is NOT serialized.
-    /// Instead, we serialize the query plans and rebuild graphs on restore.
+    /// Serialize the circuit state to a JSON string, for debugging. See
+    /// `save_to` for the compact format.
     pub fn save(&self) -> serde_json::Result<String> {
-        let queries: Vec<QueryState> = self
-            .views
-            .values()
-            .map(|view| QueryState {
-                plan: view.plan.clone(),
-                params: view.params.as_ref().map(|sv| serde_json::Value::from(sv.clone())),
-                format: view.format,
-                cache: view.cache.clone(),
-                last_hash: view.last_hash.clone(),
-                content_generation: view.content_generation,
-                subquery_cache: view.subquery_cache.clone(),
-            })
-            .collect();
+        snapshot::to_json(&self.state(self.checkpoints.base(), self.checkpoints.seq))
+    }
 
-        let state = CircuitState {
-            store: self.store.clone(),
-            queries,
-        };
+    /// Restore a circuit from a JSON string written by `save`.
+    pub fn restore(json: &str) -> serde_json::Result<Self> {
+        Ok(Self::from_state(snapshot::from_json(json)?))
+    }
 
-        serde_json::to_string(&state)
+    /// Stream the circuit state to `writer` in `format`.
+    pub fn save_to<W: Write>(&self, writer: W, format: SnapshotFormat) -> Result<(), SnapshotError> {
+        let (base, seq) = (self.checkpoints.base(), self.checkpoints.seq);
+        snapshot::write(&self.state(base, seq), writer, format)
     }
 
-    /// Restore a circuit from a JSON string.
+    /// The circuit state as a binary snapshot.
+    pub fn save_bytes(&self) -> Result<Vec<u8>, SnapshotError> {
+        let mut bytes = Vec::new();
+        self.save_to(&mut bytes, SnapshotFormat::Binary)?;
+        Ok(bytes)
+    }
+
+    /// Restore a circuit from a snapshot in either format, as written by
+    /// `save_to` or `save`. Snapshots of older versions are migrated.
+    pub fn restore_from<R: Read>(reader: R) -> Result<Self, SnapshotError> {
+        Ok(Self::from_state(snapshot::read(reader)?))
+    }
+
+    /// Write a checkpoint to `writer`: only the rows and views changed since
+    /// the previous checkpoint, or the full state when there is no base yet
+    /// or the policy asks for compaction (see `CheckpointPolicy`).
     ///
+    /// Changes made through `store` directly are not tracked.
+    pub fn checkpoint<W: Write>(&mut self, writer: W, format: SnapshotFormat) -> Result<Checkpoint, SnapshotError> {
+        let total_rows = self.store.collections.values().map(|c| c.rows.len()).sum();
+        if self.checkpoints.compaction_due(total_rows) {
+            return self.compact(writer, format);
+        }
+        let delta = self.checkpoints.delta(&self.store, &self.views, self.now);
+        snapshot::write_delta(&delta, writer, format)?;
+        Ok(self.checkpoints.written(false))
+    }
+
+    /// Write the full state as a new base checkpoint. Earlier checkpoints
+    /// can be discarded once it is persisted.
+    pub fn compact<W: Write>(&mut self, writer: W, format: SnapshotFormat) -> Result<Checkpoint, SnapshotError> {
+        let seq = self.checkpoints.seq + 1;
+        snapshot::write(&self.state(seq, seq), writer, format)?;
+        Ok(self.checkpoints.written(true))
+    }
+
+    pub fn set_checkpoint_policy(&mut self, policy: CheckpointPolicy) {
+        self.checkpoints.policy = policy;
+    }
+
+    /// Restore a circuit from a base checkpoint and the deltas written
+    /// after it, in any order. Deltas of other bases, or already contained
+    /// in the base, are skipped; a gap in the deltas is an error.
+    pub fn restore_checkpoints<R, D>(base: R, deltas: impl IntoIterator<Item = D>) -> Result<Self, SnapshotError>
+    where
+        R: Read,
+        D: Read,
+    {
+        let mut state = snapshot::read(base)?;
+        let mut pending = Vec::new();
+        for delta in deltas {
+            match snapshot::read_any(delta)? {
+                Snapshot::Delta(delta) if delta.base == state.base => pending.push(delta),
+                _ => {}
+            }
+        }
+        pending.sort_by_key(|delta| delta.seq);
+        for delta in pending {
+            if delta.seq <= state.seq {
+                continue;
+            }
+            if delta.seq != state.seq + 1 {
+                return Err(SnapshotError::Decode(format!(
+                    "checkpoint {} of base {} is missing",
+                    state.seq + 1,
+                    state.base
+                )));
+            }
+            delta.apply(&mut state);
+        }
+
+        let (base, seq) = (state.base, state.seq);
+        let mut circuit = Self::from_state(state);
+        circuit.checkpoints.restored(base, seq);
+        Ok(circuit)
+    }
+
+    fn state(&self, base: u64, seq: u64) -> CircuitStateRef<'_> {
+        CircuitStateRef {
+            version: snapshot::VERSION,
+            store: &self.store,
+            queries: self.views.values().map(QueryStateRef::new).collect(),
+            now: Some(self.now),
+            base,
+            seq,
+            permissions: &self.permissions,
+        }
+    }
+
     /// Rebuilds operator DAGs from the stored query plans and
     /// restores view caches to their saved state.
-    pub fn restore(json: &str) -> serde_json::Result<Self> {
-        let state: CircuitState = serde_json::from_str(json)?;
-
+    fn from_state(state: CircuitState) -> Self {
         let mut circuit = Self {
             store: state.store,
-            graphs: HashMap::new(),
+            graph: Graph::new(),
+            outputs: HashMap::new(),
             views: HashMap::new(),
             dependency_map: HashMap::new(),
+            now: state.now.unwrap_or_else(system_now),
+            permissions: state.permissions,
+            // Numbering continues after the checkpoint the state includes.
+            checkpoints: Checkpoints::after(state.seq),
         };
 
         for qs in state.queries {
@@ -656,8 +1494,8 @@ impl Circuit {
             let referenced_tables = qs.plan.root.referenced_tables();
             let params_sv = qs.params.map(Sp00kyValue::from);
 
-            // Rebuild the operator DAG from the plan
-            let graph = Graph::from_plan(&qs.plan.root);
+            // Rebuild the query's operators from the plan
+            let (output, _) = circuit.compile(&qs.plan, params_sv.as_ref());
 
             // Restore view state
             let mut view = View::new(
@@ -669,10 +1507,16 @@ impl Circuit {
             );
             view.cache = qs.cache;
             view.last_hash = qs.last_hash;
-            view.content_generation = qs.content_generation;
             view.subquery_cache = qs.subquery_cache;
-
-            circuit.graphs.insert(query_id.clone(), graph);
+            // Only the first parent of each subquery record is saved; the
+            // rest of its links follow from the store.
+            view.subquery_edges = compute_current_subquery_set(&circuit.store, &view, &circuit.permissions);
+            view.rows = qs.rows;
+            // The set hash is not saved; it follows from the records and
+            // their stored versions.
+            view.rebuild_hash(&Versions { store: &circuit.store, graph: &circuit.graph, output });
+
+            circuit.outputs.insert(query_id.clone(), output);
             circuit.views.insert(query_id.clone(), view);
 
             // Rebuild dependency map
@@ -683,9 +1527,11 @@ impl Circuit {
                     .or_default()
                     .push(query_id.clone());
             }
+            circuit.track_link_tables(&query_id);
         }
+        circuit.index_edge_tables();
 
-        Ok(circuit)
+        circuit
     }
 
     // --- Accessor methods ---
@@ -695,6 +1541,11 @@ impl Circuit {
         self.views.len()
     }
 
+    /// Number of operator nodes in the shared DAG.
+    pub fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
     /// IDs of all registered views.
     pub fn view_ids(&self) -> Vec<String> {
         self.views.keys().cloned().collect()
@@ -728,7 +1579,7 @@ impl Circuit {
                 let pairs: Vec<(String, serde_json::Value)> = coll
                     .rows
                     .iter()
-                    .map(|(id, val)| (id.clone(), serde_json::Value::from(val.clone())))
+                    .map(|(id, val)| (id.to_string(), serde_json::Value::from(val.clone())))
                     .collect();
                 (name.clone(), ssp_protocol::snapshot_hash::hash_table(pairs))
             })
@@ -1338,7 +2189,7 @@ mod tests {
             )],
         });
 
-        // Should still emit delta (subquery table change bumps content_generation)
+        // Should still emit delta (subquery table changes refresh the parents)
         // but NO subquery items since parent not in view
         assert_eq!(deltas.len(), 1);
         let adds: Vec<_> = deltas[0].subquery_items.iter().filter(|i| i.op == SubqueryOp::Add).collect();
@@ -1395,8 +2246,9 @@ mod tests {
                                                 value: json!({"$param": "parent.id"}),
                                             },
                                         }),
-                                        limit: 1,
+                                        limit: Some(1),
                                         order_by: None,
+                                        offset: 0,
                                     }),
                                     parent_key: Some(SubqueryParentKey {
                                         child_field: grandchild_fk_child.to_string(),
@@ -1445,6 +2297,218 @@ mod tests {
         assert!(adds.iter().any(|i| i.id == "user:alice" && i.alias == "author"));
     }
 
+    #[test]
+    fn tree_format_nests_subquery_records_below_their_parents() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"title": "Hello"})),
+            Record::new("thread", "thread:2", json!({"title": "Empty"})),
+            Record::new("comment", "comment:1", json!({"text": "hi", "thread": "thread:1", "author": "user:alice"})),
+            Record::new("user", "user:alice", json!({"name": "Alice", "id": "user:alice"})),
+        ]);
+        let plan = nested_subquery_query(
+            "q1", "thread", "comment", "comments", "thread",
+            "user", "author", "id", "author",
+        );
+
+        let d = circuit.add_query(plan, None, Some(OutputFormat::Tree)).unwrap();
+        let leaf = |id: &str| TreeNode { id: id.to_string(), children: BTreeMap::new() };
+        let alice = leaf("user:alice");
+        let comment = TreeNode {
+            id: "comment:1".to_string(),
+            children: BTreeMap::from([("author".to_string(), vec![alice])]),
+        };
+        let thread = TreeNode {
+            id: "thread:1".to_string(),
+            children: BTreeMap::from([("comments".to_string(), vec![comment])]),
+        };
+        assert_eq!(d.tree, vec![thread, leaf("thread:2")]);
+        assert_eq!(d.records.len(), 2);
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::delete("comment", "comment:1")],
+        });
+        assert_eq!(deltas[0].tree, vec![leaf("thread:1"), leaf("thread:2")]);
+    }
+
+    #[test]
+    fn tree_format_nests_a_shared_record_below_every_parent() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"title": "Hello"})),
+            Record::new("comment", "comment:1", json!({"text": "hi", "thread": "thread:1", "author": "user:alice"})),
+            Record::new("comment", "comment:2", json!({"text": "yo", "thread": "thread:1", "author": "user:alice"})),
+            Record::new("user", "user:alice", json!({"name": "Alice", "id": "user:alice"})),
+        ]);
+        let plan = nested_subquery_query(
+            "q1", "thread", "comment", "comments", "thread",
+            "user", "author", "id", "author",
+        );
+
+        let d = circuit.add_query(plan, None, Some(OutputFormat::Tree)).unwrap();
+        let authored = |id: &str| TreeNode {
+            id: id.to_string(),
+            children: BTreeMap::from([(
+                "author".to_string(),
+                vec![TreeNode { id: "user:alice".to_string(), children: BTreeMap::new() }],
+            )]),
+        };
+        let thread = TreeNode {
+            id: "thread:1".to_string(),
+            children: BTreeMap::from([("comments".to_string(), vec![authored("comment:1"), authored("comment:2")])]),
+        };
+        assert_eq!(d.tree, vec![thread.clone()]);
+        // The shared record is still reported once.
+        let alice = d.subquery_items.iter().filter(|i| i.id == "user:alice").count();
+        assert_eq!(alice, 1);
+
+        // The links survive a restore.
+        let restored = Circuit::restore(&circuit.save().unwrap()).unwrap();
+        assert_eq!(restored.views["q1"].tree(), vec![thread]);
+    }
+
+    #[test]
+    fn streaming_format_sends_only_the_changes() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"title": "Hello"})),
+            Record::new("thread", "thread:2", json!({"title": "World"})),
+        ]);
+        let plan = subquery_query_with_parent_key("q1", "thread", "comment", "comments", "thread");
+
+        let d = circuit.add_query(plan, None, Some(OutputFormat::Streaming)).unwrap();
+        assert_eq!(d.additions.len(), 2);
+        assert!(d.records.is_empty() && d.tree.is_empty());
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::create("comment", "comment:1", json!({"text": "hi", "thread": "thread:1"}))],
+        });
+        assert!(deltas[0].records.is_empty());
+        assert_eq!(deltas[0].subquery_items.len(), 1);
+    }
+
+    #[test]
+    fn rows_carry_projected_content_only_when_requested() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![Record::new("user", "user:1", json!({"name": "alice", "age": 30}))]);
+        let aliased = |id: &str| QueryPlan {
+            id: id.to_string(),
+            root: OperatorPlan::Project {
+                input: Box::new(OperatorPlan::Scan { table: "user".to_string() }),
+                projections: vec![Projection::Field {
+                    field: crate::types::Path::new("name"),
+                    alias: Some("who".to_string()),
+                }],
+            },
+        };
+
+        let d = circuit.add_query(aliased("plain"), None, None).unwrap();
+        assert!(d.rows.is_empty());
+
+        let d = circuit.add_query_with_rows(aliased("q1"), None, None).unwrap();
+        assert_eq!(d.rows.len(), 1);
+        let (key, row) = &d.rows[0];
+        assert_eq!(key, "user:1");
+        let row = serde_json::Value::from(row.clone());
+        assert_eq!(row["who"], json!("alice"));
+        assert!(row.get("name").is_none() && row.get("age").is_none());
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update("user", "user:1", json!({"name": "bob", "age": 31}))],
+        });
+        let d = deltas.iter().find(|d| d.query_id == "q1").unwrap();
+        assert_eq!(serde_json::Value::from(d.rows[0].1.clone())["who"], json!("bob"));
+        assert!(deltas.iter().find(|d| d.query_id == "plain").unwrap().rows.is_empty());
+    }
+
+    #[test]
+    fn subquery_rows_use_the_subquery_projections() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"title": "Hello"})),
+            Record::new("comment", "comment:1", json!({"text": "hi", "thread": "thread:1"})),
+        ]);
+        let mut plan = subquery_query_with_parent_key("q1", "thread", "comment", "comments", "thread");
+        if let OperatorPlan::Project { projections, .. } = &mut plan.root {
+            if let Projection::Subquery { plan: sub, .. } = &mut projections[1] {
+                let filter = std::mem::replace(sub.as_mut(), OperatorPlan::Scan { table: "comment".to_string() });
+                **sub = OperatorPlan::Project {
+                    input: Box::new(filter),
+                    projections: vec![Projection::Field {
+                        field: crate::types::Path::new("text"),
+                        alias: Some("body".to_string()),
+                    }],
+                };
+            }
+        }
+
+        let d = circuit.add_query_with_rows(plan, None, None).unwrap();
+        let row = |key: &str| {
+            let (_, row) = d.rows.iter().find(|(k, _)| k == key).unwrap();
+            serde_json::Value::from(row.clone())
+        };
+        assert_eq!(row("thread:1")["title"], json!("Hello"));
+        let comment = row("comment:1");
+        assert_eq!(comment["body"], json!("hi"));
+        assert!(comment.get("text").is_none() && comment.get("thread").is_none());
+    }
+
+    #[test]
+    fn result_hash_is_the_set_hash_of_records_and_versions() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"title": "Hello", "_00_rv": 1})),
+            Record::new("thread", "thread:2", json!({"title": "World", "_00_rv": 1})),
+        ]);
+        let plan = subquery_query_with_parent_key("q1", "thread", "comment", "comments", "thread");
+        let from_scratch = |circuit: &Circuit| {
+            let view = circuit.get_view("q1").unwrap();
+            let keys = view.cache.keys().map(|k| k.as_str()).chain(view.subquery_cache.keys().map(String::as_str));
+            ssp_protocol::view_hash::hash_records(
+                keys.map(|k| (k, circuit.store.get_record_version_by_key(k).unwrap_or(1))),
+            )
+        };
+
+        let d = circuit.add_query(plan, None, None).unwrap();
+        assert_eq!(d.result_hash, from_scratch(&circuit));
+
+        let steps = [
+            Change::create("comment", "comment:1", json!({"text": "hi", "thread": "thread:1", "_00_rv": 1})),
+            Change::update("thread", "thread:2", json!({"title": "Edited", "_00_rv": 2})),
+            Change::update("comment", "comment:1", json!({"text": "edited", "thread": "thread:1", "_00_rv": 2})),
+            Change::delete("thread", "thread:1"),
+        ];
+        let mut hashes = vec![d.result_hash];
+        for change in steps {
+            let deltas = circuit.step(ChangeSet { changes: vec![change] });
+            assert_eq!(deltas[0].result_hash, from_scratch(&circuit));
+            hashes.push(deltas[0].result_hash.clone());
+        }
+        hashes.dedup();
+        assert_eq!(hashes.len(), 5, "every step changes the hash");
+
+        let restored = Circuit::restore(&circuit.save().unwrap()).unwrap();
+        assert_eq!(restored.get_view("q1").unwrap().compute_hash(), from_scratch(&circuit));
+    }
+
+    #[test]
+    fn result_hash_folds_a_record_that_is_also_a_subquery_record_once() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:root", json!({"title": "Root", "_00_rv": 1})),
+            Record::new("thread", "thread:child", json!({"parent": "thread:root", "_00_rv": 2})),
+        ]);
+        let plan = subquery_query_with_parent_key("q1", "thread", "thread", "children", "parent");
+        let d = circuit.add_query(plan, None, None).unwrap();
+
+        let view = circuit.get_view("q1").unwrap();
+        assert!(view.cache.contains_key("thread:child") && view.subquery_cache.contains_key("thread:child"));
+        assert_eq!(
+            d.result_hash,
+            ssp_protocol::view_hash::hash_records([("thread:root", 1), ("thread:child", 2)])
+        );
+    }
+
     #[test]
     fn nested_subquery_items_added_on_step() {
         let mut circuit = Circuit::new();
@@ -1484,4 +2548,782 @@ mod tests {
         let adds: Vec<_> = deltas[0].subquery_items.iter().filter(|i| i.op == SubqueryOp::Add).collect();
         assert!(adds.iter().any(|i| i.id == "user:alice" && i.alias == "author"));
     }
+
+    // ── Aggregate view tests ─────────────────────────────
+
+    fn unread_per_thread(id: &str) -> QueryPlan {
+        let root: OperatorPlan = serde_json::from_value(
+            crate::converter::convert_surql_to_dbsp(
+                "SELECT thread, count() AS unread FROM message WHERE read = false GROUP BY thread",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        QueryPlan {
+            id: id.to_string(),
+            root,
+        }
+    }
+
+    fn unread_of(delta: &ViewDelta, key: &str) -> Option<Sp00kyValue> {
+        delta
+            .aggregate_rows
+            .iter()
+            .find(|r| r.key == key)
+            .and_then(|r| r.row.get("unread").cloned())
+    }
+
+    #[test]
+    fn aggregate_view_emits_group_values() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("message", "message:1", json!({"thread": "thread:a", "read": false})),
+            Record::new("message", "message:2", json!({"thread": "thread:a", "read": false})),
+            Record::new("message", "message:3", json!({"thread": "thread:b", "read": true})),
+        ]);
+
+        let delta = circuit
+            .add_query(unread_per_thread("q1"), None, None)
+            .expect("initial delta");
+        let group_a = crate::operator::Aggregate::group_key_for(&[Sp00kyValue::Str(
+            "thread:a".into(),
+        )]);
+
+        assert_eq!(delta.additions, vec![group_a.clone()]);
+        assert_eq!(unread_of(&delta, &group_a), Some(Sp00kyValue::Int(2)));
+    }
+
+    #[test]
+    fn aggregate_value_change_is_reported_as_update() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("message", "message:1", json!({"thread": "thread:a", "read": false})),
+            Record::new("message", "message:2", json!({"thread": "thread:a", "read": false})),
+        ]);
+        let initial = circuit.add_query(unread_per_thread("q1"), None, None).unwrap();
+        let group_a = initial.additions[0].clone();
+
+        // Marking a message as read moves it out of the filter in place.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update(
+                "message",
+                "message:1",
+                json!({"thread": "thread:a", "read": true}),
+            )],
+        });
+
+        assert_eq!(deltas.len(), 1);
+        let d = &deltas[0];
+        assert!(d.additions.is_empty());
+        assert_eq!(d.updates, vec![group_a.clone()]);
+        assert_eq!(unread_of(d, &group_a), Some(Sp00kyValue::Int(1)));
+        assert_ne!(d.result_hash, initial.result_hash);
+
+        // Deleting the last unread message removes the group.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::delete("message", "message:2")],
+        });
+        assert_eq!(deltas[0].removals, vec![group_a]);
+    }
+
+    #[test]
+    fn result_hash_of_derived_rows_follows_their_content() {
+        let messages = |read: bool| {
+            vec![
+                Record::new("message", "message:1", json!({"thread": "thread:a", "read": read})),
+                Record::new("message", "message:2", json!({"thread": "thread:a", "read": false})),
+            ]
+        };
+        // Reaches the final state over a few steps, bumping the group's `_00_rv`
+        let mut stepped = Circuit::new();
+        stepped.load(messages(false));
+        stepped.add_query(unread_per_thread("q1"), None, None).unwrap();
+        for read in [true, false, true] {
+            stepped.step(ChangeSet {
+                changes: vec![Change::update("message", "message:1", json!({"thread": "thread:a", "read": read}))],
+            });
+        }
+        let mut fresh = Circuit::new();
+        fresh.load(messages(true));
+        let initial = fresh.add_query(unread_per_thread("q1"), None, None).unwrap();
+
+        let hash = stepped.get_view("q1").unwrap().compute_hash();
+        assert_eq!(hash, initial.result_hash);
+        let restored = Circuit::restore(&stepped.save().unwrap()).unwrap();
+        assert_eq!(restored.get_view("q1").unwrap().compute_hash(), hash);
+    }
+
+    #[test]
+    fn result_hash_of_uncorrelated_subqueries_follows_their_records() {
+        let comment = |text: &str| Record::new("comment", "comment:1", json!({"text": text, "_00_rv": 2}));
+        let mut stepped = Circuit::new();
+        stepped.load(vec![Record::new("thread", "thread:1", json!({"title": "Hello"}))]);
+        let initial = stepped.add_query(subquery_query("q1", "thread", "comment"), None, None).unwrap();
+        let deltas = stepped.step(ChangeSet {
+            changes: vec![Change::create("comment", "comment:1", json!({"text": "hi", "_00_rv": 1}))],
+        });
+        assert_ne!(deltas[0].result_hash, initial.result_hash);
+        stepped.step(ChangeSet {
+            changes: vec![Change::update("comment", "comment:1", json!({"text": "edited", "_00_rv": 2}))],
+        });
+
+        let mut fresh = Circuit::new();
+        fresh.load(vec![Record::new("thread", "thread:1", json!({"title": "Hello"})), comment("edited")]);
+        let fresh_delta = fresh.add_query(subquery_query("q1", "thread", "comment"), None, None).unwrap();
+        let hash = stepped.get_view("q1").unwrap().compute_hash();
+        assert_eq!(hash, fresh_delta.result_hash);
+        let restored = Circuit::restore(&stepped.save().unwrap()).unwrap();
+        assert_eq!(restored.get_view("q1").unwrap().compute_hash(), hash);
+
+        // Back to the initial hash once the subquery table is empty again
+        let deltas = stepped.step(ChangeSet { changes: vec![Change::delete("comment", "comment:1")] });
+        assert_eq!(deltas[0].result_hash, initial.result_hash);
+    }
+
+    #[test]
+    fn update_that_fails_filter_removes_record() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![Record::new(
+            "thread",
+            "thread:1",
+            json!({"title": "Hello", "archived": false}),
+        )]);
+        let plan = QueryPlan {
+            id: "q1".to_string(),
+            root: OperatorPlan::Filter {
+                input: Box::new(OperatorPlan::Scan {
+                    table: "thread".to_string(),
+                }),
+                predicate: Predicate::Eq {
+                    field: crate::types::Path::new("archived"),
+                    value: json!(false),
+                },
+            },
+        };
+        circuit.add_query(plan, None, None);
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update(
+                "thread",
+                "thread:1",
+                json!({"title": "Hello", "archived": true}),
+            )],
+        });
+
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].removals, vec!["thread:1".to_string()]);
+        assert!(!circuit.get_view("q1").unwrap().cache.is_present("thread:1"));
+    }
+
+    #[test]
+    fn distinct_view_delivers_unique_values() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("post", "post:1", json!({"author": "ann"})),
+            Record::new("post", "post:2", json!({"author": "ann"})),
+        ]);
+        let root: OperatorPlan = serde_json::from_value(
+            crate::converter::convert_surql_to_dbsp("SELECT DISTINCT author FROM post").unwrap(),
+        )
+        .unwrap();
+        let initial = circuit
+            .add_query(QueryPlan { id: "q1".to_string(), root }, None, None)
+            .expect("initial delta");
+        let ann = crate::operator::Aggregate::group_key_for(&[Sp00kyValue::Str("ann".into())]);
+        assert_eq!(initial.additions, vec![ann.clone()]);
+        assert_eq!(
+            initial.aggregate_rows[0].row.get("author"),
+            Some(&Sp00kyValue::Str("ann".into()))
+        );
+
+        // A second author appears; an existing one changing its duplicate is silent.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update("post", "post:2", json!({"author": "bob"}))],
+        });
+        let bob = crate::operator::Aggregate::group_key_for(&[Sp00kyValue::Str("bob".into())]);
+        assert_eq!(deltas[0].additions, vec![bob.clone()]);
+        assert!(deltas[0].removals.is_empty());
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::delete("post", "post:1")],
+        });
+        assert_eq!(deltas[0].removals, vec![ann]);
+    }
+
+    fn threads_with_comments(id: &str, kind: crate::operator::JoinKind) -> QueryPlan {
+        QueryPlan {
+            id: id.to_string(),
+            root: OperatorPlan::Join {
+                left: Box::new(OperatorPlan::Scan { table: "thread".to_string() }),
+                right: Box::new(OperatorPlan::Scan { table: "comment".to_string() }),
+                on: crate::operator::JoinCondition::eq(
+                    crate::types::Path::new("id"),
+                    crate::types::Path::new("thread"),
+                ),
+                kind,
+            },
+        }
+    }
+
+    fn comment(id: &str, thread: &str) -> Change {
+        Change::create("comment", id, json!({"thread": thread}))
+    }
+
+    #[test]
+    fn left_join_keeps_parents_without_children() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"id": "thread:1"})),
+            Record::new("thread", "thread:2", json!({"id": "thread:2"})),
+            Record::new("comment", "comment:1", json!({"thread": "thread:1"})),
+        ]);
+        let initial = circuit
+            .add_query(threads_with_comments("q1", crate::operator::JoinKind::Left), None, None)
+            .expect("initial delta");
+        let mut additions = initial.additions.clone();
+        additions.sort();
+        assert_eq!(additions, vec!["thread:1".to_string(), "thread:2".to_string()]);
+
+        // The first child arriving swaps the unmatched row for a match.
+        let deltas = circuit.step(ChangeSet { changes: vec![comment("comment:2", "thread:2")] });
+        assert_eq!(deltas.len(), 1);
+        assert!(deltas[0].additions.is_empty());
+        assert!(deltas[0].removals.is_empty());
+        assert_eq!(deltas[0].updates, vec!["thread:2".to_string()]);
+
+        // Deleting the last child keeps the parent.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::delete("comment", "comment:1")],
+        });
+        assert!(deltas[0].removals.is_empty());
+        assert_eq!(deltas[0].updates, vec!["thread:1".to_string()]);
+        assert!(circuit.get_view("q1").unwrap().cache.is_present("thread:1"));
+
+        // Deleting the parent removes it.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::delete("thread", "thread:2")],
+        });
+        assert_eq!(deltas[0].removals, vec!["thread:2".to_string()]);
+    }
+
+    #[test]
+    fn inner_join_keeps_parent_until_last_match_is_deleted() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"id": "thread:1"})),
+            Record::new("comment", "comment:1", json!({"thread": "thread:1"})),
+            Record::new("comment", "comment:2", json!({"thread": "thread:1"})),
+        ]);
+        circuit.add_query(threads_with_comments("q1", crate::operator::JoinKind::Inner), None, None);
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::delete("comment", "comment:1")],
+        });
+        assert!(deltas.iter().all(|d| d.removals.is_empty()));
+        assert!(circuit.get_view("q1").unwrap().cache.is_present("thread:1"));
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::delete("comment", "comment:2")],
+        });
+        assert_eq!(deltas[0].removals, vec!["thread:1".to_string()]);
+    }
+
+    #[test]
+    fn computed_projection_rows_follow_record_updates() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("post", "post:1", json!({"title": "hello", "tags": ["a", "b"]})),
+            Record::new("post", "post:2", json!({"title": "hey", "tags": []})),
+        ]);
+        let root: OperatorPlan = serde_json::from_value(
+            crate::converter::convert_surql_to_dbsp(
+                "SELECT string::uppercase(title) AS shout, count(tags) AS n FROM post \
+                 WHERE string::len(title) > 3 ORDER BY n DESC LIMIT 10",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let initial = circuit
+            .add_query(QueryPlan { id: "q1".to_string(), root }, None, None)
+            .expect("initial delta");
+        assert_eq!(initial.additions, vec!["post:1".to_string()]);
+        let row = &initial.aggregate_rows[0].row;
+        assert_eq!(row.get("shout"), Some(&Sp00kyValue::Str("HELLO".into())));
+        assert_eq!(row.get("n"), Some(&Sp00kyValue::Int(2)));
+
+        // Renaming post:2 brings it into the filter; post:1's count changes.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![
+                Change::update("post", "post:2", json!({"title": "heyyy", "tags": []})),
+                Change::update("post", "post:1", json!({"title": "hello", "tags": ["a"]})),
+            ],
+        });
+        let d = &deltas[0];
+        assert_eq!(d.additions, vec!["post:2".to_string()]);
+        assert_eq!(d.updates, vec!["post:1".to_string()]);
+        let n_of = |key: &str| {
+            d.aggregate_rows
+                .iter()
+                .find(|r| r.key == key)
+                .and_then(|r| r.row.get("n").cloned())
+        };
+        assert_eq!(n_of("post:1"), Some(Sp00kyValue::Int(1)));
+        assert_eq!(n_of("post:2"), Some(Sp00kyValue::Int(0)));
+    }
+
+    fn millis(datetime: &str) -> i64 {
+        crate::eval::time::datetime_millis(&Sp00kyValue::Str(datetime.into())).unwrap()
+    }
+
+    fn surql_plan(id: &str, sql: &str) -> QueryPlan {
+        let root = serde_json::from_value(crate::converter::convert_surql_to_dbsp(sql).unwrap()).unwrap();
+        QueryPlan { id: id.to_string(), root }
+    }
+
+    #[test]
+    fn time_windows_move_with_the_clock() {
+        let mut circuit = Circuit::new();
+        circuit.tick(millis("2024-03-02T00:00:00Z"));
+        circuit.load(vec![
+            Record::new("event", "event:1", json!({"created_at": "2024-03-01T06:00:00Z", "expires_at": "2024-03-02T09:00:00Z"})),
+            Record::new("event", "event:2", json!({"created_at": "2024-03-01T18:00:00Z", "expires_at": "2024-03-05T00:00:00Z"})),
+            Record::new("event", "event:3", json!({"created_at": "2024-02-20T00:00:00Z", "expires_at": "2024-03-02T00:30:00Z"})),
+        ]);
+        let recent = circuit
+            .add_query(surql_plan("recent", "SELECT * FROM event WHERE created_at > time::now() - 1d"), None, None)
+            .expect("initial delta");
+        assert_eq!(sorted(recent.additions), vec!["event:1", "event:2"]);
+        let expiring = circuit
+            .add_query(surql_plan("expiring", "SELECT * FROM event WHERE expires_at < time::now() + 1h"), None, None)
+            .expect("initial delta");
+        assert_eq!(expiring.additions, vec!["event:3".to_string()]);
+
+        // 08:30: event:1 is older than a day and expires within the hour.
+        let deltas = circuit.tick(millis("2024-03-02T08:30:00Z"));
+        let recent = deltas.iter().find(|d| d.query_id == "recent").expect("recent delta");
+        assert_eq!(recent.removals, vec!["event:1".to_string()]);
+        assert!(recent.additions.is_empty());
+        let expiring = deltas.iter().find(|d| d.query_id == "expiring").expect("expiring delta");
+        assert_eq!(expiring.additions, vec!["event:1".to_string()]);
+
+        // Same time again: nothing moves.
+        assert!(circuit.tick(millis("2024-03-02T08:30:00Z")).is_empty());
+
+        // New records are judged against the current clock.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::create("event", "event:4", json!({"created_at": "2024-03-01T09:00:00Z"}))],
+        });
+        assert_eq!(deltas[0].additions, vec!["event:4".to_string()]);
+        let deltas = circuit.tick(millis("2024-03-02T09:00:00Z"));
+        assert_eq!(deltas[0].removals, vec!["event:4".to_string()]);
+
+        // The clock survives a save/restore.
+        let restored = Circuit::restore(&circuit.save().unwrap()).unwrap();
+        assert_eq!(restored.now(), millis("2024-03-02T09:00:00Z"));
+    }
+
+    fn sorted(mut keys: Vec<String>) -> Vec<String> {
+        keys.sort();
+        keys
+    }
+
+    fn items(delta: &ViewDelta, op: SubqueryOp) -> Vec<(String, String)> {
+        let mut items: Vec<(String, String)> = delta
+            .subquery_items
+            .iter()
+            .filter(|i| i.op == op)
+            .map(|i| (i.id.clone(), i.alias.clone()))
+            .collect();
+        items.sort();
+        items
+    }
+
+    #[test]
+    fn graph_traversal_follows_edges_and_targets() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("user", "user:1", json!({"name": "ada"})),
+            Record::new("user", "user:2", json!({"name": "alan"})),
+            Record::new("user", "user:3", json!({"name": "grace"})),
+            Record::new("follows", "follows:1", json!({"in": "user:1", "out": "user:2"})),
+            Record::new("follows", "follows:2", json!({"in": "user:2", "out": "user:3"})),
+        ]);
+        let initial = circuit
+            .add_query(surql_plan("q", "SELECT ->follows->user.* AS following FROM user:1"), None, None)
+            .expect("initial delta");
+        assert_eq!(initial.additions, vec!["user:1".to_string()]);
+        assert_eq!(items(&initial, SubqueryOp::Add), vec![("user:2".into(), "following".into())]);
+
+        // A new edge brings its target in; removing an edge drops it.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![
+                Change::create("follows", "follows:3", json!({"in": "user:1", "out": "user:3"})),
+                Change::delete("follows", "follows:1"),
+            ],
+        });
+        assert_eq!(items(&deltas[0], SubqueryOp::Add), vec![("user:3".into(), "following".into())]);
+        assert_eq!(items(&deltas[0], SubqueryOp::Remove), vec![("user:2".into(), "following".into())]);
+
+        // Target record changes are reported as updates.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update("user", "user:3", json!({"name": "grace h.", "_00_rv": 2}))],
+        });
+        assert_eq!(items(&deltas[0], SubqueryOp::Update), vec![("user:3".into(), "following".into())]);
+
+        // Two hops: who the people user:2 follows follow.
+        let two_hops = circuit
+            .add_query(surql_plan("q2", "SELECT ->follows->user->follows->user FROM user:1"), None, None)
+            .expect("initial delta");
+        assert!(two_hops.subquery_items.is_empty());
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::create("follows", "follows:4", json!({"in": "user:3", "out": "user:1"}))],
+        });
+        let d = deltas.iter().find(|d| d.query_id == "q2").expect("q2 delta");
+        assert_eq!(
+            items(d, SubqueryOp::Add),
+            vec![("user:1".into(), "->follows->user->follows->user".into())]
+        );
+    }
+
+    #[test]
+    fn record_links_follow_the_linked_table() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("user", "user:1", json!({"name": "ada"})),
+            Record::new("thread", "thread:1", json!({"title": "hi", "author": "user:1"})),
+        ]);
+        let initial = circuit
+            .add_query(surql_plan("q", "SELECT *, author.* FROM thread"), None, None)
+            .expect("initial delta");
+        assert_eq!(items(&initial, SubqueryOp::Add), vec![("user:1".into(), "author".into())]);
+
+        // Relinking to a record of a table the view never referenced: the
+        // view starts following that table.
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update("thread", "thread:1", json!({"title": "hi", "author": "bot:1"}))],
+        });
+        assert_eq!(items(&deltas[0], SubqueryOp::Remove), vec![("user:1".into(), "author".into())]);
+        assert!(items(&deltas[0], SubqueryOp::Add).is_empty());
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::create("bot", "bot:1", json!({"name": "helper"}))],
+        });
+        assert_eq!(items(&deltas[0], SubqueryOp::Add), vec![("bot:1".into(), "author".into())]);
+        // Once no link points into it, the table is no longer routed.
+        circuit.step(ChangeSet {
+            changes: vec![Change::update("thread", "thread:1", json!({"title": "hi", "author": "user:1"}))],
+        });
+        assert!(!circuit.dependency_map_dump().contains_key("bot"));
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update("bot", "bot:1", json!({"name": "helper 2"}))],
+        });
+        assert!(deltas.is_empty());
+
+        circuit.remove_query("q");
+        assert!(circuit.dependency_map_dump().is_empty());
+    }
+
+    #[test]
+    fn reregistering_a_query_replaces_its_operators() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![Record::new("user", "user:1", json!({"name": "ada", "age": 30}))]);
+
+        circuit.add_query(surql_plan("q", "SELECT * FROM user WHERE age > 18"), None, None);
+        let delta = circuit
+            .add_query(surql_plan("q", "SELECT * FROM user WHERE age > 25 ORDER BY age LIMIT 5"), None, None)
+            .expect("initial delta");
+        assert_eq!(delta.additions, vec!["user:1".to_string()]);
+        assert_eq!(circuit.dependency_map_dump()["user"], vec!["q".to_string()]);
+
+        circuit.remove_query("q");
+        assert_eq!(circuit.node_count(), 0);
+        assert!(circuit.dependency_map_dump().is_empty());
+    }
+
+    #[test]
+    fn identical_views_share_one_dag() {
+        let sql = "SELECT thread, count() AS unread FROM message WHERE read = false AND thread != $skip GROUP BY thread";
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("message", "message:1", json!({"thread": "thread:a", "read": false})),
+            Record::new("message", "message:2", json!({"thread": "thread:b", "read": false})),
+        ]);
+        let skip = |thread: &str| Some(json!({ "skip": thread }));
+
+        circuit.add_query(surql_plan("a1", sql), skip("thread:x"), None);
+        let nodes = circuit.node_count();
+        for i in 2..=20 {
+            circuit.add_query(surql_plan(&format!("a{}", i), sql), skip("thread:x"), None);
+        }
+        assert_eq!(circuit.node_count(), nodes);
+
+        // A new binding shares the scan; a late subscriber still gets the
+        // full current result.
+        let b = circuit.add_query(surql_plan("b", sql), skip("thread:a"), None).unwrap();
+        assert!(circuit.node_count() < 2 * nodes);
+        assert_eq!(b.additions.len(), 1);
+        let late = circuit.add_query(surql_plan("a21", sql), skip("thread:x"), None).unwrap();
+        assert_eq!(late.additions.len(), 2);
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::create("message", "message:3", json!({"thread": "thread:a", "read": false}))],
+        });
+        let updated: Vec<&str> = deltas
+            .iter()
+            .filter(|d| !d.updates.is_empty())
+            .map(|d| d.query_id.as_str())
+            .collect();
+        assert_eq!(updated.len(), 21);
+        assert!(!updated.contains(&"b"));
+        let group = &deltas.iter().find(|d| d.query_id == "a7").unwrap().updates[0];
+        assert_eq!(circuit.derived_row("a7", group).unwrap().get("unread"), Some(&Sp00kyValue::Int(2)));
+
+        for i in 1..=21 {
+            circuit.remove_query(&format!("a{}", i));
+        }
+        assert_eq!(circuit.node_count(), nodes);
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::create("message", "message:4", json!({"thread": "thread:b", "read": false}))],
+        });
+        assert_eq!(deltas.len(), 1);
+        let group = &deltas[0].updates[0];
+        assert_eq!(circuit.derived_row("b", group).unwrap().get("unread"), Some(&Sp00kyValue::Int(2)));
+    }
+
+    #[test]
+    fn parameterized_views_only_step_matching_bindings() {
+        let sql = "SELECT * FROM message WHERE read = false AND author = $auth.id";
+        let message = |id: &str, author: &str, read: bool| {
+            Change::create("message", id, json!({"author": author, "read": read}))
+        };
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("message", "message:1", json!({"author": "user:3", "read": false})),
+            Record::new("message", "message:2", json!({"author": "user:4", "read": true})),
+        ]);
+        for i in 0..50 {
+            let params = json!({ "auth": { "id": format!("user:{}", i) } });
+            let initial = circuit.add_query(surql_plan(&format!("inbox{}", i), sql), Some(params), None);
+            assert_eq!(initial.map_or(0, |d| d.additions.len()), usize::from(i == 3));
+        }
+        assert_eq!(circuit.graph.templates().len(), 1);
+        assert_eq!(circuit.graph.templates()[0].len(), 50);
+
+        // Only the author's inbox sees the new message; moving it to another
+        // author retracts it there (routed by the old row) and adds it here.
+        let deltas = circuit.step(ChangeSet { changes: vec![message("message:3", "user:7", false)] });
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].query_id, "inbox7");
+        assert_eq!(deltas[0].additions, vec!["message:3"]);
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::update("message", "message:3", json!({"author": "user:9", "read": false}))],
+        });
+        let mut moved: Vec<(&str, usize, usize)> = deltas
+            .iter()
+            .map(|d| (d.query_id.as_str(), d.additions.len(), d.removals.len()))
+            .collect();
+        moved.sort();
+        assert_eq!(moved, vec![("inbox7", 0, 1), ("inbox9", 1, 0)]);
+
+        // A delta steps the scan and one binding; bindings whose parameter
+        // no record matches are not stepped at all.
+        let deltas = HashMap::from([("message".to_string(), ZSet::from([(RowKey::from("message:1"), 1)]))]);
+        let stepped = run_graph(&mut circuit.graph, &circuit.store, &deltas);
+        assert_eq!(stepped.iter().filter(|out| out.is_some()).count(), 2);
+
+        // Removing bindings keeps the rest routed.
+        for i in 10..50 {
+            circuit.remove_query(&format!("inbox{}", i));
+        }
+        assert_eq!(circuit.graph.templates()[0].len(), 10);
+        let deltas = circuit.step(ChangeSet { changes: vec![message("message:4", "user:9", false)] });
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].query_id, "inbox9");
+    }
+
+    #[test]
+    fn explain_reports_operator_tree_state_and_work() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"title": "a"})),
+            Record::new("comment", "comment:1", json!({"thread": "thread:1"})),
+        ]);
+        circuit.add_query(threads_with_comments("joined", crate::operator::JoinKind::Inner), None, None);
+        circuit.add_query(scan_query("threads", "thread"), None, None);
+        circuit.step(ChangeSet { changes: vec![comment("comment:2", "thread:1")] });
+
+        let explain = circuit.explain("joined", false).unwrap();
+        assert_eq!(explain.nodes, 3);
+        assert_eq!(explain.step_time_us, None);
+        let join = &explain.plan;
+        assert_eq!((join.op.as_str(), join.arity), ("join", 2));
+        assert_eq!(join.state.get("left"), Some(&1));
+        assert_eq!(join.state.get("right"), Some(&2));
+        assert_eq!(explain.state_rows, 3);
+        assert!(join.stats.is_none());
+        let inputs: Vec<&str> = join.inputs.iter().map(|n| n.config["table"].as_str().unwrap()).collect();
+        assert_eq!(inputs, vec!["thread", "comment"]);
+        // The thread scan also serves the other view.
+        assert_eq!(join.inputs[0].refs, 2);
+
+        let analyzed = circuit.explain("joined", true).unwrap();
+        let stats = analyzed.plan.inputs[1].stats.unwrap();
+        assert_eq!((stats.steps, stats.rows_in, stats.rows_out), (1, 1, 1));
+        assert_eq!(analyzed.plan.inputs[0].stats.unwrap().steps, 0);
+        assert!(analyzed.step_time_us.is_some());
+
+        let all: Vec<String> = circuit.explain_all(false).into_iter().map(|e| e.view_id).collect();
+        assert_eq!(all, vec!["joined", "threads"]);
+        assert!(circuit.explain("missing", false).is_none());
+    }
+
+    #[test]
+    fn bulk_registration_and_parallel_steps_match_one_by_one() {
+        let records = || {
+            (0..20).map(|i| Record::new("thread", &format!("thread:{i}"), json!({"id": format!("thread:{i}")})))
+        };
+        let plans = || {
+            (0..40).map(|i| {
+                if i % 2 == 0 {
+                    scan_query(&format!("view{i}"), "thread")
+                } else {
+                    threads_with_comments(&format!("view{i}"), crate::operator::JoinKind::Left)
+                }
+            })
+        };
+
+        let mut serial = Circuit::new();
+        serial.load(records());
+        // Some of the views carry row content in their deltas.
+        let with_rows = |i: usize| i % 4 == 1;
+        let expected: Vec<Option<ViewDelta>> = plans()
+            .enumerate()
+            .map(|(i, plan)| {
+                if with_rows(i) {
+                    serial.add_query_with_rows(plan, None, None)
+                } else {
+                    serial.add_query(plan, None, None)
+                }
+            })
+            .collect();
+
+        let mut bulk = Circuit::new();
+        bulk.load(records());
+        let deltas = bulk.add_queries(plans().enumerate().map(|(i, plan)| (plan, None, None, with_rows(i))));
+        let summary = |deltas: &[Option<ViewDelta>]| -> Vec<(String, String, usize)> {
+            deltas.iter().flatten().map(|d| (d.query_id.clone(), d.result_hash.clone(), d.rows.len())).collect()
+        };
+        assert!(summary(&deltas).iter().any(|(_, _, rows)| *rows > 0));
+        assert_eq!(deltas.len(), 40);
+        assert_eq!(summary(&deltas), summary(&expected));
+
+        let changes = ChangeSet { changes: vec![comment("comment:1", "thread:3"), comment("comment:2", "thread:7")] };
+        let stepped = bulk.step(changes.clone());
+        let expected = serial.step(changes);
+        let ids = |deltas: &[ViewDelta]| -> Vec<String> { deltas.iter().map(|d| d.query_id.clone()).collect() };
+        assert_eq!(ids(&stepped), ids(&expected));
+        assert_eq!(stepped.len(), 20);
+        for (a, b) in stepped.iter().zip(&expected) {
+            let sorted = |keys: &[String]| -> Vec<String> {
+                let mut keys = keys.to_vec();
+                keys.sort();
+                keys
+            };
+            assert_eq!((sorted(&a.updates), &a.result_hash), (sorted(&b.updates), &b.result_hash));
+        }
+    }
+
+    #[test]
+    fn row_keys_are_allocated_once_per_row() {
+        // Longer than the inline storage of a `RowKey`.
+        let id = "message:01J9Z3W8Q6V0N4XK2M7T5R1B8C";
+        let mut circuit = Circuit::new();
+        circuit.add_query(scan_query("messages", "message"), None, None);
+        circuit.step(ChangeSet { changes: vec![Change::create("message", id, json!({"n": 1}))] });
+        let (updated, _) = circuit.store.apply_change(&Change::update("message", id, json!({"n": 2})));
+
+        let (stored, _) = circuit.store.get_collection("message").unwrap().zset.get_key_value(id).unwrap();
+        let (cached, _) = circuit.get_view("messages").unwrap().cache.get_key_value(id).unwrap();
+        assert_eq!(stored.as_ptr(), updated.as_ptr());
+        assert_eq!(stored.as_ptr(), cached.as_ptr());
+    }
+
+    #[test]
+    fn memory_report_counts_rows_caches_and_operator_state() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("thread", "thread:1", json!({"id": "thread:1"})),
+            Record::new("comment", "comment:1", json!({"thread": "thread:1", "text": "x".repeat(64)})),
+        ]);
+        circuit.add_query(threads_with_comments("joined", crate::operator::JoinKind::Inner), None, None);
+        circuit.add_query(scan_query("threads", "thread"), None, None);
+
+        let report = circuit.memory_report();
+        assert_eq!(report.views, 2);
+        assert_eq!(report.view_cache_entries, 2);
+        assert_eq!(report.operator_nodes, 3);
+        assert_eq!(report.operator_state_rows, 2);
+        let (thread, comment) = (&report.tables["thread"], &report.tables["comment"]);
+        assert_eq!((thread.rows, comment.rows), (1, 1));
+        // Only the long comment text leaves the inline string storage.
+        assert!(comment.value_bytes >= 64 && comment.value_bytes > thread.value_bytes);
+        assert!(report.estimated_bytes > comment.key_bytes + comment.value_bytes);
+    }
+
+    #[test]
+    fn select_permissions_restrict_views_to_the_registering_user() {
+        let mut circuit = Circuit::new();
+        let mut permissions = Permissions::new();
+        permissions.define("note", "owner = $auth.id").unwrap();
+        circuit.set_permissions(permissions);
+        circuit.load(vec![
+            Record::new("note", "note:1", json!({"owner": "user:a"})),
+            Record::new("note", "note:2", json!({"owner": "user:b"})),
+        ]);
+
+        let as_user = |id: &str| crate::permissions::bind_auth(None, Some(json!(id)), None);
+        let a = circuit.add_query(surql_plan("a", "SELECT * FROM note"), as_user("user:a"), None);
+        let b = circuit.add_query(surql_plan("b", "SELECT * FROM note"), as_user("user:b"), None);
+        assert_eq!(a.unwrap().additions, vec!["note:1"]);
+        assert_eq!(b.unwrap().additions, vec!["note:2"]);
+
+        // Without an auth context (a system user) every row is visible, and
+        // client params cannot claim one.
+        let spoofed = crate::permissions::bind_auth(Some(json!({"_00_auth": {"id": "user:a"}})), None, None);
+        let all = circuit.add_query(surql_plan("all", "SELECT * FROM note"), spoofed, None);
+        assert_eq!(all.unwrap().additions.len(), 2);
+
+        let deltas = circuit.step(ChangeSet {
+            changes: vec![Change::create("note", "note:3", json!({"owner": "user:b"}))],
+        });
+        let mut seen: Vec<&str> = deltas.iter().map(|d| d.query_id.as_str()).collect();
+        seen.sort();
+        assert_eq!(seen, vec!["all", "b"]);
+    }
+
+    #[test]
+    fn select_permissions_survive_a_restore() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("note", "note:1", json!({"owner": "user:a"})),
+            Record::new("note", "note:2", json!({"owner": "user:b"})),
+        ]);
+        assert!(matches!(circuit.checkpoint(Vec::new(), SnapshotFormat::Binary), Ok(Checkpoint::Base { .. })));
+
+        let mut permissions = Permissions::new();
+        permissions.define("note", "owner = $auth.id").unwrap();
+        circuit.set_permissions(permissions);
+        // Deltas do not carry the rules; the next checkpoint is a full state.
+        assert!(matches!(circuit.checkpoint(Vec::new(), SnapshotFormat::Binary), Ok(Checkpoint::Base { .. })));
+
+        let as_user = |id: &str| crate::permissions::bind_auth(None, Some(json!(id)), None);
+        for mut restored in [
+            Circuit::restore_from(circuit.save_bytes().unwrap().as_slice()).unwrap(),
+            Circuit::restore(&circuit.save().unwrap()).unwrap(),
+        ] {
+            let b = restored.add_query(surql_plan("b", "SELECT * FROM note"), as_user("user:b"), None);
+            assert_eq!(b.unwrap().additions, vec!["note:2"]);
+        }
+    }
 }
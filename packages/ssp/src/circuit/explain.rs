@@ -0,0 +1,89 @@
+use crate::circuit::graph::{Graph, NodeId, NodeStats};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// `EXPLAIN` of a registered view: the operator tree its output is computed
+/// by, with the state each operator holds.
+///
+/// With `analyze`, every node also carries the work its incremental steps
+/// did so far. Nodes are shared between views with identical sub-plans,
+/// so a shared node's state and time count towards each of its views.
+#[derive(Debug, Clone, Serialize)]
+pub struct Explain {
+    pub view_id: String,
+    /// Distinct operator nodes the view reads from.
+    pub nodes: usize,
+    /// Rows held in Z⁻¹ state over those nodes.
+    pub state_rows: usize,
+    /// Total step time of those nodes in microseconds (`analyze` only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_time_us: Option<u64>,
+    pub plan: ExplainNode,
+}
+
+/// One operator node in an `Explain` tree.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExplainNode {
+    pub id: NodeId,
+    /// Operator kind, as in the plan (`scan`, `filter`, `join`, ...).
+    pub op: String,
+    /// The plan node without its inputs.
+    pub config: Value,
+    pub arity: usize,
+    /// Views and operators reading this node.
+    pub refs: usize,
+    /// Parameters the operator is evaluated with, if it reads any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub params: Option<Value>,
+    /// Z⁻¹ state sizes, by name.
+    pub state: BTreeMap<&'static str, usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<NodeStats>,
+    pub inputs: Vec<ExplainNode>,
+}
+
+impl Explain {
+    pub(crate) fn new(view_id: &str, graph: &Graph, output: NodeId, analyze: bool) -> Self {
+        let subgraph = graph.subgraph(output);
+        let state_rows = subgraph
+            .iter()
+            .flat_map(|&id| graph.nodes[id].operator.state_sizes())
+            .map(|(_, size)| size)
+            .sum();
+        let step_time_us = analyze.then(|| {
+            subgraph
+                .iter()
+                .map(|&id| graph.nodes[id].stats.step_time_us)
+                .sum()
+        });
+        Explain {
+            view_id: view_id.to_string(),
+            nodes: subgraph.len(),
+            state_rows,
+            step_time_us,
+            plan: ExplainNode::new(graph, output, analyze),
+        }
+    }
+}
+
+impl ExplainNode {
+    fn new(graph: &Graph, id: NodeId, analyze: bool) -> Self {
+        let node = &graph.nodes[id];
+        ExplainNode {
+            id,
+            op: node.config["op"].as_str().unwrap_or_default().to_string(),
+            config: node.config.clone(),
+            arity: node.operator.arity(),
+            refs: node.refs,
+            params: node.ctx.clone().map(Value::from),
+            state: node.operator.state_sizes().into_iter().collect(),
+            stats: analyze.then_some(node.stats),
+            inputs: node
+                .inputs
+                .iter()
+                .map(|&input| ExplainNode::new(graph, input, analyze))
+                .collect(),
+        }
+    }
+}
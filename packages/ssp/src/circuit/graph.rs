@@ -1,5 +1,12 @@
+use crate::algebra::ZSet;
+use crate::circuit::store::Store;
+use crate::circuit::template::{self, Template};
 use crate::operator::{self, Operator};
-use std::collections::HashMap;
+use crate::types::{Path, Sp00kyValue};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use web_time::Instant;
 
 /// Unique identifier for a node in the circuit graph.
 pub type NodeId = usize;
@@ -10,6 +17,20 @@ pub struct Node {
     pub operator: Box<dyn Operator>,
     /// Indices of input nodes (upstream dependencies).
     pub inputs: Vec<NodeId>,
+    /// Query parameters the operator is evaluated with. Only set when the
+    /// operator's own plan references a `$param`.
+    pub ctx: Option<Sp00kyValue>,
+    /// Downstream nodes plus registered outputs reading this node.
+    pub refs: usize,
+    /// Work done by incremental steps so far.
+    pub stats: NodeStats,
+    /// Operator configuration: its plan node without the inputs.
+    pub config: Value,
+    /// Structural key: operator config, inputs and (if used) parameters.
+    key: String,
+    /// For a filter requiring a field to equal a parameter: the field and
+    /// the parameter's value (see `Template`).
+    binding: Option<(Path, Option<Sp00kyValue>)>,
 }
 
 impl std::fmt::Debug for Node {
@@ -18,105 +39,287 @@ impl std::fmt::Debug for Node {
             .field("id", &self.id)
             .field("operator", &self.operator)
             .field("inputs", &self.inputs)
+            .field("refs", &self.refs)
             .finish()
     }
 }
 
+impl Node {
+    /// Incremental step of the operator with the node's parameters,
+    /// recorded in `stats`.
+    pub fn step(&mut self, inputs: &[&ZSet], store: &Store) -> ZSet {
+        let started = Instant::now();
+        let out = self.operator.step(inputs, store, self.ctx.as_ref());
+        self.stats.record(started, inputs, &out);
+        out
+    }
+}
+
+/// Cumulative work of a node's incremental steps (`EXPLAIN ANALYZE`).
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct NodeStats {
+    pub steps: u64,
+    /// Total time spent in `step`, in microseconds.
+    pub step_time_us: u64,
+    /// Input and output delta rows over all steps.
+    pub rows_in: u64,
+    pub rows_out: u64,
+}
+
+impl NodeStats {
+    fn record(&mut self, started: Instant, inputs: &[&ZSet], out: &ZSet) {
+        self.steps += 1;
+        self.step_time_us += started.elapsed().as_micros() as u64;
+        self.rows_in += inputs.iter().map(|delta| delta.len() as u64).sum::<u64>();
+        self.rows_out += out.len() as u64;
+    }
+}
+
 /// The circuit as a directed acyclic graph of operator nodes.
 ///
 /// Contains topology only. Execution state lives in the operators
 /// (via their Z⁻¹ state) and in ViewState.
+///
+/// Nodes are hash-consed: adding a plan reuses every node whose operator,
+/// inputs and parameters are identical to an existing one, so views
+/// registering the same query (or sharing a sub-plan) share its state and
+/// its step cost. Node ids are assigned in insertion order, which is also
+/// a topological order.
 pub struct Graph {
     pub nodes: Vec<Node>,
-    /// Topological execution order (computed once on construction).
+    /// Topological execution order (recomputed when nodes are added or removed).
     topo_order: Vec<NodeId>,
     /// Scan nodes: table_name → [NodeId] for routing deltas.
     scan_index: HashMap<String, Vec<NodeId>>,
-    /// The output (terminal) node of this graph.
+    /// Node by structural key.
+    index: HashMap<String, NodeId>,
+    /// Filters binding the same input field to different parameter values.
+    templates: Vec<Template>,
+    /// Template each node is a binding of.
+    template_of: Vec<Option<usize>>,
+    /// The output node of the last plan added with `from_plan`.
     pub output_node: NodeId,
 }
 
 impl Graph {
+    /// An empty graph.
+    pub fn new() -> Self {
+        Graph {
+            nodes: Vec::new(),
+            topo_order: Vec::new(),
+            scan_index: HashMap::new(),
+            index: HashMap::new(),
+            templates: Vec::new(),
+            template_of: Vec::new(),
+            output_node: 0,
+        }
+    }
+
     /// Build a Graph from an OperatorPlan tree.
     ///
     /// Recursively walks the plan, creating operator nodes and wiring edges.
     pub fn from_plan(plan: &operator::OperatorPlan) -> Self {
-        let mut nodes = Vec::new();
-        let mut scan_index: HashMap<String, Vec<NodeId>> = HashMap::new();
-        let output_node = Self::build_node(plan, &mut nodes, &mut scan_index);
+        let mut graph = Self::new();
+        graph.output_node = graph.add_plan(plan, None);
+        graph
+    }
 
-        let topo_order = Self::compute_topo_order(&nodes);
+    /// Add a plan evaluated with `ctx` as its parameters, reusing identical
+    /// nodes, and return its output node. The output gains one reference,
+    /// dropped again by `release`.
+    ///
+    /// Nodes created by this call are the ids from the previous
+    /// `node_count()` on; they start with empty state.
+    pub fn add_plan(&mut self, plan: &operator::OperatorPlan, ctx: Option<&Sp00kyValue>) -> NodeId {
+        let output = self.build_node(plan, ctx);
+        self.nodes[output].refs += 1;
+        self.topo_order = Self::compute_topo_order(&self.nodes);
+        self.index_templates();
+        output
+    }
 
-        Graph {
-            nodes,
-            topo_order,
-            scan_index,
-            output_node,
-        }
-    }
-
-    fn build_node(
-        plan: &operator::OperatorPlan,
-        nodes: &mut Vec<Node>,
-        scan_index: &mut HashMap<String, Vec<NodeId>>,
-    ) -> NodeId {
-        match plan {
-            operator::OperatorPlan::Scan { table } => {
-                let id = nodes.len();
-                nodes.push(Node {
-                    id,
-                    operator: Box::new(operator::Scan::new(table)),
-                    inputs: vec![],
-                });
-                scan_index.entry(table.clone()).or_default().push(id);
-                id
+    fn build_node(&mut self, plan: &operator::OperatorPlan, ctx: Option<&Sp00kyValue>) -> NodeId {
+        use operator::OperatorPlan;
+
+        let inputs: Vec<NodeId> = match plan {
+            OperatorPlan::Scan { .. } => vec![],
+            OperatorPlan::Join { left, right, .. } => {
+                vec![self.build_node(left, ctx), self.build_node(right, ctx)]
             }
-            operator::OperatorPlan::Filter { input, predicate } => {
-                let input_id = Self::build_node(input, nodes, scan_index);
-                let id = nodes.len();
-                nodes.push(Node {
-                    id,
-                    operator: Box::new(operator::Filter::new(predicate.clone())),
-                    inputs: vec![input_id],
-                });
-                id
+            OperatorPlan::Filter { input, .. }
+            | OperatorPlan::Project { input, .. }
+            | OperatorPlan::Limit { input, .. }
+            | OperatorPlan::Aggregate { input, .. }
+            | OperatorPlan::Distinct { input, .. } => vec![self.build_node(input, ctx)],
+        };
+
+        let mut config = serde_json::to_value(plan).unwrap_or_default();
+        if let Value::Object(obj) = &mut config {
+            for port in ["input", "left", "right"] {
+                obj.remove(port);
             }
-            operator::OperatorPlan::Join { left, right, on } => {
-                let left_id = Self::build_node(left, nodes, scan_index);
-                let right_id = Self::build_node(right, nodes, scan_index);
-                let id = nodes.len();
-                nodes.push(Node {
-                    id,
-                    operator: Box::new(operator::Join::new(on.clone())),
-                    inputs: vec![left_id, right_id],
-                });
-                id
+        }
+        let ctx = ctx.filter(|_| mentions_param(&config)).cloned();
+        let key = format!(
+            "{}|{:?}|{}",
+            config,
+            inputs,
+            ctx.clone().map(Value::from).unwrap_or_default()
+        );
+        if let Some(&id) = self.index.get(&key) {
+            return id;
+        }
+
+        let operator: Box<dyn Operator> = match plan {
+            OperatorPlan::Scan { table } => Box::new(operator::Scan::new(table)),
+            OperatorPlan::Filter { predicate, .. } => {
+                Box::new(operator::Filter::new(predicate.clone()))
             }
-            operator::OperatorPlan::Project { input, projections } => {
-                let input_id = Self::build_node(input, nodes, scan_index);
-                let id = nodes.len();
-                nodes.push(Node {
-                    id,
-                    operator: Box::new(operator::Map::new(projections.clone())),
-                    inputs: vec![input_id],
-                });
-                id
+            OperatorPlan::Join { on, kind, .. } => {
+                Box::new(operator::Join::new(on.clone()).with_kind(*kind))
             }
-            operator::OperatorPlan::Limit {
-                input,
+            OperatorPlan::Project { projections, .. } => {
+                Box::new(operator::Map::new(projections.clone()))
+            }
+            OperatorPlan::Limit {
                 limit,
                 order_by,
+                offset,
+                ..
+            } => {
+                let top_k = match limit {
+                    Some(limit) => operator::TopK::new(*limit, order_by.clone()),
+                    None => operator::TopK::unbounded(order_by.clone()),
+                };
+                Box::new(top_k.with_offset(*offset))
+            }
+            OperatorPlan::Aggregate {
+                group_by,
+                aggregates,
+                ..
             } => {
-                let input_id = Self::build_node(input, nodes, scan_index);
-                let id = nodes.len();
-                nodes.push(Node {
-                    id,
-                    operator: Box::new(operator::TopK::new(*limit, order_by.clone())),
-                    inputs: vec![input_id],
-                });
-                id
+                let group_by = if group_by.is_empty() {
+                    None
+                } else {
+                    Some(group_by.clone())
+                };
+                Box::new(operator::Aggregate::new(group_by, aggregates.clone()))
+            }
+            OperatorPlan::Distinct { fields, .. } => {
+                Box::new(operator::Distinct::on_fields(fields.clone()))
+            }
+        };
+
+        let binding = match plan {
+            OperatorPlan::Filter { predicate, .. } if ctx.is_some() => {
+                template::param_binding(predicate, ctx.as_ref())
             }
+            _ => None,
+        };
+
+        let id = self.nodes.len();
+        if let OperatorPlan::Scan { table } = plan {
+            self.scan_index.entry(table.clone()).or_default().push(id);
+        }
+        for &input in &inputs {
+            self.nodes[input].refs += 1;
+        }
+        self.index.insert(key.clone(), id);
+        self.nodes.push(Node {
+            id,
+            operator,
+            inputs,
+            ctx,
+            refs: 0,
+            stats: NodeStats::default(),
+            config,
+            key,
+            binding,
+        });
+        id
+    }
+
+    /// Drop one reference to an output node added with `add_plan`. Nodes no
+    /// longer read by anything are removed and the rest renumbered; the
+    /// returned table maps every old id to its new one (`None` if removed).
+    pub fn release(&mut self, output: NodeId) -> Vec<Option<NodeId>> {
+        let mut dead = HashSet::new();
+        let mut pending = vec![output];
+        while let Some(id) = pending.pop() {
+            let node = &mut self.nodes[id];
+            node.refs = node.refs.saturating_sub(1);
+            if node.refs == 0 && dead.insert(id) {
+                pending.extend(node.inputs.iter().copied());
+            }
+        }
+
+        let mut remap = vec![None; self.nodes.len()];
+        if dead.is_empty() {
+            for (id, slot) in remap.iter_mut().enumerate() {
+                *slot = Some(id);
+            }
+            return remap;
+        }
+
+        let nodes = std::mem::take(&mut self.nodes);
+        self.index.clear();
+        self.scan_index.clear();
+        for mut node in nodes {
+            if dead.contains(&node.id) {
+                continue;
+            }
+            let id = self.nodes.len();
+            remap[node.id] = Some(id);
+            node.id = id;
+            for input in node.inputs.iter_mut() {
+                *input = remap[*input].expect("inputs precede their consumers");
+            }
+            for table in node.operator.collections() {
+                self.scan_index.entry(table).or_default().push(id);
+            }
+            self.index.insert(node.key.clone(), id);
+            self.nodes.push(node);
         }
+        self.topo_order = Self::compute_topo_order(&self.nodes);
+        self.index_templates();
+        remap
+    }
+
+    /// Group filters that compare the same field of the same input with a
+    /// parameter into templates. A lone binding is stepped as usual.
+    fn index_templates(&mut self) {
+        let mut groups: HashMap<(NodeId, &Path), Vec<(NodeId, &Sp00kyValue)>> = HashMap::new();
+        for node in &self.nodes {
+            if let Some((field, Some(value))) = &node.binding {
+                groups.entry((node.inputs[0], field)).or_default().push((node.id, value));
+            }
+        }
+
+        let mut templates = Vec::new();
+        let mut template_of = vec![None; self.nodes.len()];
+        for ((input, field), bindings) in groups {
+            if bindings.len() < 2 {
+                continue;
+            }
+            let mut template = Template::new(input, field.clone());
+            for (node, value) in bindings {
+                template.bind(node, value);
+                template_of[node] = Some(templates.len());
+            }
+            templates.push(template);
+        }
+        self.templates = templates;
+        self.template_of = template_of;
+    }
+
+    /// Template the filter `node` is a binding of, by index into
+    /// `templates()`.
+    pub fn template_of(&self, node: NodeId) -> Option<usize> {
+        self.template_of.get(node).copied().flatten()
+    }
+
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
     }
 
     /// Compute topological order using Kahn's algorithm.
@@ -157,6 +360,37 @@ impl Graph {
         &self.topo_order
     }
 
+    /// Nodes grouped by depth: scans first, then every node one level
+    /// below its deepest input. Nodes of one level do not read each other,
+    /// so they can be stepped concurrently.
+    pub fn levels(&self) -> Vec<Vec<NodeId>> {
+        let mut depth = vec![0usize; self.nodes.len()];
+        let mut levels: Vec<Vec<NodeId>> = Vec::new();
+        for &id in &self.topo_order {
+            let d = self.nodes[id]
+                .inputs
+                .iter()
+                .map(|&input| depth[input] + 1)
+                .max()
+                .unwrap_or(0);
+            depth[id] = d;
+            if levels.len() <= d {
+                levels.resize_with(d + 1, Vec::new);
+            }
+            levels[d].push(id);
+        }
+        levels
+    }
+
+    /// Mutable access to the nodes `ids`, in id order.
+    pub fn nodes_mut(&mut self, ids: &[NodeId]) -> Vec<&mut Node> {
+        let mut wanted = vec![false; self.nodes.len()];
+        for &id in ids {
+            wanted[id] = true;
+        }
+        self.nodes.iter_mut().filter(|node| wanted[node.id]).collect()
+    }
+
     pub fn scan_nodes_for_table(&self, table: &str) -> &[NodeId] {
         self.scan_index
             .get(table)
@@ -167,6 +401,98 @@ impl Graph {
     pub fn node_count(&self) -> usize {
         self.nodes.len()
     }
+
+    /// Every node `output` reads from, directly or not, including itself;
+    /// nearest to the output first.
+    pub fn subgraph(&self, output: NodeId) -> Vec<NodeId> {
+        let mut seen = HashSet::new();
+        let mut pending = vec![output];
+        while let Some(id) = pending.pop() {
+            if seen.insert(id) {
+                pending.extend(self.nodes[id].inputs.iter().copied());
+            }
+        }
+        let mut ids: Vec<NodeId> = seen.into_iter().collect();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        ids
+    }
+
+    /// Row content synthesized for `key` by the operator closest to
+    /// `output` (an aggregate group, or a row with computed columns).
+    pub fn derived_row(&self, output: NodeId, key: &str) -> Option<Sp00kyValue> {
+        self.subgraph(output)
+            .into_iter()
+            .find_map(|id| self.nodes[id].operator.derived_row(key))
+    }
+
+    /// Full current output of a node, evaluated from the store with
+    /// `snapshot` (operator state is not touched).
+    pub fn snapshot(&self, node: NodeId, store: &Store, memo: &mut HashMap<NodeId, ZSet>) -> ZSet {
+        if let Some(out) = memo.get(&node) {
+            return out.clone();
+        }
+        let inputs: Vec<ZSet> = self.nodes[node]
+            .inputs
+            .iter()
+            .map(|&input| self.snapshot(input, store, memo))
+            .collect();
+        let inputs: Vec<&ZSet> = inputs.iter().collect();
+        let n = &self.nodes[node];
+        let out = n.operator.snapshot(&inputs, store, n.ctx.as_ref());
+        memo.insert(node, out.clone());
+        out
+    }
+
+    /// Initialize the nodes created since `first_new` (see `add_plan`):
+    /// step each one with the full contents of its inputs, so stateful
+    /// operators build their state. Returns the full output of `output`.
+    pub fn prime(&mut self, first_new: NodeId, output: NodeId, store: &Store) -> ZSet {
+        let mut memo = HashMap::new();
+        if output < first_new {
+            return self.snapshot(output, store, &mut memo);
+        }
+        let mut primed: HashMap<NodeId, ZSet> = HashMap::new();
+        for id in first_new..self.nodes.len() {
+            let inputs: Vec<ZSet> = if self.nodes[id].operator.arity() == 0 {
+                vec![self.snapshot(id, store, &mut memo)]
+            } else {
+                self.nodes[id]
+                    .inputs
+                    .iter()
+                    .map(|&input| match primed.get(&input) {
+                        Some(out) => out.clone(),
+                        None => self.snapshot(input, store, &mut memo),
+                    })
+                    .collect()
+            };
+            let inputs: Vec<&ZSet> = inputs.iter().collect();
+            let node = &mut self.nodes[id];
+            let out = node.operator.step(&inputs, store, node.ctx.as_ref());
+            node.operator.drain_changed_rows();
+            primed.insert(id, out);
+        }
+        primed.remove(&output).unwrap_or_default()
+    }
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a plan fragment references a query parameter (`{"$param": ..}`
+/// in a predicate, or a `param` expression).
+fn mentions_param(value: &Value) -> bool {
+    match value {
+        Value::Object(obj) => {
+            obj.contains_key("$param")
+                || obj.get("type").and_then(Value::as_str) == Some("param")
+                || obj.values().any(mentions_param)
+        }
+        Value::Array(items) => items.iter().any(mentions_param),
+        _ => false,
+    }
 }
 
 #[cfg(test)]
@@ -196,10 +522,8 @@ mod tests {
         OperatorPlan::Join {
             left: Box::new(left),
             right: Box::new(right),
-            on: JoinCondition {
-                left_field: Path::new(lf),
-                right_field: Path::new(rf),
-            },
+            on: JoinCondition::eq(Path::new(lf), Path::new(rf)),
+            kind: JoinKind::Inner,
         }
     }
 
@@ -220,8 +544,9 @@ mod tests {
     fn limit(input: OperatorPlan, n: usize, order: Option<Vec<OrderSpec>>) -> OperatorPlan {
         OperatorPlan::Limit {
             input: Box::new(input),
-            limit: n,
+            limit: Some(n),
             order_by: order,
+            offset: 0,
         }
     }
 
@@ -229,6 +554,8 @@ mod tests {
         OrderSpec {
             field: Path::new(field),
             direction: "DESC".to_string(),
+            nulls: None,
+            expr: None,
         }
     }
 
@@ -317,6 +644,24 @@ mod tests {
         assert_eq!(g.output_node, 1);
     }
 
+    #[test]
+    fn scan_then_aggregate_creates_two_nodes() {
+        let plan = OperatorPlan::Aggregate {
+            input: Box::new(scan("message")),
+            group_by: vec![Path::new("thread")],
+            aggregates: vec![crate::operator::AggregateSpec::new(
+                crate::operator::AggregateFunc::Count,
+            )],
+        };
+        let g = Graph::from_plan(&plan);
+
+        assert_eq!(g.node_count(), 2);
+        assert_eq!(g.output_node, 1);
+        assert_eq!(g.nodes[1].inputs, vec![0]);
+        assert_eq!(g.nodes[1].operator.arity(), 1);
+        assert_eq!(plan.referenced_tables(), vec!["message"]);
+    }
+
     // ═══════════════════════════════════════════════════════════════════
     // 3. Topological order for linear chains
     // ═══════════════════════════════════════════════════════════════════
@@ -496,15 +841,15 @@ mod tests {
     // ═══════════════════════════════════════════════════════════════════
 
     #[test]
-    fn duplicate_table_scans_both_indexed() {
+    fn duplicate_table_scans_are_shared() {
         // Self-join: Scan(users) ⋈ Scan(users)
         let plan = join(scan("users"), scan("users"), "manager_id", "id");
         let g = Graph::from_plan(&plan);
 
-        // Both scan nodes should be in the scan index for "users"
-        let scan_nodes = g.scan_nodes_for_table("users");
-        assert_eq!(scan_nodes.len(), 2);
-        assert_eq!(scan_nodes, &[0, 1]);
+        // Both join inputs read the one scan node for "users"
+        assert_eq!(g.scan_nodes_for_table("users"), &[0]);
+        assert_eq!(g.node_count(), 2);
+        assert_eq!(g.nodes[1].inputs, vec![0, 0]);
     }
 
     #[test]
@@ -879,4 +1224,67 @@ mod tests {
         assert_topo_valid(&g);
         assert_eq!(g.node_count(), 7); // 4 scans + 2 inner joins + 1 outer join
     }
+
+    // ═══════════════════════════════════════════════════════════════════
+    // 7. Sharing across plans
+    // ═══════════════════════════════════════════════════════════════════
+
+    #[test]
+    fn identical_plans_share_nodes() {
+        let plan = limit(filter(scan("thread"), eq_pred("open", json!(true))), 10, None);
+        let mut g = Graph::new();
+        let first = g.add_plan(&plan, None);
+        let second = g.add_plan(&plan, None);
+
+        assert_eq!(first, second);
+        assert_eq!(g.node_count(), 3);
+        assert_eq!(g.nodes[first].refs, 2);
+    }
+
+    #[test]
+    fn parameters_only_split_the_nodes_that_read_them() {
+        let by_author = filter(
+            join(scan("thread"), scan("user"), "author", "id"),
+            eq_pred("author", json!({ "$param": "auth" })),
+        );
+        let params = |id: &str| Sp00kyValue::from(json!({ "auth": id }));
+        let mut g = Graph::new();
+        let alice = g.add_plan(&by_author, Some(&params("user:alice")));
+        let bob = g.add_plan(&by_author, Some(&params("user:bob")));
+
+        // Scans and the join are shared; each binding has its own filter.
+        assert_ne!(alice, bob);
+        assert_eq!(g.node_count(), 5);
+        assert_eq!(g.nodes[alice].inputs, g.nodes[bob].inputs);
+        assert!(g.nodes[2].ctx.is_none());
+
+        // Releasing one binding drops only its filter; ids are compacted.
+        let remap = g.release(alice);
+        assert_eq!(g.node_count(), 4);
+        assert_eq!(remap[alice], None);
+        let bob = remap[bob].unwrap();
+        assert_eq!(g.nodes[bob].inputs, vec![2]);
+        assert_topo_valid(&g);
+
+        let remap = g.release(bob);
+        assert!(remap.iter().all(Option::is_none));
+        assert_eq!(g.node_count(), 0);
+        assert_eq!(g.scan_nodes_for_table("thread"), &[] as &[NodeId]);
+    }
+
+    #[test]
+    fn levels_group_nodes_that_do_not_read_each_other() {
+        let by_author = filter(
+            join(scan("thread"), scan("user"), "author", "id"),
+            eq_pred("author", json!({ "$param": "auth" })),
+        );
+        let params = |id: &str| Sp00kyValue::from(json!({ "auth": id }));
+        let mut g = Graph::new();
+        let alice = g.add_plan(&by_author, Some(&params("user:alice")));
+        let bob = g.add_plan(&by_author, Some(&params("user:bob")));
+
+        assert_eq!(g.levels(), vec![vec![0, 1], vec![2], vec![alice, bob]]);
+        let ids: Vec<NodeId> = g.nodes_mut(&[bob, 0]).iter().map(|node| node.id).collect();
+        assert_eq!(ids, vec![0, bob]);
+    }
 }
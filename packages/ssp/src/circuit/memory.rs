@@ -0,0 +1,98 @@
+use crate::algebra::RowKey;
+use crate::circuit::graph::Graph;
+use crate::circuit::store::Store;
+use crate::circuit::view::View;
+use crate::types::value::str_heap_size;
+use crate::types::Sp00kyValue;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+
+/// Estimated memory held by a circuit: stored rows by table, view caches
+/// and operator Z⁻¹ state.
+///
+/// Byte counts are estimates from map capacities and string lengths, not
+/// allocator figures. Row keys shared between the store and operator state
+/// are counted once, with the store.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryReport {
+    pub tables: BTreeMap<String, TableMemory>,
+    pub views: usize,
+    /// Records held in view output caches, over all views.
+    pub view_cache_entries: usize,
+    /// Related records tracked for subquery projections, over all views.
+    pub subquery_entries: usize,
+    /// Operator nodes, shared nodes counted once.
+    pub operator_nodes: usize,
+    /// Rows held in Z⁻¹ state over all operator nodes.
+    pub operator_state_rows: usize,
+    /// Estimated bytes of everything above.
+    pub estimated_bytes: usize,
+}
+
+/// Memory held by one stored table.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TableMemory {
+    pub rows: usize,
+    /// Estimated bytes of the membership Z-set and row index, with their keys.
+    pub key_bytes: usize,
+    /// Estimated bytes of the row contents.
+    pub value_bytes: usize,
+}
+
+impl MemoryReport {
+    pub(crate) fn new(store: &Store, views: &HashMap<String, View>, graph: &Graph) -> Self {
+        let tables: BTreeMap<String, TableMemory> = store
+            .collections
+            .iter()
+            .map(|(name, coll)| {
+                let key_bytes = map_bytes::<RowKey, i64>(coll.zset.capacity())
+                    + map_bytes::<RowKey, Sp00kyValue>(coll.rows.capacity())
+                    + coll.zset.keys().chain(coll.rows.keys()).map(str_heap_size).sum::<usize>();
+                let value_bytes = coll.rows.values().map(Sp00kyValue::heap_size).sum();
+                let table = TableMemory { rows: coll.rows.len(), key_bytes, value_bytes };
+                (name.clone(), table)
+            })
+            .collect();
+
+        let view_cache_entries = views.values().map(|v| v.cache.len()).sum();
+        let subquery_entries = views.values().map(|v| v.subquery_cache.len()).sum();
+        let operator_state_rows = graph
+            .nodes
+            .iter()
+            .flat_map(|node| node.operator.state_sizes())
+            .map(|(_, size)| size)
+            .sum::<usize>();
+
+        let estimated_bytes = tables
+            .values()
+            .map(|t| t.key_bytes + t.value_bytes)
+            .sum::<usize>()
+            + view_cache_entries * size_of::<(RowKey, i64)>()
+            + views.values().map(|v| v.hashed_versions.len()).sum::<usize>() * size_of::<(RowKey, i64)>()
+            + views
+                .values()
+                .flat_map(|v| &v.subquery_cache)
+                .map(|(key, (parent, alias))| {
+                    size_of::<(String, (String, String))>() + key.len() + parent.len() + alias.len()
+                })
+                .sum::<usize>()
+            + operator_state_rows * size_of::<(RowKey, i64)>();
+
+        MemoryReport {
+            tables,
+            views: views.len(),
+            view_cache_entries,
+            subquery_entries,
+            operator_nodes: graph.nodes.len(),
+            operator_state_rows,
+            estimated_bytes,
+        }
+    }
+}
+
+/// Bytes of a `HashMap<K, V>` table with `capacity` slots, without what
+/// keys and values point to.
+fn map_bytes<K, V>(capacity: usize) -> usize {
+    capacity * (size_of::<(K, V)>() + 1)
+}
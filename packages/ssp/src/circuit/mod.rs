@@ -2,7 +2,16 @@ pub mod store;
 pub mod graph;
 pub mod view;
 pub mod circuit;
+pub mod explain;
+pub mod memory;
+pub mod template;
+pub mod snapshot;
+pub mod checkpoint;
 
-pub use circuit::{Circuit, ViewDelta, SubqueryOp, SubqueryDeltaItem};
+pub use circuit::{AggregateRow, Circuit, ViewDelta, SubqueryOp, SubqueryDeltaItem};
+pub use explain::{Explain, ExplainNode};
+pub use memory::{MemoryReport, TableMemory};
+pub use snapshot::{SnapshotError, SnapshotFormat};
+pub use checkpoint::{Checkpoint, CheckpointPolicy};
 pub use store::{Change, ChangeSet, Record, Store, Operation};
-pub use view::{OutputFormat, View};
+pub use view::{OutputFormat, SubqueryEdges, TreeNode, View};
@@ -0,0 +1,470 @@
+//! Persisted circuit state, as written by `Circuit::save_to` and read back by
+//! `Circuit::restore_from`.
+//!
+//! The binary format is a fixed header, the state as MessagePack and a
+//! blake3 checksum of that payload:
+//!
+//! ```text
+//! magic "SPKY" | version: u16 LE | encoding: u8 | kind: u8 | payload | blake3(payload): [u8; 32]
+//! ```
+//!
+//! `kind` is 0 for the full state and 1 for a delta checkpoint (see
+//! `checkpoint`). JSON (`Circuit::save`) carries the same state with a
+//! `version` field and is kept for debugging. Both are read by
+//! `restore_from`, and snapshots of an older `version` are migrated to the
+//! current schema on load.
+
+use crate::algebra::ZSet;
+use crate::circuit::checkpoint::DeltaState;
+use crate::circuit::store::Store;
+use crate::circuit::view::{OutputFormat, View};
+use crate::operator::QueryPlan;
+use crate::permissions::Permissions;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+pub const MAGIC: [u8; 4] = *b"SPKY";
+
+/// Schema version of the state written by this build.
+///
+/// Bump it whenever `CircuitState` or `QueryState` change shape, and add the
+/// step from the previous version to `MIGRATIONS`.
+pub const VERSION: u16 = 3;
+
+const ENCODING_MSGPACK: u8 = 0;
+const KIND_BASE: u8 = 0;
+const KIND_DELTA: u8 = 1;
+const HEADER_LEN: usize = 8;
+const CHECKSUM_LEN: usize = 32;
+
+/// Upgrades of the state as a JSON value, `MIGRATIONS[v]` taking version `v`
+/// to `v + 1`. Delta checkpoints are upgraded by the same steps.
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    // 0 → 1: unversioned JSON, as `Circuit::save` wrote it before snapshots
+    // were versioned. Its layout is the one of version 1.
+    |_| {},
+    // 1 → 2: the position of a full state in its chain of checkpoints.
+    |state| {
+        if let Value::Object(obj) = state {
+            obj.entry("base").or_insert(Value::from(0));
+            obj.entry("seq").or_insert(Value::from(0));
+        }
+    },
+    // 2 → 3: the table select permissions of a full state. Older states
+    // had none, which is the field's default.
+    |_| {},
+];
+
+/// Encoding of a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SnapshotFormat {
+    /// Header, MessagePack payload and checksum.
+    #[default]
+    Binary,
+    /// Plain JSON, for inspecting state.
+    Json,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The state could not be encoded.
+    Encode(String),
+    /// The payload is not a valid state of its version.
+    Decode(String),
+    /// The payload does not match its checksum.
+    Checksum,
+    /// Written by a newer build, or in an unknown encoding.
+    Unsupported(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot I/O failed: {}", e),
+            SnapshotError::Encode(e) => write!(f, "failed to encode snapshot: {}", e),
+            SnapshotError::Decode(e) => write!(f, "failed to decode snapshot: {}", e),
+            SnapshotError::Checksum => write!(f, "snapshot checksum mismatch"),
+            SnapshotError::Unsupported(e) => write!(f, "unsupported snapshot: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            SnapshotError::Io(e.into())
+        } else {
+            SnapshotError::Decode(e.to_string())
+        }
+    }
+}
+
+/// Serializable snapshot of the circuit state.
+///
+/// The operator DAG (which contains trait objects) is NOT serialized.
+/// Instead, we serialize the query plans and rebuild graphs on restore.
+#[derive(Deserialize)]
+pub(crate) struct CircuitState {
+    pub store: Store,
+    pub queries: Vec<QueryState>,
+    #[serde(default)]
+    pub now: Option<i64>,
+    /// Base of the chain of checkpoints the state belongs to, and the last
+    /// checkpoint it includes; deltas of `base` after `seq` continue it.
+    pub base: u64,
+    pub seq: u64,
+    /// Table select rules, for queries registered after a restore and for
+    /// checking the records views pull in.
+    #[serde(default)]
+    pub permissions: Permissions,
+}
+
+/// Serializable snapshot of a single query's state.
+#[derive(Deserialize)]
+pub(crate) struct QueryState {
+    pub plan: QueryPlan,
+    #[serde(default)]
+    pub params: Option<Value>,
+    pub format: OutputFormat,
+    pub cache: ZSet,
+    pub last_hash: String,
+    #[serde(default)]
+    pub subquery_cache: HashMap<String, (String, String)>,
+    #[serde(default)]
+    pub rows: bool,
+}
+
+/// `CircuitState` borrowed from a live circuit, so saving does not copy
+/// the store.
+#[derive(Serialize)]
+pub(crate) struct CircuitStateRef<'a> {
+    pub version: u16,
+    pub store: &'a Store,
+    pub queries: Vec<QueryStateRef<'a>>,
+    pub now: Option<i64>,
+    pub base: u64,
+    pub seq: u64,
+    pub permissions: &'a Permissions,
+}
+
+#[derive(Serialize)]
+pub(crate) struct QueryStateRef<'a> {
+    pub plan: &'a QueryPlan,
+    pub params: Option<Value>,
+    pub format: OutputFormat,
+    pub cache: &'a ZSet,
+    pub last_hash: &'a str,
+    pub subquery_cache: &'a HashMap<String, (String, String)>,
+    pub rows: bool,
+}
+
+impl<'a> QueryStateRef<'a> {
+    pub fn new(view: &'a View) -> Self {
+        QueryStateRef {
+            plan: &view.plan,
+            params: view.params.as_ref().map(|sv| Value::from(sv.clone())),
+            format: view.format,
+            cache: &view.cache,
+            last_hash: &view.last_hash,
+            subquery_cache: &view.subquery_cache,
+            rows: view.rows,
+        }
+    }
+}
+
+/// A full state or a delta checkpoint, as read back.
+pub(crate) enum Snapshot {
+    Base(CircuitState),
+    Delta(DeltaState),
+}
+
+pub(crate) fn to_json(state: &CircuitStateRef) -> serde_json::Result<String> {
+    serde_json::to_string(state)
+}
+
+pub(crate) fn from_json(json: &str) -> serde_json::Result<CircuitState> {
+    let value: Value = serde_json::from_str(json)?;
+    from_json_value(value)
+}
+
+fn from_json_value<T: DeserializeOwned>(mut value: Value) -> serde_json::Result<T> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    let version = u16::try_from(version).unwrap_or(u16::MAX);
+    migrate(&mut value, version).map_err(serde::de::Error::custom)?;
+    serde_json::from_value(value)
+}
+
+/// Write the full `state` in `format`. The writer is buffered internally.
+pub(crate) fn write<W: Write>(
+    state: &CircuitStateRef,
+    writer: W,
+    format: SnapshotFormat,
+) -> Result<(), SnapshotError> {
+    write_kind(state, KIND_BASE, writer, format)
+}
+
+/// Write a delta checkpoint in `format`.
+pub(crate) fn write_delta<W: Write, T: Serialize>(
+    delta: &T,
+    writer: W,
+    format: SnapshotFormat,
+) -> Result<(), SnapshotError> {
+    write_kind(delta, KIND_DELTA, writer, format)
+}
+
+fn write_kind<W: Write, T: Serialize>(
+    state: &T,
+    kind: u8,
+    writer: W,
+    format: SnapshotFormat,
+) -> Result<(), SnapshotError> {
+    let mut writer = BufWriter::new(writer);
+    match format {
+        SnapshotFormat::Json => serde_json::to_writer(&mut writer, state)
+            .map_err(|e| SnapshotError::Encode(e.to_string()))?,
+        SnapshotFormat::Binary => {
+            let mut header = [0u8; HEADER_LEN];
+            header[..4].copy_from_slice(&MAGIC);
+            header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+            header[6] = ENCODING_MSGPACK;
+            header[7] = kind;
+            writer.write_all(&header)?;
+
+            let mut payload = Hashing::new(&mut writer);
+            rmp_serde::encode::write_named(&mut payload, state)
+                .map_err(|e| SnapshotError::Encode(e.to_string()))?;
+            let checksum = payload.hasher.finalize();
+            writer.write_all(checksum.as_bytes())?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read a full state written in either format, migrating older versions.
+pub(crate) fn read<R: Read>(reader: R) -> Result<CircuitState, SnapshotError> {
+    match read_any(reader)? {
+        Snapshot::Base(state) => Ok(state),
+        Snapshot::Delta(_) => Err(SnapshotError::Decode(
+            "a delta checkpoint, restore it on top of its base".to_string(),
+        )),
+    }
+}
+
+/// Read a full state or a delta checkpoint.
+pub(crate) fn read_any<R: Read>(reader: R) -> Result<Snapshot, SnapshotError> {
+    let mut reader = BufReader::new(reader);
+    let mut header = [0u8; HEADER_LEN];
+    let read = read_up_to(&mut reader, &mut header)?;
+    if read < MAGIC.len() || header[..4] != MAGIC {
+        // Not a binary snapshot: JSON, whose first bytes we already took.
+        let value: Value = serde_json::from_reader(header[..read].chain(reader))?;
+        return Ok(if value.get("kind").and_then(Value::as_str) == Some("delta") {
+            Snapshot::Delta(from_json_value(value)?)
+        } else {
+            Snapshot::Base(from_json_value(value)?)
+        });
+    }
+    if read < HEADER_LEN {
+        return Err(SnapshotError::Decode("truncated header".to_string()));
+    }
+
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version > VERSION {
+        return Err(SnapshotError::Unsupported(format!(
+            "version {} is newer than the supported version {}",
+            version, VERSION
+        )));
+    }
+    if header[6] != ENCODING_MSGPACK {
+        return Err(SnapshotError::Unsupported(format!("encoding {}", header[6])));
+    }
+
+    let mut payload = Hashing::new(&mut reader);
+    let snapshot = match header[7] {
+        KIND_BASE => decode(&mut payload, version).map(Snapshot::Base),
+        KIND_DELTA => decode(&mut payload, version).map(Snapshot::Delta),
+        kind => return Err(SnapshotError::Unsupported(format!("kind {}", kind))),
+    };
+    let computed = payload.hasher.finalize();
+
+    // A payload that failed to decode was not read to its end, so its
+    // checksum does not match either: corruption is reported as such.
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    let read = read_up_to(&mut reader, &mut checksum)?;
+    if read < CHECKSUM_LEN || computed.as_bytes() != &checksum {
+        return Err(SnapshotError::Checksum);
+    }
+    snapshot
+}
+
+/// Decode a MessagePack payload of `version`, migrating it if it is older.
+fn decode<R: Read, T: DeserializeOwned>(payload: R, version: u16) -> Result<T, SnapshotError> {
+    if version == VERSION {
+        return rmp_serde::decode::from_read(payload).map_err(decode_error);
+    }
+    let mut value: Value = rmp_serde::decode::from_read(payload).map_err(decode_error)?;
+    migrate(&mut value, version).map_err(SnapshotError::Unsupported)?;
+    serde_json::from_value(value).map_err(|e| SnapshotError::Decode(e.to_string()))
+}
+
+/// Upgrade a state of `version` to `VERSION`.
+fn migrate(state: &mut Value, version: u16) -> Result<(), String> {
+    if version > VERSION {
+        return Err(format!(
+            "version {} is newer than the supported version {}",
+            version, VERSION
+        ));
+    }
+    for step in &MIGRATIONS[version as usize..] {
+        step(state);
+    }
+    if let Value::Object(obj) = state {
+        obj.insert("version".to_string(), Value::from(VERSION));
+    }
+    Ok(())
+}
+
+fn decode_error(e: rmp_serde::decode::Error) -> SnapshotError {
+    SnapshotError::Decode(e.to_string())
+}
+
+/// Fill `buf` as far as the reader goes. Returns the bytes read.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Passes bytes through while hashing them.
+struct Hashing<T> {
+    inner: T,
+    hasher: blake3::Hasher,
+}
+
+impl<T> Hashing<T> {
+    fn new(inner: T) -> Self {
+        Hashing {
+            inner,
+            hasher: blake3::Hasher::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{Change, ChangeSet, Circuit, Record};
+    use serde_json::json;
+
+    fn circuit() -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            Record::new("note", "note:1", json!({"owner": "user:a", "title": "café"})),
+            Record::new("note", "note:2", json!({"owner": "user:b", "size": 2.5})),
+        ]);
+        let root = crate::converter::convert_surql_to_dbsp("SELECT * FROM note WHERE owner = $owner").unwrap();
+        let plan = QueryPlan { id: "mine".to_string(), root: serde_json::from_value(root).unwrap() };
+        circuit.add_query(plan, Some(json!({"owner": "user:a"})), None);
+        circuit
+    }
+
+    fn restored(bytes: &[u8]) -> Result<Circuit, SnapshotError> {
+        Circuit::restore_from(bytes)
+    }
+
+    #[test]
+    fn binary_and_json_snapshots_restore_the_same_state() {
+        let original = circuit();
+        let bytes = original.save_bytes().unwrap();
+        assert_eq!(&bytes[..4], &MAGIC);
+        assert!(bytes.len() < original.save().unwrap().len());
+        let mut json = Vec::new();
+        original.save_to(&mut json, SnapshotFormat::Json).unwrap();
+
+        let change = || ChangeSet {
+            changes: vec![Change::create("note", "note:3", json!({"owner": "user:a"}))],
+        };
+        let expected = circuit().step(change())[0].result_hash.clone();
+        for mut circuit in [restored(&bytes).unwrap(), restored(&json).unwrap()] {
+            assert_eq!(circuit.get_view("mine").unwrap().cache, original.get_view("mine").unwrap().cache);
+            assert_eq!(
+                circuit.store.get_row_by_key("note:1"),
+                original.store.get_row_by_key("note:1")
+            );
+            assert_eq!(circuit.now(), original.now());
+
+            // Restored views keep maintaining their results.
+            let deltas = circuit.step(change());
+            assert_eq!(deltas[0].additions, vec!["note:3"]);
+            assert_eq!(deltas[0].result_hash, expected);
+        }
+    }
+
+    #[test]
+    fn damaged_and_newer_snapshots_are_rejected() {
+        let bytes = circuit().save_bytes().unwrap();
+
+        let mut flipped = bytes.clone();
+        flipped[HEADER_LEN + 10] ^= 0xff;
+        assert!(matches!(restored(&flipped), Err(SnapshotError::Checksum)));
+        assert!(matches!(restored(&bytes[..bytes.len() - 1]), Err(SnapshotError::Checksum)));
+        assert!(matches!(restored(&bytes[..bytes.len() / 2]), Err(SnapshotError::Checksum)));
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(restored(&newer), Err(SnapshotError::Unsupported(_))));
+    }
+
+    #[test]
+    fn unversioned_json_is_migrated() {
+        let mut state: Value = serde_json::from_str(&circuit().save().unwrap()).unwrap();
+        assert_eq!(state["version"], json!(VERSION));
+        state.as_object_mut().unwrap().remove("version");
+        let legacy = state.to_string();
+
+        let circuit = restored(legacy.as_bytes()).unwrap();
+        assert_eq!(circuit.get_view("mine").unwrap().cache.len(), 1);
+        assert!(Circuit::restore(&legacy).is_ok());
+    }
+}
@@ -1,7 +1,7 @@
-use crate::algebra::{Weight, ZSet};
+use crate::algebra::{RowKey, Weight, ZSet};
 use crate::types::{make_key, raw_id, Sp00kyValue};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// A base collection (table) in the store.
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -10,15 +10,27 @@ pub struct Collection {
     /// Z-set tracking record membership and weights.
     pub zset: ZSet,
     /// Actual record data, keyed by raw record ID (without table prefix).
-    pub rows: HashMap<String, Sp00kyValue>,
+    pub rows: HashMap<RowKey, Sp00kyValue>,
+    /// Rows by the record their `in` / `out` link names, for tables that
+    /// graph traversals follow as edges (see `Store::index_edges`).
+    /// Rebuilt rather than saved.
+    #[serde(skip)]
+    edge_index: Option<EdgeIndex>,
 }
 
+/// Edge endpoint field → linked record key → raw IDs of the rows.
+type EdgeIndex = HashMap<&'static str, HashMap<String, HashSet<RowKey>>>;
+
+/// The endpoint fields of a graph edge.
+const EDGE_ENDPOINTS: [&str; 2] = ["in", "out"];
+
 impl Collection {
     pub fn new(name: String) -> Self {
         Self {
             name,
             zset: HashMap::new(),
             rows: HashMap::new(),
+            edge_index: None,
         }
     }
 
@@ -28,19 +40,30 @@ impl Collection {
         op: Operation,
         id: &str,
         data: Sp00kyValue,
-    ) -> (String, Weight) {
+    ) -> (RowKey, Weight) {
         let weight = op.weight();
         let normalized = raw_id(id);
-        match op {
-            Operation::Create | Operation::Update => {
-                self.rows.insert(normalized.to_string(), data);
+        if let Some(index) = &mut self.edge_index {
+            if let Some(old) = self.rows.get(normalized) {
+                unindex_edge(index, normalized, old);
+            }
+            if op.changes_content() {
+                index_edge(index, normalized, &data);
             }
+        }
+        match op {
+            Operation::Create | Operation::Update => match self.rows.get_mut(normalized) {
+                Some(row) => *row = data,
+                None => {
+                    self.rows.insert(normalized.into(), data);
+                }
+            },
             Operation::Delete => {
                 self.rows.remove(normalized);
             }
         }
 
-        let key = make_key(&self.name, id);
+        let key = self.key(id);
         if weight != 0 {
             let entry = self.zset.entry(key.clone()).or_insert(0);
             *entry += weight;
@@ -51,6 +74,41 @@ impl Collection {
         (key, weight)
     }
 
+    /// The Z-set key of a row. A row already in the Z-set keeps its key, so
+    /// the store and the operator state cloned from its deltas share one
+    /// allocation per row instead of one per mutation.
+    pub fn key(&self, id: &str) -> RowKey {
+        let key = make_key(&self.name, id);
+        match self.zset.get_key_value(key.as_str()) {
+            Some((existing, _)) => existing.clone(),
+            None => key.into(),
+        }
+    }
+
+    /// Rows whose `field` (`in` or `out`) links to the record `key`. Uses
+    /// the edge index when the table has one, else scans the rows.
+    pub fn edges_from<'a>(
+        &'a self,
+        field: &'static str,
+        key: &'a str,
+    ) -> Box<dyn Iterator<Item = &'a Sp00kyValue> + 'a> {
+        match &self.edge_index {
+            Some(index) => Box::new(
+                index
+                    .get(field)
+                    .and_then(|by_key| by_key.get(key))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|id| self.rows.get(id)),
+            ),
+            None => Box::new(
+                self.rows
+                    .values()
+                    .filter(move |row| row.get(field).and_then(Sp00kyValue::as_str) == Some(key)),
+            ),
+        }
+    }
+
     /// Look up a row by its raw ID.
     pub fn get_row(&self, id: &str) -> Option<&Sp00kyValue> {
         self.rows.get(raw_id(id))
@@ -66,6 +124,32 @@ impl Collection {
     }
 }
 
+fn index_edge(index: &mut EdgeIndex, id: &str, row: &Sp00kyValue) {
+    for field in EDGE_ENDPOINTS {
+        if let Some(target) = row.get(field).and_then(Sp00kyValue::as_str) {
+            let by_key = index.entry(field).or_default();
+            by_key.entry(target.to_string()).or_default().insert(id.into());
+        }
+    }
+}
+
+fn unindex_edge(index: &mut EdgeIndex, id: &str, row: &Sp00kyValue) {
+    for field in EDGE_ENDPOINTS {
+        let Some(target) = row.get(field).and_then(Sp00kyValue::as_str) else {
+            continue;
+        };
+        let Some(by_key) = index.get_mut(field) else {
+            continue;
+        };
+        if let Some(ids) = by_key.get_mut(target) {
+            ids.remove(id);
+            if ids.is_empty() {
+                by_key.remove(target);
+            }
+        }
+    }
+}
+
 /// The store holds all base collections (tables).
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Store {
@@ -87,8 +171,31 @@ impl Store {
         self.collections.get(name)
     }
 
+    /// Index the rows of `tables` by their `in` / `out` links, so graph
+    /// hops over them look up edges instead of scanning; other tables drop
+    /// their index.
+    pub fn index_edges(&mut self, tables: &HashSet<String>) {
+        for table in tables {
+            self.ensure_collection(table);
+        }
+        for (name, coll) in &mut self.collections {
+            if !tables.contains(name) {
+                coll.edge_index = None;
+                continue;
+            }
+            if coll.edge_index.is_some() {
+                continue;
+            }
+            let mut index = EdgeIndex::new();
+            for (id, row) in &coll.rows {
+                index_edge(&mut index, id, row);
+            }
+            coll.edge_index = Some(index);
+        }
+    }
+
     /// Apply a Change to the store. Returns (zset_key, weight).
-    pub fn apply_change(&mut self, change: &Change) -> (String, Weight) {
+    pub fn apply_change(&mut self, change: &Change) -> (RowKey, Weight) {
         let coll = self.ensure_collection(&change.table);
         coll.apply_mutation(change.op, &change.id, change.data.clone())
     }
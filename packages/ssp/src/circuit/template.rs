@@ -0,0 +1,243 @@
+use crate::algebra::ZSet;
+use crate::circuit::graph::NodeId;
+use crate::circuit::store::Store;
+use crate::operator::filter::{record_field, resolve_predicate_value};
+use crate::operator::predicate::Predicate;
+use crate::types::{Path, Sp00kyValue};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The filters reading one `input` that each require `field` to equal a
+/// query parameter: the bindings of one parameterized plan, such as a view
+/// registered once per user with `WHERE author = $auth.id`.
+///
+/// An input delta is split once by the value of `field`, and each binding
+/// is only stepped with the records it can match, instead of every binding
+/// evaluating every record.
+#[derive(Debug)]
+pub struct Template {
+    pub input: NodeId,
+    pub field: Path,
+    bindings: Bindings,
+}
+
+impl Template {
+    pub(crate) fn new(input: NodeId, field: Path) -> Self {
+        Template {
+            input,
+            field,
+            bindings: Bindings::default(),
+        }
+    }
+
+    /// Register `node`, whose parameter resolved to `value`.
+    pub(crate) fn bind(&mut self, node: NodeId, value: &Sp00kyValue) {
+        let b = &mut self.bindings;
+        let slot = match Class::of(value) {
+            Class::Null => &mut b.null,
+            Class::Bool(v) => b.bools.entry(v).or_default(),
+            Class::Number(v) => b.numbers.entry(v).or_default(),
+            Class::Str(v) => b.strings.entry(v.to_string()).or_default(),
+            Class::Other => &mut b.other,
+        };
+        slot.push(node);
+    }
+
+    /// Number of bound filters.
+    pub fn len(&self) -> usize {
+        self.bindings.all().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Split `delta` by binding. A binding receives every record whose
+    /// `field` may equal its parameter; bindings without such records are
+    /// left out.
+    pub fn route(&self, delta: &ZSet, store: &Store) -> HashMap<NodeId, ZSet> {
+        let mut parts: HashMap<NodeId, ZSet> = HashMap::new();
+        let mut targets = Vec::new();
+        for (key, &weight) in delta {
+            let Some(value) = record_field(store, key, &self.field) else {
+                continue;
+            };
+            targets.clear();
+            self.bindings.matching(&value, &mut targets);
+            for &node in &targets {
+                parts.entry(node).or_default().insert(key.clone(), weight);
+            }
+        }
+        parts
+    }
+}
+
+/// Bound filters by parameter value.
+///
+/// Mirrors `compare_values` equality: numbers match across `Int`/`Float`,
+/// same-typed scalars by value, and values of different non-null types
+/// (or arrays, objects and NaN) always compare equal.
+#[derive(Debug, Default)]
+struct Bindings {
+    null: Vec<NodeId>,
+    bools: HashMap<bool, Vec<NodeId>>,
+    numbers: HashMap<u64, Vec<NodeId>>,
+    strings: HashMap<String, Vec<NodeId>>,
+    other: Vec<NodeId>,
+}
+
+impl Bindings {
+    fn all(&self) -> impl Iterator<Item = &NodeId> {
+        self.null.iter().chain(self.non_null())
+    }
+
+    fn non_null(&self) -> impl Iterator<Item = &NodeId> {
+        self.bools
+            .values()
+            .chain(self.numbers.values())
+            .chain(self.strings.values())
+            .flatten()
+            .chain(&self.other)
+    }
+
+    fn matching(&self, value: &Sp00kyValue, out: &mut Vec<NodeId>) {
+        let class = Class::of(value);
+        let exact = match &class {
+            Class::Null => {
+                out.extend(&self.null);
+                return;
+            }
+            Class::Other => {
+                out.extend(self.non_null());
+                return;
+            }
+            Class::Bool(v) => self.bools.get(v),
+            Class::Number(v) => self.numbers.get(v),
+            Class::Str(v) => self.strings.get(*v),
+        };
+        out.extend(exact.into_iter().flatten());
+        if !matches!(class, Class::Bool(_)) {
+            out.extend(self.bools.values().flatten());
+        }
+        if !matches!(class, Class::Number(_)) {
+            out.extend(self.numbers.values().flatten());
+        }
+        if !matches!(class, Class::Str(_)) {
+            out.extend(self.strings.values().flatten());
+        }
+        out.extend(&self.other);
+    }
+}
+
+enum Class<'a> {
+    Null,
+    Bool(bool),
+    /// Bits of the value as `f64`, with `-0.0` folded into `0.0`.
+    Number(u64),
+    Str(&'a str),
+    Other,
+}
+
+impl<'a> Class<'a> {
+    fn of(value: &'a Sp00kyValue) -> Self {
+        let number = |n: f64| {
+            if n.is_nan() {
+                Class::Other
+            } else {
+                Class::Number((n + 0.0).to_bits())
+            }
+        };
+        match value {
+            Sp00kyValue::Null => Class::Null,
+            Sp00kyValue::Bool(b) => Class::Bool(*b),
+            Sp00kyValue::Int(n) => number(*n as f64),
+            Sp00kyValue::Float(n) => number(*n),
+            Sp00kyValue::Decimal(_) => value.as_f64().map(number).unwrap_or(Class::Other),
+            Sp00kyValue::Str(s) | Sp00kyValue::RecordId(s) => Class::Str(s),
+            // Equal to strings that parse as them, so never bucketed by text.
+            Sp00kyValue::Datetime(_) | Sp00kyValue::Duration(_) | Sp00kyValue::Uuid(_) => Class::Other,
+            Sp00kyValue::Array(_) | Sp00kyValue::Object(_) | Sp00kyValue::Bytes(_) => Class::Other,
+        }
+    }
+}
+
+/// The field a filter requires to equal a `$param`, and the parameter's
+/// value under `ctx`: an `Eq` predicate, alone or inside a conjunction.
+///
+/// Returns `None` for other predicates; the value is `None` when the
+/// parameter is unbound, so the filter can never match.
+pub(crate) fn param_binding(
+    predicate: &Predicate,
+    ctx: Option<&Sp00kyValue>,
+) -> Option<(Path, Option<Sp00kyValue>)> {
+    match predicate {
+        Predicate::Eq { field, value }
+            if value.get("$param").is_some_and(Value::is_string) =>
+        {
+            Some((field.clone(), resolve_predicate_value(value, ctx)))
+        }
+        Predicate::And { predicates } => predicates.iter().find_map(|p| param_binding(p, ctx)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::RowKey;
+    use crate::circuit::store::Change;
+    use serde_json::json;
+
+    #[test]
+    fn records_are_routed_to_the_bindings_they_can_match() {
+        let mut store = Store::new();
+        for (id, author) in [("1", json!("user:a")), ("2", json!("user:b")), ("3", json!(7)), ("4", json!(null))] {
+            let key = format!("message:{}", id);
+            store.apply_change(&Change::create("message", &key, json!({ "author": author })));
+        }
+        store.apply_change(&Change::create("message", "message:5", json!({})));
+
+        let mut template = Template::new(0, Path::new("author"));
+        template.bind(10, &Sp00kyValue::from(json!("user:a")));
+        template.bind(11, &Sp00kyValue::from(json!("user:b")));
+        template.bind(12, &Sp00kyValue::from(json!(7.0)));
+        template.bind(13, &Sp00kyValue::Null);
+        assert_eq!(template.len(), 4);
+
+        let delta: ZSet = (1..=5).map(|i| (RowKey::from(format!("message:{}", i)), 1)).collect();
+        let parts = template.route(&delta, &store);
+        let keys = |node: NodeId| {
+            let mut keys: Vec<&str> = parts[&node].keys().map(|k| k.as_str()).collect();
+            keys.sort();
+            keys
+        };
+
+        // A number never equals a string key exactly, but `compare_values`
+        // treats values of different types as equal, so routing must too.
+        assert_eq!(keys(10), vec!["message:1", "message:3"]);
+        assert_eq!(keys(11), vec!["message:2", "message:3"]);
+        assert_eq!(keys(12), vec!["message:1", "message:2", "message:3"]);
+        assert_eq!(keys(13), vec!["message:4"]);
+    }
+
+    #[test]
+    fn param_equality_is_found_inside_conjunctions() {
+        let predicate: Predicate = serde_json::from_value(json!({
+            "type": "and",
+            "predicates": [
+                { "type": "eq", "field": "read", "value": false },
+                { "type": "eq", "field": "author", "value": { "$param": "auth.id" } },
+            ],
+        }))
+        .unwrap();
+        let ctx = Sp00kyValue::from(json!({ "auth": { "id": "user:a" } }));
+
+        let (field, value) = param_binding(&predicate, Some(&ctx)).unwrap();
+        assert_eq!(field, Path::new("author"));
+        assert_eq!(value, Some(Sp00kyValue::Str("user:a".into())));
+        assert_eq!(param_binding(&predicate, None).unwrap().1, None);
+
+        let or = Predicate::Or { predicates: vec![predicate] };
+        assert!(param_binding(&or, Some(&ctx)).is_none());
+    }
+}
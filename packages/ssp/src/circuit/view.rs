@@ -1,24 +1,66 @@
-use crate::algebra::ZSet;
+use crate::algebra::{RowKey, ZSet};
+use crate::circuit::graph::{Graph, NodeId};
+use crate::circuit::store::Store;
 use crate::operator::QueryPlan;
-use crate::types::Sp00kyValue;
+use crate::types::{make_key, Sp00kyValue};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use ssp_protocol::view_hash::{content_version, SetHash};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Output format for a registered query.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
+    /// Deltas list every key in the view in `records`.
     #[default]
     Flat,
+    /// As `Flat`, plus the view as a `tree` of records with the related
+    /// records of their subquery projections nested below them.
     Tree,
+    /// Deltas only carry the changes of the step; `records` stays empty.
     Streaming,
 }
 
+/// Subquery records by parent: parent key → alias → child keys. Every
+/// edge is kept, so a record linked from several parents is listed below
+/// each of them.
+pub type SubqueryEdges = HashMap<String, BTreeMap<String, BTreeSet<String>>>;
+
+/// Where a view reads the versions it hashes its records at. Derived rows
+/// of its output node (aggregate groups, computed projections) are hashed
+/// at the `content_version` of their content: their `_00_rv` is a counter
+/// of the process that derived them. Every other record has the version
+/// of the store.
+pub struct Versions<'a> {
+    pub store: &'a Store,
+    pub graph: &'a Graph,
+    pub output: NodeId,
+}
+
+impl Versions<'_> {
+    pub fn of(&self, key: &str) -> Option<i64> {
+        match self.graph.derived_row(self.output, key) {
+            Some(row) => Some(content_version(&serde_json::Value::from(row))),
+            None => self.store.get_record_version_by_key(key),
+        }
+    }
+}
+
+/// A record of a `Tree` view, with the related records its subquery
+/// projections select, by alias.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TreeNode {
+    pub id: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub children: BTreeMap<String, Vec<TreeNode>>,
+}
+
 /// Materialized query output state.
 ///
 /// Holds the accumulated output of a query's operator DAG.
-/// The cache uses membership normalization (weights clamped to 0/1)
-/// because the view output represents "what records are visible."
+/// The cache keeps each record's multiplicity (e.g. its number of join
+/// matches) so that retracting one of several derivations does not hide
+/// it; a record is visible while its weight is positive.
 #[derive(Debug, Clone)]
 pub struct View {
     pub query_id: String,
@@ -34,11 +76,28 @@ pub struct View {
     pub referenced_tables: Vec<String>,
     /// Tables referenced inside subquery projections (may overlap with primary tables).
     pub subquery_tables: Vec<String>,
-    /// Monotonic counter bumped on subquery content changes, included in hash.
-    pub content_generation: u64,
+    /// Tables record links of the view currently point into, routed to it
+    /// on top of the plan's tables.
+    pub link_tables: Vec<String>,
+    /// Tables of the subqueries not correlated to their parent record.
+    /// Their results are not tracked per parent, so every record of these
+    /// tables is hashed into `set_hash`.
+    pub uncorrelated_tables: Vec<String>,
     /// Subquery record tracking: child_key → (parent_key, alias).
     /// Tracks which subquery records are visible through parent records in the view.
+    /// A child linked from several parents is tracked under the first of
+    /// them; `subquery_edges` keeps them all.
     pub subquery_cache: HashMap<String, (String, String)>,
+    /// Every parent → child link of the subquery records.
+    pub subquery_edges: SubqueryEdges,
+    /// Whether deltas carry the content of the rows they add or update
+    /// (`ViewDelta::rows`).
+    pub rows: bool,
+    /// XOR set hash over the `(key, version)` of the records in the view
+    /// and of its subquery records (see `ssp_protocol::view_hash`).
+    pub set_hash: SetHash,
+    /// The version each record was folded into `set_hash` with.
+    pub hashed_versions: HashMap<RowKey, i64>,
 }
 
 impl View {
@@ -50,6 +109,15 @@ impl View {
         referenced_tables: Vec<String>,
     ) -> Self {
         let subquery_tables = plan.root.subquery_tables();
+        let mut uncorrelated_tables: Vec<String> = plan
+            .root
+            .subquery_projection_info()
+            .into_iter()
+            .filter(|(_, _, parent_key, _)| parent_key.is_none())
+            .map(|(_, table, _, _)| table)
+            .collect();
+        uncorrelated_tables.sort();
+        uncorrelated_tables.dedup();
         Self {
             query_id,
             plan,
@@ -59,42 +127,177 @@ impl View {
             params,
             referenced_tables,
             subquery_tables,
-            content_generation: 0,
+            link_tables: Vec::new(),
+            uncorrelated_tables,
             subquery_cache: HashMap::new(),
+            subquery_edges: HashMap::new(),
+            rows: false,
+            set_hash: SetHash::default(),
+            hashed_versions: HashMap::new(),
         }
     }
 
-    /// Apply a view delta to the cache with membership normalization.
-    /// Positive weights → set to 1 (present).
+    /// Apply a view delta to the cache.
+    /// Positive weights → kept with their multiplicity (present).
     /// Zero or negative → remove (absent).
-    pub fn apply_delta(&mut self, delta: &ZSet) {
+    /// Records entering or leaving the view are folded into or out of
+    /// `set_hash` at their version in `versions`.
+    pub fn apply_delta(&mut self, delta: &ZSet, versions: &Versions) {
         for (key, &weight_delta) in delta {
             let old = self.cache.get(key).copied().unwrap_or(0);
             let new_weight = old + weight_delta;
             if new_weight > 0 {
-                self.cache.insert(key.clone(), 1);
+                self.cache.insert(key.clone(), new_weight);
             } else {
                 self.cache.remove(key);
             }
+            if (old > 0) != (new_weight > 0) {
+                self.sync_hash(key, versions);
+            }
+        }
+    }
+
+    /// Replace the subquery records with those of `edges`, folding those
+    /// that appear or disappear into or out of `set_hash`. Returns the
+    /// previous `subquery_cache`.
+    pub fn set_subquery_cache(
+        &mut self,
+        edges: SubqueryEdges,
+        versions: &Versions,
+    ) -> HashMap<String, (String, String)> {
+        let mut subquery_cache: HashMap<String, (String, String)> = HashMap::new();
+        for (parent, linked) in &edges {
+            for (alias, children) in linked {
+                for child in children {
+                    let edge = (parent.clone(), alias.clone());
+                    match subquery_cache.get_mut(child) {
+                        Some(first) if *first <= edge => {}
+                        Some(first) => *first = edge,
+                        None => {
+                            subquery_cache.insert(child.clone(), edge);
+                        }
+                    }
+                }
+            }
+        }
+        self.subquery_edges = edges;
+        let old = std::mem::replace(&mut self.subquery_cache, subquery_cache);
+        let added: Vec<String> = self
+            .subquery_cache
+            .keys()
+            .filter(|key| !old.contains_key(*key))
+            .cloned()
+            .collect();
+        let removed: Vec<String> = old
+            .keys()
+            .filter(|key| !self.subquery_cache.contains_key(*key))
+            .cloned()
+            .collect();
+        for key in removed.into_iter().chain(added) {
+            self.sync_hash(&key, versions);
+        }
+        old
+    }
+
+    /// Bring the contribution of `key` to `set_hash` in line with the
+    /// view: folded in at its version in `versions` while the view holds
+    /// it as a record, a subquery record or a record of an uncorrelated
+    /// subquery table, folded out otherwise. Called for records whose
+    /// content (and so version) changed. A key held several ways is folded
+    /// in once. Records without a version are hashed at version 1, the
+    /// version clients receive for them.
+    pub fn sync_hash(&mut self, key: &str, versions: &Versions) {
+        let held = self.cache.contains_key(key)
+            || self.subquery_cache.contains_key(key)
+            || self.holds_uncorrelated(key, versions.store);
+        let version = held.then(|| versions.of(key).unwrap_or(1));
+        let hashed = self.hashed_versions.get(key).copied();
+        if version == hashed {
+            return;
+        }
+        if let Some(old) = hashed {
+            self.set_hash.toggle(key, old);
+            self.hashed_versions.remove(key);
+        }
+        if let Some(new) = version {
+            self.set_hash.toggle(key, new);
+            self.hashed_versions.insert(key.into(), new);
+        }
+    }
+
+    fn holds_uncorrelated(&self, key: &str, store: &Store) -> bool {
+        crate::types::parse_key(key).is_some_and(|(table, _)| {
+            self.uncorrelated_tables.iter().any(|t| t == table)
+        }) && store.get_row_by_key(key).is_some()
+    }
+
+    /// Recompute `set_hash` from the cache, the subquery records and the
+    /// records of uncorrelated subquery tables, as after a restore.
+    pub fn rebuild_hash(&mut self, versions: &Versions) {
+        self.set_hash = SetHash::default();
+        self.hashed_versions.clear();
+        let uncorrelated = self
+            .uncorrelated_tables
+            .iter()
+            .filter_map(|table| versions.store.get_collection(table))
+            .flat_map(|coll| coll.rows.keys().map(|id| RowKey::from(make_key(&coll.name, id))));
+        let keys: Vec<RowKey> = self
+            .cache
+            .keys()
+            .cloned()
+            .chain(self.subquery_cache.keys().map(RowKey::from))
+            .chain(uncorrelated)
+            .collect();
+        for key in keys {
+            self.sync_hash(&key, versions);
         }
     }
 
-    /// Compute a hash of the current cache state for change detection.
-    /// Includes `content_generation` so the hash changes when subquery data changes.
+    /// Hash of the records the view holds, for change detection and for
+    /// clients to verify their copy against (see `ssp_protocol::view_hash`).
+    /// Kept up to date incrementally; this does not walk the cache.
     pub fn compute_hash(&self) -> String {
-        let mut keys: Vec<&String> = self.cache.keys().collect();
-        keys.sort();
-        use std::hash::{Hash, Hasher};
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        for key in &keys {
-            key.hash(&mut hasher);
+        self.set_hash.to_hex()
+    }
+
+    /// Keys for a delta's `records`: every key in the view, or none for
+    /// `Streaming` views.
+    pub fn records(&self) -> Vec<String> {
+        if self.format == OutputFormat::Streaming {
+            return vec![];
+        }
+        self.cache.keys().map(|k| k.to_string()).collect()
+    }
+
+    /// The records of the view, sorted, each with the subquery records
+    /// linked below it (see `subquery_edges`). Empty unless the view has
+    /// the `Tree` format.
+    pub fn tree(&self) -> Vec<TreeNode> {
+        if self.format != OutputFormat::Tree {
+            return vec![];
         }
-        self.content_generation.hash(&mut hasher);
-        format!("{:016x}", hasher.finish())
+        let mut roots: Vec<&str> = self.cache.keys().map(|k| k.as_str()).collect();
+        roots.sort();
+        let mut path = Vec::new();
+        roots
+            .into_iter()
+            .map(|id| tree_node(id, &self.subquery_edges, &mut path))
+            .collect()
     }
+}
 
-    /// Bump the content generation counter (called when subquery data changes).
-    pub fn bump_content_generation(&mut self) {
-        self.content_generation += 1;
+/// The tree below `id`. Records already on `path` (a link cycle) are not
+/// expanded again.
+fn tree_node<'a>(id: &'a str, edges: &'a SubqueryEdges, path: &mut Vec<&'a str>) -> TreeNode {
+    let mut node = TreeNode { id: id.to_string(), children: BTreeMap::new() };
+    if path.contains(&id) {
+        return node;
+    }
+    path.push(id);
+    for (alias, children) in edges.get(id).into_iter().flatten() {
+        let subtrees = children.iter().map(|child| tree_node(child, edges, path)).collect();
+        node.children.insert(alias.clone(), subtrees);
     }
+    path.pop();
+    node
 }
@@ -2,33 +2,333 @@ use anyhow::{anyhow, Result};
 use nom::{
     branch::alt,
     bytes::complete::{is_not, tag, tag_no_case, take_while},
-    character::complete::{alpha1, char, digit1, multispace0},
-    combinator::{cut, map, map_res, opt, recognize, value},
-    multi::separated_list1,
-    sequence::{delimited, pair, preceded, tuple},
-    IResult,
+    character::complete::{
+        alpha1, anychar, char, digit1, multispace0, multispace1, none_of, one_of, satisfy,
+    },
+    combinator::{consumed, cut, map, map_res, not, opt, peek, recognize, value, verify},
+    error::{context, ContextError, ErrorKind, FromExternalError},
+    multi::{many0, many1, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    InputLength,
 };
 use serde_json::{json, Value};
+use std::fmt;
+use std::ops::Range;
+
+/// Parse a SurrealQL `SELECT` into a JSON operator plan.
+///
+/// Syntax that live queries cannot maintain (`FETCH`, `SPLIT`, `OMIT`,
+/// `TIMEOUT`, ...) is rejected with every occurrence listed in
+/// `ParseError::unsupported`.
+pub fn convert_surql_to_dbsp(sql: &str) -> Result<Value, ParseError> {
+    let clean_sql = sql.trim();
+    let clean_sql = clean_sql.strip_suffix(';').unwrap_or(clean_sql).trim_end();
+    let (rest, plan) = match parse_full_query(clean_sql) {
+        Ok(parsed) => parsed,
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => return Err(ParseError::new(sql, e)),
+        Err(nom::Err::Incomplete(_)) => {
+            return Err(ParseError::new(sql, SyntaxError::new(&clean_sql[clean_sql.len()..])))
+        }
+    };
+    if rest.is_empty() {
+        return Ok(plan);
+    }
+    let error = match rest.strip_prefix(';') {
+        Some(next) if !next.trim().is_empty() => SyntaxError {
+            unsupported: vec![(rest, "multiple statements".to_string())],
+            ..SyntaxError::new(rest)
+        },
+        _ => SyntaxError {
+            message: Some(format!("unexpected {} after the end of the query", describe_token(rest))),
+            ..SyntaxError::new(rest)
+        },
+    };
+    Err(ParseError::new(sql, error))
+}
+
+/// Convert a standalone condition (the part after `WHERE`, e.g. from a
+/// table's `PERMISSIONS`) into predicate JSON.
+///
+/// A comparison between two fields becomes an expression predicate rather
+/// than a join.
+pub fn convert_condition(condition: &str) -> Result<Value, ParseError> {
+    let clean = condition.trim();
+    let (rest, predicate) = match parse_or_expression(clean) {
+        Ok(parsed) => parsed,
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => return Err(ParseError::new(condition, e)),
+        Err(nom::Err::Incomplete(_)) => {
+            return Err(ParseError::new(condition, SyntaxError::new(&clean[clean.len()..])))
+        }
+    };
+    if !rest.trim().is_empty() {
+        let rest = rest.trim_start();
+        let error = SyntaxError {
+            message: Some(format!("unexpected {} after the end of the condition", describe_token(rest))),
+            ..SyntaxError::new(rest)
+        };
+        return Err(ParseError::new(condition, error));
+    }
+    Ok(field_comparisons_as_exprs(predicate))
+}
+
+fn field_comparisons_as_exprs(predicate: Value) -> Value {
+    match predicate {
+        Value::Object(mut obj) if obj.get("type").and_then(Value::as_str) == Some("__JOIN_CANDIDATE__") => {
+            let field = |name: Option<Value>| json!({ "type": "field", "path": name.unwrap_or_default() });
+            let left = field(obj.remove("left"));
+            let right = field(obj.remove("right"));
+            let op = obj.remove("op").unwrap_or_default();
+            json!({ "type": "expr", "expr": { "type": "binary", "op": op, "left": left, "right": right } })
+        }
+        Value::Object(obj) => Value::Object(
+            obj.into_iter()
+                .map(|(k, v)| (k, field_comparisons_as_exprs(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(field_comparisons_as_exprs).collect()),
+        other => other,
+    }
+}
+
+// --- ERRORS ---
+
+/// Why a query was rejected, pointing at the offending part of its text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Byte range in the query text.
+    pub span: Range<usize>,
+    /// 1-based line and column of the span start.
+    pub line: usize,
+    pub column: usize,
+    /// Syntax the query uses that live queries do not support.
+    pub unsupported: Vec<Unsupported>,
+    /// The query line containing the span, for display.
+    source_line: String,
+    /// Characters of the span on that line, for the caret underline.
+    width: usize,
+}
+
+/// One occurrence of syntax that live queries do not support.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsupported {
+    pub syntax: String,
+    pub span: Range<usize>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl ParseError {
+    fn new(source: &str, error: SyntaxError<&str>) -> Self {
+        let at = offset_in(source, error.input);
+        let (line, column) = line_column(source, at);
+        let unsupported: Vec<Unsupported> = error
+            .unsupported
+            .iter()
+            .map(|(text, syntax)| {
+                let start = offset_in(source, text);
+                let (line, column) = line_column(source, start);
+                Unsupported {
+                    syntax: syntax.clone(),
+                    span: start..start + text.trim_end().len(),
+                    line,
+                    column,
+                }
+            })
+            .collect();
+
+        let (message, span) = if let Some(first) = unsupported.first() {
+            let mut names: Vec<&str> = Vec::new();
+            for u in &unsupported {
+                if !names.contains(&u.syntax.as_str()) {
+                    names.push(&u.syntax);
+                }
+            }
+            (
+                format!("not supported in live queries: {}", names.join(", ")),
+                first.span.clone(),
+            )
+        } else {
+            let found = describe_token(error.input);
+            let message = match (error.message, error.expected.as_slice()) {
+                (Some(message), _) => message,
+                (None, []) => format!("unexpected {}", found),
+                (None, expected) => format!("expected {}, found {}", expected.join(" or "), found),
+            };
+            (message, at..at + error.len.unwrap_or_else(|| token_len(error.input)))
+        };
+
+        let (line, column) = match unsupported.first() {
+            Some(first) => (first.line, first.column),
+            None => (line, column),
+        };
+        let source_line = source.lines().nth(line - 1).unwrap_or_default().to_string();
+        let width = source
+            .get(span.clone())
+            .map_or(0, |text| text.chars().count())
+            .min(source_line.chars().count() + 1 - column)
+            .max(1);
+        ParseError {
+            message,
+            span,
+            line,
+            column,
+            unsupported,
+            source_line,
+            width,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.column)?;
+        write!(
+            f,
+            "\n  {}\n  {}{}",
+            self.source_line,
+            " ".repeat(self.column - 1),
+            "^".repeat(self.width)
+        )?;
+        if self.unsupported.len() > 1 {
+            for u in &self.unsupported {
+                write!(f, "\n  - {} at line {}, column {}", u.syntax, u.line, u.column)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Byte offset of `at`, a suffix slice of `source`.
+fn offset_in(source: &str, at: &str) -> usize {
+    (at.as_ptr() as usize).saturating_sub(source.as_ptr() as usize).min(source.len())
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+/// Byte length of the token at the start of `input`.
+fn token_len(input: &str) -> usize {
+    match input.chars().next() {
+        None => 0,
+        Some(c) if c.is_alphanumeric() || c == '_' => input
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':' || c == '.'))
+            .unwrap_or(input.len()),
+        Some(c) => c.len_utf8(),
+    }
+}
+
+fn describe_token(input: &str) -> String {
+    match token_len(input) {
+        0 => "end of query".to_string(),
+        len => format!("`{}`", &input[..len]),
+    }
+}
 
-pub fn convert_surql_to_dbsp(sql: &str) -> Result<Value> {
-    let clean_sql = sql.trim().trim_end_matches(';');
-    match parse_full_query(clean_sql) {
-        Ok((_, plan)) => Ok(plan),
-        Err(e) => Err(anyhow!("SQL Parsing Error: {}", e)),
+/// nom error that keeps the furthest point any branch reached, what was
+/// expected there, and the unsupported syntax that stopped the parse.
+#[derive(Debug, Clone)]
+struct SyntaxError<I> {
+    input: I,
+    expected: Vec<&'static str>,
+    message: Option<String>,
+    /// Length of the offending text, when it is known.
+    len: Option<usize>,
+    unsupported: Vec<(I, String)>,
+}
+
+type IResult<I, O> = nom::IResult<I, O, SyntaxError<I>>;
+
+impl<I> SyntaxError<I> {
+    fn new(input: I) -> Self {
+        SyntaxError {
+            input,
+            expected: Vec::new(),
+            message: None,
+            len: None,
+            unsupported: Vec::new(),
+        }
+    }
+}
+
+impl<'a> SyntaxError<&'a str> {
+    /// A non-recoverable error with its own message, spanning `text`.
+    fn fail(text: &'a str, message: impl Into<String>) -> nom::Err<Self> {
+        nom::Err::Failure(SyntaxError {
+            message: Some(message.into()),
+            len: Some(text.trim_end().len()),
+            ..SyntaxError::new(text)
+        })
+    }
+}
+
+impl<I: InputLength> nom::error::ParseError<I> for SyntaxError<I> {
+    fn from_error_kind(input: I, _kind: ErrorKind) -> Self {
+        SyntaxError::new(input)
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        match self.input.input_len().cmp(&other.input.input_len()) {
+            std::cmp::Ordering::Less => self,
+            std::cmp::Ordering::Greater => other,
+            std::cmp::Ordering::Equal if self.message.is_some() => self,
+            std::cmp::Ordering::Equal if other.message.is_some() => other,
+            std::cmp::Ordering::Equal => {
+                let mut merged = self;
+                for expected in other.expected {
+                    if !merged.expected.contains(&expected) {
+                        merged.expected.push(expected);
+                    }
+                }
+                merged
+            }
+        }
+    }
+}
+
+impl<I> ContextError<I> for SyntaxError<I> {
+    fn add_context(_input: I, ctx: &'static str, mut other: Self) -> Self {
+        if other.expected.is_empty() && other.message.is_none() {
+            other.expected.push(ctx);
+        }
+        other
+    }
+}
+
+impl<I, E> FromExternalError<I, E> for SyntaxError<I> {
+    fn from_external_error(input: I, _kind: ErrorKind, _e: E) -> Self {
+        SyntaxError::new(input)
     }
 }
 
 // --- HELPERS ---
 
-fn ws<'a, F, O, E: nom::error::ParseError<&'a str>>(
-    inner: F,
-) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+fn ws<'a, F, O>(inner: F) -> impl FnMut(&'a str) -> IResult<&'a str, O>
 where
-    F: FnMut(&'a str) -> IResult<&'a str, O, E>,
+    F: FnMut(&'a str) -> IResult<&'a str, O>,
 {
     delimited(multispace0, inner, multispace0)
 }
 
+/// A closing delimiter, named in the error when it is missing.
+fn close<'a>(delimiter: char) -> impl FnMut(&'a str) -> IResult<&'a str, char> {
+    let label = match delimiter {
+        ')' => "`)`",
+        _ => "`]`",
+    };
+    ws(context(label, char(delimiter)))
+}
+
 // Identifier: Start with Alpha/_, then Alphanumeric/_/:/.
 fn parse_identifier(input: &str) -> IResult<&str, String> {
     let parser = recognize(pair(
@@ -45,15 +345,22 @@ enum ParsedValue {
     Json(Value),
     Identifier(String),
     Prefix(String),
+    /// `NONE` / `NULL`.
+    None,
+    /// `/pattern/` regex literal.
+    Regex(String),
 }
 
 fn parse_string_literal(input: &str) -> IResult<&str, ParsedValue> {
     let parse_content = |delimiter| {
-        delimited(
-            char(delimiter),
-            is_not(if delimiter == '\'' { "'" } else { "\"" }),
-            char(delimiter),
-        )
+        move |input| {
+            let (rest, _) = char(delimiter)(input)?;
+            let (rest, content) = is_not(if delimiter == '\'' { "'" } else { "\"" })(rest)?;
+            match char::<_, SyntaxError<&str>>(delimiter)(rest) {
+                Ok((rest, _)) => Ok((rest, content)),
+                Err(_) => Err(SyntaxError::fail(input, "unterminated string literal")),
+            }
+        }
     };
     map(alt((parse_content('\''), parse_content('"'))), |s: &str| {
         if s.ends_with('*') {
@@ -64,9 +371,60 @@ fn parse_string_literal(input: &str) -> IResult<&str, ParsedValue> {
     })(input)
 }
 
+/// A keyword that is not the start of a longer identifier.
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+    terminated(
+        tag_no_case(word),
+        not(satisfy(|c: char| c.is_alphanumeric() || c == '_' || c == ':' || c == '.')),
+    )
+}
+
+/// `[v1, v2, ...]` of literal values.
+fn parse_array_literal(input: &str) -> IResult<&str, ParsedValue> {
+    let start = input;
+    let (input, items) = delimited(
+        ws(char('[')),
+        separated_list0(ws(char(',')), ws(parse_value_entry)),
+        close(']'),
+    )(input)?;
+    let mut values = Vec::with_capacity(items.len());
+    for item in items {
+        values.push(match item {
+            ParsedValue::Json(v) => v,
+            ParsedValue::Prefix(p) => json!(format!("{}*", p)),
+            ParsedValue::None => Value::Null,
+            ParsedValue::Identifier(_) | ParsedValue::Regex(_) => {
+                return Err(nom::Err::Error(SyntaxError::new(start)))
+            }
+        });
+    }
+    Ok((input, ParsedValue::Json(Value::Array(values))))
+}
+
+/// `/pattern/` with `\/` escaping a slash. The pattern must compile.
+fn parse_regex_literal(input: &str) -> IResult<&str, ParsedValue> {
+    let (rest, raw) = delimited(
+        char('/'),
+        recognize(many1(alt((
+            recognize(pair(char('\\'), anychar)),
+            recognize(none_of("/\\")),
+        )))),
+        char('/'),
+    )(input)?;
+    let pattern = raw.replace("\\/", "/");
+    if let Err(e) = regex::Regex::new(&pattern) {
+        let literal = &input[..input.len() - rest.len()];
+        return Err(SyntaxError::fail(literal, format!("invalid regex: {}", e)));
+    }
+    Ok((rest, ParsedValue::Regex(pattern)))
+}
+
 fn parse_value_entry(input: &str) -> IResult<&str, ParsedValue> {
     alt((
         parse_string_literal,
+        parse_regex_literal,
+        parse_array_literal,
+        value(ParsedValue::None, alt((keyword("NONE"), keyword("NULL")))),
         map(preceded(char('$'), parse_identifier), |s| {
             ParsedValue::Json(json!({ "$param": s }))
         }),
@@ -82,45 +440,123 @@ fn parse_value_entry(input: &str) -> IResult<&str, ParsedValue> {
 
 // --- LOGIC ---
 
+/// Left side of a comparison: a field, optionally case-folded with
+/// `string::lowercase(field)` / `string::uppercase(field)`.
+fn parse_predicate_field(input: &str) -> IResult<&str, (String, Option<&'static str>)> {
+    alt((
+        map(
+            pair(
+                alt((
+                    value("lower", tag_no_case("string::lowercase")),
+                    value("upper", tag_no_case("string::uppercase")),
+                )),
+                delimited(ws(char('(')), parse_identifier, close(')')),
+            ),
+            |(case, field)| (field, Some(case)),
+        ),
+        map(parse_identifier, |field| (field, None)),
+    ))(input)
+}
+
 fn parse_leaf_predicate(input: &str) -> IResult<&str, Value> {
+    let start = input;
     let (input, (left, op, right)) = tuple((
-        ws(parse_identifier),
+        ws(parse_predicate_field),
         ws(alt((
             tag(">="),
             tag("<="),
             tag("!="),
+            tag("!~"),
             tag("="),
+            tag("~"),
             tag(">"),
             tag("<"),
-            tag_no_case("CONTAINS"),
-            tag_no_case("INSIDE"),
+            keyword("CONTAINSANY"),
+            keyword("CONTAINSALL"),
+            keyword("CONTAINSNONE"),
+            keyword("CONTAINSNOT"),
+            keyword("CONTAINS"),
+            keyword("NOTINSIDE"),
+            keyword("INSIDE"),
+            recognize(tuple((tag_no_case("NOT"), multispace1, keyword("IN")))),
+            keyword("IN"),
+            recognize(tuple((tag_no_case("IS"), multispace1, keyword("NOT")))),
+            keyword("IS"),
         ))),
         ws(parse_value_entry),
     ))(input)?;
 
-    let type_str = match op.to_uppercase().as_str() {
-        "=" => "eq",
-        ">" => "gt",
-        "<" => "lt",
-        ">=" => "gte",
-        "<=" => "lte",
-        "!=" => "neq",
-        _ => "eq",
+    let op = op.split_whitespace().collect::<Vec<_>>().join(" ").to_uppercase();
+    // (predicate type, negated)
+    let (type_str, negated) = match op.as_str() {
+        "=" | "IS" => ("eq", false),
+        "!=" | "IS NOT" => ("eq", true),
+        ">" => ("gt", false),
+        "<" => ("lt", false),
+        ">=" => ("gte", false),
+        "<=" => ("lte", false),
+        "IN" | "INSIDE" => ("inside", false),
+        "NOT IN" | "NOTINSIDE" => ("inside", true),
+        "CONTAINS" => ("contains", false),
+        "CONTAINSNOT" => ("contains", true),
+        "CONTAINSANY" => ("containsany", false),
+        "CONTAINSNONE" => ("containsany", true),
+        "~" => ("fuzzy", false),
+        "!~" => ("fuzzy", true),
+        "CONTAINSALL" => ("containsall", false),
+        _ => return Err(nom::Err::Error(SyntaxError::new(start))),
+    };
+    let (left, fold) = left;
+    let negate = |pred: Value| {
+        let pred = match fold {
+            Some(case) => json!({ "type": "fold", "case": case, "predicate": pred }),
+            None => pred,
+        };
+        if negated {
+            json!({ "type": "not", "predicate": pred })
+        } else {
+            pred
+        }
     };
+    let reject = || nom::Err::Error(SyntaxError::new(start));
 
     match right {
+        // `!=` keeps its own predicate type rather than `not(eq)`.
+        ParsedValue::Json(val) if type_str == "eq" && negated && fold.is_none() => Ok((
+            input,
+            json!({ "type": "neq", "field": left, "value": val }),
+        )),
         ParsedValue::Json(val) => Ok((
             input,
-            json!({ "type": type_str, "field": left, "value": val }),
+            negate(json!({ "type": type_str, "field": left, "value": val })),
+        )),
+        ParsedValue::Prefix(val) if type_str == "eq" => Ok((
+            input,
+            negate(json!({ "type": "prefix", "field": left, "prefix": val })),
         )),
         ParsedValue::Prefix(val) => Ok((
             input,
-            json!({ "type": "prefix", "field": left, "prefix": val }),
+            negate(json!({ "type": type_str, "field": left, "value": format!("{}*", val) })),
         )),
-        ParsedValue::Identifier(right_field) => Ok((
+        ParsedValue::None if type_str == "eq" => Ok((
             input,
-            json!({ "type": "__JOIN_CANDIDATE__", "left": left, "right": right_field }),
+            negate(json!({ "type": "isnone", "field": left })),
         )),
+        ParsedValue::None => Err(reject()),
+        ParsedValue::Regex(pattern) if type_str == "eq" => Ok((
+            input,
+            negate(json!({ "type": "regex", "field": left, "pattern": pattern })),
+        )),
+        ParsedValue::Regex(_) => Err(reject()),
+        ParsedValue::Identifier(right_field)
+            if matches!(type_str, "eq" | "gt" | "gte" | "lt" | "lte") && !negated && fold.is_none() =>
+        {
+            Ok((
+                input,
+                json!({ "type": "__JOIN_CANDIDATE__", "left": left, "right": right_field, "op": type_str }),
+            ))
+        }
+        ParsedValue::Identifier(_) => Err(reject()),
     }
 }
 
@@ -128,14 +564,67 @@ fn parse_leaf_predicate(input: &str) -> IResult<&str, Value> {
 // Logic: Or -> And -> Term (Leaf or Parens)
 
 fn parse_term(input: &str) -> IResult<&str, Value> {
+    // A leaf or parenthesized group followed by an operator is really the
+    // start of a larger expression (`a = b + 1`, `(a + b) > c`).
+    let complete = || not(peek(preceded(multispace0, one_of("+-*/%<>=!~("))));
     alt((
-        delimited(ws(char('(')), parse_or_expression, ws(char(')'))),
-        parse_leaf_predicate,
+        map(
+            preceded(ws(alt((tag("!"), keyword("NOT")))), parse_term),
+            |pred| json!({ "type": "not", "predicate": pred }),
+        ),
+        terminated(
+            delimited(ws(char('(')), parse_or_expression, close(')')),
+            complete(),
+        ),
+        terminated(parse_leaf_predicate, complete()),
+        ws(parse_expr_predicate),
     ))(input)
 }
 
+/// A condition that is not a plain `field <op> value` leaf, e.g.
+/// `string::len(title) > 3` or `price * qty >= 100`.
+///
+/// Parsed at comparison level so that AND / OR still split it into
+/// separate predicates. `field <cmp> literal` folds back into the regular
+/// comparison predicate.
+fn parse_expr_predicate(input: &str) -> IResult<&str, Value> {
+    let (input, expr) = parse_expr_cmp(input)?;
+    if expr["type"] == "binary" && expr["left"]["type"] == "field" && expr["right"]["type"] == "value" {
+        let op = expr["op"].as_str().unwrap_or_default();
+        if matches!(op, "eq" | "neq" | "gt" | "gte" | "lt" | "lte") {
+            return Ok((
+                input,
+                json!({ "type": op, "field": expr["left"]["path"], "value": expr["right"]["value"] }),
+            ));
+        }
+    }
+    Ok((input, json!({ "type": "expr", "expr": expr })))
+}
+
+/// Like `separated_list1`, but a separator must be followed by another
+/// condition: `a = 1 AND` is an error rather than a trailing `AND`.
+fn separated_list1_cut<'a, S, T>(
+    mut separator: S,
+    mut term: T,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<Value>>
+where
+    S: FnMut(&'a str) -> IResult<&'a str, &'a str>,
+    T: FnMut(&'a str) -> IResult<&'a str, Value>,
+{
+    move |input| {
+        let (mut input, first) = term(input)?;
+        let mut terms = vec![first];
+        while let Ok((rest, _)) = separator(input) {
+            let (rest, next) = cut(context("a condition", &mut term))(rest)?;
+            terms.push(next);
+            input = rest;
+        }
+        Ok((input, terms))
+    }
+}
+
 fn parse_and_expression(input: &str) -> IResult<&str, Value> {
-    let (input, terms) = separated_list1(ws(tag_no_case("AND")), parse_term)(input)?;
+    let (input, terms) = separated_list1_cut(ws(keyword("AND")), parse_term)(input)?;
     if terms.len() == 1 {
         Ok((input, terms[0].clone()))
     } else {
@@ -144,7 +633,7 @@ fn parse_and_expression(input: &str) -> IResult<&str, Value> {
 }
 
 fn parse_or_expression(input: &str) -> IResult<&str, Value> {
-    let (input, terms) = separated_list1(ws(tag_no_case("OR")), parse_and_expression)(input)?;
+    let (input, terms) = separated_list1_cut(ws(keyword("OR")), parse_and_expression)(input)?;
     if terms.len() == 1 {
         Ok((input, terms[0].clone()))
     } else {
@@ -153,29 +642,229 @@ fn parse_or_expression(input: &str) -> IResult<&str, Value> {
 }
 
 fn parse_where_logic(input: &str) -> IResult<&str, Value> {
-    preceded(tag_no_case("WHERE"), cut(parse_or_expression))(input)
+    preceded(keyword("WHERE"), cut(context("a condition", parse_or_expression)))(input)
+}
+
+// --- EXPRESSIONS ---
+//
+// Emit `eval::Expr` JSON. Precedence, loosest first:
+// OR, AND, comparison, + -, * / %, unary - !.
+
+fn binary_expr(op: &str, left: Value, right: Value) -> Value {
+    json!({ "type": "binary", "op": op, "left": left, "right": right })
+}
+
+/// Left-associative chain of `operand (op operand)*`.
+fn fold_binary<'a>(
+    input: &'a str,
+    operand: fn(&'a str) -> IResult<&'a str, Value>,
+    op: fn(&'a str) -> IResult<&'a str, &'static str>,
+) -> IResult<&'a str, Value> {
+    let (mut input, mut left) = operand(input)?;
+    while let Ok((rest, (op, right))) = pair(ws(op), operand)(input) {
+        left = binary_expr(op, left, right);
+        input = rest;
+    }
+    Ok((input, left))
+}
+
+fn parse_expr(input: &str) -> IResult<&str, Value> {
+    fold_binary(input, parse_expr_and, |i| {
+        value("or", alt((keyword("OR"), tag("||"))))(i)
+    })
+}
+
+fn parse_expr_and(input: &str) -> IResult<&str, Value> {
+    fold_binary(input, parse_expr_cmp, |i| {
+        value("and", alt((keyword("AND"), tag("&&"))))(i)
+    })
+}
+
+fn parse_expr_cmp(input: &str) -> IResult<&str, Value> {
+    let (input, left) = parse_expr_sum(input)?;
+    let (input, rhs) = opt(pair(
+        ws(alt((
+            value("gte", tag(">=")),
+            value("lte", tag("<=")),
+            value("neq", tag("!=")),
+            value("eq", tag("==")),
+            value("eq", tag("=")),
+            value("gt", tag(">")),
+            value("lt", tag("<")),
+        ))),
+        parse_expr_sum,
+    ))(input)?;
+    Ok(match rhs {
+        Some((op, right)) => (input, binary_expr(op, left, right)),
+        None => (input, left),
+    })
+}
+
+fn parse_expr_sum(input: &str) -> IResult<&str, Value> {
+    fold_binary(input, parse_expr_product, |i| {
+        alt((value("add", char('+')), value("sub", char('-'))))(i)
+    })
+}
+
+fn parse_expr_product(input: &str) -> IResult<&str, Value> {
+    fold_binary(input, parse_expr_unary, |i| {
+        alt((value("mul", char('*')), value("div", char('/')), value("mod", char('%'))))(i)
+    })
+}
+
+fn parse_expr_unary(input: &str) -> IResult<&str, Value> {
+    alt((
+        map(preceded(ws(char('-')), parse_expr_unary), |expr| {
+            // Fold negative literals.
+            match expr["value"].as_f64() {
+                Some(_) if expr["type"] == "value" => {
+                    let value = match expr["value"].as_i64() {
+                        Some(i) => json!(-i),
+                        None => json!(-expr["value"].as_f64().unwrap_or_default()),
+                    };
+                    json!({ "type": "value", "value": value })
+                }
+                _ => json!({ "type": "unary", "op": "neg", "expr": expr }),
+            }
+        }),
+        map(preceded(ws(alt((tag("!"), keyword("NOT")))), parse_expr_unary), |expr| {
+            json!({ "type": "unary", "op": "not", "expr": expr })
+        }),
+        ws(parse_expr_primary),
+    ))(input)
+}
+
+fn parse_number(input: &str) -> IResult<&str, Value> {
+    map_res(
+        recognize(pair(digit1, opt(pair(char('.'), digit1)))),
+        |s: &str| -> Result<Value, std::num::ParseFloatError> {
+            Ok(match s.parse::<i64>() {
+                Ok(i) => json!(i),
+                Err(_) => json!(s.parse::<f64>()?),
+            })
+        },
+    )(input)
+}
+
+/// A duration literal such as `1d`, `2h30m` or `500ms`, as its text.
+fn parse_duration_literal(input: &str) -> IResult<&str, Value> {
+    let unit = alt((
+        tag("ms"),
+        tag("s"),
+        tag("m"),
+        tag("h"),
+        tag("d"),
+        tag("w"),
+        tag("y"),
+    ));
+    map(
+        terminated(
+            recognize(many1(pair(digit1, unit))),
+            not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+        ),
+        |text: &str| json!(text),
+    )(input)
+}
+
+/// `name(args)`; the function must be known to `eval::functions`.
+fn parse_function_call(input: &str) -> IResult<&str, Value> {
+    let (rest, (func, args)) = pair(
+        parse_identifier,
+        delimited(
+            ws(char('(')),
+            separated_list0(ws(char(',')), parse_expr),
+            close(')'),
+        ),
+    )(input)?;
+    if crate::eval::functions::lookup(&func).is_none() {
+        let name = &input[..func.len()];
+        return Err(nom::Err::Failure(SyntaxError {
+            unsupported: vec![(name, format!("function {}()", func))],
+            ..SyntaxError::new(input)
+        }));
+    }
+    Ok((rest, json!({ "type": "call", "func": func.to_lowercase(), "args": args })))
+}
+
+fn parse_expr_primary(input: &str) -> IResult<&str, Value> {
+    let literal = |v: Value| json!({ "type": "value", "value": v });
+    alt((
+        delimited(ws(char('(')), parse_expr, close(')')),
+        map(
+            delimited(
+                ws(char('[')),
+                separated_list0(ws(char(',')), parse_expr),
+                close(']'),
+            ),
+            |items| json!({ "type": "array", "items": items }),
+        ),
+        map(parse_string_literal, move |v| match v {
+            ParsedValue::Prefix(p) => literal(json!(format!("{}*", p))),
+            ParsedValue::Json(v) => literal(v),
+            _ => literal(Value::Null),
+        }),
+        map(parse_duration_literal, literal),
+        map(parse_number, literal),
+        value(literal(json!(true)), keyword("true")),
+        value(literal(json!(false)), keyword("false")),
+        value(literal(Value::Null), alt((keyword("NONE"), keyword("NULL")))),
+        map(preceded(char('$'), parse_identifier), |name| {
+            json!({ "type": "param", "name": name })
+        }),
+        parse_function_call,
+        map(parse_identifier, |path| json!({ "type": "field", "path": path })),
+    ))(input)
 }
 
 // --- MAIN QUERY ---
 
 fn parse_limit_clause(input: &str) -> IResult<&str, usize> {
     preceded(
-        tag_no_case("LIMIT"),
-        ws(map_res(digit1, |s: &str| s.parse::<usize>())),
+        keyword("LIMIT"),
+        cut(ws(context("a row count", map_res(digit1, |s: &str| s.parse::<usize>())))),
+    )(input)
+}
+
+/// `START [AT] n` (SurrealQL) or `OFFSET n`.
+fn parse_start_clause(input: &str) -> IResult<&str, usize> {
+    preceded(
+        alt((
+            value((), pair(keyword("START"), opt(preceded(multispace1, keyword("AT"))))),
+            value((), keyword("OFFSET")),
+        )),
+        cut(ws(context("a row count", map_res(digit1, |s: &str| s.parse::<usize>())))),
     )(input)
 }
 
 fn parse_order_clause(input: &str) -> IResult<&str, Vec<Value>> {
+    let nulls = preceded(
+        tag_no_case("NULLS"),
+        ws(alt((tag_no_case("FIRST"), tag_no_case("LAST")))),
+    );
     let single_order = map(
         tuple((
-            ws(parse_identifier),
+            ws(context("a field or expression to sort by", consumed(parse_expr_sum))),
             opt(ws(alt((tag_no_case("ASC"), tag_no_case("DESC"))))),
+            opt(ws(nulls)),
         )),
-        |(field, dir)| json!({ "field": field, "direction": dir.unwrap_or("ASC").to_uppercase() }),
+        |((text, expr), dir, nulls)| {
+            let direction = dir.unwrap_or("ASC").to_uppercase();
+            let mut order = match expr["type"].as_str() {
+                Some("field") => json!({ "field": expr["path"], "direction": direction }),
+                _ => json!({ "field": text.trim(), "direction": direction, "expr": expr }),
+            };
+            if let Some(nulls) = nulls {
+                order["nulls"] = json!(nulls.to_uppercase());
+            }
+            order
+        },
     );
     preceded(
-        tag_no_case("ORDER BY"),
-        separated_list1(ws(char(',')), single_order),
+        keyword("ORDER"),
+        cut(preceded(
+            ws(context("BY", keyword("BY"))),
+            separated_list1(ws(char(',')), single_order),
+        )),
     )(input)
 }
 
@@ -230,7 +919,7 @@ fn extract_parent_key(plan: &Value) -> Option<Value> {
 
 fn parse_subquery_projection(input: &str) -> IResult<&str, Value> {
     // (SELECT ... ) [optional_index] AS alias
-    let (input, sub_plan) = delimited(ws(char('(')), parse_full_query, ws(char(')')))(input)?;
+    let (input, sub_plan) = delimited(ws(char('(')), parse_full_query, close(')'))(input)?;
 
     // Parse optional array index like [0]
     let (input, _index) = opt(ws(delimited(
@@ -253,64 +942,489 @@ fn parse_subquery_projection(input: &str) -> IResult<&str, Value> {
     Ok((input, result))
 }
 
+/// `*`, `field [AS alias]`, or a computed column `expr [AS alias]`.
+/// A computed column without an alias is named by its source text.
 fn parse_field_projection(input: &str) -> IResult<&str, Value> {
-    // field OR field AS alias (though we usually just use field name)
-    // keeping it simple: just identifier for now, or *
     alt((
         map(tag("*"), |_| json!({ "type": "all" })),
-        map(parse_identifier, |f| json!({ "type": "field", "name": f })),
+        map(
+            pair(
+                consumed(parse_expr),
+                opt(preceded(ws(keyword("AS")), ws(parse_identifier))),
+            ),
+            |((text, expr), alias)| match expr["type"].as_str() {
+                Some("field") => match alias {
+                    Some(alias) => json!({ "type": "field", "name": expr["path"], "alias": alias }),
+                    None => json!({ "type": "field", "name": expr["path"] }),
+                },
+                _ => json!({
+                    "type": "expr",
+                    "expr": expr,
+                    "alias": alias.unwrap_or_else(|| text.trim().to_string()),
+                }),
+            },
+        ),
     ))(input)
 }
 
+/// Aggregate column, optionally followed by `AS alias`:
+/// `count()`, `count(DISTINCT field)`, `sum(field)`, `avg(field)`,
+/// `min(field)`, `max(field)` (or their `math::sum` / `math::mean` /
+/// `math::min` / `math::max` spellings).
+///
+/// Produces a `{ "type": "aggregate", ... }` marker that `parse_full_query`
+/// folds into an `aggregate` operator; it is never a valid `Projection`.
+fn parse_aggregate_projection(input: &str) -> IResult<&str, Value> {
+    let start = input;
+    let (input, name) = parse_identifier(input)?;
+    let (input, (distinct, arg)) = delimited(
+        ws(char('(')),
+        pair(
+            opt(terminated(tag_no_case("DISTINCT"), multispace1)),
+            opt(ws(parse_identifier)),
+        ),
+        close(')'),
+    )(input)?;
+
+    let mut agg = match (name.to_lowercase().as_str(), distinct.is_some(), arg) {
+        ("count", false, None) => json!({ "func": "count" }),
+        ("count", true, Some(field)) => json!({ "func": "count_distinct", "field": field }),
+        ("sum" | "math::sum", false, Some(field)) => json!({ "func": "sum", "field": field }),
+        ("avg" | "math::mean", false, Some(field)) => json!({ "func": "avg", "field": field }),
+        ("min" | "math::min", false, Some(field)) => json!({ "func": "min", "field": field }),
+        ("max" | "math::max", false, Some(field)) => json!({ "func": "max", "field": field }),
+        _ => {
+            return Err(nom::Err::Error(SyntaxError::new(start)))
+        }
+    };
+
+    let (input, alias) = opt(preceded(ws(tag_no_case("AS")), ws(parse_identifier)))(input)?;
+    let obj = agg.as_object_mut().unwrap();
+    obj.insert("type".to_string(), json!("aggregate"));
+    let default_alias = if distinct.is_some() { "count_distinct".to_string() } else { name };
+    obj.insert("alias".to_string(), json!(alias.unwrap_or(default_alias)));
+
+    Ok((input, agg))
+}
+
+/// A table or edge name inside a graph path (no dots or colons).
+fn parse_table_name(input: &str) -> IResult<&str, String> {
+    map(
+        recognize(pair(
+            alt((alpha1, tag("_"))),
+            take_while(|c: char| c.is_alphanumeric() || c == '_'),
+        )),
+        String::from,
+    )(input)
+}
+
+/// Graph traversal `->edge->table` / `<-edge<-table` (one or more hops),
+/// optionally followed by `.*` and `AS alias`. Without an alias the column
+/// is named by the path.
+fn parse_graph_projection(input: &str) -> IResult<&str, Value> {
+    let hop = |arrow: &'static str, direction: &'static str| {
+        map(
+            tuple((tag(arrow), parse_table_name, tag(arrow), parse_table_name)),
+            move |(_, edge, _, table)| json!({ "direction": direction, "edge": edge, "table": table }),
+        )
+    };
+    let (input, (path, steps)) = consumed(many1(alt((hop("->", "out"), hop("<-", "in")))))(input)?;
+    let (input, _) = opt(tag(".*"))(input)?;
+    let (input, alias) = opt(preceded(ws(keyword("AS")), ws(parse_identifier)))(input)?;
+    let alias = alias.unwrap_or_else(|| path.to_string());
+    Ok((input, json!({ "type": "graph", "alias": alias, "steps": steps })))
+}
+
+/// `field.*`: the record a link field points to, named after the field
+/// unless aliased.
+fn parse_link_projection(input: &str) -> IResult<&str, Value> {
+    let (input, field) = verify(terminated(parse_identifier, char('*')), |f: &String| {
+        f.len() > 1 && f.ends_with('.')
+    })(input)?;
+    let field = field.trim_end_matches('.').to_string();
+    let (input, alias) = opt(preceded(ws(keyword("AS")), ws(parse_identifier)))(input)?;
+    let alias = alias.unwrap_or_else(|| field.clone());
+    Ok((input, json!({ "type": "link", "alias": alias, "field": field })))
+}
+
 fn parse_projection_item(input: &str) -> IResult<&str, Value> {
-    alt((parse_subquery_projection, parse_field_projection))(input)
+    alt((
+        parse_subquery_projection,
+        parse_graph_projection,
+        parse_link_projection,
+        parse_aggregate_projection,
+        parse_field_projection,
+    ))(input)
 }
 
-fn parse_full_query(input: &str) -> IResult<&str, Value> {
-    let (input, _) = ws(tag_no_case("SELECT"))(input)?;
+/// FROM target: a table, a record id (`user:1`), or a parameter holding a
+/// record id (`$auth`). A single record is a scan of its table filtered to
+/// that id; a parameter's table is filled in by `bind_record_sources`.
+fn parse_from_source(input: &str) -> IResult<&str, Value> {
+    let record = |table: Value, id: Value| {
+        json!({
+            "op": "filter",
+            "predicate": { "type": "eq", "field": "id", "value": id },
+            "input": { "op": "scan", "table": table },
+        })
+    };
+    alt((
+        map(preceded(char('$'), parse_identifier), move |name| {
+            record(json!({ "$param": name }), json!({ "$param": name }))
+        }),
+        map(parse_identifier, move |name| match name.split_once(':') {
+            Some((table, _)) => record(json!(table), json!(name)),
+            None => json!({ "op": "scan", "table": name }),
+        }),
+    ))(input)
+}
+
+/// Resolve `FROM $param` sources: the scanned table is the table of the
+/// record id held by the parameter.
+pub fn bind_record_sources(plan: &mut Value, params: &Value) -> Result<()> {
+    match plan {
+        Value::Object(obj) => {
+            if obj.get("op").and_then(|v| v.as_str()) == Some("scan") {
+                if let Some(name) = obj.get("table").and_then(|t| t.get("$param")).and_then(|n| n.as_str()) {
+                    let table = params
+                        .get(name)
+                        .and_then(|v| v.as_str())
+                        .and_then(|id| id.split_once(':'))
+                        .map(|(table, _)| table.to_string())
+                        .ok_or_else(|| anyhow!("FROM ${} needs a record id parameter", name))?;
+                    obj.insert("table".to_string(), json!(table));
+                }
+            }
+            obj.values_mut().try_for_each(|v| bind_record_sources(v, params))
+        }
+        Value::Array(items) => items.iter_mut().try_for_each(|v| bind_record_sources(v, params)),
+        _ => Ok(()),
+    }
+}
+
+/// `GROUP ALL` → no group fields; `GROUP [BY] a, b` → group fields.
+fn parse_group_clause(input: &str) -> IResult<&str, Vec<String>> {
+    preceded(
+        keyword("GROUP"),
+        cut(context(
+            "ALL or fields to group by",
+            alt((
+                value(vec![], ws(keyword("ALL"))),
+                preceded(
+                    opt(ws(keyword("BY"))),
+                    separated_list1(ws(char(',')), ws(parse_identifier)),
+                ),
+            )),
+        )),
+    )(input)
+}
 
-    let (input, fields) = separated_list1(ws(char(',')), parse_projection_item)(input)?;
+/// Fold the SELECT list of a grouped query into an `aggregate` operator,
+/// or a `distinct` operator over the group fields when it has no aggregates.
+///
+/// Every plain field must appear in the GROUP clause; `*` and subqueries
+/// are rejected because a group has no single underlying record.
+fn build_group_op(input_op: Value, fields: &[Value], group_by: Vec<String>) -> Option<Value> {
+    let mut aggregates = Vec::new();
+    for field in fields {
+        match field.get("type").and_then(|t| t.as_str()) {
+            Some("aggregate") => {
+                let mut agg = field.clone();
+                agg.as_object_mut()?.remove("type");
+                aggregates.push(agg);
+            }
+            Some("field") => {
+                let name = field.get("name").and_then(|n| n.as_str())?;
+                if !group_by.iter().any(|g| g == name) {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+    if aggregates.is_empty() {
+        return Some(json!({ "op": "distinct", "input": input_op, "fields": group_by }));
+    }
+    Some(json!({
+        "op": "aggregate",
+        "input": input_op,
+        "group_by": group_by,
+        "aggregates": aggregates,
+    }))
+}
+
+// --- UNSUPPORTED CLAUSES ---
+//
+// Clauses a live query cannot maintain incrementally. They are parsed so
+// that each occurrence can be reported with its position, instead of the
+// query failing at the first unknown word.
+
+fn parse_name_list(input: &str) -> IResult<&str, Vec<String>> {
+    separated_list1(ws(char(',')), ws(parse_identifier))(input)
+}
+
+/// `SELECT VALUE field`, unless `value` is itself the selected field.
+fn parse_select_value(input: &str) -> IResult<&str, &'static str> {
+    value(
+        "SELECT VALUE",
+        tuple((
+            keyword("VALUE"),
+            multispace1,
+            not(alt((keyword("FROM"), keyword("AS")))),
+        )),
+    )(input)
+}
+
+fn parse_omit_clause(input: &str) -> IResult<&str, &'static str> {
+    value("OMIT", preceded(keyword("OMIT"), cut(parse_name_list)))(input)
+}
+
+/// `FROM ONLY target`, unless `only` is the table name.
+fn parse_only_keyword(input: &str) -> IResult<&str, &'static str> {
+    value(
+        "FROM ONLY",
+        tuple((keyword("ONLY"), multispace1, not(parse_clause_keyword))),
+    )(input)
+}
+
+fn parse_with_clause(input: &str) -> IResult<&str, &'static str> {
+    let index = alt((
+        value((), keyword("NOINDEX")),
+        value((), preceded(keyword("INDEX"), parse_name_list)),
+    ));
+    value(
+        "WITH",
+        preceded(keyword("WITH"), cut(ws(context("INDEX or NOINDEX", index)))),
+    )(input)
+}
+
+fn parse_split_clause(input: &str) -> IResult<&str, &'static str> {
+    value(
+        "SPLIT",
+        preceded(
+            pair(keyword("SPLIT"), opt(ws(keyword("ON")))),
+            cut(parse_name_list),
+        ),
+    )(input)
+}
 
-    let (input, _) = ws(tag_no_case("FROM"))(input)?;
-    let (input, table) = ws(parse_identifier)(input)?;
+/// Clauses that follow `LIMIT` / `START`.
+fn parse_trailing_clause(input: &str) -> IResult<&str, &'static str> {
+    alt((
+        value("FETCH", preceded(keyword("FETCH"), cut(parse_name_list))),
+        value(
+            "TIMEOUT",
+            preceded(
+                keyword("TIMEOUT"),
+                cut(ws(context("a duration", parse_duration_literal))),
+            ),
+        ),
+        value("PARALLEL", keyword("PARALLEL")),
+        value("TEMPFILES", keyword("TEMPFILES")),
+        value("VERSION", preceded(keyword("VERSION"), cut(ws(parse_expr_primary)))),
+        value("EXPLAIN", pair(keyword("EXPLAIN"), opt(ws(keyword("FULL"))))),
+    ))(input)
+}
+
+fn parse_clause_keyword(input: &str) -> IResult<&str, &str> {
+    alt((
+        keyword("WITH"),
+        keyword("WHERE"),
+        keyword("SPLIT"),
+        keyword("GROUP"),
+        keyword("ORDER"),
+        keyword("LIMIT"),
+        keyword("START"),
+        keyword("FETCH"),
+        keyword("TIMEOUT"),
+        keyword("PARALLEL"),
+        keyword("TEMPFILES"),
+        keyword("EXPLAIN"),
+    ))(input)
+}
+
+fn parse_full_query(input: &str) -> IResult<&str, Value> {
+    fn note<'a>((text, syntax): (&'a str, &str)) -> (&'a str, String) {
+        (text, syntax.to_string())
+    }
+    let mut unsupported = Vec::new();
+
+    let (input, _) = ws(context("SELECT", keyword("SELECT")))(input)?;
+    let (input, select_value) = opt(consumed(parse_select_value))(input)?;
+    unsupported.extend(select_value.map(note));
+    let (input, distinct) = opt(terminated(keyword("DISTINCT"), multispace1))(input)?;
+
+    let (input, (fields_text, fields)) = consumed(separated_list1(
+        ws(char(',')),
+        context(
+            "a field, expression or subquery",
+            preceded(not(keyword("FROM")), parse_projection_item),
+        ),
+    ))(input)?;
+    let (input, omit) = opt(ws(consumed(parse_omit_clause)))(input)?;
+    unsupported.extend(omit.map(note));
+
+    let (input, _) = ws(context("FROM", keyword("FROM")))(input)?;
+    let (input, only) = opt(consumed(parse_only_keyword))(input)?;
+    unsupported.extend(only.map(note));
+    let source_label = "a table, record id or $parameter";
+    let (input, source) = ws(context(source_label, parse_from_source))(input)?;
+    let (input, more_sources) = many0(preceded(
+        ws(char(',')),
+        cut(ws(context(source_label, consumed(parse_from_source)))),
+    ))(input)?;
+    unsupported.extend(
+        more_sources
+            .into_iter()
+            .map(|(text, _)| (text, "multiple FROM targets".to_string())),
+    );
+    let (input, with) = opt(ws(consumed(parse_with_clause)))(input)?;
+    unsupported.extend(with.map(note));
 
     let (input, where_logic) = opt(ws(parse_where_logic))(input)?;
+    let (input, split) = opt(ws(consumed(parse_split_clause)))(input)?;
+    unsupported.extend(split.map(note));
+    let (input, group_by) = opt(ws(consumed(parse_group_clause)))(input)?;
+
+    let (input, order_by) = opt(ws(consumed(parse_order_clause)))(input)?;
+    let (input, limit) = opt(ws(consumed(parse_limit_clause)))(input)?;
+    let (input, start) = opt(ws(consumed(parse_start_clause)))(input)?;
+
+    let (input, trailing) = many0(ws(consumed(parse_trailing_clause)))(input)?;
+    unsupported.extend(trailing.into_iter().map(note));
+
+    // ORDER BY / LIMIT on a grouped query would have to sort synthesized
+    // group rows, which TopK cannot resolve from the store.
+    let grouping = match (&group_by, distinct) {
+        (Some(_), _) => Some("GROUP"),
+        (None, Some(_)) => Some("DISTINCT"),
+        (None, None) => None,
+    };
+    let grouped_clauses = [
+        ("ORDER BY", order_by.as_ref().map(|(text, _)| *text)),
+        ("LIMIT", limit.map(|(text, _)| text)),
+        ("START", start.map(|(text, _)| text)),
+    ];
+    let is_select_all =
+        fields.len() == 1 && fields[0].get("type").and_then(|t| t.as_str()) == Some("all");
+    if let Some(grouping) = grouping.filter(|_| group_by.is_some() || !is_select_all) {
+        for (clause, text) in grouped_clauses {
+            if let Some(text) = text {
+                unsupported.push((text, format!("{} with {}", clause, grouping)));
+            }
+        }
+    }
 
-    let (input, order_by) = opt(ws(parse_order_clause))(input)?;
-    let (input, limit) = opt(ws(parse_limit_clause))(input)?;
+    if !unsupported.is_empty() {
+        return Err(nom::Err::Failure(SyntaxError {
+            unsupported,
+            ..SyntaxError::new(input)
+        }));
+    }
+    let group_by = group_by.map(|(_, fields)| fields);
+    let order_by = order_by.map(|(_, orders)| orders);
+    let limit = limit.map(|(_, limit)| limit);
+    let start = start.map(|(_, start)| start);
 
     // --- TREE BUILDING ---
-    let mut current_op = json!({ "op": "scan", "table": table });
+    let mut current_op = source;
 
     if let Some(logic) = where_logic {
         current_op = wrap_conditions(current_op, logic);
     }
 
+    let has_aggregates = fields
+        .iter()
+        .any(|f| f.get("type").and_then(|t| t.as_str()) == Some("aggregate"));
+
+    // Records are already distinct, so `SELECT DISTINCT *` is a plain select.
+    // Otherwise DISTINCT groups by every selected field.
+    let group_by = match (group_by, distinct) {
+        (None, Some(_)) if !is_select_all && !has_aggregates => Some(
+            fields
+                .iter()
+                .map(|f| f.get("name").and_then(|n| n.as_str()).map(String::from))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    SyntaxError::fail(fields_text, "SELECT DISTINCT only supports plain fields")
+                })?,
+        ),
+        (group_by, _) => group_by,
+    };
+
+    if let Some(group_by) = group_by {
+        let group_op = build_group_op(current_op, &fields, group_by).ok_or_else(|| {
+            SyntaxError::fail(
+                fields_text,
+                "a grouped query can only select grouped fields and aggregates",
+            )
+        })?;
+        return Ok((input, group_op));
+    }
+
+    if has_aggregates {
+        return Err(SyntaxError::fail(
+            fields_text,
+            "aggregates need a GROUP BY or GROUP ALL clause",
+        ));
+    }
+
     // Projections
     // If we have just one "type": "all", and nothing else, we skip projection technically
     // But let's be explicit if desired.
     // If fields contains any subquery or if fields is not just "*", we project.
-    let needs_projection =
-        fields.len() > 1 || fields[0].get("type").and_then(|t| t.as_str()) != Some("all");
-
-    if needs_projection {
+    if !is_select_all {
         current_op = json!({ "op": "project", "projections": fields, "input": current_op });
     }
 
-    if let Some(l) = limit {
-        let mut limit_op = json!({ "op": "limit", "limit": l, "input": current_op });
+    if limit.is_some() || start.is_some() {
+        // START without LIMIT has no "limit": every row after the offset.
+        let mut limit_op = json!({ "op": "limit", "input": current_op });
+        if let Some(l) = limit {
+            limit_op
+                .as_object_mut()
+                .unwrap()
+                .insert("limit".to_string(), json!(l));
+        }
         if let Some(orders) = order_by {
+            let orders = resolve_order_aliases(orders, &fields);
             limit_op
                 .as_object_mut()
                 .unwrap()
                 .insert("order_by".to_string(), json!(orders));
         }
+        if let Some(offset) = start.filter(|&o| o > 0) {
+            limit_op
+                .as_object_mut()
+                .unwrap()
+                .insert("offset".to_string(), json!(offset));
+        }
         current_op = limit_op;
     }
 
     Ok((input, current_op))
 }
 
+/// ORDER BY a projection alias sorts by what the alias names: the
+/// underlying field, or the computed column's expression.
+fn resolve_order_aliases(orders: Vec<Value>, fields: &[Value]) -> Vec<Value> {
+    orders
+        .into_iter()
+        .map(|mut order| {
+            if order.get("expr").is_some() {
+                return order;
+            }
+            let aliased = fields.iter().find(|f| f.get("alias").is_some() && f["alias"] == order["field"]);
+            match aliased {
+                Some(f) if f["type"] == "field" => order["field"] = f["name"].clone(),
+                Some(f) if f["type"] == "expr" => order["expr"] = f["expr"].clone(),
+                _ => {}
+            }
+            order
+        })
+        .collect()
+}
+
 fn wrap_conditions(input_op: Value, predicate: Value) -> Value {
     let mut joins = Vec::new();
     let mut filters = Vec::new();
@@ -338,7 +1452,11 @@ fn wrap_conditions(input_op: Value, predicate: Value) -> Value {
 
     let mut current_op = input_op;
 
-    // 2. Apply Joins (Bottom-Up)
+    // 2. Apply Joins (Bottom-Up), one per right-hand table. All conditions
+    // against the same table form a single join: the first equality is the
+    // primary key, further equalities extend it to a composite key, and
+    // comparisons become range conditions.
+    let mut join_tables: Vec<(String, Value)> = Vec::new();
     for join_pred in joins {
         let left_field = join_pred
             .get("left")
@@ -348,20 +1466,43 @@ fn wrap_conditions(input_op: Value, predicate: Value) -> Value {
             .get("right")
             .and_then(|v| v.as_str())
             .unwrap_or("unknown");
+        let op = join_pred.get("op").and_then(|v| v.as_str()).unwrap_or("eq");
 
         // Assume right_full is "table.field"
-        let parts: Vec<&str> = right_full.split('.').collect();
-        let (r_table, r_col) = if parts.len() > 1 {
-            (parts[0], parts[1])
-        } else {
-            (right_full, "id")
+        let (r_table, r_col) = right_full.split_once('.').unwrap_or((right_full, "id"));
+
+        let idx = match join_tables.iter().position(|(t, _)| t == r_table) {
+            Some(idx) => idx,
+            None => {
+                join_tables.push((r_table.to_string(), json!({})));
+                join_tables.len() - 1
+            }
         };
+        let on = join_tables[idx].1.as_object_mut().unwrap();
+        if op != "eq" {
+            on.entry("ranges")
+                .or_insert_with(|| json!([]))
+                .as_array_mut()
+                .unwrap()
+                .push(json!({ "left": left_field, "op": op, "right": r_col }));
+        } else if on.contains_key("left_field") {
+            on.entry("keys")
+                .or_insert_with(|| json!([]))
+                .as_array_mut()
+                .unwrap()
+                .push(json!({ "left": left_field, "right": r_col }));
+        } else {
+            on.insert("left_field".to_string(), json!(left_field));
+            on.insert("right_field".to_string(), json!(r_col));
+        }
+    }
 
+    for (r_table, on) in join_tables {
         current_op = json!({
             "op": "join",
             "left": current_op,
             "right": { "op": "scan", "table": r_table },
-            "on": { "left_field": left_field, "right_field": r_col }
+            "on": on
         });
     }
 
@@ -496,4 +1637,412 @@ mod tests {
             _ => panic!("Expected Project operator at top level"),
         }
     }
+
+    #[test]
+    fn test_group_by_builds_aggregate_operator() {
+        let sql = "SELECT thread, count() AS unread, math::sum(size) AS bytes FROM message WHERE read = false GROUP BY thread";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        assert_eq!(result["op"], "aggregate");
+        assert_eq!(result["group_by"], json!(["thread"]));
+        assert_eq!(
+            result["aggregates"],
+            json!([
+                { "func": "count", "alias": "unread" },
+                { "func": "sum", "field": "size", "alias": "bytes" }
+            ])
+        );
+        assert_eq!(result["input"]["op"], "filter");
+
+        let operator: Operator = serde_json::from_value(result).expect("Failed to deserialize");
+        assert!(matches!(operator, Operator::Aggregate { .. }));
+    }
+
+    #[test]
+    fn test_group_all_has_no_group_fields() {
+        let sql = "SELECT count() FROM message GROUP ALL";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        assert_eq!(result["op"], "aggregate");
+        assert_eq!(result["group_by"], json!([]));
+        assert_eq!(result["aggregates"][0]["alias"], "count");
+
+        let sql = "SELECT count(), count(DISTINCT author) FROM message GROUP ALL";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        assert_eq!(result["aggregates"][0]["alias"], "count");
+        assert_eq!(result["aggregates"][1]["alias"], "count_distinct");
+    }
+
+    #[test]
+    fn test_extended_aggregate_functions() {
+        let sql = "SELECT category, math::min(price) AS cheapest, max(price), avg(price) AS mean, \
+                   count(DISTINCT brand) AS brands FROM product GROUP BY category";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        let aggs = &result["aggregates"];
+        assert_eq!(aggs[0], json!({ "func": "min", "field": "price", "alias": "cheapest" }));
+        assert_eq!(aggs[1], json!({ "func": "max", "field": "price", "alias": "max" }));
+        assert_eq!(aggs[2], json!({ "func": "avg", "field": "price", "alias": "mean" }));
+        assert_eq!(aggs[3], json!({ "func": "count_distinct", "field": "brand", "alias": "brands" }));
+    }
+
+    #[test]
+    fn test_select_distinct_builds_distinct_operator() {
+        let sql = "SELECT DISTINCT author FROM post WHERE published = true";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        assert_eq!(result["op"], "distinct");
+        assert_eq!(result["fields"], json!(["author"]));
+        assert_eq!(result["input"]["op"], "filter");
+
+        let plan: Operator = serde_json::from_value(result).expect("valid plan");
+        assert!(matches!(plan, Operator::Distinct { .. }));
+    }
+
+    #[test]
+    fn test_group_by_without_aggregates_is_distinct() {
+        let sql = "SELECT channel, user FROM membership GROUP BY channel, user";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        assert_eq!(result["op"], "distinct");
+        assert_eq!(result["fields"], json!(["channel", "user"]));
+    }
+
+    #[test]
+    fn test_limit_with_start_sets_offset() {
+        let sql = "SELECT * FROM post ORDER BY created_at DESC LIMIT 20 START AT 40";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        assert_eq!(result["op"], "limit");
+        assert_eq!(result["limit"], 20);
+        assert_eq!(result["offset"], 40);
+
+        let plan: Operator = serde_json::from_value(result).expect("valid plan");
+        assert!(matches!(plan, Operator::Limit { offset: 40, .. }));
+    }
+
+    #[test]
+    fn test_order_by_nulls_placement() {
+        let sql = "SELECT * FROM task ORDER BY due DESC NULLS LAST, title LIMIT 10";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        let orders = &result["order_by"];
+        assert_eq!(orders[0], json!({ "field": "due", "direction": "DESC", "nulls": "LAST" }));
+        assert_eq!(orders[1], json!({ "field": "title", "direction": "ASC" }));
+    }
+
+    #[test]
+    fn test_start_without_limit() {
+        let sql = "SELECT * FROM post ORDER BY created_at START 10";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        assert_eq!(result["op"], "limit");
+        assert_eq!(result["offset"], 10);
+        assert!(result.get("limit").is_none());
+    }
+
+    #[test]
+    fn test_membership_operators() {
+        let sql = "SELECT * FROM task WHERE status IN ['open', 'pending'] AND tags CONTAINS 'bug' \
+                   AND labels CONTAINSANY ['ui', 'ux'] AND owner NOT IN [$me]";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        let preds = &result["predicate"]["predicates"];
+
+        assert_eq!(preds[0], json!({ "type": "inside", "field": "status", "value": ["open", "pending"] }));
+        assert_eq!(preds[1], json!({ "type": "contains", "field": "tags", "value": "bug" }));
+        assert_eq!(preds[2], json!({ "type": "containsany", "field": "labels", "value": ["ui", "ux"] }));
+        assert_eq!(
+            preds[3],
+            json!({ "type": "not", "predicate": {
+                "type": "inside", "field": "owner", "value": [{ "$param": "me" }]
+            }})
+        );
+
+        let sql = "SELECT * FROM task WHERE tags CONTAINSALL ['bug', 'ui']";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        assert_eq!(result["predicate"], json!({ "type": "containsall", "field": "tags", "value": ["bug", "ui"] }));
+        assert!(convert_surql_to_dbsp("SELECT * FROM task WHERE tags ALLINSIDE ['bug']").is_err());
+    }
+
+    #[test]
+    fn test_is_none_and_not() {
+        let sql = "SELECT * FROM task WHERE assignee IS NONE OR NOT (done = true) OR due IS NOT NULL";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        let preds = &result["predicate"]["predicates"];
+
+        assert_eq!(preds[0], json!({ "type": "isnone", "field": "assignee" }));
+        assert_eq!(
+            preds[1],
+            json!({ "type": "not", "predicate": { "type": "eq", "field": "done", "value": true } })
+        );
+        assert_eq!(
+            preds[2],
+            json!({ "type": "not", "predicate": { "type": "isnone", "field": "due" } })
+        );
+
+        let plan: Operator = serde_json::from_value(result).expect("valid plan");
+        assert!(matches!(plan, Operator::Filter { .. }));
+    }
+
+    #[test]
+    fn test_string_matching_predicates() {
+        let sql = "SELECT * FROM user WHERE string::lowercase(name) CONTAINS $q \
+                   AND nick ~ 'ada' AND email = /@example\\.com$/ AND handle !~ 'bot'";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        let preds = &result["predicate"]["predicates"];
+
+        assert_eq!(
+            preds[0],
+            json!({ "type": "fold", "case": "lower", "predicate": {
+                "type": "contains", "field": "name", "value": { "$param": "q" }
+            }})
+        );
+        assert_eq!(preds[1], json!({ "type": "fuzzy", "field": "nick", "value": "ada" }));
+        assert_eq!(preds[2], json!({ "type": "regex", "field": "email", "pattern": "@example\\.com$" }));
+        assert_eq!(
+            preds[3],
+            json!({ "type": "not", "predicate": { "type": "fuzzy", "field": "handle", "value": "bot" } })
+        );
+        let _: Operator = serde_json::from_value(result).expect("valid plan");
+    }
+
+    #[test]
+    fn test_invalid_regex_is_rejected() {
+        assert!(convert_surql_to_dbsp("SELECT * FROM user WHERE name = /(unclosed/").is_err());
+    }
+
+    #[test]
+    fn test_group_by_rejects_ungrouped_field() {
+        let sql = "SELECT title, count() FROM message GROUP BY thread";
+        assert!(convert_surql_to_dbsp(sql).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_without_group_is_rejected() {
+        let sql = "SELECT count() FROM message";
+        assert!(convert_surql_to_dbsp(sql).is_err());
+    }
+
+    #[test]
+    fn test_composite_and_range_join_conditions() {
+        let sql = "SELECT * FROM member WHERE tenant = role.tenant AND user = role.user AND active = true";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        assert_eq!(result["op"], "filter");
+        assert_eq!(
+            result["input"]["on"],
+            json!({
+                "left_field": "tenant",
+                "right_field": "tenant",
+                "keys": [{ "left": "user", "right": "user" }]
+            })
+        );
+
+        let sql = "SELECT * FROM event WHERE ts >= window.start AND ts < window.end";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        assert_eq!(result["op"], "join");
+        assert_eq!(result["right"]["table"], "window");
+        assert_eq!(
+            result["on"],
+            json!({ "ranges": [
+                { "left": "ts", "op": "gte", "right": "start" },
+                { "left": "ts", "op": "lt", "right": "end" }
+            ]})
+        );
+
+        let plan: Operator = serde_json::from_value(result).expect("valid plan");
+        let Operator::Join { on, .. } = plan else {
+            panic!("expected join");
+        };
+        assert!(on.equalities().is_empty());
+        assert_eq!(on.ranges.len(), 2);
+    }
+
+    #[test]
+    fn test_computed_projections() {
+        let sql = "SELECT title AS t, count(comments) AS n, string::uppercase(name), price * (1 - discount) AS total FROM post";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+        let projections = &result["projections"];
+
+        assert_eq!(projections[0], json!({ "type": "field", "name": "title", "alias": "t" }));
+        assert_eq!(
+            projections[1],
+            json!({ "type": "expr", "alias": "n", "expr": {
+                "type": "call", "func": "count", "args": [{ "type": "field", "path": "comments" }]
+            }})
+        );
+        assert_eq!(projections[2]["alias"], "string::uppercase(name)");
+        assert_eq!(
+            projections[3]["expr"],
+            json!({ "type": "binary", "op": "mul",
+                "left": { "type": "field", "path": "price" },
+                "right": { "type": "binary", "op": "sub",
+                    "left": { "type": "value", "value": 1 },
+                    "right": { "type": "field", "path": "discount" } } })
+        );
+
+        let plan: Operator = serde_json::from_value(result).expect("valid plan");
+        assert!(matches!(plan, Operator::Project { .. }));
+    }
+
+    #[test]
+    fn test_expression_filters_and_order() {
+        let sql = "SELECT *, string::len(title) AS len FROM post \
+                   WHERE string::len(title) > 3 AND score >= -2 AND (likes + 1) * 2 > views \
+                   ORDER BY len DESC, string::lowercase(author) LIMIT 5";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        let preds = &result["input"]["input"]["predicate"]["predicates"];
+        assert_eq!(preds[0]["type"], "expr");
+        assert_eq!(preds[0]["expr"]["left"]["func"], "string::len");
+        // `field <cmp> literal` stays a plain comparison.
+        assert_eq!(preds[1], json!({ "type": "gte", "field": "score", "value": -2 }));
+        assert_eq!(preds[2]["expr"]["op"], "gt");
+
+        let orders = &result["order_by"];
+        assert_eq!(orders[0]["field"], "len");
+        assert_eq!(orders[0]["expr"]["func"], "string::len");
+        assert_eq!(orders[1]["field"], "string::lowercase(author)");
+        assert_eq!(orders[1]["expr"]["func"], "string::lowercase");
+
+        let plan: Operator = serde_json::from_value(result).expect("valid plan");
+        assert!(matches!(plan, Operator::Limit { .. }));
+    }
+
+    #[test]
+    fn test_time_window_filters() {
+        let sql = "SELECT * FROM event WHERE created_at > time::now() - 1d AND expires_at < time::now() + 2h30m";
+        let result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        let preds = &result["predicate"]["predicates"];
+        assert_eq!(preds[0]["type"], "expr");
+        assert_eq!(
+            preds[0]["expr"]["right"],
+            json!({ "type": "binary", "op": "sub",
+                    "left": { "type": "call", "func": "time::now", "args": [] },
+                    "right": { "type": "value", "value": "1d" } })
+        );
+        assert_eq!(preds[1]["expr"]["right"]["right"]["value"], "2h30m");
+
+        assert!(convert_surql_to_dbsp("SELECT * FROM event WHERE n > 1dx").is_err());
+    }
+
+    #[test]
+    fn test_graph_traversal_and_links() {
+        let sql = "SELECT *, ->follows->user.* AS following, <-likes<-user->posted->post, author.* FROM $auth";
+        let mut result = convert_surql_to_dbsp(sql).expect("Failed to parse SQL");
+
+        let projections = &result["projections"];
+        assert_eq!(
+            projections[1],
+            json!({ "type": "graph", "alias": "following", "steps": [
+                { "direction": "out", "edge": "follows", "table": "user" }
+            ]})
+        );
+        assert_eq!(projections[2]["alias"], "<-likes<-user->posted->post");
+        assert_eq!(projections[2]["steps"][0]["direction"], "in");
+        assert_eq!(projections[2]["steps"][1]["table"], "post");
+        assert_eq!(projections[3], json!({ "type": "link", "alias": "author", "field": "author" }));
+
+        // FROM $auth scans the table of the record id the parameter holds.
+        assert!(bind_record_sources(&mut result.clone(), &json!({})).is_err());
+        bind_record_sources(&mut result, &json!({ "auth": "user:42" })).unwrap();
+        let source = &result["input"];
+        assert_eq!(source["input"], json!({ "op": "scan", "table": "user" }));
+        assert_eq!(source["predicate"]["value"], json!({ "$param": "auth" }));
+        let plan: Operator = serde_json::from_value(result).expect("valid plan");
+        assert_eq!(plan.referenced_tables(), vec!["user", "follows", "likes", "posted", "post"]);
+    }
+
+    #[test]
+    fn test_from_record_id() {
+        let result = convert_surql_to_dbsp("SELECT * FROM user:1 WHERE active = true").unwrap();
+        assert_eq!(result["input"]["op"], "filter");
+        assert_eq!(result["input"]["predicate"], json!({ "type": "eq", "field": "id", "value": "user:1" }));
+        assert_eq!(result["input"]["input"], json!({ "op": "scan", "table": "user" }));
+    }
+
+    #[test]
+    fn test_unknown_function_is_rejected() {
+        assert!(convert_surql_to_dbsp("SELECT string::shout(name) AS s FROM user").is_err());
+        assert!(convert_surql_to_dbsp("SELECT * FROM user WHERE nope::nope(name) = 1").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_clauses_are_listed_with_spans() {
+        let sql = "SELECT * OMIT secret FROM post WHERE a = 1 FETCH author TIMEOUT 5s";
+        let err = convert_surql_to_dbsp(sql).unwrap_err();
+        let found: Vec<_> = err
+            .unsupported
+            .iter()
+            .map(|u| (u.syntax.as_str(), &sql[u.span.clone()]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("OMIT", "OMIT secret"), ("FETCH", "FETCH author"), ("TIMEOUT", "TIMEOUT 5s")]
+        );
+        assert_eq!(err.message, "not supported in live queries: OMIT, FETCH, TIMEOUT");
+        assert_eq!((err.line, err.column), (1, 10));
+
+        let err = convert_surql_to_dbsp("SELECT * FROM post; DELETE post").unwrap_err();
+        assert_eq!(err.unsupported[0].syntax, "multiple statements");
+
+        let err = convert_surql_to_dbsp("SELECT string::shout(name) AS s FROM user").unwrap_err();
+        assert_eq!(err.unsupported[0].syntax, "function string::shout()");
+        assert_eq!(err.span, 7..20);
+
+        let err = convert_surql_to_dbsp(
+            "SELECT author, count() AS n FROM post GROUP BY author ORDER BY n LIMIT 3",
+        )
+        .unwrap_err();
+        assert_eq!(err.message, "not supported in live queries: ORDER BY with GROUP, LIMIT with GROUP");
+
+        // Names that merely look like clause keywords are still fields and tables.
+        assert!(convert_surql_to_dbsp("SELECT value FROM only").is_ok());
+    }
+
+    #[test]
+    fn test_syntax_errors_point_at_the_problem() {
+        let err = convert_surql_to_dbsp("SELECT title post").unwrap_err();
+        assert_eq!(err.message, "expected FROM, found `post`");
+        assert_eq!(err.span, 13..17);
+
+        let sql = "SELECT *\nFROM post\nWHERE title = 'x' AND (a = 1";
+        let err = convert_surql_to_dbsp(sql).unwrap_err();
+        assert_eq!(err.message, "expected `)`, found end of query");
+        assert_eq!((err.line, err.column), (3, 29));
+
+        let err = convert_surql_to_dbsp("SELECT * FROM post WHERE a = 1 garbage").unwrap_err();
+        assert_eq!(err.message, "unexpected `garbage` after the end of the query");
+        assert_eq!(
+            err.to_string(),
+            "unexpected `garbage` after the end of the query at line 1, column 32\n  \
+             SELECT * FROM post WHERE a = 1 garbage\n  \
+             \u{20}                              ^^^^^^^"
+        );
+
+        let err = convert_surql_to_dbsp("SELECT count() FROM post").unwrap_err();
+        assert_eq!(err.message, "aggregates need a GROUP BY or GROUP ALL clause");
+        assert_eq!(err.span, 7..14);
+
+        let err = convert_surql_to_dbsp("UPDATE post SET a = 1").unwrap_err();
+        assert_eq!(err.message, "expected SELECT, found `UPDATE`");
+
+        let err = convert_surql_to_dbsp("SELECT * FROM post WHERE title = 'abc").unwrap_err();
+        assert_eq!(err.message, "unterminated string literal");
+        assert_eq!(err.span, 33..37);
+
+        // The caret counts characters, not bytes.
+        let err = convert_surql_to_dbsp("SELECT * FROM post WHERE a = 'é' héllo").unwrap_err();
+        assert_eq!(err.span, 34..40);
+        assert_eq!(
+            err.to_string(),
+            "unexpected `héllo` after the end of the query at line 1, column 34\n  \
+             SELECT * FROM post WHERE a = 'é' héllo\n  \
+             \u{20}                                ^^^^^"
+        );
+
+        assert!(convert_surql_to_dbsp("SELECT * FROM post;").is_ok());
+        let err = convert_surql_to_dbsp("SELECT * FROM post;;").unwrap_err();
+        assert_eq!(err.message, "unexpected `;` after the end of the query");
+    }
 }
@@ -0,0 +1,501 @@
+use super::functions::{self, add_numbers, decimal_operands, truthy};
+use super::time;
+use super::value_ops::{cmp_int_float, resolve_field, scalar_cmp, sort_cmp};
+use crate::types::{Path, Sp00kyValue};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Scalar expression over one record.
+///
+/// Produced by the converter for computed projections
+/// (`SELECT price * qty AS total`), expression filters
+/// (`WHERE string::len(title) > 3`) and ORDER BY expressions. Evaluation is
+/// total: missing fields, type mismatches and bad arguments yield `Null`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Expr {
+    Field {
+        path: Path,
+    },
+    /// A literal.
+    Value {
+        value: serde_json::Value,
+    },
+    /// `$name` / `$parent.name`, resolved against the view's parameters.
+    Param {
+        name: String,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// A builtin from `eval::functions`.
+    Call {
+        func: String,
+        #[serde(default)]
+        args: Vec<Expr>,
+    },
+    Array {
+        items: Vec<Expr>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    And,
+    Or,
+}
+
+impl Expr {
+    pub fn field(path: &str) -> Self {
+        Expr::Field {
+            path: Path::new(path),
+        }
+    }
+
+    /// Evaluate against `row` (the record, if any) with the view's `params`.
+    /// `time::now()` has no value; see `eval_at`.
+    pub fn eval(&self, row: Option<&Sp00kyValue>, params: Option<&Sp00kyValue>) -> Sp00kyValue {
+        self.eval_at(row, params, None)
+    }
+
+    /// Evaluate with `now` (Unix milliseconds) as the value of `time::now()`.
+    pub fn eval_at(
+        &self,
+        row: Option<&Sp00kyValue>,
+        params: Option<&Sp00kyValue>,
+        now: Option<i64>,
+    ) -> Sp00kyValue {
+        let eval = |e: &Expr| e.eval_at(row, params, now);
+        match self {
+            Expr::Field { path } => resolve_field(row, path).cloned().unwrap_or_default(),
+            Expr::Value { value } => Sp00kyValue::from(value.clone()),
+            Expr::Param { name } => {
+                let name = name.strip_prefix("parent.").unwrap_or(name);
+                resolve_field(params, &Path::new(name)).cloned().unwrap_or_default()
+            }
+            Expr::Unary { op, expr } => {
+                let value = eval(expr);
+                match op {
+                    UnaryOp::Not => Sp00kyValue::Bool(!truthy(&value)),
+                    UnaryOp::Neg => match value {
+                        Sp00kyValue::Int(i) => i
+                            .checked_neg()
+                            .map(Sp00kyValue::Int)
+                            .unwrap_or(Sp00kyValue::Float(-(i as f64))),
+                        Sp00kyValue::Float(f) => Sp00kyValue::Float(-f),
+                        _ => Sp00kyValue::Null,
+                    },
+                }
+            }
+            Expr::Binary { op, left, right } => {
+                // Short-circuit the logical operators.
+                match op {
+                    BinaryOp::And => {
+                        let l = eval(left);
+                        return if truthy(&l) { eval(right) } else { l };
+                    }
+                    BinaryOp::Or => {
+                        let l = eval(left);
+                        return if truthy(&l) { l } else { eval(right) };
+                    }
+                    _ => {}
+                }
+                binary(*op, &eval(left), &eval(right))
+            }
+            Expr::Call { func, .. } if is_now(func) => now
+                .and_then(chrono::DateTime::from_timestamp_millis)
+                .map(Sp00kyValue::Datetime)
+                .unwrap_or_default(),
+            Expr::Call { func, args } => {
+                let args: Vec<Sp00kyValue> = args.iter().map(eval).collect();
+                functions::lookup(func)
+                    .map(|f| f(&args))
+                    .unwrap_or_default()
+            }
+            Expr::Array { items } => Sp00kyValue::Array(items.iter().map(eval).collect()),
+        }
+    }
+
+    /// Whether the expression holds for `row` (SurrealQL truthiness).
+    pub fn matches(&self, row: Option<&Sp00kyValue>, params: Option<&Sp00kyValue>) -> bool {
+        self.matches_at(row, params, None)
+    }
+
+    /// `matches` with `now` as the value of `time::now()`.
+    pub fn matches_at(
+        &self,
+        row: Option<&Sp00kyValue>,
+        params: Option<&Sp00kyValue>,
+        now: Option<i64>,
+    ) -> bool {
+        truthy(&self.eval_at(row, params, now))
+    }
+
+    /// Whether the value depends on the clock (`time::now()`).
+    pub fn uses_clock(&self) -> bool {
+        match self {
+            Expr::Field { .. } | Expr::Value { .. } | Expr::Param { .. } => false,
+            Expr::Unary { expr, .. } => expr.uses_clock(),
+            Expr::Binary { left, right, .. } => left.uses_clock() || right.uses_clock(),
+            Expr::Call { func, args } => is_now(func) || args.iter().any(Expr::uses_clock),
+            Expr::Array { items } => items.iter().any(Expr::uses_clock),
+        }
+    }
+
+    /// The first function name not known to `eval::functions`, if any.
+    pub fn unknown_function(&self) -> Option<&str> {
+        match self {
+            Expr::Field { .. } | Expr::Value { .. } | Expr::Param { .. } => None,
+            Expr::Unary { expr, .. } => expr.unknown_function(),
+            Expr::Binary { left, right, .. } => {
+                left.unknown_function().or_else(|| right.unknown_function())
+            }
+            Expr::Call { func, args } => {
+                if functions::lookup(func).is_none() {
+                    return Some(func);
+                }
+                args.iter().find_map(|a| a.unknown_function())
+            }
+            Expr::Array { items } => items.iter().find_map(|i| i.unknown_function()),
+        }
+    }
+}
+
+fn is_now(func: &str) -> bool {
+    func.eq_ignore_ascii_case("time::now")
+}
+
+/// Ordering for comparisons: numbers compare by value across
+/// `Int`/`Float`/`Decimal`, typed datetimes (or durations) exactly, also
+/// against strings in their own form, and otherwise by the time they
+/// denote in milliseconds; UUIDs and record links against their strings,
+/// everything else by `sort_cmp`.
+/// Two plain strings never compare as times: `"1d" = "24h"` is false.
+fn compare(a: &Sp00kyValue, b: &Sp00kyValue) -> Ordering {
+    use Sp00kyValue::{Datetime, Decimal, Duration, RecordId, Str, Uuid};
+    match (a, b) {
+        (Sp00kyValue::Int(i), Sp00kyValue::Float(f)) => cmp_int_float(*i, *f),
+        (Sp00kyValue::Float(f), Sp00kyValue::Int(i)) => cmp_int_float(*i, *f).reverse(),
+        (Datetime(x), Datetime(y)) => x.cmp(y),
+        (Duration(x), Duration(y)) => x.cmp(y),
+        (Datetime(_) | Duration(_), Str(_) | Datetime(_) | Duration(_))
+        | (Str(_), Datetime(_) | Duration(_)) => scalar_cmp(a, b).unwrap_or_else(|| {
+            match (time::datetime_millis(a), time::datetime_millis(b)) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                _ => match (time::duration_millis(a), time::duration_millis(b)) {
+                    (Some(x), Some(y)) => x.cmp(&y),
+                    _ => sort_cmp(a, b),
+                },
+            }
+        }),
+        (Decimal(_) | Uuid(_) | RecordId(_), _) | (_, Decimal(_) | Uuid(_) | RecordId(_)) => {
+            scalar_cmp(a, b).unwrap_or_else(|| sort_cmp(a, b))
+        }
+        _ => sort_cmp(a, b),
+    }
+}
+
+/// Datetime ± duration and datetime − datetime, or `None` if the operands
+/// are not time values. With a typed datetime operand the result is typed
+/// too (see `typed_time_arithmetic`); on strings it is a string.
+fn time_arithmetic(op: BinaryOp, l: &Sp00kyValue, r: &Sp00kyValue) -> Option<Sp00kyValue> {
+    if let Some(result) = typed_time_arithmetic(op, l, r) {
+        return Some(result);
+    }
+    let (datetime, duration) = (time::datetime_millis, time::duration_millis);
+    let at = |millis: Option<i64>| {
+        Some(millis.and_then(time::format_datetime).map(Sp00kyValue::from).unwrap_or_default())
+    };
+    match op {
+        BinaryOp::Add => match (datetime(l), duration(r), duration(l), datetime(r)) {
+            (Some(t), Some(d), ..) | (.., Some(d), Some(t)) => at(t.checked_add(d)),
+            _ => None,
+        },
+        BinaryOp::Sub => match (datetime(l), duration(r), datetime(r)) {
+            (Some(t), Some(d), _) => at(t.checked_sub(d)),
+            (Some(t), _, Some(u)) => Some(
+                t.checked_sub(u)
+                    .map(|d| Sp00kyValue::from(time::format_duration(d)))
+                    .unwrap_or_default(),
+            ),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Exact arithmetic when one operand is a typed `Datetime`: ± a duration
+/// gives a `Datetime`, − a datetime a `Duration`. `None` for other
+/// operands and for results out of range, such as negative durations.
+fn typed_time_arithmetic(op: BinaryOp, l: &Sp00kyValue, r: &Sp00kyValue) -> Option<Sp00kyValue> {
+    use Sp00kyValue::{Datetime, Duration};
+    let span = |v: &Sp00kyValue| match v {
+        Duration(d) => chrono::Duration::from_std(*d).ok(),
+        _ => time::duration_millis(v).map(chrono::Duration::milliseconds),
+    };
+    let instant = |v: &Sp00kyValue| functions::parse_datetime(v).map(|t| t.to_utc());
+    match (op, l, r) {
+        (BinaryOp::Add, Datetime(t), d) | (BinaryOp::Add, d, Datetime(t)) => {
+            t.checked_add_signed(span(d)?).map(Datetime)
+        }
+        (BinaryOp::Sub, Datetime(t), other) => match span(other) {
+            Some(d) => t.checked_sub_signed(d).map(Datetime),
+            None => (*t - instant(other)?).to_std().ok().map(Duration),
+        },
+        (BinaryOp::Sub, other, Datetime(u)) if span(other).is_none() => {
+            (instant(other)? - *u).to_std().ok().map(Duration)
+        }
+        _ => None,
+    }
+}
+
+fn binary(op: BinaryOp, l: &Sp00kyValue, r: &Sp00kyValue) -> Sp00kyValue {
+    use Sp00kyValue::{Array, Datetime, Duration, Float, Int, Null, Str};
+    let time_like = |v: &Sp00kyValue| matches!(v, Str(_) | Datetime(_) | Duration(_));
+    match op {
+        BinaryOp::Eq => Sp00kyValue::Bool(compare(l, r) == Ordering::Equal),
+        BinaryOp::Neq => Sp00kyValue::Bool(compare(l, r) != Ordering::Equal),
+        // NONE is not ordered against values.
+        BinaryOp::Lt | BinaryOp::Lte | BinaryOp::Gt | BinaryOp::Gte if l.is_null() || r.is_null() => Null,
+        BinaryOp::Lt => Sp00kyValue::Bool(compare(l, r) == Ordering::Less),
+        BinaryOp::Lte => Sp00kyValue::Bool(compare(l, r) != Ordering::Greater),
+        BinaryOp::Gt => Sp00kyValue::Bool(compare(l, r) == Ordering::Greater),
+        BinaryOp::Gte => Sp00kyValue::Bool(compare(l, r) != Ordering::Less),
+        BinaryOp::Add | BinaryOp::Sub if time_like(l) && time_like(r) => {
+            time_arithmetic(op, l, r).unwrap_or_else(|| match (op, l, r) {
+                (BinaryOp::Add, Str(a), Str(b)) => Str(format!("{a}{b}").into()),
+                _ => Null,
+            })
+        }
+        // Decimals stay exact against decimals and integers.
+        BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod if decimal_operands(l, r).is_some() => {
+            let (a, b) = decimal_operands(l, r).unwrap_or_default();
+            let result = match op {
+                BinaryOp::Sub => a.checked_sub(b),
+                BinaryOp::Mul => a.checked_mul(b),
+                BinaryOp::Div => a.checked_div(b),
+                _ => a.checked_rem(b),
+            };
+            result.map(Sp00kyValue::Decimal).unwrap_or(Null)
+        }
+        BinaryOp::Add => match (l, r) {
+            (Array(a), Array(b)) => Array(a.iter().chain(b).cloned().collect()),
+            _ => add_numbers(l, r),
+        },
+        BinaryOp::Sub => match (l, r) {
+            (Int(a), Int(b)) => a.checked_sub(*b).map(Int).unwrap_or(Float(*a as f64 - *b as f64)),
+            _ => float_op(l, r, |a, b| a - b),
+        },
+        BinaryOp::Mul => match (l, r) {
+            (Int(a), Int(b)) => a.checked_mul(*b).map(Int).unwrap_or(Float(*a as f64 * *b as f64)),
+            _ => float_op(l, r, |a, b| a * b),
+        },
+        // Division by zero has no value.
+        BinaryOp::Div => match (l, r) {
+            (_, Int(0)) => Null,
+            (_, Float(b)) if *b == 0.0 => Null,
+            (Int(a), Int(b)) if a.checked_rem(*b) == Some(0) => {
+                a.checked_div(*b).map_or_else(|| float_op(l, r, |a, b| a / b), Int)
+            }
+            _ => float_op(l, r, |a, b| a / b),
+        },
+        BinaryOp::Mod => match (l, r) {
+            (_, Int(0)) => Null,
+            (_, Float(b)) if *b == 0.0 => Null,
+            (Int(a), Int(b)) => a.checked_rem(*b).map(Int).unwrap_or(Null),
+            _ => float_op(l, r, |a, b| a % b),
+        },
+        BinaryOp::And | BinaryOp::Or => unreachable!("short-circuited in Expr::eval"),
+    }
+}
+
+fn float_op(l: &Sp00kyValue, r: &Sp00kyValue, f: impl Fn(f64, f64) -> f64) -> Sp00kyValue {
+    match (l, r) {
+        (
+            Sp00kyValue::Int(_) | Sp00kyValue::Float(_) | Sp00kyValue::Decimal(_),
+            Sp00kyValue::Int(_) | Sp00kyValue::Float(_) | Sp00kyValue::Decimal(_),
+        ) => {
+            Sp00kyValue::Float(f(l.as_f64().unwrap_or(0.0), r.as_f64().unwrap_or(0.0)))
+        }
+        _ => Sp00kyValue::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(expr: serde_json::Value, row: serde_json::Value) -> serde_json::Value {
+        let expr: Expr = serde_json::from_value(expr).expect("valid expr");
+        expr.eval(Some(&Sp00kyValue::from(row)), None).into()
+    }
+
+    #[test]
+    fn arithmetic_keeps_integers_when_exact() {
+        let total = json!({ "type": "binary", "op": "mul",
+            "left": { "type": "field", "path": "price" },
+            "right": { "type": "field", "path": "qty" } });
+        assert_eq!(eval(total.clone(), json!({"price": 3, "qty": 4})), json!(12));
+        assert_eq!(eval(total, json!({"price": 2.5, "qty": 4})), json!(10.0));
+
+        let ratio = json!({ "type": "binary", "op": "div",
+            "left": { "type": "field", "path": "a" },
+            "right": { "type": "field", "path": "b" } });
+        assert_eq!(eval(ratio.clone(), json!({"a": 6, "b": 3})), json!(2));
+        assert_eq!(eval(ratio.clone(), json!({"a": 1, "b": 2})), json!(0.5));
+        assert_eq!(eval(ratio.clone(), json!({"a": 1, "b": 0})), json!(null));
+        assert_eq!(eval(ratio, json!({"a": i64::MIN, "b": -1})), json!(-(i64::MIN as f64)));
+    }
+
+    #[test]
+    fn missing_fields_and_type_errors_are_none() {
+        let sum = json!({ "type": "binary", "op": "add",
+            "left": { "type": "field", "path": "a" },
+            "right": { "type": "value", "value": 1 } });
+        assert_eq!(eval(sum.clone(), json!({})), json!(null));
+        assert_eq!(eval(sum, json!({"a": true})), json!(null));
+    }
+
+    #[test]
+    fn calls_and_comparisons() {
+        let long_title = json!({ "type": "binary", "op": "gt",
+            "left": { "type": "call", "func": "string::len",
+                      "args": [{ "type": "field", "path": "title" }] },
+            "right": { "type": "value", "value": 3 } });
+        assert_eq!(eval(long_title.clone(), json!({"title": "spooky"})), json!(true));
+        assert_eq!(eval(long_title, json!({"title": "boo"})), json!(false));
+
+        let mixed = json!({ "type": "binary", "op": "eq",
+            "left": { "type": "value", "value": 1 },
+            "right": { "type": "value", "value": 1.0 } });
+        assert_eq!(eval(mixed, json!({})), json!(true));
+    }
+
+    #[test]
+    fn params_resolve_from_context() {
+        let expr = Expr::Param { name: "parent.user".into() };
+        let params = Sp00kyValue::from(json!({"user": "user:1"}));
+        assert_eq!(expr.eval(None, Some(&params)), Sp00kyValue::Str("user:1".into()));
+    }
+
+    #[test]
+    fn time_now_reads_the_clock() {
+        let recent = json!({ "type": "binary", "op": "gt",
+            "left": { "type": "field", "path": "created_at" },
+            "right": { "type": "binary", "op": "sub",
+                       "left": { "type": "call", "func": "time::now" },
+                       "right": { "type": "value", "value": "1d" } } });
+        let expr: Expr = serde_json::from_value(recent).unwrap();
+        assert!(expr.uses_clock());
+        let row = Sp00kyValue::from(json!({"created_at": "2024-03-01T12:00:00Z"}));
+        let at = |now: &str| {
+            let now = time::datetime_millis(&Sp00kyValue::Str(now.into()));
+            expr.matches_at(Some(&row), None, now)
+        };
+        assert!(at("2024-03-02T11:59:59Z"));
+        assert!(!at("2024-03-02T12:00:00Z"));
+        // Without a clock, `time::now()` is NONE.
+        assert!(!expr.matches(Some(&row), None));
+    }
+
+    #[test]
+    fn datetime_and_duration_arithmetic() {
+        let op = |op: &str, l: &str, r: &str| {
+            eval(json!({ "type": "binary", "op": op,
+                "left": { "type": "value", "value": l },
+                "right": { "type": "value", "value": r } }), json!({}))
+        };
+        assert_eq!(op("add", "2024-02-28T23:00:00Z", "2h"), json!("2024-02-29T01:00:00.000Z"));
+        assert_eq!(op("sub", "2024-03-01T00:00:00Z", "1w"), json!("2024-02-23T00:00:00.000Z"));
+        assert_eq!(op("sub", "2024-01-02T01:30:00Z", "2024-01-01T00:00:00Z"), json!("1d1h30m"));
+        // Plain strings compare as strings, not as the times they denote.
+        assert_eq!(op("lt", "90m", "2h"), json!(false));
+        assert_eq!(op("eq", "1d", "24h"), json!(false));
+        let typed = |op: BinaryOp, l: Sp00kyValue, r: &str| binary(op, &l, &Sp00kyValue::Str(r.into()));
+        let minutes = |m: u64| Sp00kyValue::Duration(std::time::Duration::from_secs(60 * m));
+        assert_eq!(typed(BinaryOp::Lt, minutes(90), "2h"), Sp00kyValue::Bool(true));
+        // Same instant, different offsets.
+        let midnight = Sp00kyValue::Datetime("2024-01-01T00:00:00Z".parse().unwrap());
+        assert_eq!(typed(BinaryOp::Eq, midnight, "2024-01-01T01:00:00+01:00"), Sp00kyValue::Bool(true));
+        assert_eq!(op("add", "ab", "cd"), json!("abcd"));
+    }
+
+    #[test]
+    fn typed_values_compare_with_their_strings() {
+        let at = Sp00kyValue::Datetime("2024-01-01T00:00:00Z".parse().unwrap());
+        let later = Sp00kyValue::Str("2024-01-01T01:00:00+00:00".into());
+        assert_eq!(binary(BinaryOp::Lt, &at, &later), Sp00kyValue::Bool(true));
+        let hour = Sp00kyValue::Duration(std::time::Duration::from_secs(3600));
+        assert_eq!(binary(BinaryOp::Eq, &hour, &Sp00kyValue::Str("60m".into())), Sp00kyValue::Bool(true));
+        let an_hour_later = Sp00kyValue::Datetime("2024-01-01T01:00:00Z".parse().unwrap());
+        assert_eq!(binary(BinaryOp::Add, &at, &hour), an_hour_later);
+        assert_eq!(binary(BinaryOp::Add, &hour, &at), an_hour_later);
+        assert_eq!(binary(BinaryOp::Add, &at, &Sp00kyValue::Str("1h".into())), an_hour_later);
+        assert_eq!(binary(BinaryOp::Sub, &an_hour_later, &hour), at);
+        assert_eq!(binary(BinaryOp::Sub, &an_hour_later, &at), hour);
+        // Negative durations have no typed form.
+        assert_eq!(binary(BinaryOp::Sub, &at, &an_hour_later), Sp00kyValue::Str("-1h".into()));
+
+        // Typed values compare exactly, below the millisecond.
+        let nanos = |n| Sp00kyValue::Duration(std::time::Duration::from_nanos(n));
+        assert_eq!(binary(BinaryOp::Lt, &nanos(1), &nanos(2)), Sp00kyValue::Bool(true));
+        let instant = |n| Sp00kyValue::Datetime(chrono::DateTime::from_timestamp_nanos(n));
+        assert_eq!(binary(BinaryOp::Eq, &instant(1), &instant(2)), Sp00kyValue::Bool(false));
+        assert_eq!(binary(BinaryOp::Lt, &instant(1), &instant(2)), Sp00kyValue::Bool(true));
+        let micros = Sp00kyValue::Duration(std::time::Duration::from_micros(60_000_500));
+        assert_eq!(binary(BinaryOp::Lt, &micros, &Sp00kyValue::Str("5m500µs".into())), Sp00kyValue::Bool(true));
+        assert_eq!(binary(BinaryOp::Eq, &micros, &Sp00kyValue::Str("1m500µs".into())), Sp00kyValue::Bool(true));
+
+        // Strings equal to a typed value hash like it, so joins on them match.
+        use crate::eval::value_ops::hash_value;
+        let offset = Sp00kyValue::Str("2024-01-01T02:00:00+02:00".into());
+        assert_eq!(binary(BinaryOp::Eq, &at, &offset), Sp00kyValue::Bool(true));
+        assert_eq!(hash_value(&at), hash_value(&offset));
+        let seconds = Sp00kyValue::Duration(std::time::Duration::from_secs(90));
+        assert_eq!(hash_value(&seconds), hash_value(&Sp00kyValue::Str("90s".into())));
+
+        let link = Sp00kyValue::RecordId("user:ann".into());
+        assert_eq!(binary(BinaryOp::Eq, &link, &Sp00kyValue::Str("user:ann".into())), Sp00kyValue::Bool(true));
+
+        let price = Sp00kyValue::Decimal("0.1".parse().unwrap());
+        let sum = binary(BinaryOp::Add, &price, &Sp00kyValue::Decimal("0.2".parse().unwrap()));
+        assert_eq!(sum, Sp00kyValue::Decimal("0.3".parse().unwrap()));
+        assert_eq!(binary(BinaryOp::Mul, &price, &Sp00kyValue::Int(3)), sum);
+        assert_eq!(binary(BinaryOp::Eq, &Sp00kyValue::Decimal(5.into()), &Sp00kyValue::Int(5)), Sp00kyValue::Bool(true));
+    }
+
+    #[test]
+    fn reports_unknown_functions() {
+        let expr = Expr::Call {
+            func: "string::uppercase".into(),
+            args: vec![Expr::Call { func: "nope::nope".into(), args: vec![] }],
+        };
+        assert_eq!(expr.unknown_function(), Some("nope::nope"));
+    }
+}
@@ -0,0 +1,407 @@
+//! Scalar functions callable from expressions: a subset of SurrealQL's
+//! standard library (`string::*`, `array::*`, `math::*`, `time::*`,
+//! `type::*`, `count`).
+//!
+//! Functions never fail: arguments of the wrong type evaluate to `Null`.
+
+use super::value_ops::sort_cmp;
+use crate::types::Sp00kyValue;
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+
+/// Largest string `string::repeat` builds, in bytes; longer results are `Null`.
+const MAX_REPEAT_LEN: usize = 1 << 20;
+
+/// A builtin function over already-evaluated arguments.
+pub type Builtin = fn(&[Sp00kyValue]) -> Sp00kyValue;
+
+/// Look up a builtin by its (case-insensitive) SurrealQL name.
+pub fn lookup(name: &str) -> Option<Builtin> {
+    let f: Builtin = match name.to_ascii_lowercase().as_str() {
+        "count" => |a| match a.first() {
+            None => Sp00kyValue::Int(1),
+            Some(Sp00kyValue::Array(items)) => Sp00kyValue::Int(items.len() as i64),
+            Some(v) => Sp00kyValue::Int(truthy(v) as i64),
+        },
+
+        "string::uppercase" => |a| map_str(a, |s| s.to_uppercase().into()),
+        "string::lowercase" => |a| map_str(a, |s| s.to_lowercase().into()),
+        "string::trim" => |a| map_str(a, |s| s.trim().to_string().into()),
+        "string::reverse" => |a| map_str(a, |s| s.chars().rev().collect::<String>().into()),
+        "string::len" => |a| map_str(a, |s| Sp00kyValue::Int(s.chars().count() as i64)),
+        "string::concat" => |a| Sp00kyValue::Str(a.iter().map(to_text).collect()),
+        "string::join" => |a| match a.split_first() {
+            Some((Sp00kyValue::Str(sep), rest)) => {
+                Sp00kyValue::from(rest.iter().map(to_text).collect::<Vec<_>>().join(sep))
+            }
+            _ => Sp00kyValue::Null,
+        },
+        "string::contains" => |a| match (a.first(), a.get(1)) {
+            (Some(Sp00kyValue::Str(s)), Some(Sp00kyValue::Str(sub))) => {
+                Sp00kyValue::Bool(s.contains(sub.as_str()))
+            }
+            _ => Sp00kyValue::Null,
+        },
+        "string::starts_with" => |a| match (a.first(), a.get(1)) {
+            (Some(Sp00kyValue::Str(s)), Some(Sp00kyValue::Str(p))) => {
+                Sp00kyValue::Bool(s.starts_with(p.as_str()))
+            }
+            _ => Sp00kyValue::Null,
+        },
+        "string::ends_with" => |a| match (a.first(), a.get(1)) {
+            (Some(Sp00kyValue::Str(s)), Some(Sp00kyValue::Str(p))) => {
+                Sp00kyValue::Bool(s.ends_with(p.as_str()))
+            }
+            _ => Sp00kyValue::Null,
+        },
+        "string::replace" => |a| match (a.first(), a.get(1), a.get(2)) {
+            (Some(Sp00kyValue::Str(s)), Some(Sp00kyValue::Str(from)), Some(Sp00kyValue::Str(to))) => {
+                Sp00kyValue::from(s.replace(from.as_str(), to))
+            }
+            _ => Sp00kyValue::Null,
+        },
+        "string::repeat" => |a| match (a.first(), a.get(1).and_then(|n| n.as_i64())) {
+            (Some(Sp00kyValue::Str(s)), Some(n)) if n >= 0 => match usize::try_from(n)
+                .ok()
+                .and_then(|n| Some((n, s.len().checked_mul(n)?)))
+            {
+                Some((n, len)) if len <= MAX_REPEAT_LEN => Sp00kyValue::from(s.repeat(n)),
+                _ => Sp00kyValue::Null,
+            },
+            _ => Sp00kyValue::Null,
+        },
+        "string::split" => |a| match (a.first(), a.get(1)) {
+            (Some(Sp00kyValue::Str(s)), Some(Sp00kyValue::Str(sep))) => Sp00kyValue::Array(
+                s.split(sep.as_str()).map(|p| Sp00kyValue::Str(p.into())).collect(),
+            ),
+            _ => Sp00kyValue::Null,
+        },
+        "string::slice" => |a| match a.first() {
+            Some(Sp00kyValue::Str(s)) => {
+                let chars: Vec<char> = s.chars().collect();
+                let (start, end) = slice_bounds(chars.len(), a.get(1), a.get(2));
+                Sp00kyValue::Str(chars[start..end].iter().copied().collect())
+            }
+            _ => Sp00kyValue::Null,
+        },
+
+        "array::len" => |a| map_array(a, |items| Sp00kyValue::Int(items.len() as i64)),
+        "array::first" => |a| map_array(a, |items| items.first().cloned().unwrap_or_default()),
+        "array::last" => |a| map_array(a, |items| items.last().cloned().unwrap_or_default()),
+        "array::reverse" => |a| map_array(a, |items| items.iter().rev().cloned().collect::<Vec<_>>().into()),
+        "array::sort" => |a| {
+            let descending = matches!(a.get(1), Some(Sp00kyValue::Bool(false)))
+                || matches!(a.get(1), Some(Sp00kyValue::Str(s)) if s.eq_ignore_ascii_case("desc"));
+            map_array(a, |items| {
+                let mut sorted = items.to_vec();
+                sorted.sort_by(|x, y| {
+                    let ord = sort_cmp(x, y);
+                    if descending { ord.reverse() } else { ord }
+                });
+                sorted.into()
+            })
+        },
+        "array::distinct" => |a| {
+            map_array(a, |items| {
+                let mut out: Vec<Sp00kyValue> = Vec::new();
+                for item in items {
+                    if !out.iter().any(|o| sort_cmp(o, item) == Ordering::Equal) {
+                        out.push(item.clone());
+                    }
+                }
+                out.into()
+            })
+        },
+        "array::flatten" => |a| {
+            map_array(a, |items| {
+                items
+                    .iter()
+                    .flat_map(|item| match item {
+                        Sp00kyValue::Array(inner) => inner.clone(),
+                        other => vec![other.clone()],
+                    })
+                    .collect::<Vec<_>>()
+                    .into()
+            })
+        },
+        "array::includes" => |a| match (a.first(), a.get(1)) {
+            (Some(Sp00kyValue::Array(items)), Some(needle)) => {
+                Sp00kyValue::Bool(items.iter().any(|i| sort_cmp(i, needle) == Ordering::Equal))
+            }
+            _ => Sp00kyValue::Null,
+        },
+        "array::join" => |a| match (a.first(), a.get(1)) {
+            (Some(Sp00kyValue::Array(items)), Some(Sp00kyValue::Str(sep))) => {
+                Sp00kyValue::from(items.iter().map(to_text).collect::<Vec<_>>().join(sep))
+            }
+            _ => Sp00kyValue::Null,
+        },
+        "array::slice" => |a| match a.first() {
+            Some(Sp00kyValue::Array(items)) => {
+                let (start, end) = slice_bounds(items.len(), a.get(1), a.get(2));
+                Sp00kyValue::Array(items[start..end].to_vec())
+            }
+            _ => Sp00kyValue::Null,
+        },
+
+        "math::abs" => |a| map_num(a, |n| n.abs(), |i| i.checked_abs()),
+        "math::ceil" => |a| map_num(a, f64::ceil, Some),
+        "math::floor" => |a| map_num(a, f64::floor, Some),
+        "math::round" => |a| map_num(a, f64::round, Some),
+        "math::sqrt" => |a| match a.first().and_then(|v| v.as_f64()) {
+            Some(n) if n >= 0.0 => Sp00kyValue::Float(n.sqrt()),
+            _ => Sp00kyValue::Null,
+        },
+        "math::pow" => |a| match (a.first(), a.get(1)) {
+            (Some(Sp00kyValue::Int(b)), Some(Sp00kyValue::Int(e))) if *e >= 0 => u32::try_from(*e)
+                .ok()
+                .and_then(|e| b.checked_pow(e))
+                .map(Sp00kyValue::Int)
+                .unwrap_or_else(|| Sp00kyValue::Float((*b as f64).powf(*e as f64))),
+            (Some(b), Some(e)) => match (b.as_f64(), e.as_f64()) {
+                (Some(b), Some(e)) => Sp00kyValue::Float(b.powf(e)),
+                _ => Sp00kyValue::Null,
+            },
+            _ => Sp00kyValue::Null,
+        },
+        "math::sum" => |a| map_array(a, |items| numbers(items).fold(Sp00kyValue::Int(0), |acc, n| add_numbers(&acc, n))),
+        "math::mean" => |a| {
+            map_array(a, |items| {
+                let values: Vec<f64> = numbers(items).filter_map(|n| n.as_f64()).collect();
+                if values.is_empty() {
+                    return Sp00kyValue::Null;
+                }
+                Sp00kyValue::Float(values.iter().sum::<f64>() / values.len() as f64)
+            })
+        },
+        "math::max" => |a| map_array(a, |items| numbers(items).max_by(|x, y| sort_cmp(x, y)).cloned().unwrap_or_default()),
+        "math::min" => |a| map_array(a, |items| numbers(items).min_by(|x, y| sort_cmp(x, y)).cloned().unwrap_or_default()),
+
+        // The clock is supplied by `Expr::eval_at`; without one there is no
+        // current time.
+        "time::now" => |_| Sp00kyValue::Null,
+        "time::year" => |a| map_time(a, |t| t.year() as i64),
+        "time::month" => |a| map_time(a, |t| t.month() as i64),
+        "time::day" => |a| map_time(a, |t| t.day() as i64),
+        "time::hour" => |a| map_time(a, |t| t.hour() as i64),
+        "time::minute" => |a| map_time(a, |t| t.minute() as i64),
+        "time::second" => |a| map_time(a, |t| t.second() as i64),
+        "time::wday" => |a| map_time(a, |t| t.weekday().number_from_monday() as i64),
+        "time::yday" => |a| map_time(a, |t| t.ordinal() as i64),
+        "time::unix" => |a| map_time(a, |t| t.timestamp()),
+        "time::format" => |a| match (a.first().and_then(parse_datetime), a.get(1)) {
+            (Some(t), Some(Sp00kyValue::Str(fmt))) => {
+                use std::fmt::Write;
+                let mut out = String::new();
+                // Invalid format strings error while rendering.
+                match write!(out, "{}", t.format(fmt)) {
+                    Ok(()) => Sp00kyValue::from(out),
+                    Err(_) => Sp00kyValue::Null,
+                }
+            }
+            _ => Sp00kyValue::Null,
+        },
+
+        "type::string" => |a| a.first().map(|v| Sp00kyValue::from(to_text(v))).unwrap_or_default(),
+        "type::int" => |a| match a.first() {
+            Some(Sp00kyValue::Int(i)) => Sp00kyValue::Int(*i),
+            Some(Sp00kyValue::Float(f)) if f.is_finite() => Sp00kyValue::Int(f.trunc() as i64),
+            Some(Sp00kyValue::Str(s)) => s.trim().parse().map(Sp00kyValue::Int).unwrap_or_default(),
+            _ => Sp00kyValue::Null,
+        },
+        "type::float" => |a| match a.first() {
+            Some(Sp00kyValue::Str(s)) => s.trim().parse().map(Sp00kyValue::Float).unwrap_or_default(),
+            Some(v) => v.as_f64().map(Sp00kyValue::Float).unwrap_or_default(),
+            None => Sp00kyValue::Null,
+        },
+        "type::bool" => |a| a.first().map(|v| Sp00kyValue::Bool(truthy(v))).unwrap_or_default(),
+
+        _ => return None,
+    };
+    Some(f)
+}
+
+/// SurrealQL truthiness: `NONE`, `false`, `0`, `""`, `[]`, `{}`, the zero
+/// duration and empty bytes are falsy.
+pub fn truthy(value: &Sp00kyValue) -> bool {
+    match value {
+        Sp00kyValue::Null => false,
+        Sp00kyValue::Bool(b) => *b,
+        Sp00kyValue::Int(i) => *i != 0,
+        Sp00kyValue::Float(f) => *f != 0.0,
+        Sp00kyValue::Str(s) => !s.is_empty(),
+        Sp00kyValue::Array(items) => !items.is_empty(),
+        Sp00kyValue::Object(map) => !map.is_empty(),
+        Sp00kyValue::Decimal(d) => !d.is_zero(),
+        Sp00kyValue::Duration(d) => !d.is_zero(),
+        Sp00kyValue::Bytes(bytes) => !bytes.is_empty(),
+        Sp00kyValue::Datetime(_) | Sp00kyValue::RecordId(_) | Sp00kyValue::Uuid(_) => true,
+    }
+}
+
+/// Sum of two numbers, staying integral unless it overflows and exact
+/// when decimals meet integers.
+pub(crate) fn add_numbers(a: &Sp00kyValue, b: &Sp00kyValue) -> Sp00kyValue {
+    if let Some((x, y)) = decimal_operands(a, b) {
+        if let Some(sum) = x.checked_add(y) {
+            return Sp00kyValue::Decimal(sum);
+        }
+    }
+    match (a, b) {
+        (Sp00kyValue::Int(x), Sp00kyValue::Int(y)) => x
+            .checked_add(*y)
+            .map(Sp00kyValue::Int)
+            .unwrap_or(Sp00kyValue::Float(*x as f64 + *y as f64)),
+        _ => match (a.as_f64(), b.as_f64()) {
+            (Some(x), Some(y)) => Sp00kyValue::Float(x + y),
+            _ => Sp00kyValue::Null,
+        },
+    }
+}
+
+/// Both operands as decimals, when one is a `Decimal` and the other a
+/// `Decimal` or `Int`. Floats are inexact, so mixing them in goes through
+/// `f64` instead.
+pub(crate) fn decimal_operands(a: &Sp00kyValue, b: &Sp00kyValue) -> Option<(Decimal, Decimal)> {
+    let exact = |v: &Sp00kyValue| match v {
+        Sp00kyValue::Decimal(d) => Some(*d),
+        Sp00kyValue::Int(i) => Some(Decimal::from(*i)),
+        _ => None,
+    };
+    match (a, b) {
+        (Sp00kyValue::Decimal(_), _) | (_, Sp00kyValue::Decimal(_)) => Some((exact(a)?, exact(b)?)),
+        _ => None,
+    }
+}
+
+/// A datetime, or an RFC 3339 datetime string.
+pub(crate) fn parse_datetime(value: &Sp00kyValue) -> Option<DateTime<FixedOffset>> {
+    match value {
+        Sp00kyValue::Datetime(t) => Some(t.fixed_offset()),
+        _ => DateTime::parse_from_rfc3339(value.as_str()?).ok(),
+    }
+}
+
+/// Text form used when concatenating: strings as-is, others as JSON.
+fn to_text(value: &Sp00kyValue) -> String {
+    match value {
+        Sp00kyValue::Str(s) => s.to_string(),
+        Sp00kyValue::Null => String::new(),
+        other => serde_json::Value::from(other.clone()).to_string(),
+    }
+}
+
+fn numbers(items: &[Sp00kyValue]) -> impl Iterator<Item = &Sp00kyValue> {
+    items
+        .iter()
+        .filter(|v| matches!(v, Sp00kyValue::Int(_) | Sp00kyValue::Float(_) | Sp00kyValue::Decimal(_)))
+}
+
+/// `[start, end)` of a slice from an optional start and length; a negative
+/// start counts from the end.
+fn slice_bounds(len: usize, start: Option<&Sp00kyValue>, count: Option<&Sp00kyValue>) -> (usize, usize) {
+    let start = match start.and_then(|s| s.as_i64()).unwrap_or(0) {
+        s if s < 0 => len.saturating_sub(s.unsigned_abs() as usize),
+        s => (s as usize).min(len),
+    };
+    let end = match count.and_then(|c| c.as_i64()) {
+        Some(c) if c >= 0 => start.saturating_add(c as usize).min(len),
+        Some(c) => len.saturating_sub(c.unsigned_abs() as usize).max(start),
+        None => len,
+    };
+    (start, end)
+}
+
+fn map_str(args: &[Sp00kyValue], f: impl Fn(&str) -> Sp00kyValue) -> Sp00kyValue {
+    match args.first() {
+        Some(Sp00kyValue::Str(s)) => f(s),
+        _ => Sp00kyValue::Null,
+    }
+}
+
+fn map_array(args: &[Sp00kyValue], f: impl Fn(&[Sp00kyValue]) -> Sp00kyValue) -> Sp00kyValue {
+    match args.first() {
+        Some(Sp00kyValue::Array(items)) => f(items),
+        _ => Sp00kyValue::Null,
+    }
+}
+
+fn map_num(
+    args: &[Sp00kyValue],
+    float: impl Fn(f64) -> f64,
+    int: impl Fn(i64) -> Option<i64>,
+) -> Sp00kyValue {
+    match args.first() {
+        Some(Sp00kyValue::Int(i)) => int(*i).map(Sp00kyValue::Int).unwrap_or_default(),
+        Some(Sp00kyValue::Float(f)) => Sp00kyValue::Float(float(*f)),
+        _ => Sp00kyValue::Null,
+    }
+}
+
+fn map_time(args: &[Sp00kyValue], f: impl Fn(DateTime<FixedOffset>) -> i64) -> Sp00kyValue {
+    args.first()
+        .and_then(parse_datetime)
+        .map(|t| Sp00kyValue::Int(f(t)))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, args: serde_json::Value) -> serde_json::Value {
+        let args: Vec<Sp00kyValue> = match Sp00kyValue::from(args) {
+            Sp00kyValue::Array(items) => items,
+            _ => unreachable!(),
+        };
+        lookup(name).expect("known function")(&args).into()
+    }
+
+    #[test]
+    fn string_functions() {
+        assert_eq!(call("string::uppercase", json!(["ada"])), json!("ADA"));
+        assert_eq!(call("STRING::LEN", json!(["héllo"])), json!(5));
+        assert_eq!(call("string::concat", json!(["a", 1, "b"])), json!("a1b"));
+        assert_eq!(call("string::slice", json!(["spooky", 1, 3])), json!("poo"));
+        assert_eq!(call("string::slice", json!(["spooky", -2])), json!("ky"));
+        assert_eq!(call("string::uppercase", json!([5])), json!(null));
+    }
+
+    #[test]
+    fn string_repeat_is_capped() {
+        assert_eq!(call("string::repeat", json!(["ab", 3])), json!("ababab"));
+        assert_eq!(call("string::repeat", json!(["ab", i64::MAX])), json!(null));
+        assert_eq!(call("string::repeat", json!(["", i64::MAX])), json!(""));
+        assert_eq!(call("string::repeat", json!(["ab", -1])), json!(null));
+    }
+
+    #[test]
+    fn array_and_math_functions() {
+        assert_eq!(call("count", json!([[1, 2, 3]])), json!(3));
+        assert_eq!(call("array::distinct", json!([[1, 2, 1, "1"]])), json!([1, 2, "1"]));
+        assert_eq!(call("array::sort", json!([[3, 1, 2], "desc"])), json!([3, 2, 1]));
+        assert_eq!(call("math::sum", json!([[1, 2, 3.5]])), json!(6.5));
+        assert_eq!(call("math::sum", json!([[1, 2]])), json!(3));
+        assert_eq!(call("math::max", json!([[1, 7, 3]])), json!(7));
+        assert_eq!(call("math::round", json!([2.5])), json!(3.0));
+        assert_eq!(call("math::pow", json!([2, 10])), json!(1024));
+    }
+
+    #[test]
+    fn time_functions() {
+        let t = "2024-02-29T13:45:10Z";
+        assert_eq!(call("time::year", json!([t])), json!(2024));
+        assert_eq!(call("time::yday", json!([t])), json!(60));
+        assert_eq!(call("time::wday", json!([t])), json!(4));
+        assert_eq!(call("time::unix", json!(["1970-01-01T00:01:00Z"])), json!(60));
+        assert_eq!(call("time::format", json!([t, "%Y/%m/%d"])), json!("2024/02/29"));
+        assert_eq!(call("time::year", json!(["not a date"])), json!(null));
+    }
+
+    #[test]
+    fn unknown_function_is_not_found() {
+        assert!(lookup("string::nope").is_none());
+    }
+}
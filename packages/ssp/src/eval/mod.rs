@@ -1,3 +1,9 @@
+pub mod expr;
+pub mod functions;
+pub mod time;
 pub mod value_ops;
 
-pub use value_ops::{compare_values, hash_value, normalize_record_id, resolve_field};
+pub use expr::{BinaryOp, Expr, UnaryOp};
+pub use value_ops::{
+    compare_values, hash_value, normalize_record_id, resolve_field, sort_cmp, OrderedValue,
+};
@@ -0,0 +1,113 @@
+//! Datetime and duration values.
+//!
+//! Datetimes are RFC 3339 strings and durations are SurrealQL duration
+//! strings (`1d`, `2h30m`, `500ms`); both are handled at millisecond
+//! resolution. The clock behind `time::now()` is Unix milliseconds.
+
+use super::functions::parse_datetime;
+use crate::types::Sp00kyValue;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// Duration units, largest first, in milliseconds.
+const UNITS: [(&str, i64); 7] = [
+    ("y", 365 * 86_400_000),
+    ("w", 7 * 86_400_000),
+    ("d", 86_400_000),
+    ("h", 3_600_000),
+    ("m", 60_000),
+    ("s", 1_000),
+    ("ms", 1),
+];
+
+/// Parse a duration string (`1d`, `2h30m`, `-15m`) into milliseconds.
+pub fn parse_duration(text: &str) -> Option<i64> {
+    let (sign, mut rest) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text),
+    };
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total: i64 = 0;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        if digits == 0 {
+            return None;
+        }
+        let amount: i64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let (_, millis) = UNITS.iter().find(|(u, _)| *u == &rest[..unit_len])?;
+        total = total.checked_add(amount.checked_mul(*millis)?)?;
+        rest = &rest[unit_len..];
+    }
+    Some(sign * total)
+}
+
+/// Format milliseconds as a duration string, largest units first.
+pub fn format_duration(millis: i64) -> String {
+    if millis == 0 {
+        return "0ms".to_string();
+    }
+    let mut out = String::new();
+    if millis < 0 {
+        out.push('-');
+    }
+    let mut rest = millis.unsigned_abs();
+    for (unit, size) in UNITS {
+        let size = size as u64;
+        if rest >= size {
+            out.push_str(&format!("{}{unit}", rest / size));
+            rest %= size;
+        }
+    }
+    out
+}
+
+/// Format Unix milliseconds as an RFC 3339 UTC datetime.
+pub fn format_datetime(millis: i64) -> Option<String> {
+    DateTime::<Utc>::from_timestamp_millis(millis)
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+}
+
+/// Unix milliseconds of a datetime or datetime string.
+pub fn datetime_millis(value: &Sp00kyValue) -> Option<i64> {
+    parse_datetime(value).map(|t| t.timestamp_millis())
+}
+
+/// Milliseconds of a duration or duration string.
+pub fn duration_millis(value: &Sp00kyValue) -> Option<i64> {
+    match value {
+        Sp00kyValue::Duration(d) => i64::try_from(d.as_millis()).ok(),
+        _ => value.as_str().and_then(parse_duration),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_round_trip() {
+        assert_eq!(parse_duration("1d"), Some(86_400_000));
+        assert_eq!(parse_duration("2h30m"), Some(9_000_000));
+        assert_eq!(parse_duration("500ms"), Some(500));
+        assert_eq!(parse_duration("-15m"), Some(-900_000));
+        assert_eq!(parse_duration("1x"), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(format_duration(9_000_000), "2h30m");
+        assert_eq!(format_duration(8 * 86_400_000 + 1), "1w1d1ms");
+        assert_eq!(format_duration(-60_000), "-1m");
+    }
+
+    #[test]
+    fn datetimes_use_milliseconds() {
+        let t = Sp00kyValue::Str("2024-01-01T00:00:00+01:00".into());
+        assert_eq!(datetime_millis(&t), Some(1_704_063_600_000));
+        assert_eq!(
+            format_datetime(1_704_063_600_000).as_deref(),
+            Some("2023-12-31T23:00:00.000Z")
+        );
+    }
+}
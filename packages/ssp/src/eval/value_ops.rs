@@ -1,4 +1,8 @@
+use crate::types::value::parse_duration;
 use crate::types::{Path, Sp00kyValue};
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use ssp_protocol::snapshot_hash::{canonical_datetime, canonical_duration};
 use std::cmp::Ordering;
 
 /// Resolve a nested value from a Sp00kyValue by following a Path.
@@ -25,16 +29,61 @@ fn compare_values_inner(a: &Sp00kyValue, b: &Sp00kyValue) -> Ordering {
         (Sp00kyValue::Null, Sp00kyValue::Null) => Ordering::Equal,
         (Sp00kyValue::Null, _) => Ordering::Less,
         (_, Sp00kyValue::Null) => Ordering::Greater,
-        (Sp00kyValue::Int(a), Sp00kyValue::Int(b)) => a.cmp(b),
-        (Sp00kyValue::Float(a), Sp00kyValue::Float(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
-        (Sp00kyValue::Int(a), Sp00kyValue::Float(b)) => (*a as f64).partial_cmp(b).unwrap_or(Ordering::Equal),
-        (Sp00kyValue::Float(a), Sp00kyValue::Int(b)) => a.partial_cmp(&(*b as f64)).unwrap_or(Ordering::Equal),
-        (Sp00kyValue::Str(a), Sp00kyValue::Str(b)) => a.cmp(b),
-        (Sp00kyValue::Bool(a), Sp00kyValue::Bool(b)) => a.cmp(b),
-        _ => Ordering::Equal,
+        _ => scalar_cmp(a, b).unwrap_or(Ordering::Equal),
     }
 }
 
+/// Ordering of two scalars of comparable types, `None` for other pairs.
+///
+/// Numbers compare by value across `Int`/`Float`/`Decimal`, record links
+/// by their `table:key` text like strings. Datetimes, durations and UUIDs
+/// also compare against strings that parse as one.
+pub(crate) fn scalar_cmp(a: &Sp00kyValue, b: &Sp00kyValue) -> Option<Ordering> {
+    use Sp00kyValue::*;
+    Some(match (a, b) {
+        (Int(a), Int(b)) => a.cmp(b),
+        (Float(a), Float(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (Int(a), Float(b)) => (*a as f64).partial_cmp(b).unwrap_or(Ordering::Equal),
+        (Float(a), Int(b)) => a.partial_cmp(&(*b as f64)).unwrap_or(Ordering::Equal),
+        (Decimal(a), Decimal(b)) => a.cmp(b),
+        (Decimal(a), Int(b)) => a.cmp(&rust_decimal::Decimal::from(*b)),
+        (Int(a), Decimal(b)) => rust_decimal::Decimal::from(*a).cmp(b),
+        (Decimal(_), Float(_)) | (Float(_), Decimal(_)) => {
+            a.as_f64()?.partial_cmp(&b.as_f64()?).unwrap_or(Ordering::Equal)
+        }
+        (Str(a) | RecordId(a), Str(b) | RecordId(b)) => a.cmp(b),
+        (Bool(a), Bool(b)) => a.cmp(b),
+        (Datetime(a), Datetime(b)) => a.cmp(b),
+        (Datetime(t), Str(s)) => t.cmp(&parse_utc(s)?),
+        (Str(s), Datetime(t)) => parse_utc(s)?.cmp(t),
+        (Duration(a), Duration(b)) => a.cmp(b),
+        (Duration(d), Str(s)) => d.cmp(&parse_duration(s)?),
+        (Str(s), Duration(d)) => parse_duration(s)?.cmp(d),
+        (Uuid(a), Uuid(b)) => a.cmp(b),
+        (Uuid(u), Str(s)) => u.cmp(&uuid::Uuid::try_parse(s).ok()?),
+        (Str(s), Uuid(u)) => uuid::Uuid::try_parse(s).ok()?.cmp(u),
+        (Bytes(a), Bytes(b)) => a.cmp(b),
+        _ => return None,
+    })
+}
+
+fn parse_utc(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text).ok().map(|t| t.to_utc())
+}
+
+/// The canonical form of a string that `scalar_cmp` compares equal to a
+/// datetime, duration or UUID, if it is not already in that form.
+fn typed_text(text: &str) -> Option<String> {
+    let canonical = if let Some(t) = parse_utc(text) {
+        canonical_datetime(&t)
+    } else if let Some(d) = parse_duration(text) {
+        canonical_duration(d)
+    } else {
+        uuid::Uuid::try_parse(text).ok()?.hyphenated().to_string()
+    };
+    (canonical != text).then_some(canonical)
+}
+
 /// Hash a Sp00kyValue for use in join index lookups.
 pub fn hash_value(value: &Sp00kyValue) -> u64 {
     use std::hash::{Hash, Hasher};
@@ -46,18 +95,182 @@ pub fn hash_value(value: &Sp00kyValue) -> u64 {
         // index lookups need to match across numeric types.
         Sp00kyValue::Int(n) => (*n as f64).to_bits().hash(&mut hasher),
         Sp00kyValue::Float(n) => n.to_bits().hash(&mut hasher),
-        Sp00kyValue::Str(s) => s.hash(&mut hasher),
+        Sp00kyValue::Decimal(d) => d.to_f64().unwrap_or(f64::NAN).to_bits().hash(&mut hasher),
+        // Typed values hash like their canonical string forms, and strings
+        // that compare equal to a typed value hash like its canonical form.
+        Sp00kyValue::Str(s) | Sp00kyValue::RecordId(s) => match typed_text(s) {
+            Some(canonical) => canonical.hash(&mut hasher),
+            None => s.hash(&mut hasher),
+        },
+        Sp00kyValue::Datetime(t) => canonical_datetime(t).hash(&mut hasher),
+        Sp00kyValue::Duration(d) => canonical_duration(*d).hash(&mut hasher),
+        Sp00kyValue::Uuid(u) => u.hyphenated().to_string().hash(&mut hasher),
         Sp00kyValue::Array(_) => 2u8.hash(&mut hasher),
         Sp00kyValue::Object(_) => 3u8.hash(&mut hasher),
+        Sp00kyValue::Bytes(b) => (4u8, b).hash(&mut hasher),
     }
     hasher.finish()
 }
 
-/// Normalize a record ID value (strip table prefix if present in a string).
+/// Total order used for sorting (ORDER BY, min/max).
+///
+/// Unlike `compare_values`, never reports distinct values as equal:
+/// numbers compare exactly across `Int`/`Float` (no rounding through f64),
+/// and values of different types order by type —
+/// null < bool < number < string < duration < datetime < uuid < array <
+/// object < bytes. Record links sort with strings, by their text.
+pub fn sort_cmp(a: &Sp00kyValue, b: &Sp00kyValue) -> Ordering {
+    fn rank(v: &Sp00kyValue) -> u8 {
+        match v {
+            Sp00kyValue::Null => 0,
+            Sp00kyValue::Bool(_) => 1,
+            Sp00kyValue::Int(_) | Sp00kyValue::Float(_) | Sp00kyValue::Decimal(_) => 2,
+            Sp00kyValue::Str(_) | Sp00kyValue::RecordId(_) => 3,
+            Sp00kyValue::Duration(_) => 4,
+            Sp00kyValue::Datetime(_) => 5,
+            Sp00kyValue::Uuid(_) => 6,
+            Sp00kyValue::Array(_) => 7,
+            Sp00kyValue::Object(_) => 8,
+            Sp00kyValue::Bytes(_) => 9,
+        }
+    }
+
+    match (a, b) {
+        (Sp00kyValue::Null, Sp00kyValue::Null) => Ordering::Equal,
+        (Sp00kyValue::Bool(a), Sp00kyValue::Bool(b)) => a.cmp(b),
+        (Sp00kyValue::Int(a), Sp00kyValue::Int(b)) => a.cmp(b),
+        (Sp00kyValue::Float(a), Sp00kyValue::Float(b)) => a.total_cmp(b),
+        // Numerically equal Int/Float still differ; Int sorts first.
+        (Sp00kyValue::Int(a), Sp00kyValue::Float(b)) => cmp_int_float(*a, *b).then(Ordering::Less),
+        (Sp00kyValue::Float(a), Sp00kyValue::Int(b)) => {
+            cmp_int_float(*b, *a).reverse().then(Ordering::Greater)
+        }
+        // Decimals sort after equal Ints and Floats.
+        (Sp00kyValue::Decimal(a), Sp00kyValue::Decimal(b)) => a.cmp(b),
+        (Sp00kyValue::Decimal(_), Sp00kyValue::Int(_) | Sp00kyValue::Float(_)) => {
+            cmp_decimal(a, b).then(Ordering::Greater)
+        }
+        (Sp00kyValue::Int(_) | Sp00kyValue::Float(_), Sp00kyValue::Decimal(_)) => {
+            cmp_decimal(b, a).reverse().then(Ordering::Less)
+        }
+        (Sp00kyValue::Str(a), Sp00kyValue::Str(b)) => a.cmp(b),
+        (Sp00kyValue::RecordId(a), Sp00kyValue::RecordId(b)) => a.cmp(b),
+        // A record link sorts after the string of the same text.
+        (Sp00kyValue::Str(a), Sp00kyValue::RecordId(b)) => a.cmp(b).then(Ordering::Less),
+        (Sp00kyValue::RecordId(a), Sp00kyValue::Str(b)) => a.cmp(b).then(Ordering::Greater),
+        (Sp00kyValue::Duration(a), Sp00kyValue::Duration(b)) => a.cmp(b),
+        (Sp00kyValue::Datetime(a), Sp00kyValue::Datetime(b)) => a.cmp(b),
+        (Sp00kyValue::Uuid(a), Sp00kyValue::Uuid(b)) => a.cmp(b),
+        (Sp00kyValue::Bytes(a), Sp00kyValue::Bytes(b)) => a.cmp(b),
+        (Sp00kyValue::Array(a), Sp00kyValue::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(x, y)| sort_cmp(x, y))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Sp00kyValue::Object(a), Sp00kyValue::Object(b)) => {
+            let mut a: Vec<_> = a.iter().collect();
+            let mut b: Vec<_> = b.iter().collect();
+            a.sort_by(|x, y| x.0.cmp(y.0));
+            b.sort_by(|x, y| x.0.cmp(y.0));
+            a.iter()
+                .zip(&b)
+                .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| sort_cmp(va, vb)))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| a.len().cmp(&b.len()))
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// `Sp00kyValue` ordered by `sort_cmp`, for ordered collections
+/// (min/max multisets, range-join indexes).
+#[derive(Debug, Clone)]
+pub struct OrderedValue(pub Sp00kyValue);
+
+impl PartialEq for OrderedValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrderedValue {}
+
+impl PartialOrd for OrderedValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        sort_cmp(&self.0, &other.0)
+    }
+}
+
+/// Compare a `Decimal` with an `Int` or `Float`: exactly where the float
+/// has a decimal representation, otherwise through `f64` (NaN sorts last).
+fn cmp_decimal(decimal: &Sp00kyValue, number: &Sp00kyValue) -> Ordering {
+    let Sp00kyValue::Decimal(d) = decimal else {
+        return Ordering::Equal;
+    };
+    let exact = match number {
+        Sp00kyValue::Int(i) => Some(rust_decimal::Decimal::from(*i)),
+        Sp00kyValue::Float(f) => rust_decimal::Decimal::from_f64_retain(*f),
+        _ => None,
+    };
+    match exact {
+        Some(n) => d.cmp(&n),
+        None => {
+            let f = number.as_f64().unwrap_or(f64::NAN);
+            if f.is_nan() {
+                Ordering::Less
+            } else {
+                d.to_f64().unwrap_or(0.0).total_cmp(&f)
+            }
+        }
+    }
+}
+
+/// Exact comparison of an integer with a float (NaN sorts last).
+pub(crate) fn cmp_int_float(i: i64, f: f64) -> Ordering {
+    if f.is_nan() {
+        return Ordering::Less;
+    }
+    // Outside the i64 range the float decides on its own.
+    if f >= 9_223_372_036_854_775_808.0 {
+        return Ordering::Less;
+    }
+    if f < -9_223_372_036_854_775_808.0 {
+        return Ordering::Greater;
+    }
+    let whole = f.trunc();
+    match i.cmp(&(whole as i64)) {
+        Ordering::Equal => 0.0_f64.partial_cmp(&(f - whole)).unwrap_or(Ordering::Equal),
+        other => other,
+    }
+}
+
+/// Numbers compared by value alone across `Int`/`Float`/`Decimal`, without
+/// the type tie-break of `sort_cmp`; `None` unless both are numbers.
+pub(crate) fn number_cmp(a: &Sp00kyValue, b: &Sp00kyValue) -> Option<Ordering> {
+    use Sp00kyValue::{Decimal, Float, Int};
+    Some(match (a, b) {
+        (Int(x), Float(y)) => cmp_int_float(*x, *y),
+        (Float(x), Int(y)) => cmp_int_float(*y, *x).reverse(),
+        (Decimal(_), Int(_) | Float(_)) => cmp_decimal(a, b),
+        (Int(_) | Float(_), Decimal(_)) => cmp_decimal(b, a).reverse(),
+        (Int(_), Int(_)) | (Float(_), Float(_)) | (Decimal(_), Decimal(_)) => sort_cmp(a, b),
+        _ => return None,
+    })
+}
+
+/// Normalize a record ID value (strip table prefix if present in a string
+/// or record link).
 pub fn normalize_record_id(value: Sp00kyValue) -> Sp00kyValue {
-    if let Sp00kyValue::Str(s) = &value {
+    if let Sp00kyValue::Str(s) | Sp00kyValue::RecordId(s) = &value {
         if let Some((_table, id)) = s.split_once(':') {
-            return Sp00kyValue::Str(id.to_string());
+            return Sp00kyValue::Str(id.into());
         }
     }
     value
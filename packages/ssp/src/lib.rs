@@ -5,6 +5,7 @@
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 pub mod converter;
+pub mod permissions;
 pub mod sanitizer;
 pub mod service;
 
@@ -1,116 +1,395 @@
-use crate::algebra::{ZSet, ZSetOps};
+use crate::algebra::{RowKey, ZSet, ZSetOps};
 use crate::circuit::store::Store;
-use crate::eval::value_ops::resolve_field;
-use crate::types::{Path, Sp00kyValue};
-use std::collections::HashMap;
+use crate::eval::value_ops::{resolve_field, OrderedValue};
+use crate::types::{make_key, Path, Sp00kyValue};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Table prefix for synthetic aggregate group keys (`_00_agg:<group-hash>`).
+///
+/// Group keys follow the "table:id" convention so they flow through views,
+/// `ViewDelta` and edge writers like ordinary record keys.
+pub const AGGREGATE_TABLE: &str = "_00_agg";
 
 /// Supported aggregate functions.
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "func", rename_all = "lowercase")]
 pub enum AggregateFunc {
     Count,
     Sum { field: Path },
+    Avg { field: Path },
+    Min { field: Path },
+    Max { field: Path },
+    /// Number of distinct non-null values of `field` in the group.
+    #[serde(rename = "count_distinct")]
+    CountDistinct { field: Path },
+}
+
+impl AggregateFunc {
+    /// Default output column name when the query gives no alias.
+    pub fn default_alias(&self) -> &'static str {
+        match self {
+            AggregateFunc::Count => "count",
+            AggregateFunc::Sum { .. } => "sum",
+            AggregateFunc::Avg { .. } => "avg",
+            AggregateFunc::Min { .. } => "min",
+            AggregateFunc::Max { .. } => "max",
+            AggregateFunc::CountDistinct { .. } => "count_distinct",
+        }
+    }
+
+    /// The input field this function reads, if any.
+    pub fn field(&self) -> Option<&Path> {
+        match self {
+            AggregateFunc::Count => None,
+            AggregateFunc::Sum { field }
+            | AggregateFunc::Avg { field }
+            | AggregateFunc::Min { field }
+            | AggregateFunc::Max { field }
+            | AggregateFunc::CountDistinct { field } => Some(field),
+        }
+    }
+}
+
+/// One aggregate output column (e.g. `count() AS unread`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AggregateSpec {
+    #[serde(flatten)]
+    pub func: AggregateFunc,
+    pub alias: String,
+}
+
+impl AggregateSpec {
+    pub fn new(func: AggregateFunc) -> Self {
+        let alias = func.default_alias().to_string();
+        Self { func, alias }
+    }
+}
+
+fn canonical_bytes(value: &Sp00kyValue) -> Vec<u8> {
+    ssp_protocol::snapshot_hash::canonical_json(&serde_json::Value::from(value.clone()))
+}
+
+/// Running state of one aggregate column within a group.
+///
+/// Every variant supports exact retraction: min/max keep the full multiset
+/// of values so the next extremum is known when the current one is deleted.
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count,
+    Sum(NumSum),
+    Avg { sum: NumSum, count: i64 },
+    Ordered(BTreeMap<OrderedValue, i64>),
+    Distinct(HashMap<Vec<u8>, i64>),
+}
+
+impl Accumulator {
+    fn new(func: &AggregateFunc) -> Self {
+        match func {
+            AggregateFunc::Count => Accumulator::Count,
+            AggregateFunc::Sum { .. } => Accumulator::Sum(NumSum::default()),
+            AggregateFunc::Avg { .. } => Accumulator::Avg { sum: NumSum::default(), count: 0 },
+            AggregateFunc::Min { .. } | AggregateFunc::Max { .. } => {
+                Accumulator::Ordered(BTreeMap::new())
+            }
+            AggregateFunc::CountDistinct { .. } => Accumulator::Distinct(HashMap::new()),
+        }
+    }
+
+    /// Add `value` with multiplicity `weight` (negative to retract).
+    /// `Null` inputs are ignored, as in SQL.
+    fn apply(&mut self, value: &Sp00kyValue, weight: i64) {
+        if value.is_null() {
+            return;
+        }
+        match self {
+            Accumulator::Count => {}
+            Accumulator::Sum(sum) => {
+                sum.apply(value, weight);
+            }
+            Accumulator::Avg { sum, count } => {
+                if sum.apply(value, weight) {
+                    *count += weight;
+                }
+            }
+            Accumulator::Ordered(values) => {
+                let entry = values.entry(OrderedValue(value.clone())).or_insert(0);
+                *entry += weight;
+                if *entry <= 0 {
+                    values.remove(&OrderedValue(value.clone()));
+                }
+            }
+            Accumulator::Distinct(values) => {
+                let entry = values.entry(canonical_bytes(value)).or_insert(0);
+                *entry += weight;
+                if *entry <= 0 {
+                    values.remove(&canonical_bytes(value));
+                }
+            }
+        }
+    }
+
+    fn output(&self, func: &AggregateFunc, group_count: i64) -> Sp00kyValue {
+        match (self, func) {
+            (Accumulator::Count, _) => Sp00kyValue::Int(group_count),
+            (Accumulator::Sum(sum), _) => sum.value(),
+            (Accumulator::Avg { sum, count }, _) => {
+                if *count > 0 {
+                    Sp00kyValue::Float(sum.total() / *count as f64)
+                } else {
+                    Sp00kyValue::Null
+                }
+            }
+            (Accumulator::Ordered(values), AggregateFunc::Max { .. }) => values
+                .keys()
+                .next_back()
+                .map(|v| v.0.clone())
+                .unwrap_or(Sp00kyValue::Null),
+            (Accumulator::Ordered(values), _) => values
+                .keys()
+                .next()
+                .map(|v| v.0.clone())
+                .unwrap_or(Sp00kyValue::Null),
+            (Accumulator::Distinct(values), _) => Sp00kyValue::Int(values.len() as i64),
+        }
+    }
+}
+
+/// Running sum of numeric inputs. Integers are summed exactly in `i128`
+/// (an `f64` loses them above 2^53); other numbers in `f64`, reset once
+/// every non-integer input is retracted so rounding does not linger.
+#[derive(Debug, Clone, Default)]
+struct NumSum {
+    ints: i128,
+    floats: f64,
+    /// Net weight of the non-integer inputs.
+    float_weight: i64,
+}
+
+impl NumSum {
+    /// Add a number with multiplicity `weight`; false for non-numbers.
+    fn apply(&mut self, value: &Sp00kyValue, weight: i64) -> bool {
+        match value {
+            Sp00kyValue::Int(n) => self.ints += *n as i128 * weight as i128,
+            _ => match value.as_f64() {
+                Some(n) => {
+                    self.floats += n * weight as f64;
+                    self.float_weight += weight;
+                    if self.float_weight == 0 {
+                        self.floats = 0.0;
+                    }
+                }
+                None => return false,
+            },
+        }
+        true
+    }
+
+    fn total(&self) -> f64 {
+        self.ints as f64 + self.floats
+    }
+
+    fn value(&self) -> Sp00kyValue {
+        match i64::try_from(self.ints) {
+            Ok(n) if self.float_weight == 0 => Sp00kyValue::Int(n),
+            _ => number_value(self.total()),
+        }
+    }
 }
 
 /// Per-group aggregate state.
 #[derive(Debug, Clone, Default)]
 struct AggState {
     count: i64,
-    sums: Vec<f64>,
+    /// One accumulator per `AggregateSpec`, in order.
+    accumulators: Vec<Accumulator>,
+    /// Group-by values, kept for building the output row.
+    group_values: Vec<Sp00kyValue>,
+    /// Bumped whenever the emitted row changes (exposed as `_00_rv`).
+    version: i64,
+}
+
+/// What a single input key contributed to its group.
+///
+/// Remembered on insertion so retractions subtract exactly what was added,
+/// even when the row is already gone from the store.
+#[derive(Debug, Clone)]
+struct Contribution {
+    group: RowKey,
+    values: Vec<Sp00kyValue>,
+    weight: i64,
 }
 
 /// Aggregate operator with per-group running state (Z⁻¹).
 ///
 /// For each input delta record with weight w:
 ///   - COUNT: running_count += w
-///   - SUM(field): running_sum += field_value * w
+///   - SUM/AVG(field): running_sum += field_value * w (AVG also counts)
+///   - MIN/MAX(field): multiset[field_value] += w
+///   - COUNT(DISTINCT field): distinct[field_value] += w
 ///
-/// Emits a delta reflecting the change in aggregate output.
+/// The output Z-set contains one synthetic key per non-empty group; the
+/// aggregate values for a key are available through `derived_row`.
 #[derive(Debug)]
 pub struct Aggregate {
     pub group_by: Option<Vec<Path>>,
-    pub funcs: Vec<AggregateFunc>,
+    pub funcs: Vec<AggregateSpec>,
     /// Per-group accumulated state.
-    group_state: HashMap<String, AggState>,
+    group_state: HashMap<RowKey, AggState>,
+    /// Per-input-key contribution (for retraction).
+    contributions: HashMap<RowKey, Contribution>,
+    /// Groups touched since the last `drain_changed_rows`.
+    dirty: HashSet<RowKey>,
     /// Previous output (for computing output delta).
     prev_output: ZSet,
 }
 
 impl Aggregate {
-    pub fn new(group_by: Option<Vec<Path>>, funcs: Vec<AggregateFunc>) -> Self {
+    pub fn new(group_by: Option<Vec<Path>>, funcs: Vec<AggregateSpec>) -> Self {
         Self {
             group_by,
             funcs,
             group_state: HashMap::new(),
+            contributions: HashMap::new(),
+            dirty: HashSet::new(),
             prev_output: HashMap::new(),
         }
     }
 
-    /// Compute a group key for a record.
-    fn group_key(&self, key: &str, store: &Store) -> String {
+    /// The output key of the group a set of group-by values falls into.
+    pub fn group_key_for(values: &[Sp00kyValue]) -> RowKey {
+        if values.is_empty() {
+            return make_key(AGGREGATE_TABLE, "global").into();
+        }
+        let json = serde_json::Value::Array(
+            values.iter().cloned().map(serde_json::Value::from).collect(),
+        );
+        let canonical = ssp_protocol::snapshot_hash::canonical_json(&json);
+        let hex = blake3::hash(&canonical).to_hex();
+        make_key(AGGREGATE_TABLE, &hex[..32]).into()
+    }
+
+    fn group_values(&self, row: Option<&Sp00kyValue>) -> Vec<Sp00kyValue> {
         match &self.group_by {
-            None => "__global__".to_string(),
-            Some(fields) => {
-                let row = store.get_row_by_key(key);
-                let parts: Vec<String> = fields
-                    .iter()
-                    .map(|f| {
-                        row.and_then(|r| resolve_field(Some(r), f))
-                            .map(|v| format!("{:?}", v))
-                            .unwrap_or_else(|| "null".to_string())
-                    })
-                    .collect();
-                parts.join("|")
-            }
+            None => vec![],
+            Some(fields) => fields
+                .iter()
+                .map(|f| {
+                    row.and_then(|r| resolve_field(Some(r), f))
+                        .cloned()
+                        .unwrap_or(Sp00kyValue::Null)
+                })
+                .collect(),
+        }
+    }
+
+    fn field_values(&self, row: Option<&Sp00kyValue>) -> Vec<Sp00kyValue> {
+        self.funcs
+            .iter()
+            .map(|spec| {
+                spec.func
+                    .field()
+                    .and_then(|field| row.and_then(|r| resolve_field(Some(r), field)))
+                    .cloned()
+                    .unwrap_or(Sp00kyValue::Null)
+            })
+            .collect()
+    }
+
+    fn apply(
+        funcs: &[AggregateSpec],
+        groups: &mut HashMap<RowKey, AggState>,
+        group: &str,
+        group_values: Option<Vec<Sp00kyValue>>,
+        values: &[Sp00kyValue],
+        weight: i64,
+    ) {
+        let state = groups.entry(group.into()).or_insert_with(|| AggState {
+            accumulators: funcs.iter().map(|s| Accumulator::new(&s.func)).collect(),
+            ..AggState::default()
+        });
+        if let Some(gv) = group_values {
+            state.group_values = gv;
+        }
+        state.count += weight;
+        for (acc, v) in state.accumulators.iter_mut().zip(values) {
+            acc.apply(v, weight);
         }
     }
 
     /// Build the output Z-set from current group state.
-    fn build_output(&self) -> ZSet {
+    fn build_output(groups: &HashMap<RowKey, AggState>) -> ZSet {
         let mut out = HashMap::new();
-        for (group_key, state) in &self.group_state {
+        for (group_key, state) in groups {
             if state.count > 0 {
                 out.insert(group_key.clone(), 1);
             }
         }
         out
     }
+
+    fn row_for(&self, key: &str, state: &AggState) -> Sp00kyValue {
+        let mut row = Sp00kyValue::Object(HashMap::new());
+        if let Some(fields) = &self.group_by {
+            for (path, value) in fields.iter().zip(&state.group_values) {
+                set_path(&mut row, path, value.clone());
+            }
+        }
+        if let Sp00kyValue::Object(map) = &mut row {
+            for (spec, acc) in self.funcs.iter().zip(&state.accumulators) {
+                map.insert(spec.alias.as_str().into(), acc.output(&spec.func, state.count));
+            }
+            map.insert("id".into(), Sp00kyValue::Str(key.into()));
+            map.insert("_00_rv".into(), Sp00kyValue::Int(state.version));
+        }
+        row
+    }
+}
+
+/// Emit integral sums as `Int` so `sum(int_field)` round-trips as an integer.
+fn number_value(n: f64) -> Sp00kyValue {
+    if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+        Sp00kyValue::Int(n as i64)
+    } else {
+        Sp00kyValue::Float(n)
+    }
+}
+
+/// Write `value` at a (possibly nested) path inside an object row.
+pub(crate) fn set_path(row: &mut Sp00kyValue, path: &Path, value: Sp00kyValue) {
+    let segments = path.segments();
+    let mut current = row;
+    for (i, segment) in segments.iter().enumerate() {
+        let Sp00kyValue::Object(map) = current else {
+            return;
+        };
+        if i + 1 == segments.len() {
+            map.insert(segment.as_str().into(), value);
+            return;
+        }
+        current = map
+            .entry(segment.as_str().into())
+            .or_insert_with(|| Sp00kyValue::Object(HashMap::new()));
+    }
 }
 
 impl super::Operator for Aggregate {
     fn snapshot(&self, inputs: &[&ZSet], store: &Store, _ctx: Option<&Sp00kyValue>) -> ZSet {
         let upstream = inputs[0];
-        let mut groups: HashMap<String, AggState> = HashMap::new();
+        let mut groups: HashMap<RowKey, AggState> = HashMap::new();
 
         for (key, &weight) in upstream {
             if weight <= 0 {
                 continue;
             }
-            let gk = self.group_key(key, store);
-            let state = groups.entry(gk).or_default();
-            state.count += weight;
-
             let row = store.get_row_by_key(key);
-            for (i, func) in self.funcs.iter().enumerate() {
-                if let AggregateFunc::Sum { field } = func {
-                    let val = row
-                        .and_then(|r| resolve_field(Some(r), field))
-                        .and_then(|v| v.as_f64())
-                        .unwrap_or(0.0);
-                    if state.sums.len() <= i {
-                        state.sums.resize(i + 1, 0.0);
-                    }
-                    state.sums[i] += val * weight as f64;
-                }
-            }
+            let group_values = self.group_values(row);
+            let gk = Self::group_key_for(&group_values);
+            let values = self.field_values(row);
+            Self::apply(&self.funcs, &mut groups, &gk, None, &values, weight);
         }
 
-        let mut out = HashMap::new();
-        for (group_key, state) in &groups {
-            if state.count > 0 {
-                out.insert(group_key.clone(), 1);
-            }
-        }
-        out
+        Self::build_output(&groups)
     }
 
     fn step(
@@ -122,26 +401,46 @@ impl super::Operator for Aggregate {
         let upstream_delta = input_deltas[0];
 
         for (key, &weight) in upstream_delta {
-            let gk = self.group_key(key, store);
-            let state = self.group_state.entry(gk).or_default();
-            state.count += weight;
+            if weight > 0 {
+                let row = store.get_row_by_key(key);
+                let group_values = self.group_values(row);
+                let gk = Self::group_key_for(&group_values);
+                let values = self.field_values(row);
+                Self::apply(
+                    &self.funcs,
+                    &mut self.group_state,
+                    &gk,
+                    Some(group_values),
+                    &values,
+                    weight,
+                );
+                self.dirty.insert(gk.clone());
 
-            let row = store.get_row_by_key(key);
-            for (i, func) in self.funcs.iter().enumerate() {
-                if let AggregateFunc::Sum { field } = func {
-                    let val = row
-                        .and_then(|r| resolve_field(Some(r), field))
-                        .and_then(|v| v.as_f64())
-                        .unwrap_or(0.0);
-                    if state.sums.len() <= i {
-                        state.sums.resize(i + 1, 0.0);
-                    }
-                    state.sums[i] += val * weight as f64;
+                let entry = self.contributions.entry(key.clone()).or_insert(Contribution {
+                    group: gk,
+                    values,
+                    weight: 0,
+                });
+                entry.weight += weight;
+            } else if weight < 0 {
+                let Some(contribution) = self.contributions.get_mut(key) else {
+                    continue;
+                };
+                let retracted = (-weight).min(contribution.weight);
+                contribution.weight -= retracted;
+                let group = contribution.group.clone();
+                let values = contribution.values.clone();
+                if contribution.weight == 0 {
+                    self.contributions.remove(key);
                 }
+                Self::apply(&self.funcs, &mut self.group_state, &group, None, &values, -retracted);
+                self.dirty.insert(group);
             }
         }
 
-        let new_output = self.build_output();
+        self.group_state.retain(|_, s| s.count > 0);
+
+        let new_output = Self::build_output(&self.group_state);
         let delta_out = self.prev_output.diff(&new_output);
         self.prev_output = new_output;
         delta_out
@@ -151,10 +450,32 @@ impl super::Operator for Aggregate {
         1
     }
 
+    fn state_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![("groups", self.group_state.len()), ("contributions", self.contributions.len())]
+    }
+
     fn reset(&mut self) {
         self.group_state.clear();
+        self.contributions.clear();
+        self.dirty.clear();
         self.prev_output.clear();
     }
+
+    fn derived_row(&self, key: &str) -> Option<Sp00kyValue> {
+        let state = self.group_state.get(key)?;
+        Some(self.row_for(key, state))
+    }
+
+    fn drain_changed_rows(&mut self) -> Vec<RowKey> {
+        let mut changed = Vec::new();
+        for group in self.dirty.drain() {
+            if let Some(state) = self.group_state.get_mut(&group) {
+                state.version += 1;
+                changed.push(group);
+            }
+        }
+        changed
+    }
 }
 
 #[cfg(test)]
@@ -165,15 +486,19 @@ mod tests {
     use serde_json::json;
 
     fn zset(items: &[(&str, i64)]) -> ZSet {
-        items.iter().map(|(k, w)| (k.to_string(), *w)).collect()
+        items.iter().map(|(k, w)| ((*k).into(), *w)).collect()
+    }
+
+    fn global_key() -> RowKey {
+        Aggregate::group_key_for(&[])
     }
 
     #[test]
     fn count_increments_on_insert() {
         let store = Store::new();
-        let mut agg = Aggregate::new(None, vec![AggregateFunc::Count]);
+        let mut agg = Aggregate::new(None, vec![AggregateSpec::new(AggregateFunc::Count)]);
 
-        let d1 = zset(&[("a", 1), ("b", 1)]);
+        let d1 = zset(&[("a:1", 1), ("a:2", 1)]);
         let result = agg.step(&[&d1], &store, None);
         assert!(!result.is_empty());
     }
@@ -181,17 +506,17 @@ mod tests {
     #[test]
     fn count_decrements_on_delete() {
         let store = Store::new();
-        let mut agg = Aggregate::new(None, vec![AggregateFunc::Count]);
+        let mut agg = Aggregate::new(None, vec![AggregateSpec::new(AggregateFunc::Count)]);
 
-        let d1 = zset(&[("a", 1), ("b", 1)]);
+        let d1 = zset(&[("a:1", 1), ("a:2", 1)]);
         let _ = agg.step(&[&d1], &store, None);
 
         // Count went from 2 to 1 — group still exists, no membership delta
         // But if count goes to 0, group disappears
-        let d2 = zset(&[("a", -1), ("b", -1)]);
+        let d2 = zset(&[("a:1", -1), ("a:2", -1)]);
         let result = agg.step(&[&d2], &store, None);
         // Group disappears → delta should show removal
-        assert_eq!(result.get("__global__"), Some(&-1));
+        assert_eq!(result.get(&global_key()), Some(&-1));
     }
 
     #[test]
@@ -203,9 +528,9 @@ mod tests {
 
         let mut agg = Aggregate::new(
             None,
-            vec![AggregateFunc::Sum {
+            vec![AggregateSpec::new(AggregateFunc::Sum {
                 field: Path::new("price"),
-            }],
+            })],
         );
 
         let d1 = zset(&[("items:1", 1), ("items:2", 1)]);
@@ -219,4 +544,180 @@ mod tests {
         // Group still present, no membership delta
         assert!(result2.is_empty());
     }
+
+    #[test]
+    fn sums_of_large_integers_are_exact() {
+        let mut store = Store::new();
+        let big = i64::MAX - 10;
+        store.apply_change(&Change::create("item", "item:1", json!({"n": big})));
+        store.apply_change(&Change::create("item", "item:2", json!({"n": 1})));
+        store.apply_change(&Change::create("item", "item:3", json!({"n": 1.5})));
+        let mut agg = Aggregate::new(None, vec![AggregateSpec::new(AggregateFunc::Sum { field: Path::new("n") })]);
+
+        agg.step(&[&zset(&[("item:1", 1), ("item:2", 1)])], &store, None);
+        let row = agg.derived_row(&global_key()).unwrap();
+        assert_eq!(row.get("sum"), Some(&Sp00kyValue::Int(big + 1)));
+
+        agg.step(&[&zset(&[("item:3", 1)])], &store, None);
+        assert!(matches!(agg.derived_row(&global_key()).unwrap().get("sum"), Some(Sp00kyValue::Float(_))));
+        agg.step(&[&zset(&[("item:3", -1)])], &store, None);
+        let row = agg.derived_row(&global_key()).unwrap();
+        assert_eq!(row.get("sum"), Some(&Sp00kyValue::Int(big + 1)));
+    }
+
+    #[test]
+    fn count_and_count_distinct_have_distinct_default_aliases() {
+        let count = AggregateSpec::new(AggregateFunc::Count);
+        let distinct = AggregateSpec::new(AggregateFunc::CountDistinct { field: Path::new("x") });
+        assert_ne!(count.alias, distinct.alias);
+    }
+
+    #[test]
+    fn derived_row_reports_grouped_values() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("invoice", "invoice:1", json!({"customer": "c1", "amount": 10})));
+        store.apply_change(&Change::create("invoice", "invoice:2", json!({"customer": "c1", "amount": 5})));
+        store.apply_change(&Change::create("invoice", "invoice:3", json!({"customer": "c2", "amount": 7})));
+
+        let mut agg = Aggregate::new(
+            Some(vec![Path::new("customer")]),
+            vec![
+                AggregateSpec::new(AggregateFunc::Count),
+                AggregateSpec {
+                    func: AggregateFunc::Sum { field: Path::new("amount") },
+                    alias: "total".into(),
+                },
+            ],
+        );
+        let d = zset(&[("invoice:1", 1), ("invoice:2", 1), ("invoice:3", 1)]);
+        let out = agg.step(&[&d], &store, None);
+        assert_eq!(out.len(), 2);
+
+        let c1 = Aggregate::group_key_for(&[Sp00kyValue::Str("c1".into())]);
+        let row = agg.derived_row(&c1).expect("group row");
+        assert_eq!(row.get("customer"), Some(&Sp00kyValue::Str("c1".into())));
+        assert_eq!(row.get("count"), Some(&Sp00kyValue::Int(2)));
+        assert_eq!(row.get("total"), Some(&Sp00kyValue::Int(15)));
+    }
+
+    #[test]
+    fn retraction_uses_remembered_contribution() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("invoice", "invoice:1", json!({"customer": "c1", "amount": 10})));
+        store.apply_change(&Change::create("invoice", "invoice:2", json!({"customer": "c1", "amount": 5})));
+
+        let mut agg = Aggregate::new(
+            Some(vec![Path::new("customer")]),
+            vec![AggregateSpec::new(AggregateFunc::Sum { field: Path::new("amount") })],
+        );
+        let _ = agg.step(&[&zset(&[("invoice:1", 1), ("invoice:2", 1)])], &store, None);
+        agg.drain_changed_rows();
+
+        // Row is gone from the store before the operator sees the retraction.
+        store.apply_change(&Change::delete("invoice", "invoice:1"));
+        let out = agg.step(&[&zset(&[("invoice:1", -1)])], &store, None);
+        assert!(out.is_empty());
+
+        let c1 = Aggregate::group_key_for(&[Sp00kyValue::Str("c1".into())]);
+        assert_eq!(agg.drain_changed_rows(), vec![c1.clone()]);
+        let row = agg.derived_row(&c1).unwrap();
+        assert_eq!(row.get("sum"), Some(&Sp00kyValue::Int(5)));
+    }
+
+    fn priced_store() -> Store {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("product", "product:1", json!({"category": "a", "price": 30})));
+        store.apply_change(&Change::create("product", "product:2", json!({"category": "a", "price": 10})));
+        store.apply_change(&Change::create("product", "product:3", json!({"category": "a", "price": 20})));
+        store.apply_change(&Change::create("product", "product:4", json!({"category": "a", "price": 10})));
+        store
+    }
+
+    fn spec(func: AggregateFunc, alias: &str) -> AggregateSpec {
+        AggregateSpec { func, alias: alias.into() }
+    }
+
+    #[test]
+    fn min_max_survive_deleting_the_extremum() {
+        let mut store = priced_store();
+        let price = Path::new("price");
+        let mut agg = Aggregate::new(
+            Some(vec![Path::new("category")]),
+            vec![
+                spec(AggregateFunc::Min { field: price.clone() }, "cheapest"),
+                spec(AggregateFunc::Max { field: price }, "priciest"),
+            ],
+        );
+        let all = zset(&[("product:1", 1), ("product:2", 1), ("product:3", 1), ("product:4", 1)]);
+        let _ = agg.step(&[&all], &store, None);
+        let a = Aggregate::group_key_for(&[Sp00kyValue::Str("a".into())]);
+
+        let row = agg.derived_row(&a).unwrap();
+        assert_eq!(row.get("cheapest"), Some(&Sp00kyValue::Int(10)));
+        assert_eq!(row.get("priciest"), Some(&Sp00kyValue::Int(30)));
+
+        // Two rows share the minimum: removing one keeps it.
+        store.apply_change(&Change::delete("product", "product:2"));
+        let _ = agg.step(&[&zset(&[("product:2", -1)])], &store, None);
+        assert_eq!(agg.derived_row(&a).unwrap().get("cheapest"), Some(&Sp00kyValue::Int(10)));
+
+        store.apply_change(&Change::delete("product", "product:4"));
+        store.apply_change(&Change::delete("product", "product:1"));
+        let _ = agg.step(&[&zset(&[("product:4", -1), ("product:1", -1)])], &store, None);
+        let row = agg.derived_row(&a).unwrap();
+        assert_eq!(row.get("cheapest"), Some(&Sp00kyValue::Int(20)));
+        assert_eq!(row.get("priciest"), Some(&Sp00kyValue::Int(20)));
+    }
+
+    #[test]
+    fn avg_pairs_sum_with_non_null_count() {
+        let mut store = priced_store();
+        store.apply_change(&Change::create("product", "product:5", json!({"category": "a"})));
+        let mut agg = Aggregate::new(
+            None,
+            vec![spec(AggregateFunc::Avg { field: Path::new("price") }, "avg_price")],
+        );
+        let all = zset(&[
+            ("product:1", 1),
+            ("product:2", 1),
+            ("product:3", 1),
+            ("product:4", 1),
+            ("product:5", 1),
+        ]);
+        let _ = agg.step(&[&all], &store, None);
+        let row = agg.derived_row(&global_key()).unwrap();
+        assert_eq!(row.get("avg_price"), Some(&Sp00kyValue::Float(17.5)));
+
+        let _ = agg.step(&[&zset(&[("product:1", -1), ("product:3", -1)])], &store, None);
+        let row = agg.derived_row(&global_key()).unwrap();
+        assert_eq!(row.get("avg_price"), Some(&Sp00kyValue::Float(10.0)));
+    }
+
+    #[test]
+    fn count_distinct_retracts_per_value() {
+        let store = priced_store();
+        let mut agg = Aggregate::new(
+            None,
+            vec![spec(AggregateFunc::CountDistinct { field: Path::new("price") }, "prices")],
+        );
+        let all = zset(&[("product:1", 1), ("product:2", 1), ("product:3", 1), ("product:4", 1)]);
+        let _ = agg.step(&[&all], &store, None);
+        let prices = |agg: &Aggregate| agg.derived_row(&global_key()).unwrap().get("prices").cloned();
+        assert_eq!(prices(&agg), Some(Sp00kyValue::Int(3)));
+
+        let _ = agg.step(&[&zset(&[("product:2", -1)])], &store, None);
+        assert_eq!(prices(&agg), Some(Sp00kyValue::Int(3)));
+
+        let _ = agg.step(&[&zset(&[("product:4", -1)])], &store, None);
+        assert_eq!(prices(&agg), Some(Sp00kyValue::Int(2)));
+    }
+
+    #[test]
+    fn count_distinct_plan_json() {
+        let spec: AggregateSpec = serde_json::from_value(
+            json!({"func": "count_distinct", "field": "author", "alias": "authors"}),
+        )
+        .unwrap();
+        assert_eq!(spec.func, AggregateFunc::CountDistinct { field: Path::new("author") });
+    }
 }
@@ -1,8 +1,11 @@
-use crate::algebra::{ZSet, ZSetOps};
+use crate::algebra::{RowKey, ZSet, ZSetOps};
 use crate::circuit::store::Store;
-use crate::types::Sp00kyValue;
+use crate::eval::value_ops::resolve_field;
+use crate::types::{Path, Sp00kyValue};
 use std::collections::HashMap;
 
+use super::aggregate::Aggregate;
+
 /// Distinct operator: ensures output weights are 0 or 1.
 ///
 /// DBSP rule: `distinct = D(threshold(I(input)))`
@@ -13,19 +16,35 @@ use std::collections::HashMap;
 ///   2. new_output = threshold(integrated)  (clamp to 0/1)
 ///   3. delta_out = new_output - prev_output (D: differentiation)
 ///   4. prev_output = new_output
-#[derive(Debug)]
+///
+/// With `fields` set (`SELECT DISTINCT a, b` / `GROUP BY a, b` without
+/// aggregates), input keys are first mapped to one synthetic key per
+/// distinct value tuple — the same key `Aggregate` uses for that group —
+/// and the tuple is served through `derived_row`.
+#[derive(Debug, Default)]
 pub struct Distinct {
+    /// Fields defining value identity; empty means distinct by key.
+    pub fields: Vec<Path>,
     /// Z⁻¹: accumulated input state.
     integrated: ZSet,
     /// Previous thresholded output (for differentiation).
     prev_output: ZSet,
+    /// Per-input-key value key and weight (for retraction).
+    members: HashMap<RowKey, (RowKey, i64)>,
+    /// Field values of each present value key.
+    values: HashMap<RowKey, Vec<Sp00kyValue>>,
 }
 
 impl Distinct {
     pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Distinct over the values of `fields` instead of record keys.
+    pub fn on_fields(fields: Vec<Path>) -> Self {
         Self {
-            integrated: HashMap::new(),
-            prev_output: HashMap::new(),
+            fields,
+            ..Self::default()
         }
     }
 
@@ -35,21 +54,79 @@ impl Distinct {
             .map(|(k, _)| (k.clone(), 1i64))
             .collect()
     }
+
+    fn field_values(&self, row: Option<&Sp00kyValue>) -> Vec<Sp00kyValue> {
+        self.fields
+            .iter()
+            .map(|f| {
+                resolve_field(row, f)
+                    .cloned()
+                    .unwrap_or(Sp00kyValue::Null)
+            })
+            .collect()
+    }
+
+    /// Map an input delta onto value keys, remembering each key's value
+    /// so retractions work after the row has left the store.
+    fn map_to_values(&mut self, delta: &ZSet, store: &Store) -> ZSet {
+        if self.fields.is_empty() {
+            return delta.clone();
+        }
+        let mut mapped = ZSet::new();
+        for (key, &weight) in delta {
+            if weight > 0 {
+                let values = self.field_values(store.get_row_by_key(key));
+                let value_key = Aggregate::group_key_for(&values);
+                self.values.entry(value_key.clone()).or_insert(values);
+                let member = self
+                    .members
+                    .entry(key.clone())
+                    .or_insert((value_key.clone(), 0));
+                member.1 += weight;
+                *mapped.entry(value_key).or_insert(0) += weight;
+            } else if weight < 0 {
+                let Some((value_key, present)) = self.members.get_mut(key) else {
+                    continue;
+                };
+                let retracted = (-weight).min(*present);
+                *present -= retracted;
+                let value_key = value_key.clone();
+                if *present == 0 {
+                    self.members.remove(key);
+                }
+                *mapped.entry(value_key).or_insert(0) -= retracted;
+            }
+        }
+        mapped.retain(|_, w| *w != 0);
+        mapped
+    }
 }
 
 impl super::Operator for Distinct {
-    fn snapshot(&self, inputs: &[&ZSet], _store: &Store, _ctx: Option<&Sp00kyValue>) -> ZSet {
-        Self::threshold(inputs[0])
+    fn snapshot(&self, inputs: &[&ZSet], store: &Store, _ctx: Option<&Sp00kyValue>) -> ZSet {
+        if self.fields.is_empty() {
+            return Self::threshold(inputs[0]);
+        }
+        inputs[0]
+            .iter()
+            .filter(|(_, &w)| w > 0)
+            .map(|(key, _)| {
+                let values = self.field_values(store.get_row_by_key(key));
+                (Aggregate::group_key_for(&values), 1i64)
+            })
+            .collect()
     }
 
     fn step(
         &mut self,
         input_deltas: &[&ZSet],
-        _store: &Store,
+        store: &Store,
         _ctx: Option<&Sp00kyValue>,
     ) -> ZSet {
+        let delta_in = self.map_to_values(input_deltas[0], store);
+
         // I: integrate input
-        self.integrated.add(input_deltas[0]);
+        self.integrated.add(&delta_in);
 
         // threshold: clamp to {0, 1}
         let new_output = Self::threshold(&self.integrated);
@@ -59,6 +136,11 @@ impl super::Operator for Distinct {
 
         // Update state for next step
         self.prev_output = new_output;
+        self.integrated.retain(|_, w| *w != 0);
+        if !self.fields.is_empty() {
+            let present = &self.prev_output;
+            self.values.retain(|k, _| present.contains_key(k));
+        }
 
         delta_out
     }
@@ -67,9 +149,32 @@ impl super::Operator for Distinct {
         1
     }
 
+    fn state_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![("input", self.integrated.len()), ("values", self.prev_output.len())]
+    }
+
     fn reset(&mut self) {
         self.integrated.clear();
         self.prev_output.clear();
+        self.members.clear();
+        self.values.clear();
+    }
+
+    fn derived_row(&self, key: &str) -> Option<Sp00kyValue> {
+        if self.fields.is_empty() || !self.prev_output.contains_key(key) {
+            return None;
+        }
+        let values = self.values.get(key)?;
+        let mut row = Sp00kyValue::Object(HashMap::new());
+        for (path, value) in self.fields.iter().zip(values) {
+            super::aggregate::set_path(&mut row, path, value.clone());
+        }
+        if let Sp00kyValue::Object(map) = &mut row {
+            map.insert("id".into(), Sp00kyValue::Str(key.into()));
+            // A value key always denotes the same values, so it never changes.
+            map.insert("_00_rv".into(), Sp00kyValue::Int(1));
+        }
+        Some(row)
     }
 }
 
@@ -79,7 +184,7 @@ mod tests {
     use crate::operator::Operator;
 
     fn zset(items: &[(&str, i64)]) -> ZSet {
-        items.iter().map(|(k, w)| (k.to_string(), *w)).collect()
+        items.iter().map(|(k, w)| ((*k).into(), *w)).collect()
     }
 
     #[test]
@@ -119,4 +224,33 @@ mod tests {
         let result = distinct.step(&[&d2], &store, None); // weight 1→3, threshold unchanged
         assert!(result.is_empty());
     }
+
+    #[test]
+    fn distinct_on_fields_tracks_value_multiplicity() {
+        use crate::circuit::store::Change;
+        use serde_json::json;
+
+        let mut store = Store::new();
+        store.apply_change(&Change::create("post", "post:1", json!({"author": "ann"})));
+        store.apply_change(&Change::create("post", "post:2", json!({"author": "ann"})));
+        store.apply_change(&Change::create("post", "post:3", json!({"author": "bob"})));
+
+        let mut distinct = Distinct::on_fields(vec![Path::new("author")]);
+        let out = distinct.step(&[&zset(&[("post:1", 1), ("post:2", 1), ("post:3", 1)])], &store, None);
+        assert_eq!(out.len(), 2);
+
+        let ann = Aggregate::group_key_for(&[Sp00kyValue::Str("ann".into())]);
+        let row = distinct.derived_row(&ann).expect("distinct row");
+        assert_eq!(row.get("author"), Some(&Sp00kyValue::Str("ann".into())));
+
+        // One of two "ann" posts leaves (already gone from the store): no change.
+        store.apply_change(&Change::delete("post", "post:1"));
+        let out = distinct.step(&[&zset(&[("post:1", -1)])], &store, None);
+        assert!(out.is_empty());
+
+        store.apply_change(&Change::delete("post", "post:2"));
+        let out = distinct.step(&[&zset(&[("post:2", -1)])], &store, None);
+        assert_eq!(out.get(&ann), Some(&-1));
+        assert!(distinct.derived_row(&ann).is_none());
+    }
 }
@@ -1,8 +1,9 @@
-use crate::algebra::ZSet;
+use crate::algebra::{ZSet, ZSetOps};
 use crate::circuit::store::Store;
-use crate::eval::value_ops::{compare_values, resolve_field};
-use crate::operator::predicate::Predicate;
+use crate::eval::value_ops::{compare_values, resolve_field, scalar_cmp};
+use crate::operator::predicate::{CaseFold, Predicate};
 use crate::types::{Path, Sp00kyValue};
+use regex::Regex;
 use serde_json::Value;
 use std::cmp::Ordering;
 use std::collections::HashMap;
@@ -14,21 +15,75 @@ use std::collections::HashMap;
 ///
 /// This works because filter is a linear operator in DBSP:
 /// `filter(A + B) = filter(A) + filter(B)`
+///
+/// A predicate that reads `time::now()` is evaluated at the operator's
+/// clock. Such a filter also integrates its input, so that `tick` can
+/// re-evaluate every held record and emit the ones that entered or left
+/// the time window.
 #[derive(Debug)]
 pub struct Filter {
     pub predicate: Predicate,
+    /// Compiled `Regex` predicates, by pattern. Invalid patterns are left
+    /// out and never match.
+    regexes: HashMap<String, Regex>,
+    /// Whether the predicate reads the clock.
+    clocked: bool,
+    /// Current clock, Unix milliseconds (set by `tick`).
+    now: Option<i64>,
+    /// Accumulated input weights (clocked predicates only).
+    members: ZSet,
 }
 
 impl Filter {
     pub fn new(predicate: Predicate) -> Self {
-        Self { predicate }
+        let regexes = predicate
+            .regex_patterns()
+            .into_iter()
+            .filter_map(|p| Regex::new(p).ok().map(|re| (p.to_string(), re)))
+            .collect();
+        let clocked = predicate.uses_clock();
+        Self {
+            predicate,
+            regexes,
+            clocked,
+            now: None,
+            members: HashMap::new(),
+        }
     }
 
     fn check_predicate(&self, key: &str, store: &Store, ctx: Option<&Sp00kyValue>) -> bool {
-        check_predicate_recursive(&self.predicate, key, store, ctx)
+        self.check_predicate_at(key, store, ctx, self.now)
+    }
+
+    fn check_predicate_at(
+        &self,
+        key: &str,
+        store: &Store,
+        ctx: Option<&Sp00kyValue>,
+        now: Option<i64>,
+    ) -> bool {
+        let env = EvalEnv {
+            store,
+            ctx,
+            now,
+            regexes: &self.regexes,
+            fold: None,
+        };
+        check_predicate_recursive(&self.predicate, key, &env)
     }
 }
 
+/// State threaded through predicate evaluation.
+struct EvalEnv<'a> {
+    store: &'a Store,
+    ctx: Option<&'a Sp00kyValue>,
+    /// Value of `time::now()`.
+    now: Option<i64>,
+    regexes: &'a HashMap<String, Regex>,
+    /// Case folding applied to field values (inside `Predicate::Fold`).
+    fold: Option<CaseFold>,
+}
+
 impl super::Operator for Filter {
     fn snapshot(&self, inputs: &[&ZSet], store: &Store, ctx: Option<&Sp00kyValue>) -> ZSet {
         let upstream = inputs[0];
@@ -47,7 +102,11 @@ impl super::Operator for Filter {
         store: &Store,
         ctx: Option<&Sp00kyValue>,
     ) -> ZSet {
-        // Filter is stateless: delta rule = apply predicate to delta
+        if self.clocked {
+            self.members.add(input_deltas[0]);
+        }
+        // Apart from the clock, filter is stateless: delta rule = apply
+        // predicate to delta
         self.snapshot(input_deltas, store, ctx)
     }
 
@@ -55,11 +114,37 @@ impl super::Operator for Filter {
         1
     }
 
-    fn reset(&mut self) {}
+    fn state_sizes(&self) -> Vec<(&'static str, usize)> {
+        if self.clocked {
+            vec![("members", self.members.len())]
+        } else {
+            vec![]
+        }
+    }
+
+    fn reset(&mut self) {
+        self.members.clear();
+    }
+
+    fn tick(&mut self, now: i64, store: &Store, ctx: Option<&Sp00kyValue>) -> ZSet {
+        let before = self.now.replace(now);
+        let mut out = HashMap::new();
+        if !self.clocked || before == Some(now) {
+            return out;
+        }
+        for (key, &weight) in &self.members {
+            let was = self.check_predicate_at(key, store, ctx, before);
+            let is = self.check_predicate_at(key, store, ctx, Some(now));
+            if was != is {
+                out.insert(key.clone(), if is { weight } else { -weight });
+            }
+        }
+        out
+    }
 }
 
 /// Resolve a predicate value, handling $param references.
-fn resolve_predicate_value(value: &Value, ctx: Option<&Sp00kyValue>) -> Option<Sp00kyValue> {
+pub(crate) fn resolve_predicate_value(value: &Value, ctx: Option<&Sp00kyValue>) -> Option<Sp00kyValue> {
     if let Some(obj) = value.as_object() {
         if let Some(param_path) = obj.get("$param") {
             let ctx = ctx?;
@@ -79,31 +164,135 @@ fn resolve_predicate_value(value: &Value, ctx: Option<&Sp00kyValue>) -> Option<S
     }
 }
 
-fn check_predicate_recursive(
-    pred: &Predicate,
-    key: &str,
-    store: &Store,
-    ctx: Option<&Sp00kyValue>,
-) -> bool {
+/// Strict value equality for membership tests (IN / CONTAINS).
+///
+/// Numbers compare across `Int`/`Float`/`Decimal`, and typed values equal
+/// the strings they are written as (see `scalar_cmp`); other values must
+/// have the same type, so `"1"` is not an element of `[1]`.
+fn values_equal(a: &Sp00kyValue, b: &Sp00kyValue) -> bool {
+    match (a, b) {
+        (Sp00kyValue::Array(a), Sp00kyValue::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| values_equal(x, y))
+        }
+        _ => scalar_cmp(a, b).map_or(a == b, Ordering::is_eq),
+    }
+}
+
+/// Whether `haystack` contains `needle`: element of an array, or substring
+/// of a string.
+fn contains_value(haystack: &Sp00kyValue, needle: &Sp00kyValue) -> bool {
+    match (haystack, needle) {
+        (Sp00kyValue::Array(items), _) => items.iter().any(|item| values_equal(item, needle)),
+        (Sp00kyValue::Str(s), Sp00kyValue::Str(sub)) => s.contains(sub.as_str()),
+        _ => false,
+    }
+}
+
+/// The value list of an IN / CONTAINSANY / CONTAINSALL operand; a scalar is
+/// treated as a one-element list.
+fn as_list(value: Sp00kyValue) -> Vec<Sp00kyValue> {
+    match value {
+        Sp00kyValue::Array(items) => items,
+        other => vec![other],
+    }
+}
+
+fn fold_value(value: Sp00kyValue, fold: CaseFold) -> Sp00kyValue {
+    match value {
+        Sp00kyValue::Str(s) => Sp00kyValue::Str(fold.apply(&s).into()),
+        Sp00kyValue::Array(items) => {
+            Sp00kyValue::Array(items.into_iter().map(|v| fold_value(v, fold)).collect())
+        }
+        other => other,
+    }
+}
+
+/// Value of `field` in the stored row of `key`.
+pub(crate) fn record_field(store: &Store, key: &str, field: &Path) -> Option<Sp00kyValue> {
+    let row = store.get_row_by_key(key);
+    match resolve_field(row, field) {
+        Some(value) => Some(value.clone()),
+        // Rows need not carry their own id; it is the key.
+        None if row.is_some() && field.segments() == ["id"] => Some(Sp00kyValue::Str(key.into())),
+        None => None,
+    }
+}
+
+fn field_value(key: &str, env: &EvalEnv, field: &Path) -> Option<Sp00kyValue> {
+    let value = record_field(env.store, key, field)?;
+    Some(match env.fold {
+        Some(fold) => fold_value(value, fold),
+        None => value,
+    })
+}
+
+fn check_predicate_recursive(pred: &Predicate, key: &str, env: &EvalEnv) -> bool {
+    let ctx = env.ctx;
     match pred {
         Predicate::And { predicates } => predicates
             .iter()
-            .all(|p| check_predicate_recursive(p, key, store, ctx)),
+            .all(|p| check_predicate_recursive(p, key, env)),
         Predicate::Or { predicates } => predicates
             .iter()
-            .any(|p| check_predicate_recursive(p, key, store, ctx)),
+            .any(|p| check_predicate_recursive(p, key, env)),
+        Predicate::Not { predicate } => !check_predicate_recursive(predicate, key, env),
+        Predicate::Fold { case, predicate } => {
+            let folded = EvalEnv {
+                fold: Some(*case),
+                ..*env
+            };
+            check_predicate_recursive(predicate, key, &folded)
+        }
         Predicate::Prefix { field, prefix } => {
             if field.segments().len() == 1 && field.segments()[0] == "id" {
                 return key.starts_with(prefix.as_str());
             }
-            if let Some(row) = store.get_row_by_key(key) {
-                if let Some(val) = resolve_field(Some(row), field) {
-                    if let Sp00kyValue::Str(s) = val {
-                        return s.starts_with(prefix.as_str());
+            field_value(key, env, field)
+                .is_some_and(|v| v.as_str().is_some_and(|s| s.starts_with(prefix.as_str())))
+        }
+        Predicate::Expr { expr } => expr.matches_at(env.store.get_row_by_key(key), ctx, env.now),
+        Predicate::IsNone { field } => {
+            matches!(field_value(key, env, field), None | Some(Sp00kyValue::Null))
+        }
+        Predicate::Fuzzy { field, value } => {
+            match (field_value(key, env, field), resolve_predicate_value(value, ctx)) {
+                (Some(Sp00kyValue::Str(s)), Some(Sp00kyValue::Str(q))) => {
+                    s.to_lowercase().contains(&q.to_lowercase())
+                }
+                _ => false,
+            }
+        }
+        Predicate::Regex { field, pattern } => {
+            match (field_value(key, env, field), env.regexes.get(pattern)) {
+                (Some(Sp00kyValue::Str(s)), Some(re)) => re.is_match(&s),
+                _ => false,
+            }
+        }
+        Predicate::Inside { field, value }
+        | Predicate::Contains { field, value }
+        | Predicate::ContainsAny { field, value }
+        | Predicate::ContainsAll { field, value } => {
+            let (Some(target), Some(actual)) = (
+                resolve_predicate_value(value, ctx),
+                field_value(key, env, field),
+            ) else {
+                return false;
+            };
+            match pred {
+                // Membership of the value itself: an empty array is inside
+                // only a list that holds an empty array.
+                Predicate::Inside { .. } => match &actual {
+                    Sp00kyValue::Array(items) if items.is_empty() => {
+                        as_list(target).iter().any(|t| matches!(t, Sp00kyValue::Array(a) if a.is_empty()))
                     }
+                    _ => contains_value(&target, &actual),
+                },
+                Predicate::Contains { .. } => contains_value(&actual, &target),
+                Predicate::ContainsAny { .. } => {
+                    as_list(target).iter().any(|t| contains_value(&actual, t))
                 }
+                _ => as_list(target).iter().all(|t| contains_value(&actual, t)),
             }
-            false
         }
         Predicate::Eq { field, value }
         | Predicate::Neq { field, value }
@@ -116,9 +305,7 @@ fn check_predicate_recursive(
                 None => return false,
             };
 
-            let actual = store
-                .get_row_by_key(key)
-                .and_then(|r| resolve_field(Some(r), field).cloned());
+            let actual = field_value(key, env, field);
 
             if let Some(actual) = actual {
                 let ord = compare_values(Some(&actual), Some(&target));
@@ -146,7 +333,7 @@ mod tests {
     use serde_json::json;
 
     fn zset(items: &[(&str, i64)]) -> ZSet {
-        items.iter().map(|(k, w)| (k.to_string(), *w)).collect()
+        items.iter().map(|(k, w)| ((*k).into(), *w)).collect()
     }
 
     #[test]
@@ -202,4 +389,106 @@ mod tests {
         let result = filter.step(&[&delta], &store, None);
         assert_eq!(result.get("users:1"), Some(&-1));
     }
+
+    fn tagged_store() -> Store {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("task", "task:1", json!({"status": "open", "tags": ["bug", "ui"], "title": "Fix login"})));
+        store.apply_change(&Change::create("task", "task:2", json!({"status": "done", "tags": ["docs"], "title": "Write guide"})));
+        store.apply_change(&Change::create("task", "task:3", json!({"status": "pending", "tags": [], "assignee": null, "title": "Triage"})));
+        store
+    }
+
+    fn matching(pred: Predicate, store: &Store) -> Vec<String> {
+        let input = zset(&[("task:1", 1), ("task:2", 1), ("task:3", 1)]);
+        let mut keys: Vec<String> = Filter::new(pred)
+            .snapshot(&[&input], store, None)
+            .into_keys()
+            .map(|k| k.to_string())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    fn pred(value: serde_json::Value) -> Predicate {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn inside_matches_listed_values() {
+        let store = tagged_store();
+        let p = pred(json!({"type": "inside", "field": "status", "value": ["open", "pending"]}));
+        assert_eq!(matching(p, &store), vec!["task:1", "task:3"]);
+
+        // An array field is one value, not a set of elements.
+        let p = pred(json!({"type": "inside", "field": "tags", "value": ["bug", "ui", "docs"]}));
+        assert!(matching(p, &store).is_empty());
+        let p = pred(json!({"type": "inside", "field": "tags", "value": [["bug", "ui"], ["docs"]]}));
+        assert_eq!(matching(p, &store), vec!["task:1", "task:2"]);
+
+        // An empty array is inside only a list holding an empty array.
+        let p = pred(json!({"type": "inside", "field": "tags", "value": ["bug"]}));
+        assert!(matching(p, &store).is_empty());
+        let p = pred(json!({"type": "inside", "field": "tags", "value": [[]]}));
+        assert_eq!(matching(p, &store), vec!["task:3"]);
+    }
+
+    #[test]
+    fn contains_checks_array_elements_and_substrings() {
+        let store = tagged_store();
+        let p = pred(json!({"type": "contains", "field": "tags", "value": "bug"}));
+        assert_eq!(matching(p, &store), vec!["task:1"]);
+
+        let p = pred(json!({"type": "contains", "field": "title", "value": "guide"}));
+        assert_eq!(matching(p, &store), vec!["task:2"]);
+
+        let p = pred(json!({"type": "containsany", "field": "tags", "value": ["docs", "ui"]}));
+        assert_eq!(matching(p, &store), vec!["task:1", "task:2"]);
+
+        let p = pred(json!({"type": "containsall", "field": "tags", "value": ["bug", "docs"]}));
+        assert!(matching(p, &store).is_empty());
+    }
+
+    #[test]
+    fn is_none_and_not() {
+        let store = tagged_store();
+        let p = pred(json!({"type": "isnone", "field": "assignee"}));
+        assert_eq!(matching(p, &store), vec!["task:1", "task:2", "task:3"]);
+
+        let p = pred(json!({
+            "type": "not",
+            "predicate": {"type": "inside", "field": "status", "value": ["open", "pending"]}
+        }));
+        assert_eq!(matching(p, &store), vec!["task:2"]);
+    }
+
+    #[test]
+    fn fuzzy_fold_and_regex_string_matching() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("user", "user:1", json!({"name": "Ada Lovelace", "email": "ada@example.com"})));
+        store.apply_change(&Change::create("user", "user:2", json!({"name": "Alan Turing", "email": "alan@test.org"})));
+        let input = zset(&[("user:1", 1), ("user:2", 1)]);
+        let ctx = Sp00kyValue::from(json!({"q": "love"}));
+        let keys = |pred: serde_json::Value| {
+            let mut keys: Vec<String> = Filter::new(serde_json::from_value(pred).unwrap())
+                .snapshot(&[&input], &store, Some(&ctx))
+                .into_keys()
+                .map(|k| k.to_string())
+                .collect();
+            keys.sort();
+            keys
+        };
+
+        assert_eq!(keys(json!({"type": "fuzzy", "field": "name", "value": "TURING"})), vec!["user:2"]);
+        assert_eq!(
+            keys(json!({"type": "fold", "case": "lower", "predicate":
+                {"type": "contains", "field": "name", "value": {"$param": "q"}}})),
+            vec!["user:1"]
+        );
+        // Without folding, "Lovelace" does not contain "love".
+        assert!(keys(json!({"type": "contains", "field": "name", "value": {"$param": "q"}})).is_empty());
+        assert_eq!(
+            keys(json!({"type": "regex", "field": "email", "pattern": "@example\\.(com|org)$"})),
+            vec!["user:1"]
+        );
+    }
 }
@@ -1,14 +1,16 @@
-use crate::algebra::{ZSet, ZSetOps};
+use crate::algebra::{RowKey, ZSet, ZSetOps};
 use crate::circuit::store::Store;
-use crate::eval::value_ops::{compare_values, hash_value, resolve_field};
-use crate::operator::plan::JoinCondition;
+use crate::eval::value_ops::{compare_values, hash_value, number_cmp, resolve_field, scalar_cmp, sort_cmp};
+use crate::operator::plan::{JoinCondition, JoinKind, JoinRange, RangeOp};
 use crate::types::Sp00kyValue;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Bound;
 
 /// Join operator with Z⁻¹ integration state for both inputs.
 ///
-/// DBSP delta rule for equi-join:
+/// DBSP delta rule for joins:
 ///   delta_out = (delta_A ⋈ state_B)
 ///             + (state_A ⋈ delta_B)
 ///             + (delta_A ⋈ delta_B)
@@ -17,72 +19,301 @@ use std::collections::HashMap;
 ///   1. Compute the three join terms using stored state
 ///   2. Update state: state_A += delta_A, state_B += delta_B
 ///   3. Return the sum of the three terms
+///
+/// Each term is a hash join on the (composite) equality key; conditions
+/// with only range comparisons probe a sorted index on the right side
+/// instead.
+///
+/// A left outer join adds the unmatched left records:
+///   out = (A ⋈ B) + { a ∈ A : a has no match in B }
+/// The second term is not bilinear, so its delta is recomputed for the
+/// left keys the step can affect (those in delta_A, and those matching
+/// delta_B) before and after the state update. Left rows are indexed by
+/// their equality key, so only the keys delta_B touches are revisited.
 #[derive(Debug)]
 pub struct Join {
     pub condition: JoinCondition,
+    pub kind: JoinKind,
     /// Z⁻¹ accumulated state for left input.
     pub left_state: ZSet,
     /// Z⁻¹ accumulated state for right input.
     pub right_state: ZSet,
+    /// Left keys whose set of matches changed since the last drain.
+    rematched: HashSet<RowKey>,
+    /// Left-join only: keys of `left_state` by the hash of their equality
+    /// key values, and the hash each key was indexed under.
+    left_index: HashMap<u64, HashSet<RowKey>>,
+    left_hashes: HashMap<RowKey, u64>,
+}
+
+/// A right-side row as held by a join index.
+type IndexedRow<'a> = (&'a RowKey, i64, &'a Sp00kyValue);
+
+/// Key of the range-join index: `sort_cmp` order, except that numbers
+/// compare by value alone, so `Int(5)` and `Float(5.0)` share an entry and
+/// range scans do not skip equal keys of another numeric type.
+#[derive(Debug)]
+struct RangeKey<'a>(&'a Sp00kyValue);
+
+impl Ord for RangeKey<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        number_cmp(self.0, other.0).unwrap_or_else(|| sort_cmp(self.0, other.0))
+    }
+}
+
+impl PartialOrd for RangeKey<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RangeKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RangeKey<'_> {}
+
+/// Whether `l <op> r` holds. Values of different types never compare,
+/// except numbers with numbers (by value) and record links with strings.
+fn range_holds(l: &Sp00kyValue, op: RangeOp, r: &Sp00kyValue) -> bool {
+    let ord = match (l, r) {
+        (Sp00kyValue::Null, _) | (_, Sp00kyValue::Null) => return false,
+        (Sp00kyValue::Str(_) | Sp00kyValue::RecordId(_), Sp00kyValue::Str(_) | Sp00kyValue::RecordId(_)) => {
+            match scalar_cmp(l, r) {
+                Some(ord) => ord,
+                None => return false,
+            }
+        }
+        _ => match number_cmp(l, r) {
+            Some(ord) => ord,
+            None if std::mem::discriminant(l) == std::mem::discriminant(r) => sort_cmp(l, r),
+            None => return false,
+        },
+    };
+    match op {
+        RangeOp::Gt => ord == Ordering::Greater,
+        RangeOp::Gte => ord != Ordering::Less,
+        RangeOp::Lt => ord == Ordering::Less,
+        RangeOp::Lte => ord != Ordering::Greater,
+    }
+}
+
+fn ranges_hold(ranges: &[JoinRange], l_row: &Sp00kyValue, r_row: &Sp00kyValue) -> bool {
+    ranges.iter().all(|range| {
+        match (
+            resolve_field(Some(l_row), &range.left),
+            resolve_field(Some(r_row), &range.right),
+        ) {
+            (Some(l), Some(r)) => range_holds(l, range.op, r),
+            _ => false,
+        }
+    })
 }
 
 impl Join {
     pub fn new(condition: JoinCondition) -> Self {
         Self {
             condition,
+            kind: JoinKind::Inner,
             left_state: HashMap::new(),
             right_state: HashMap::new(),
+            rematched: HashSet::new(),
+            left_index: HashMap::new(),
+            left_hashes: HashMap::new(),
         }
     }
 
-    /// Hash-join: probe left against right using the join condition.
+    pub fn with_kind(mut self, kind: JoinKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Left records of `left` with no match in `right`, at their own weight.
+    fn unmatched(left: &ZSet, right: &ZSet, condition: &JoinCondition, store: &Store) -> ZSet {
+        let probe: ZSet = left.keys().map(|k| (k.clone(), 1)).collect();
+        let matched = Self::hash_join(&probe, right, condition, store);
+        left.iter()
+            .filter(|(k, _)| matched.get(*k).copied().unwrap_or(0) <= 0)
+            .map(|(k, &w)| (k.clone(), w))
+            .collect()
+    }
+
+    /// Re-index the left keys of `delta_a` after it was added to `left_state`.
+    fn index_left(&mut self, delta_a: &ZSet, store: &Store) {
+        let equalities = self.condition.equalities();
+        for key in delta_a.keys() {
+            if let Some(hash) = self.left_hashes.remove(key) {
+                if let Some(keys) = self.left_index.get_mut(&hash) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        self.left_index.remove(&hash);
+                    }
+                }
+            }
+            if !self.left_state.contains_key(key) {
+                continue;
+            }
+            let hash = store
+                .get_row_by_key(key)
+                .and_then(|row| Self::composite_hash(row, equalities.iter().map(|(l, _)| *l)));
+            if let Some(hash) = hash {
+                self.left_index.entry(hash).or_default().insert(key.clone());
+                self.left_hashes.insert(key.clone(), hash);
+            }
+        }
+    }
+
+    /// Left rows of `left_state + delta_a` that share an equality key with
+    /// a row of `delta_b`, looked up in the left index.
+    fn left_candidates(&self, delta_a: &ZSet, delta_b: &ZSet, store: &Store) -> ZSet {
+        let equalities = self.condition.equalities();
+        let mut keys: HashSet<RowKey> = delta_a.keys().cloned().collect();
+        for r_key in delta_b.keys() {
+            let hash = store
+                .get_row_by_key(r_key)
+                .and_then(|row| Self::composite_hash(row, equalities.iter().map(|(_, r)| *r)));
+            if let Some(left_keys) = hash.and_then(|hash| self.left_index.get(&hash)) {
+                keys.extend(left_keys.iter().cloned());
+            }
+        }
+        let mut candidates = Self::restrict(&self.left_state, &keys);
+        candidates.add(delta_a);
+        candidates
+    }
+
+    /// Restrict `zset` to `keys`.
+    fn restrict(zset: &ZSet, keys: &HashSet<RowKey>) -> ZSet {
+        keys.iter()
+            .filter_map(|k| zset.get(k).map(|&w| (k.clone(), w)))
+            .collect()
+    }
+
+    /// Join `left` with `right` using the join condition.
     /// Output weight = left_weight * right_weight (ring multiplication).
     fn hash_join(left: &ZSet, right: &ZSet, condition: &JoinCondition, store: &Store) -> ZSet {
         if left.is_empty() || right.is_empty() {
             return HashMap::new();
         }
 
+        let mut out = if condition.equalities().is_empty() {
+            Self::range_join(left, right, condition, store)
+        } else {
+            Self::equi_join(left, right, condition, store)
+        };
+
+        // Clean up zero weights
+        out.retain(|_, w| *w != 0);
+        out
+    }
+
+    /// Hash of the composite key values, or `None` if a key field is missing.
+    fn composite_hash<'a>(
+        row: &'a Sp00kyValue,
+        fields: impl Iterator<Item = &'a crate::types::Path>,
+    ) -> Option<u64> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for field in fields {
+            hash_value(resolve_field(Some(row), field)?).hash(&mut hasher);
+        }
+        Some(hasher.finish())
+    }
+
+    fn equi_join(left: &ZSet, right: &ZSet, condition: &JoinCondition, store: &Store) -> ZSet {
+        let equalities = condition.equalities();
+
         // Build index on the right side
-        let mut right_index: HashMap<u64, Vec<(&String, &i64, &Sp00kyValue)>> = HashMap::new();
-        for (r_key, r_weight) in right {
-            if let Some(r_val) = store.get_row_by_key(r_key) {
-                if let Some(r_field) = resolve_field(Some(r_val), &condition.right_field) {
-                    let hash = hash_value(r_field);
-                    right_index
-                        .entry(hash)
-                        .or_default()
-                        .push((r_key, r_weight, r_field));
-                }
+        let mut right_index: HashMap<u64, Vec<IndexedRow>> = HashMap::new();
+        for (r_key, &r_weight) in right {
+            let Some(r_row) = store.get_row_by_key(r_key) else {
+                continue;
+            };
+            if let Some(hash) = Self::composite_hash(r_row, equalities.iter().map(|(_, r)| *r)) {
+                right_index.entry(hash).or_default().push((r_key, r_weight, r_row));
             }
         }
 
         // Probe from the left side
         let mut out = HashMap::new();
         for (l_key, &l_weight) in left {
-            if let Some(l_val) = store.get_row_by_key(l_key) {
-                if let Some(l_field) = resolve_field(Some(l_val), &condition.left_field) {
-                    let hash = hash_value(l_field);
-                    if let Some(matches) = right_index.get(&hash) {
-                        for (_r_key, &r_weight, r_field) in matches {
-                            if compare_values(Some(l_field), Some(r_field)) == Ordering::Equal {
-                                let w = l_weight * r_weight;
-                                *out.entry(l_key.clone()).or_insert(0) += w;
-                            }
-                        }
-                    }
+            let Some(l_row) = store.get_row_by_key(l_key) else {
+                continue;
+            };
+            let Some(hash) = Self::composite_hash(l_row, equalities.iter().map(|(l, _)| *l)) else {
+                continue;
+            };
+            let Some(matches) = right_index.get(&hash) else {
+                continue;
+            };
+            for &(_r_key, r_weight, r_row) in matches {
+                let keys_equal = equalities.iter().all(|(lf, rf)| {
+                    compare_values(resolve_field(Some(l_row), lf), resolve_field(Some(r_row), rf))
+                        == Ordering::Equal
+                });
+                if keys_equal && ranges_hold(&condition.ranges, l_row, r_row) {
+                    *out.entry(l_key.clone()).or_insert(0) += l_weight * r_weight;
                 }
             }
         }
+        out
+    }
 
-        // Clean up zero weights
-        out.retain(|_, w| *w != 0);
+    /// Range-only join: index the right side by the first range's right
+    /// field and scan the matching half-open interval for each left row.
+    fn range_join(left: &ZSet, right: &ZSet, condition: &JoinCondition, store: &Store) -> ZSet {
+        let mut out = HashMap::new();
+        let Some(first) = condition.ranges.first() else {
+            return out;
+        };
+
+        let mut right_index: BTreeMap<RangeKey, Vec<IndexedRow>> = BTreeMap::new();
+        for (r_key, &r_weight) in right {
+            let Some(r_row) = store.get_row_by_key(r_key) else {
+                continue;
+            };
+            if let Some(value) = resolve_field(Some(r_row), &first.right) {
+                right_index
+                    .entry(RangeKey(value))
+                    .or_default()
+                    .push((r_key, r_weight, r_row));
+            }
+        }
+
+        for (l_key, &l_weight) in left {
+            let Some(l_row) = store.get_row_by_key(l_key) else {
+                continue;
+            };
+            let Some(l_value) = resolve_field(Some(l_row), &first.left) else {
+                continue;
+            };
+            // `l <op> r` restated as a bound on `r`.
+            let probe = RangeKey(l_value);
+            let bounds = match first.op {
+                RangeOp::Gt => (Bound::Unbounded, Bound::Excluded(probe)),
+                RangeOp::Gte => (Bound::Unbounded, Bound::Included(probe)),
+                RangeOp::Lt => (Bound::Excluded(probe), Bound::Unbounded),
+                RangeOp::Lte => (Bound::Included(probe), Bound::Unbounded),
+            };
+            for (_, rows) in right_index.range(bounds) {
+                for &(_r_key, r_weight, r_row) in rows {
+                    if ranges_hold(&condition.ranges, l_row, r_row) {
+                        *out.entry(l_key.clone()).or_insert(0) += l_weight * r_weight;
+                    }
+                }
+            }
+        }
         out
     }
 }
 
 impl super::Operator for Join {
     fn snapshot(&self, inputs: &[&ZSet], store: &Store, _ctx: Option<&Sp00kyValue>) -> ZSet {
-        Self::hash_join(inputs[0], inputs[1], &self.condition, store)
+        let mut out = Self::hash_join(inputs[0], inputs[1], &self.condition, store);
+        if self.kind == JoinKind::Left {
+            out.add(&Self::unmatched(inputs[0], inputs[1], &self.condition, store));
+        }
+        out
     }
 
     fn step(
@@ -99,14 +330,39 @@ impl super::Operator for Join {
         let term2 = Self::hash_join(&self.left_state, delta_b, &self.condition, store);
         let term3 = Self::hash_join(delta_a, delta_b, &self.condition, store);
 
+        // Left keys whose unmatched status may flip in this step.
+        let mut affected = HashSet::new();
+        let mut unmatched_before = ZSet::new();
+        if self.kind == JoinKind::Left {
+            let candidates = self.left_candidates(delta_a, delta_b, store);
+            let rematched: HashSet<RowKey> = Self::hash_join(&candidates, delta_b, &self.condition, store)
+                .into_keys()
+                .collect();
+            affected.extend(delta_a.keys().cloned());
+            affected.extend(rematched.iter().cloned());
+            self.rematched.extend(rematched);
+
+            let before = Self::restrict(&self.left_state, &affected);
+            unmatched_before = Self::unmatched(&before, &self.right_state, &self.condition, store);
+        }
+
         // Update Z⁻¹ integration state AFTER computing delta
         self.left_state.add(delta_a);
         self.right_state.add(delta_b);
+        if self.kind == JoinKind::Left {
+            self.index_left(delta_a, store);
+        }
 
         // Sum the three terms
         let mut result = term1;
         result.add(&term2);
         result.add(&term3);
+
+        if self.kind == JoinKind::Left {
+            let after = Self::restrict(&self.left_state, &affected);
+            result.add(&Self::unmatched(&after, &self.right_state, &self.condition, store));
+            result.add(&unmatched_before.negate());
+        }
         result
     }
 
@@ -114,9 +370,20 @@ impl super::Operator for Join {
         2
     }
 
+    fn state_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![("left", self.left_state.len()), ("right", self.right_state.len())]
+    }
+
     fn reset(&mut self) {
         self.left_state.clear();
         self.right_state.clear();
+        self.rematched.clear();
+        self.left_index.clear();
+        self.left_hashes.clear();
+    }
+
+    fn drain_changed_rows(&mut self) -> Vec<RowKey> {
+        self.rematched.drain().collect()
     }
 }
 
@@ -129,7 +396,7 @@ mod tests {
     use serde_json::json;
 
     fn zset(items: &[(&str, i64)]) -> ZSet {
-        items.iter().map(|(k, w)| (k.to_string(), *w)).collect()
+        items.iter().map(|(k, w)| ((*k).into(), *w)).collect()
     }
 
     fn setup_store() -> Store {
@@ -152,10 +419,7 @@ mod tests {
     #[test]
     fn snapshot_produces_matching_pairs() {
         let store = setup_store();
-        let join = Join::new(JoinCondition {
-            left_field: Path::new("id"),
-            right_field: Path::new("author"),
-        });
+        let join = Join::new(JoinCondition::eq(Path::new("id"), Path::new("author")));
 
         let left = zset(&[("users:1", 1)]);
         let right = zset(&[("posts:1", 1)]);
@@ -167,10 +431,7 @@ mod tests {
     #[test]
     fn step_handles_deletion_from_one_side() {
         let store = setup_store();
-        let condition = JoinCondition {
-            left_field: Path::new("id"),
-            right_field: Path::new("author"),
-        };
+        let condition = JoinCondition::eq(Path::new("id"), Path::new("author"));
         let mut join = Join::new(condition);
 
         // Initial step: add both sides
@@ -191,10 +452,7 @@ mod tests {
         // The fundamental DBSP correctness property:
         // step(dA, dB) == snapshot(A+dA, B+dB) - snapshot(A, B)
         let mut store = setup_store();
-        let condition = JoinCondition {
-            left_field: Path::new("id"),
-            right_field: Path::new("author"),
-        };
+        let condition = JoinCondition::eq(Path::new("id"), Path::new("author"));
 
         // Initial state
         let state_a = zset(&[("users:1", 1)]);
@@ -232,4 +490,126 @@ mod tests {
 
         assert_eq!(actual_delta, expected_delta);
     }
+
+    #[test]
+    fn composite_key_requires_every_column() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("member", "member:1", json!({"tenant": "t1", "user": "u1"})));
+        store.apply_change(&Change::create("member", "member:2", json!({"tenant": "t2", "user": "u1"})));
+        store.apply_change(&Change::create("role", "role:1", json!({"tenant": "t1", "user": "u1"})));
+
+        let mut condition = JoinCondition::eq(Path::new("tenant"), Path::new("tenant"));
+        condition.keys.push(crate::operator::plan::JoinKey {
+            left: Path::new("user"),
+            right: Path::new("user"),
+        });
+        let join = Join::new(condition);
+        let result = join.snapshot(
+            &[&zset(&[("member:1", 1), ("member:2", 1)]), &zset(&[("role:1", 1)])],
+            &store,
+            None,
+        );
+        assert_eq!(result, zset(&[("member:1", 1)]));
+    }
+
+    #[test]
+    fn range_join_matches_events_inside_windows() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("event", "event:1", json!({"ts": 5})));
+        store.apply_change(&Change::create("event", "event:2", json!({"ts": 15})));
+        store.apply_change(&Change::create("event", "event:3", json!({"ts": 25.5})));
+        store.apply_change(&Change::create("window", "window:1", json!({"start": 0, "end": 10})));
+        store.apply_change(&Change::create("window", "window:2", json!({"start": 20, "end": 30})));
+
+        let mut condition = JoinCondition::eq(Path::default(), Path::default());
+        condition.ranges = vec![
+            JoinRange { left: Path::new("ts"), op: RangeOp::Gte, right: Path::new("start") },
+            JoinRange { left: Path::new("ts"), op: RangeOp::Lt, right: Path::new("end") },
+        ];
+        let mut join = Join::new(condition);
+        let events = zset(&[("event:1", 1), ("event:2", 1), ("event:3", 1)]);
+        let windows = zset(&[("window:1", 1), ("window:2", 1)]);
+        let result = join.step(&[&events, &windows], &store, None);
+        assert_eq!(result, zset(&[("event:1", 1), ("event:3", 1)]));
+
+        // Removing a window retracts the events it matched.
+        let empty = ZSet::new();
+        let result = join.step(&[&empty, &zset(&[("window:2", -1)])], &store, None);
+        assert_eq!(result, zset(&[("event:3", -1)]));
+    }
+
+    #[test]
+    fn left_join_revisits_rows_under_their_current_key() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("member", "member:a", json!({"team": "t1"})));
+        store.apply_change(&Change::create("member", "member:b", json!({"team": "t2"})));
+        store.apply_change(&Change::create("team", "team:2", json!({"team": "t2"})));
+
+        let condition = JoinCondition::eq(Path::new("team"), Path::new("team"));
+        let mut join = Join::new(condition).with_kind(JoinKind::Left);
+        let members = zset(&[("member:a", 1), ("member:b", 1)]);
+        join.step(&[&members, &ZSet::new()], &store, None);
+        join.drain_changed_rows();
+
+        join.step(&[&ZSet::new(), &zset(&[("team:2", 1)])], &store, None);
+        assert_eq!(join.drain_changed_rows(), vec!["member:b".to_string()]);
+
+        // member:b moves to t1: retracted under its old key, re-added under the new one.
+        join.step(&[&zset(&[("member:b", -1)]), &ZSet::new()], &store, None);
+        store.apply_change(&Change::update("member", "member:b", json!({"team": "t1"})));
+        join.step(&[&zset(&[("member:b", 1)]), &ZSet::new()], &store, None);
+        join.drain_changed_rows();
+
+        store.apply_change(&Change::create("team", "team:1", json!({"team": "t1"})));
+        join.step(&[&ZSet::new(), &zset(&[("team:1", 1)])], &store, None);
+        let mut rematched = join.drain_changed_rows();
+        rematched.sort();
+        assert_eq!(rematched, vec!["member:a".to_string(), "member:b".to_string()]);
+    }
+
+    #[test]
+    fn range_bounds_compare_int_and_float_by_value() {
+        assert!(range_holds(&Sp00kyValue::Int(5), RangeOp::Gte, &Sp00kyValue::Float(5.0)));
+        assert!(range_holds(&Sp00kyValue::Float(5.0), RangeOp::Lte, &Sp00kyValue::Int(5)));
+        assert!(!range_holds(&Sp00kyValue::Int(5), RangeOp::Gt, &Sp00kyValue::Float(5.0)));
+
+        let mut store = Store::new();
+        store.apply_change(&Change::create("event", "event:1", json!({"ts": 5})));
+        store.apply_change(&Change::create("event", "event:2", json!({"ts": 10.0})));
+        store.apply_change(&Change::create("window", "window:1", json!({"start": 5.0, "end": 10})));
+
+        let mut condition = JoinCondition::eq(Path::default(), Path::default());
+        condition.ranges = vec![
+            JoinRange { left: Path::new("ts"), op: RangeOp::Gte, right: Path::new("start") },
+            JoinRange { left: Path::new("ts"), op: RangeOp::Lte, right: Path::new("end") },
+        ];
+        let result = Join::new(condition).snapshot(
+            &[&zset(&[("event:1", 1), ("event:2", 1)]), &zset(&[("window:1", 1)])],
+            &store,
+            None,
+        );
+        assert_eq!(result, zset(&[("event:1", 1), ("event:2", 1)]));
+    }
+
+    #[test]
+    fn left_join_step_matches_snapshot_diff() {
+        let store = setup_store();
+        let condition = JoinCondition::eq(Path::new("id"), Path::new("author"));
+        let mut join = Join::new(condition.clone()).with_kind(JoinKind::Left);
+
+        let users = zset(&[("users:1", 1), ("users:2", 1)]);
+        let initial = join.step(&[&users, &ZSet::new()], &store, None);
+        assert_eq!(initial, users);
+
+        let posts = zset(&[("posts:1", 1)]);
+        let delta = join.step(&[&ZSet::new(), &posts], &store, None);
+        // users:1 now matches: the unmatched row is replaced by the match.
+        assert!(delta.is_empty());
+        assert_eq!(join.drain_changed_rows(), vec!["users:1".to_string()]);
+
+        let snapshot = Join::new(condition).with_kind(JoinKind::Left).snapshot(&[&users, &posts], &store, None);
+        let mut integrated = initial;
+        integrated.add(&delta);
+        assert_eq!(integrated, snapshot);
+    }
 }
@@ -1,7 +1,10 @@
-use crate::algebra::ZSet;
+use crate::algebra::{RowKey, ZSet, ZSetOps};
 use crate::circuit::store::Store;
-use crate::types::Sp00kyValue;
+use crate::eval::value_ops::resolve_field;
+use crate::types::{Path, Sp00kyValue};
+use std::collections::{HashMap, HashSet};
 
+use super::aggregate::set_path;
 use super::plan::Projection;
 
 /// Map operator: field projection / rename.
@@ -12,14 +15,76 @@ use super::plan::Projection;
 ///
 /// Stateless (arity 1). The delta rule is the identity:
 /// `delta_out = delta_in`
+///
+/// With computed columns (`Projection::Expr`) the projected row is no
+/// longer the stored record, so the operator keeps the current row of each
+/// key it passes and exposes it as a derived row. `time::now()` in a column
+/// is read when the row is projected; a clock tick alone does not recompute it.
 #[derive(Debug)]
 pub struct Map {
     pub projections: Vec<Projection>,
+    /// Whether any projection is computed.
+    computed: bool,
+    /// Accumulated input weights (computed projections only).
+    members: ZSet,
+    /// Projected row per present key (computed projections only).
+    rows: HashMap<RowKey, Sp00kyValue>,
+    /// Keys whose projected row changed since the last drain.
+    dirty: HashSet<RowKey>,
+    /// Current clock, Unix milliseconds (set by `tick`).
+    now: Option<i64>,
 }
 
 impl Map {
     pub fn new(projections: Vec<Projection>) -> Self {
-        Self { projections }
+        let computed = projections
+            .iter()
+            .any(|p| matches!(p, Projection::Expr { .. }));
+        Self {
+            projections,
+            computed,
+            members: HashMap::new(),
+            rows: HashMap::new(),
+            dirty: HashSet::new(),
+            now: None,
+        }
+    }
+
+    /// The projected row of `key`: selected fields under their aliases,
+    /// computed columns, `id`, and the record version as `_00_rv`.
+    pub(crate) fn project(&self, key: &str, store: &Store, ctx: Option<&Sp00kyValue>) -> Sp00kyValue {
+        let record = store.get_row_by_key(key);
+        let mut row = Sp00kyValue::Object(HashMap::new());
+        for projection in &self.projections {
+            match projection {
+                Projection::All => {
+                    if let (Sp00kyValue::Object(out), Some(Sp00kyValue::Object(fields))) =
+                        (&mut row, record)
+                    {
+                        out.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+                    }
+                }
+                Projection::Field { field, alias } => {
+                    let value = resolve_field(record, field).cloned().unwrap_or_default();
+                    match alias {
+                        Some(alias) => set_path(&mut row, &Path::new(alias), value),
+                        None => set_path(&mut row, field, value),
+                    }
+                }
+                Projection::Expr { expr, alias } => {
+                    set_path(&mut row, &Path::new(alias), expr.eval_at(record, ctx, self.now));
+                }
+                // Related records are tracked by the view, not the row.
+                Projection::Subquery { .. } | Projection::Graph { .. } | Projection::Link { .. } => {}
+            }
+        }
+        if let Sp00kyValue::Object(map) = &mut row {
+            map.insert("id".into(), Sp00kyValue::Str(key.into()));
+            if let Some(version) = store.get_record_version_by_key(key) {
+                map.insert("_00_rv".into(), Sp00kyValue::Int(version));
+            }
+        }
+        row
     }
 }
 
@@ -33,15 +98,109 @@ impl super::Operator for Map {
     fn step(
         &mut self,
         input_deltas: &[&ZSet],
-        _store: &Store,
-        _ctx: Option<&Sp00kyValue>,
+        store: &Store,
+        ctx: Option<&Sp00kyValue>,
     ) -> ZSet {
-        input_deltas[0].clone()
+        let delta = input_deltas[0];
+        if self.computed {
+            self.members.add(delta);
+            for key in delta.keys() {
+                if self.members.is_present(key) {
+                    let row = self.project(key, store, ctx);
+                    if self.rows.get(key) != Some(&row) {
+                        self.rows.insert(key.clone(), row);
+                        self.dirty.insert(key.clone());
+                    }
+                } else {
+                    self.rows.remove(key);
+                }
+            }
+        }
+        delta.clone()
     }
 
     fn arity(&self) -> usize {
         1
     }
 
-    fn reset(&mut self) {}
+    fn state_sizes(&self) -> Vec<(&'static str, usize)> {
+        if self.computed {
+            vec![("rows", self.rows.len())]
+        } else {
+            vec![]
+        }
+    }
+
+    fn reset(&mut self) {
+        self.members.clear();
+        self.rows.clear();
+        self.dirty.clear();
+    }
+
+    fn derived_row(&self, key: &str) -> Option<Sp00kyValue> {
+        self.rows.get(key).cloned()
+    }
+
+    fn drain_changed_rows(&mut self) -> Vec<RowKey> {
+        self.dirty.drain().collect()
+    }
+
+    fn tick(&mut self, now: i64, _store: &Store, _ctx: Option<&Sp00kyValue>) -> ZSet {
+        self.now = Some(now);
+        HashMap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::store::Change;
+    use crate::eval::Expr;
+    use crate::operator::Operator;
+    use serde_json::json;
+
+    fn title_upper() -> Projection {
+        Projection::Expr {
+            expr: Expr::Call {
+                func: "string::uppercase".into(),
+                args: vec![Expr::field("title")],
+            },
+            alias: "shout".into(),
+        }
+    }
+
+    #[test]
+    fn computed_projection_exposes_projected_rows() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("post", "post:1", json!({"title": "boo", "body": "x"})));
+
+        let mut map = Map::new(vec![
+            Projection::Field { field: Path::new("title"), alias: Some("name".into()) },
+            title_upper(),
+        ]);
+        let delta: ZSet = [(RowKey::from("post:1"), 1)].into();
+        assert_eq!(map.step(&[&delta], &store, None), delta);
+
+        let row: serde_json::Value = map.derived_row("post:1").expect("row").into();
+        assert_eq!(row["name"], "boo");
+        assert_eq!(row["shout"], "BOO");
+        assert_eq!(row["id"], "post:1");
+        assert!(row.get("body").is_none());
+        assert_eq!(map.drain_changed_rows(), vec![RowKey::from("post:1")]);
+
+        let removal: ZSet = [(RowKey::from("post:1"), -1)].into();
+        map.step(&[&removal], &store, None);
+        assert!(map.derived_row("post:1").is_none());
+    }
+
+    #[test]
+    fn plain_projection_keeps_no_rows() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("post", "post:1", json!({"title": "boo"})));
+
+        let mut map = Map::new(vec![Projection::Field { field: Path::new("title"), alias: None }]);
+        let delta: ZSet = [(RowKey::from("post:1"), 1)].into();
+        map.step(&[&delta], &store, None);
+        assert!(map.derived_row("post:1").is_none());
+    }
 }
@@ -7,8 +7,9 @@ pub mod map;
 pub mod top_k;
 pub mod aggregate;
 pub mod distinct;
+pub mod optimize;
 
-use crate::algebra::ZSet;
+use crate::algebra::{RowKey, ZSet};
 use crate::circuit::store::Store;
 use crate::types::Sp00kyValue;
 use std::fmt::Debug;
@@ -49,18 +50,52 @@ pub trait Operator: Debug + Send + Sync {
     /// Reset all internal state (for re-initialization).
     fn reset(&mut self);
 
+    /// Sizes of the Z⁻¹ state this operator holds, by name (join sides,
+    /// TopK buffer, aggregate groups, ...). Stateless operators hold none.
+    fn state_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![]
+    }
+
     /// Base collections this operator directly reads from (Scan only).
     fn collections(&self) -> Vec<String> {
         vec![]
     }
+
+    /// Row content for keys this operator synthesizes (Aggregate groups).
+    ///
+    /// Operators that forward base-collection keys return `None` — their
+    /// rows live in the `Store`.
+    fn derived_row(&self, _key: &str) -> Option<Sp00kyValue> {
+        None
+    }
+
+    /// Drain the output keys whose content changed since the last call while
+    /// staying in the output (no membership delta was emitted): aggregate
+    /// values, or the matches of a left outer join.
+    fn drain_changed_rows(&mut self) -> Vec<RowKey> {
+        vec![]
+    }
+
+    /// Move the clock seen by `time::now()` to `now` (Unix milliseconds) and
+    /// return the output delta caused by the clock change alone.
+    ///
+    /// Only time-windowed filters produce anything; they re-evaluate the
+    /// records they hold against the new time.
+    fn tick(&mut self, _now: i64, _store: &Store, _ctx: Option<&Sp00kyValue>) -> ZSet {
+        ZSet::new()
+    }
 }
 
-pub use aggregate::{Aggregate, AggregateFunc};
+pub use aggregate::{Aggregate, AggregateFunc, AggregateSpec};
 pub use distinct::Distinct;
 pub use filter::Filter;
 pub use join::Join;
 pub use map::Map;
-pub use plan::{JoinCondition, OperatorPlan, OrderSpec, Projection, QueryPlan};
+pub use optimize::optimize;
+pub use plan::{
+    GraphDirection, GraphStep, JoinCondition, JoinKey, JoinKind, JoinRange, OperatorPlan, OrderSpec,
+    Projection, QueryPlan, RangeOp,
+};
 pub use predicate::Predicate;
 pub use scan::Scan;
 pub use top_k::TopK;
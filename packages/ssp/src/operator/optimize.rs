@@ -0,0 +1,181 @@
+use super::plan::{OperatorPlan, Projection};
+use super::predicate::Predicate;
+
+/// Rewrite a plan into an equivalent one that is cheaper to maintain.
+///
+/// - Filters above a join move to its left input. A join emits the keys
+///   of its left records, and a filter looks records up by key, so the
+///   predicate sees the same row on either side, and the join's state
+///   only holds records that pass it.
+/// - Adjacent filters merge into one.
+/// - Projections without computed columns are dropped: `Map` passes their
+///   keys through unchanged, and the view reads the selected fields from
+///   its own plan.
+///
+/// The view keeps the original plan; only the circuit graph is built from
+/// the optimized one.
+pub fn optimize(plan: &OperatorPlan) -> OperatorPlan {
+    match plan {
+        OperatorPlan::Scan { .. } => plan.clone(),
+        OperatorPlan::Filter { input, predicate } => push_filter(optimize(input), predicate.clone()),
+        OperatorPlan::Join {
+            left,
+            right,
+            on,
+            kind,
+        } => OperatorPlan::Join {
+            left: Box::new(optimize(left)),
+            right: Box::new(optimize(right)),
+            on: on.clone(),
+            kind: *kind,
+        },
+        OperatorPlan::Project { input, projections } => {
+            let input = optimize(input);
+            if projections.iter().any(|p| matches!(p, Projection::Expr { .. })) {
+                OperatorPlan::Project {
+                    input: Box::new(input),
+                    projections: projections.clone(),
+                }
+            } else {
+                input
+            }
+        }
+        OperatorPlan::Limit {
+            input,
+            limit,
+            order_by,
+            offset,
+        } => OperatorPlan::Limit {
+            input: Box::new(optimize(input)),
+            limit: *limit,
+            order_by: order_by.clone(),
+            offset: *offset,
+        },
+        OperatorPlan::Aggregate {
+            input,
+            group_by,
+            aggregates,
+        } => OperatorPlan::Aggregate {
+            input: Box::new(optimize(input)),
+            group_by: group_by.clone(),
+            aggregates: aggregates.clone(),
+        },
+        OperatorPlan::Distinct { input, fields } => OperatorPlan::Distinct {
+            input: Box::new(optimize(input)),
+            fields: fields.clone(),
+        },
+    }
+}
+
+/// Place `predicate` over an already optimized `input`, as far down the
+/// left spine of joins as it goes.
+fn push_filter(input: OperatorPlan, predicate: Predicate) -> OperatorPlan {
+    match input {
+        OperatorPlan::Join {
+            left,
+            right,
+            on,
+            kind,
+        } => OperatorPlan::Join {
+            left: Box::new(push_filter(*left, predicate)),
+            right,
+            on,
+            kind,
+        },
+        OperatorPlan::Filter {
+            input,
+            predicate: inner,
+        } => {
+            let mut predicates = match inner {
+                Predicate::And { predicates } => predicates,
+                other => vec![other],
+            };
+            match predicate {
+                Predicate::And { predicates: outer } => predicates.extend(outer),
+                other => predicates.push(other),
+            }
+            OperatorPlan::Filter {
+                input,
+                predicate: Predicate::And { predicates },
+            }
+        }
+        input => OperatorPlan::Filter {
+            input: Box::new(input),
+            predicate,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plan(value: serde_json::Value) -> OperatorPlan {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn shape(plan: &OperatorPlan) -> serde_json::Value {
+        serde_json::to_value(plan).unwrap()
+    }
+
+    #[test]
+    fn filters_move_below_joins_and_merge() {
+        let original = plan(json!({
+            "op": "filter",
+            "predicate": { "type": "eq", "field": "status", "value": "open" },
+            "input": {
+                "op": "join",
+                "left": {
+                    "op": "join",
+                    "left": {
+                        "op": "filter",
+                        "predicate": { "type": "gt", "field": "score", "value": 3 },
+                        "input": { "op": "scan", "table": "thread" },
+                    },
+                    "right": { "op": "scan", "table": "user" },
+                    "on": { "left_field": "author", "right_field": "id" },
+                },
+                "right": { "op": "scan", "table": "tag" },
+                "on": { "left_field": "tag", "right_field": "id" },
+            },
+        }));
+
+        let optimized = shape(&optimize(&original));
+        assert_eq!(optimized["op"], "join");
+        let inner = &optimized["left"]["left"];
+        assert_eq!(inner["op"], "filter");
+        assert_eq!(
+            inner["predicate"],
+            json!({ "type": "and", "predicates": [
+                { "type": "gt", "field": "score", "value": 3 },
+                { "type": "eq", "field": "status", "value": "open" },
+            ]})
+        );
+        assert_eq!(inner["input"], json!({ "op": "scan", "table": "thread" }));
+    }
+
+    #[test]
+    fn projections_without_computed_columns_are_dropped() {
+        let fields = plan(json!({
+            "op": "limit",
+            "limit": 10,
+            "input": {
+                "op": "project",
+                "projections": [{ "type": "all" }, { "type": "field", "name": "title", "alias": "t" }],
+                "input": { "op": "scan", "table": "thread" },
+            },
+        }));
+        assert_eq!(
+            shape(&optimize(&fields))["input"],
+            json!({ "op": "scan", "table": "thread" })
+        );
+
+        let computed = plan(json!({
+            "op": "project",
+            "projections": [{ "type": "expr", "alias": "n", "expr": { "type": "field", "path": "a" } }],
+            "input": { "op": "scan", "table": "thread" },
+        }));
+        assert_eq!(shape(&optimize(&computed))["op"], "project");
+    }
+}
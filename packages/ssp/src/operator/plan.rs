@@ -1,6 +1,9 @@
-use crate::types::Path;
+use crate::eval::value_ops::resolve_field;
+use crate::eval::Expr;
+use crate::types::{Path, Sp00kyValue};
 use serde::{Deserialize, Serialize};
 
+use super::aggregate::AggregateSpec;
 use super::predicate::Predicate;
 
 /// A query plan is a tree of operator descriptions.
@@ -34,6 +37,8 @@ pub enum OperatorPlan {
         left: Box<OperatorPlan>,
         right: Box<OperatorPlan>,
         on: JoinCondition,
+        #[serde(default)]
+        kind: JoinKind,
     },
     Project {
         input: Box<OperatorPlan>,
@@ -41,17 +46,107 @@ pub enum OperatorPlan {
     },
     Limit {
         input: Box<OperatorPlan>,
-        limit: usize,
+        /// Rows to emit; `None` for `START` without `LIMIT`, which emits
+        /// every row after the offset.
+        #[serde(default)]
+        limit: Option<usize>,
         #[serde(default)]
         order_by: Option<Vec<OrderSpec>>,
+        /// Rows to skip before the first emitted one (SurrealQL `START`).
+        #[serde(default)]
+        offset: usize,
+    },
+    /// GROUP BY / GROUP ALL with aggregate columns.
+    /// `group_by` is empty for GROUP ALL (a single global group).
+    Aggregate {
+        input: Box<OperatorPlan>,
+        #[serde(default)]
+        group_by: Vec<Path>,
+        aggregates: Vec<AggregateSpec>,
+    },
+    /// SELECT DISTINCT / GROUP BY without aggregates.
+    /// `fields` is empty for distinct-by-record-key.
+    Distinct {
+        input: Box<OperatorPlan>,
+        #[serde(default)]
+        fields: Vec<Path>,
     },
 }
 
-/// Condition for equi-joins.
+/// Join semantics. Output is keyed by the left record either way.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinKind {
+    /// Only left records with at least one match.
+    #[default]
+    Inner,
+    /// Every left record; unmatched ones are kept with their own weight.
+    Left,
+}
+
+/// Join condition: equalities (composite keys) plus optional range
+/// comparisons between the two sides.
+///
+/// `left_field = right_field` is the primary equality; it is absent (empty
+/// paths) for pure range joins.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct JoinCondition {
+    #[serde(default)]
     pub left_field: Path,
+    #[serde(default)]
     pub right_field: Path,
+    /// Further equalities, for composite keys (`tenant = m.tenant AND user = m.user`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<JoinKey>,
+    /// Non-equi comparisons (`ts >= w.start AND ts < w.end`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranges: Vec<JoinRange>,
+}
+
+impl JoinCondition {
+    /// Single-column equi-join.
+    pub fn eq(left_field: Path, right_field: Path) -> Self {
+        Self {
+            left_field,
+            right_field,
+            keys: vec![],
+            ranges: vec![],
+        }
+    }
+
+    /// All equality pairs `(left, right)`, primary first.
+    pub fn equalities(&self) -> Vec<(&Path, &Path)> {
+        let primary = (!self.left_field.is_empty() && !self.right_field.is_empty())
+            .then_some((&self.left_field, &self.right_field));
+        primary
+            .into_iter()
+            .chain(self.keys.iter().map(|k| (&k.left, &k.right)))
+            .collect()
+    }
+}
+
+/// One equality of a composite join key: `left.left = right.right`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JoinKey {
+    pub left: Path,
+    pub right: Path,
+}
+
+/// A range comparison `left.left <op> right.right`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JoinRange {
+    pub left: Path,
+    pub op: RangeOp,
+    pub right: Path,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RangeOp {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
 }
 
 /// Sort specification for ORDER BY.
@@ -59,6 +154,35 @@ pub struct JoinCondition {
 pub struct OrderSpec {
     pub field: Path,
     pub direction: String,
+    /// `"FIRST"` or `"LAST"` (NULLS FIRST / NULLS LAST). When absent, null
+    /// sorts as the smallest value: first ascending, last descending.
+    #[serde(default)]
+    pub nulls: Option<String>,
+    /// Computed sort key (`ORDER BY string::lowercase(name)`); `field` then
+    /// only names the column.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expr: Option<Expr>,
+}
+
+impl OrderSpec {
+    pub fn is_descending(&self) -> bool {
+        self.direction.eq_ignore_ascii_case("DESC")
+    }
+
+    pub fn nulls_first(&self) -> bool {
+        match &self.nulls {
+            Some(nulls) => nulls.eq_ignore_ascii_case("FIRST"),
+            None => !self.is_descending(),
+        }
+    }
+
+    /// The sort key of `row`.
+    pub fn sort_value(&self, row: Option<&Sp00kyValue>, params: Option<&Sp00kyValue>) -> Sp00kyValue {
+        match &self.expr {
+            Some(expr) => expr.eval(row, params),
+            None => resolve_field(row, &self.field).cloned().unwrap_or_default(),
+        }
+    }
 }
 
 /// Foreign key linking a subquery's child records to their parent.
@@ -82,12 +206,57 @@ pub enum Projection {
         #[serde(default)]
         alias: Option<String>,
     },
+    /// Computed column (`price * qty AS total`).
+    Expr {
+        expr: Expr,
+        alias: String,
+    },
     Subquery {
         alias: String,
         plan: Box<OperatorPlan>,
         #[serde(default)]
         parent_key: Option<SubqueryParentKey>,
     },
+    /// Records reached over `RELATE` edges (`->follows->user`), reported
+    /// like subquery records.
+    Graph {
+        alias: String,
+        steps: Vec<GraphStep>,
+    },
+    /// The record a field links to (`author.*`), reported like subquery
+    /// records. The target table is read from the link itself.
+    Link {
+        alias: String,
+        field: Path,
+    },
+}
+
+/// Direction of a graph hop: `->edge->table` follows edges out of the
+/// record (`in` → `out`), `<-edge<-table` follows them back.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphDirection {
+    Out,
+    In,
+}
+
+/// One hop of a graph traversal: across `edge` records to `table`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GraphStep {
+    pub direction: GraphDirection,
+    pub edge: String,
+    pub table: String,
+}
+
+impl GraphStep {
+    /// Edge fields holding the record a hop starts from and the one it
+    /// reaches.
+    pub fn endpoints(&self) -> (&'static str, &'static str) {
+        match self.direction {
+            GraphDirection::Out => ("in", "out"),
+            GraphDirection::In => ("out", "in"),
+        }
+    }
 }
 
 impl OperatorPlan {
@@ -103,7 +272,10 @@ impl OperatorPlan {
     fn collect_tables(&self, tables: &mut Vec<String>) {
         match self {
             OperatorPlan::Scan { table } => tables.push(table.clone()),
-            OperatorPlan::Filter { input, .. } | OperatorPlan::Limit { input, .. } => {
+            OperatorPlan::Filter { input, .. }
+            | OperatorPlan::Limit { input, .. }
+            | OperatorPlan::Aggregate { input, .. }
+            | OperatorPlan::Distinct { input, .. } => {
                 input.collect_tables(tables);
             }
             OperatorPlan::Project { input, projections } => {
@@ -111,7 +283,11 @@ impl OperatorPlan {
                 for proj in projections {
                     match proj {
                         Projection::Subquery { plan, .. } => plan.collect_tables(tables),
-                        Projection::All | Projection::Field { .. } => {}
+                        Projection::Graph { steps, .. } => collect_step_tables(steps, tables),
+                        Projection::All
+                        | Projection::Field { .. }
+                        | Projection::Expr { .. }
+                        | Projection::Link { .. } => {}
                     }
                 }
             }
@@ -148,7 +324,10 @@ impl OperatorPlan {
     ) {
         match self {
             OperatorPlan::Scan { .. } => {}
-            OperatorPlan::Filter { input, .. } | OperatorPlan::Limit { input, .. } => {
+            OperatorPlan::Filter { input, .. }
+            | OperatorPlan::Limit { input, .. }
+            | OperatorPlan::Aggregate { input, .. }
+            | OperatorPlan::Distinct { input, .. } => {
                 input.collect_subquery_projection_info(result, parent_table);
             }
             OperatorPlan::Project { input, projections } => {
@@ -181,18 +360,71 @@ impl OperatorPlan {
         }
     }
 
+    /// The plans of the subquery projections, nested ones included, with
+    /// their aliases.
+    pub fn subquery_plans(&self) -> Vec<(&str, &OperatorPlan)> {
+        match self {
+            OperatorPlan::Scan { .. } => vec![],
+            OperatorPlan::Filter { input, .. }
+            | OperatorPlan::Limit { input, .. }
+            | OperatorPlan::Aggregate { input, .. }
+            | OperatorPlan::Distinct { input, .. } => input.subquery_plans(),
+            OperatorPlan::Project { input, projections } => {
+                let mut result = input.subquery_plans();
+                for proj in projections {
+                    if let Projection::Subquery { alias, plan, .. } = proj {
+                        result.push((alias.as_str(), plan.as_ref()));
+                        result.extend(plan.subquery_plans());
+                    }
+                }
+                result
+            }
+            OperatorPlan::Join { left, right, .. } => {
+                let mut result = left.subquery_plans();
+                result.extend(right.subquery_plans());
+                result
+            }
+        }
+    }
+
+    /// Projections of the top-level SELECT (not those of subquery plans).
+    pub fn root_projections(&self) -> Vec<&Projection> {
+        match self {
+            OperatorPlan::Scan { .. } => vec![],
+            OperatorPlan::Filter { input, .. }
+            | OperatorPlan::Limit { input, .. }
+            | OperatorPlan::Aggregate { input, .. }
+            | OperatorPlan::Distinct { input, .. } => input.root_projections(),
+            OperatorPlan::Project { input, projections } => {
+                let mut result = input.root_projections();
+                result.extend(projections);
+                result
+            }
+            OperatorPlan::Join { left, right, .. } => {
+                let mut result = left.root_projections();
+                result.extend(right.root_projections());
+                result
+            }
+        }
+    }
+
     fn collect_subquery_tables(&self, tables: &mut Vec<String>) {
         match self {
             OperatorPlan::Scan { .. } => {}
-            OperatorPlan::Filter { input, .. } | OperatorPlan::Limit { input, .. } => {
+            OperatorPlan::Filter { input, .. }
+            | OperatorPlan::Limit { input, .. }
+            | OperatorPlan::Aggregate { input, .. }
+            | OperatorPlan::Distinct { input, .. } => {
                 input.collect_subquery_tables(tables);
             }
             OperatorPlan::Project { input, projections } => {
                 input.collect_subquery_tables(tables);
                 for proj in projections {
-                    if let Projection::Subquery { plan, .. } = proj {
+                    match proj {
                         // Collect ALL tables referenced within the subquery plan
-                        plan.collect_tables(tables);
+                        Projection::Subquery { plan, .. } => plan.collect_tables(tables),
+                        Projection::Graph { steps, .. } => collect_step_tables(steps, tables),
+                        _ => {}
                     }
                 }
             }
@@ -203,3 +435,10 @@ impl OperatorPlan {
         }
     }
 }
+
+fn collect_step_tables(steps: &[GraphStep], tables: &mut Vec<String>) {
+    for step in steps {
+        tables.push(step.edge.clone());
+        tables.push(step.table.clone());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::eval::Expr;
 use crate::types::Path;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -13,6 +14,77 @@ pub enum Predicate {
     Lt { field: Path, value: Value },
     Lte { field: Path, value: Value },
     Prefix { field: Path, prefix: String },
+    /// `field IN value` / `field INSIDE value`: the field's value is an
+    /// element of the `value` array. An array field is matched as a whole
+    /// element, not element by element (that is `ALLINSIDE`).
+    Inside { field: Path, value: Value },
+    /// `field CONTAINS value`: the field array has `value` as an element,
+    /// or the field string has it as a substring.
+    Contains { field: Path, value: Value },
+    /// `field CONTAINSANY [..]`: at least one of the values is contained.
+    ContainsAny { field: Path, value: Value },
+    /// `field CONTAINSALL [..]`: every one of the values is contained.
+    ContainsAll { field: Path, value: Value },
+    /// `field IS NONE` / `field IS NULL`: the field is missing or null.
+    IsNone { field: Path },
+    /// `field ~ value`: case-insensitive substring match (fuzzy equals).
+    Fuzzy { field: Path, value: Value },
+    /// `field = /pattern/`: the field string matches the regular expression.
+    Regex { field: Path, pattern: String },
+    /// `string::lowercase(field) ...` / `string::uppercase(field) ...`:
+    /// `predicate` is evaluated with string field values case-folded.
+    Fold { case: CaseFold, predicate: Box<Predicate> },
+    /// Any other condition (`string::len(title) > 3`, `price * qty >= 100`):
+    /// holds when the expression is truthy.
+    Expr { expr: Expr },
     And { predicates: Vec<Predicate> },
     Or { predicates: Vec<Predicate> },
+    Not { predicate: Box<Predicate> },
+}
+
+/// Case folding applied by `Predicate::Fold`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseFold {
+    Lower,
+    Upper,
+}
+
+impl CaseFold {
+    pub fn apply(self, s: &str) -> String {
+        match self {
+            CaseFold::Lower => s.to_lowercase(),
+            CaseFold::Upper => s.to_uppercase(),
+        }
+    }
+}
+
+impl Predicate {
+    /// Every regex pattern used in this predicate tree.
+    pub fn regex_patterns(&self) -> Vec<&str> {
+        match self {
+            Predicate::Regex { pattern, .. } => vec![pattern.as_str()],
+            Predicate::And { predicates } | Predicate::Or { predicates } => {
+                predicates.iter().flat_map(|p| p.regex_patterns()).collect()
+            }
+            Predicate::Not { predicate } | Predicate::Fold { predicate, .. } => {
+                predicate.regex_patterns()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Whether the outcome depends on the clock (`time::now()`).
+    pub fn uses_clock(&self) -> bool {
+        match self {
+            Predicate::Expr { expr } => expr.uses_clock(),
+            Predicate::And { predicates } | Predicate::Or { predicates } => {
+                predicates.iter().any(Predicate::uses_clock)
+            }
+            Predicate::Not { predicate } | Predicate::Fold { predicate, .. } => {
+                predicate.uses_clock()
+            }
+            _ => false,
+        }
+    }
 }
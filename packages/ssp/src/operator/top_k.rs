@@ -1,108 +1,223 @@
-use crate::algebra::ZSet;
+use crate::algebra::{RowKey, ZSet};
 use crate::circuit::store::Store;
-use crate::eval::value_ops::resolve_field;
+use crate::eval::value_ops::sort_cmp;
 use crate::operator::plan::OrderSpec;
 use crate::types::Sp00kyValue;
-use std::collections::{BTreeSet, HashMap};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 /// TopK operator with sorted buffer state (Z⁻¹).
 ///
 /// Maintains a sorted buffer of all input records. On each delta:
 ///   1. Insert/remove records from the buffer
-///   2. Compute which records enter/leave the top K
+///   2. Compute which records enter/leave the window `[offset, offset + K)`
 ///   3. Emit +1 for new entrants, -1 for displaced records
+///
+/// With an offset, inserting or deleting a row *before* the window shifts
+/// rows across both of its edges. Without a limit (`START` alone) the
+/// window is everything after the first `offset` rows, and only those
+/// leading rows are walked per step.
 #[derive(Debug)]
 pub struct TopK {
-    pub limit: usize,
+    /// Window size; `None` for an unbounded window.
+    pub limit: Option<usize>,
     pub order_by: Option<Vec<OrderSpec>>,
+    /// Number of leading rows skipped before the window starts.
+    pub offset: usize,
     /// All records seen so far, sorted. Each entry is (sort_key_parts, row_key).
     /// Using BTreeSet for automatic sorted order.
-    buffer: BTreeSet<(Vec<SortableValue>, String)>,
+    buffer: BTreeSet<(Vec<SortableValue>, RowKey)>,
     /// Reverse index: row_key → sort key parts (for removal)
-    key_index: HashMap<String, Vec<SortableValue>>,
+    key_index: HashMap<RowKey, Vec<SortableValue>>,
 }
 
-/// A value that implements Ord for sorting purposes.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum SortableValue {
-    Null,
-    Bool(bool),
-    Int(i64),
-    Str(String),
+/// One ORDER BY column of a row's sort key.
+///
+/// Each part carries its column's direction and null placement, so the
+/// buffer's ordering is the full comparator (mixed directions included)
+/// and values are compared exactly via `sort_cmp`.
+#[derive(Debug, Clone)]
+pub struct SortableValue {
+    value: Sp00kyValue,
+    descending: bool,
+    nulls_first: bool,
 }
 
 impl SortableValue {
-    fn from_sp00ky(val: Option<&Sp00kyValue>, descending: bool) -> Self {
-        let sv = match val {
-            None | Some(Sp00kyValue::Null) => SortableValue::Null,
-            Some(Sp00kyValue::Bool(b)) => SortableValue::Bool(*b),
-            Some(v) if v.as_f64().is_some() => {
-                // Use integer representation for consistent ordering
-                SortableValue::Int((v.as_f64().unwrap() * 1_000_000.0) as i64)
-            }
-            Some(Sp00kyValue::Str(s)) => SortableValue::Str(s.clone()),
-            _ => SortableValue::Null,
-        };
-        if descending {
-            // For descending, negate the sort key
-            match sv {
-                SortableValue::Int(n) => SortableValue::Int(-n),
-                SortableValue::Bool(b) => SortableValue::Bool(!b),
-                other => other, // Strings: rely on reverse iteration
+    fn new(value: Sp00kyValue, spec: &OrderSpec) -> Self {
+        Self {
+            value,
+            descending: spec.is_descending(),
+            nulls_first: spec.nulls_first(),
+        }
+    }
+
+    fn ascending(value: Sp00kyValue) -> Self {
+        Self {
+            value,
+            descending: false,
+            nulls_first: true,
+        }
+    }
+}
+
+impl Ord for SortableValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.value.is_null(), other.value.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if self.nulls_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if self.nulls_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => {
+                let ord = sort_cmp(&self.value, &other.value);
+                if self.descending {
+                    ord.reverse()
+                } else {
+                    ord
+                }
             }
-        } else {
-            sv
         }
     }
 }
 
+impl PartialOrd for SortableValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortableValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SortableValue {}
+
 impl TopK {
     pub fn new(limit: usize, order_by: Option<Vec<OrderSpec>>) -> Self {
         Self {
-            limit,
+            limit: Some(limit),
             order_by,
+            offset: 0,
             buffer: BTreeSet::new(),
             key_index: HashMap::new(),
         }
     }
 
-    fn compute_sort_key(&self, key: &str, store: &Store) -> Vec<SortableValue> {
+    /// Every row after the offset (`START` without `LIMIT`).
+    pub fn unbounded(order_by: Option<Vec<OrderSpec>>) -> Self {
+        Self {
+            limit: None,
+            ..Self::new(0, order_by)
+        }
+    }
+
+    /// Skip the first `offset` rows (paginated views: `LIMIT n START m`).
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    fn compute_sort_key(
+        &self,
+        key: &str,
+        store: &Store,
+        ctx: Option<&Sp00kyValue>,
+    ) -> Vec<SortableValue> {
         let row = store.get_row_by_key(key);
         match &self.order_by {
             Some(orders) => orders
                 .iter()
-                .map(|ord| {
-                    let val = row.and_then(|r| resolve_field(Some(r), &ord.field));
-                    let desc = ord.direction.eq_ignore_ascii_case("DESC");
-                    SortableValue::from_sp00ky(val, desc)
-                })
+                .map(|ord| SortableValue::new(ord.sort_value(row, ctx), ord))
                 .collect(),
-            None => vec![SortableValue::Str(key.to_string())],
+            None => vec![SortableValue::ascending(Sp00kyValue::Str(key.into()))],
         }
     }
 
-    fn current_top_k(&self) -> Vec<String> {
+    fn current_top_k(&self) -> Vec<RowKey> {
         self.buffer
             .iter()
-            .take(self.limit)
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
             .map(|(_, key)| key.clone())
             .collect()
     }
+
+    /// The skipped rows before the window.
+    fn head(&self) -> HashSet<RowKey> {
+        self.buffer.iter().take(self.offset).map(|(_, key)| key.clone()).collect()
+    }
+
+    /// Insert and remove the delta's rows in the buffer. Returns the keys
+    /// touched, each with whether it was buffered before.
+    fn apply_delta(
+        &mut self,
+        delta: &ZSet,
+        store: &Store,
+        ctx: Option<&Sp00kyValue>,
+    ) -> Vec<(RowKey, bool)> {
+        let mut touched = Vec::with_capacity(delta.len());
+        for (key, &weight) in delta {
+            let was_buffered = self.key_index.contains_key(key);
+            if weight > 0 {
+                let sort_key = self.compute_sort_key(key, store, ctx);
+                self.buffer.insert((sort_key.clone(), key.clone()));
+                self.key_index.insert(key.clone(), sort_key);
+            } else if weight < 0 {
+                if let Some(sort_key) = self.key_index.remove(key) {
+                    self.buffer.remove(&(sort_key, key.clone()));
+                }
+            } else {
+                continue;
+            }
+            touched.push((key.clone(), was_buffered));
+        }
+        touched
+    }
+
+    /// Step an unbounded window: it is the buffer minus its head, so only
+    /// touched rows and rows crossing the head's edge can change.
+    fn step_unbounded(&mut self, delta: &ZSet, store: &Store, ctx: Option<&Sp00kyValue>) -> ZSet {
+        let old_head = self.head();
+        let touched = self.apply_delta(delta, store, ctx);
+        let new_head = self.head();
+
+        let mut output_delta = HashMap::new();
+        let mut emit = |key: &RowKey, before: bool, after: bool| {
+            if before != after {
+                output_delta.insert(key.clone(), if after { 1 } else { -1 });
+            }
+        };
+        for (key, was_buffered) in &touched {
+            let before = *was_buffered && !old_head.contains(key);
+            let after = self.key_index.contains_key(key) && !new_head.contains(key);
+            emit(key, before, after);
+        }
+        let touched: HashSet<&RowKey> = touched.iter().map(|(key, _)| key).collect();
+        for key in old_head.symmetric_difference(&new_head) {
+            if !touched.contains(key) {
+                emit(key, !old_head.contains(key), !new_head.contains(key));
+            }
+        }
+        output_delta
+    }
 }
 
 impl super::Operator for TopK {
-    fn snapshot(&self, inputs: &[&ZSet], store: &Store, _ctx: Option<&Sp00kyValue>) -> ZSet {
+    fn snapshot(&self, inputs: &[&ZSet], store: &Store, ctx: Option<&Sp00kyValue>) -> ZSet {
         let upstream = inputs[0];
-        let mut items: Vec<(Vec<SortableValue>, &String)> = upstream
+        let mut items: Vec<(Vec<SortableValue>, &RowKey)> = upstream
             .iter()
             .filter(|(_, &w)| w > 0)
-            .map(|(key, _)| (self.compute_sort_key(key, store), key))
+            .map(|(key, _)| (self.compute_sort_key(key, store, ctx), key))
             .collect();
 
         items.sort();
 
         let mut out = HashMap::new();
-        for (_, key) in items.into_iter().take(self.limit) {
+        for (_, key) in items.into_iter().skip(self.offset).take(self.limit.unwrap_or(usize::MAX)) {
             out.insert(key.clone(), 1);
         }
         out
@@ -112,29 +227,21 @@ impl super::Operator for TopK {
         &mut self,
         input_deltas: &[&ZSet],
         store: &Store,
-        _ctx: Option<&Sp00kyValue>,
+        ctx: Option<&Sp00kyValue>,
     ) -> ZSet {
         let upstream_delta = input_deltas[0];
-        let old_top_k = self.current_top_k();
-
-        for (key, &weight) in upstream_delta {
-            if weight > 0 {
-                let sort_key = self.compute_sort_key(key, store);
-                self.buffer.insert((sort_key.clone(), key.clone()));
-                self.key_index.insert(key.clone(), sort_key);
-            } else if weight < 0 {
-                if let Some(sort_key) = self.key_index.remove(key) {
-                    self.buffer.remove(&(sort_key, key.clone()));
-                }
-            }
+        if self.limit.is_none() {
+            return self.step_unbounded(upstream_delta, store, ctx);
         }
+        let old_top_k = self.current_top_k();
+        self.apply_delta(upstream_delta, store, ctx);
 
         let new_top_k = self.current_top_k();
 
         // Compute displacement delta
         let mut output_delta = HashMap::new();
-        let old_set: std::collections::HashSet<&String> = old_top_k.iter().collect();
-        let new_set: std::collections::HashSet<&String> = new_top_k.iter().collect();
+        let old_set: HashSet<&RowKey> = old_top_k.iter().collect();
+        let new_set: HashSet<&RowKey> = new_top_k.iter().collect();
 
         for key in &new_top_k {
             if !old_set.contains(key) {
@@ -155,6 +262,10 @@ impl super::Operator for TopK {
         1
     }
 
+    fn state_sizes(&self) -> Vec<(&'static str, usize)> {
+        vec![("buffer", self.buffer.len())]
+    }
+
     fn reset(&mut self) {
         self.buffer.clear();
         self.key_index.clear();
@@ -171,7 +282,7 @@ mod tests {
     use serde_json::json;
 
     fn zset(items: &[(&str, i64)]) -> ZSet {
-        items.iter().map(|(k, w)| (k.to_string(), *w)).collect()
+        items.iter().map(|(k, w)| ((*k).into(), *w)).collect()
     }
 
     #[test]
@@ -187,6 +298,8 @@ mod tests {
             Some(vec![OrderSpec {
                 field: Path::new("score"),
                 direction: "DESC".into(),
+                nulls: None,
+                expr: None,
             }]),
         );
         let input = zset(&[("posts:1", 1), ("posts:2", 1), ("posts:3", 1)]);
@@ -209,6 +322,8 @@ mod tests {
             Some(vec![OrderSpec {
                 field: Path::new("score"),
                 direction: "DESC".into(),
+                nulls: None,
+                expr: None,
             }]),
         );
 
@@ -237,6 +352,8 @@ mod tests {
             Some(vec![OrderSpec {
                 field: Path::new("score"),
                 direction: "DESC".into(),
+                nulls: None,
+                expr: None,
             }]),
         );
         let d1 = zset(&[("posts:1", 1), ("posts:2", 1)]);
@@ -249,4 +366,126 @@ mod tests {
 
         assert!(result.is_empty());
     }
+
+    #[test]
+    fn offset_window_shifts_when_earlier_row_changes() {
+        let mut store = Store::new();
+        for (id, score) in [("post:1", 40), ("post:2", 30), ("post:3", 20), ("post:4", 10)] {
+            store.apply_change(&Change::create("posts", id, json!({ "score": score })));
+        }
+
+        // Page 2 with page size 2: ranks 3 and 4.
+        let mut top_k = TopK::new(
+            2,
+            Some(vec![OrderSpec {
+                field: Path::new("score"),
+                direction: "DESC".into(),
+                nulls: None,
+                expr: None,
+            }]),
+        )
+        .with_offset(2);
+        let all = zset(&[("posts:1", 1), ("posts:2", 1), ("posts:3", 1), ("posts:4", 1)]);
+        let result = top_k.step(&[&all], &store, None);
+        assert_eq!(result, zset(&[("posts:3", 1), ("posts:4", 1)]));
+
+        // A new leader pushes post:2 onto page 2 and post:4 off it.
+        store.apply_change(&Change::create("posts", "post:5", json!({"score": 50})));
+        let result = top_k.step(&[&zset(&[("posts:5", 1)])], &store, None);
+        assert_eq!(result, zset(&[("posts:2", 1), ("posts:4", -1)]));
+
+        // Deleting a page-1 row pulls post:4 back and post:2 forward.
+        let result = top_k.step(&[&zset(&[("posts:1", -1)])], &store, None);
+        assert_eq!(result, zset(&[("posts:2", -1), ("posts:4", 1)]));
+    }
+
+    #[test]
+    fn offset_without_limit_emits_rows_after_the_offset() {
+        let mut store = Store::new();
+        for (id, score) in [("post:1", 40), ("post:2", 30), ("post:3", 20)] {
+            store.apply_change(&Change::create("posts", id, json!({ "score": score })));
+        }
+
+        let mut top_k = TopK::unbounded(Some(vec![order("score", "DESC", None)])).with_offset(1);
+        let all = zset(&[("posts:1", 1), ("posts:2", 1), ("posts:3", 1)]);
+        assert_eq!(top_k.step(&[&all], &store, None), zset(&[("posts:2", 1), ("posts:3", 1)]));
+
+        // A new leader pushes post:1 out of the skipped head into the window.
+        store.apply_change(&Change::create("posts", "post:4", json!({"score": 50})));
+        let result = top_k.step(&[&zset(&[("posts:4", 1)])], &store, None);
+        assert_eq!(result, zset(&[("posts:1", 1)]));
+
+        // A row past the head enters and leaves on its own.
+        store.apply_change(&Change::create("posts", "post:5", json!({"score": 5})));
+        assert_eq!(top_k.step(&[&zset(&[("posts:5", 1)])], &store, None), zset(&[("posts:5", 1)]));
+        assert_eq!(top_k.step(&[&zset(&[("posts:5", -1)])], &store, None), zset(&[("posts:5", -1)]));
+
+        // Deleting the head pulls post:1 back into it.
+        let result = top_k.step(&[&zset(&[("posts:4", -1)])], &store, None);
+        assert_eq!(result, zset(&[("posts:1", -1)]));
+    }
+
+    fn order(field: &str, direction: &str, nulls: Option<&str>) -> OrderSpec {
+        OrderSpec {
+            field: Path::new(field),
+            direction: direction.into(),
+            nulls: nulls.map(String::from),
+            expr: None,
+        }
+    }
+
+    fn ordered_keys(top_k: &TopK) -> Vec<String> {
+        top_k.current_top_k().into_iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn mixed_directions_sort_strings_descending() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("user", "user:1", json!({"name": "ann", "created_at": 2})));
+        store.apply_change(&Change::create("user", "user:2", json!({"name": "bob", "created_at": 1})));
+        store.apply_change(&Change::create("user", "user:3", json!({"name": "ann", "created_at": 1})));
+
+        let mut top_k = TopK::new(
+            10,
+            Some(vec![order("name", "DESC", None), order("created_at", "ASC", None)]),
+        );
+        let _ = top_k.step(&[&zset(&[("user:1", 1), ("user:2", 1), ("user:3", 1)])], &store, None);
+
+        assert_eq!(ordered_keys(&top_k), vec!["user:2", "user:3", "user:1"]);
+    }
+
+    #[test]
+    fn floats_order_exactly() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("m", "m:1", json!({"v": 0.0000002})));
+        store.apply_change(&Change::create("m", "m:2", json!({"v": 0.0000001})));
+        store.apply_change(&Change::create("m", "m:3", json!({"v": 1e300})));
+        store.apply_change(&Change::create("m", "m:4", json!({"v": 5})));
+
+        let mut top_k = TopK::new(10, Some(vec![order("v", "ASC", None)]));
+        let _ = top_k.step(&[&zset(&[("m:1", 1), ("m:2", 1), ("m:3", 1), ("m:4", 1)])], &store, None);
+
+        assert_eq!(ordered_keys(&top_k), vec!["m:2", "m:1", "m:4", "m:3"]);
+    }
+
+    #[test]
+    fn nulls_placement_follows_spec() {
+        let mut store = Store::new();
+        store.apply_change(&Change::create("t", "t:1", json!({"due": 3})));
+        store.apply_change(&Change::create("t", "t:2", json!({})));
+        store.apply_change(&Change::create("t", "t:3", json!({"due": 1})));
+        let all = zset(&[("t:1", 1), ("t:2", 1), ("t:3", 1)]);
+
+        let mut asc = TopK::new(10, Some(vec![order("due", "ASC", None)]));
+        let _ = asc.step(&[&all], &store, None);
+        assert_eq!(ordered_keys(&asc), vec!["t:2", "t:3", "t:1"]);
+
+        let mut asc_last = TopK::new(10, Some(vec![order("due", "ASC", Some("LAST"))]));
+        let _ = asc_last.step(&[&all], &store, None);
+        assert_eq!(ordered_keys(&asc_last), vec!["t:3", "t:1", "t:2"]);
+
+        let mut desc_first = TopK::new(10, Some(vec![order("due", "DESC", Some("FIRST"))]));
+        let _ = desc_first.step(&[&all], &store, None);
+        assert_eq!(ordered_keys(&desc_first), vec!["t:2", "t:1", "t:3"]);
+    }
 }
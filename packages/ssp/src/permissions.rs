@@ -0,0 +1,355 @@
+//! Row-level security: table `select` permissions, evaluated inside the
+//! circuit against the registering user's auth context.
+
+use crate::algebra::{RowKey, ZSet};
+use crate::circuit::store::Store;
+use crate::converter;
+use crate::operator::plan::{OperatorPlan, Projection};
+use crate::operator::predicate::Predicate;
+use crate::operator::{Filter, Operator};
+use crate::types::Sp00kyValue;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Prefix of the parameters permission rules read. Rule parameters are
+/// renamed into it (`$auth.id` → `_00_auth.id`), so they are only bound by
+/// the trusted auth context (see `bind_auth`), never by client params.
+pub const RULE_PARAM_PREFIX: &str = "_00_";
+
+/// Who may select a table's rows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SelectRule {
+    /// Every row.
+    Full,
+    /// No row.
+    None,
+    /// Rows matching the predicate, evaluated with the view's parameters.
+    Where(Predicate),
+}
+
+/// `select` permissions by table, from `DEFINE TABLE ... PERMISSIONS`.
+///
+/// Tables without a definition are not restricted. Saved with the circuit's
+/// state, as its rules.
+#[derive(Debug, Default)]
+pub struct Permissions {
+    rules: HashMap<String, SelectRule>,
+    /// Compiled `Where` rules, for checking single rows.
+    filters: HashMap<String, Filter>,
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rule(&self, table: &str) -> Option<&SelectRule> {
+        self.rules.get(table)
+    }
+
+    /// Set a table's rule.
+    pub fn set(&mut self, table: &str, rule: SelectRule) {
+        self.filters.remove(table);
+        if let SelectRule::Where(predicate) = &rule {
+            self.filters.insert(table.to_string(), Filter::new(predicate.clone()));
+        }
+        self.rules.insert(table.to_string(), rule);
+    }
+
+    /// Set a table's rule to `WHERE condition`, the condition as written
+    /// in its `DEFINE TABLE ... PERMISSIONS FOR select WHERE` clause.
+    ///
+    /// A condition the circuit cannot evaluate (e.g. one with a subquery)
+    /// denies every row, and the error says why.
+    pub fn define(&mut self, table: &str, condition: &str) -> Result<()> {
+        match compile_condition(condition) {
+            Ok(rule) => {
+                self.set(table, rule);
+                Ok(())
+            }
+            Err(e) => {
+                self.set(table, SelectRule::None);
+                Err(anyhow!("select permission of table {} denies every row: {}", table, e))
+            }
+        }
+    }
+
+    /// Restrict every table a plan reads to the rows its rule allows, by
+    /// placing the rule as a filter directly above each scan.
+    pub fn secure(&self, plan: &OperatorPlan) -> OperatorPlan {
+        let secure = |input: &OperatorPlan| Box::new(self.secure(input));
+        match plan {
+            OperatorPlan::Scan { table } => match self.rules.get(table) {
+                None | Some(SelectRule::Full) => plan.clone(),
+                Some(SelectRule::None) => OperatorPlan::Filter {
+                    input: Box::new(plan.clone()),
+                    predicate: deny_all(),
+                },
+                Some(SelectRule::Where(predicate)) => OperatorPlan::Filter {
+                    input: Box::new(plan.clone()),
+                    predicate: predicate.clone(),
+                },
+            },
+            OperatorPlan::Filter { input, predicate } => OperatorPlan::Filter {
+                input: secure(input),
+                predicate: predicate.clone(),
+            },
+            OperatorPlan::Join {
+                left,
+                right,
+                on,
+                kind,
+            } => OperatorPlan::Join {
+                left: secure(left),
+                right: secure(right),
+                on: on.clone(),
+                kind: *kind,
+            },
+            OperatorPlan::Project { input, projections } => OperatorPlan::Project {
+                input: secure(input),
+                projections: projections
+                    .iter()
+                    .map(|p| match p {
+                        Projection::Subquery {
+                            alias,
+                            plan,
+                            parent_key,
+                        } => Projection::Subquery {
+                            alias: alias.clone(),
+                            plan: secure(plan),
+                            parent_key: parent_key.clone(),
+                        },
+                        other => other.clone(),
+                    })
+                    .collect(),
+            },
+            OperatorPlan::Limit {
+                input,
+                limit,
+                order_by,
+                offset,
+            } => OperatorPlan::Limit {
+                input: secure(input),
+                limit: *limit,
+                order_by: order_by.clone(),
+                offset: *offset,
+            },
+            OperatorPlan::Aggregate {
+                input,
+                group_by,
+                aggregates,
+            } => OperatorPlan::Aggregate {
+                input: secure(input),
+                group_by: group_by.clone(),
+                aggregates: aggregates.clone(),
+            },
+            OperatorPlan::Distinct { input, fields } => OperatorPlan::Distinct {
+                input: secure(input),
+                fields: fields.clone(),
+            },
+        }
+    }
+
+    /// Whether the row `key` ("table:id") may be selected with `params`.
+    pub fn allows(&self, store: &Store, key: &str, params: Option<&Sp00kyValue>) -> bool {
+        if !is_record_user(params) {
+            return true;
+        }
+        let table = key.split_once(':').map_or(key, |(table, _)| table);
+        match self.rules.get(table) {
+            None | Some(SelectRule::Full) => true,
+            Some(SelectRule::None) => false,
+            Some(SelectRule::Where(_)) => {
+                let row = ZSet::from([(RowKey::from(key), 1)]);
+                !self.filters[table].snapshot(&[&row], store, params).is_empty()
+            }
+        }
+    }
+}
+
+impl Serialize for Permissions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rules.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Permissions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rules = HashMap::<String, SelectRule>::deserialize(deserializer)?;
+        let mut permissions = Permissions::new();
+        for (table, rule) in rules {
+            permissions.set(&table, rule);
+        }
+        Ok(permissions)
+    }
+}
+
+/// Bind the registering user's auth context into a view's parameters as
+/// `_00_auth` / `_00_access`, the names permission rules read. Client
+/// parameters in the reserved namespace are dropped.
+///
+/// `auth` may be a record (`{ "id": "user:1", ... }`) or a record id.
+/// Parameters that are not an object cannot hold it and are dropped when
+/// there is an auth context to bind.
+pub fn bind_auth(params: Option<Value>, auth: Option<Value>, access: Option<Value>) -> Option<Value> {
+    let mut params = match params {
+        Some(Value::Object(obj)) => obj,
+        params if auth.is_none() && access.is_none() => return params,
+        _ => Default::default(),
+    };
+    params.retain(|name, _| !name.starts_with(RULE_PARAM_PREFIX));
+    if let Some(auth) = auth.filter(|a| !a.is_null()) {
+        let auth = match auth {
+            Value::String(id) => json!({ "id": id }),
+            other => other,
+        };
+        params.insert(format!("{}auth", RULE_PARAM_PREFIX), auth);
+    }
+    if let Some(access) = access.filter(|a| !a.is_null()) {
+        params.insert(format!("{}access", RULE_PARAM_PREFIX), access);
+    }
+    Some(Value::Object(params))
+}
+
+/// Whether a view's parameters carry a record user's auth context. As in
+/// SurrealDB, permissions only restrict record users; views registered
+/// without one (system users, internal jobs) see every row.
+pub fn is_record_user(params: Option<&Sp00kyValue>) -> bool {
+    params
+        .and_then(|p| p.get(&format!("{}auth", RULE_PARAM_PREFIX)))
+        .is_some()
+}
+
+fn deny_all() -> Predicate {
+    Predicate::Expr {
+        expr: serde_json::from_value(json!({ "type": "value", "value": false }))
+            .expect("literal expression"),
+    }
+}
+
+/// The rule of a `WHERE` condition, its parameters moved into the
+/// reserved namespace.
+fn compile_condition(condition: &str) -> Result<SelectRule> {
+    let mut predicate = converter::convert_condition(condition)?;
+    rename_params(&mut predicate);
+    let predicate: Predicate = serde_json::from_value(predicate)?;
+    Ok(match literal(&predicate) {
+        Some(true) => SelectRule::Full,
+        Some(false) => SelectRule::None,
+        None => SelectRule::Where(predicate),
+    })
+}
+
+/// `WHERE true` / `WHERE false`.
+fn literal(predicate: &Predicate) -> Option<bool> {
+    match predicate {
+        Predicate::Expr { expr } => match serde_json::to_value(expr).ok()? {
+            Value::Object(obj) if obj.get("type") == Some(&json!("value")) => obj.get("value")?.as_bool(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Move every parameter a rule reads into the reserved namespace.
+fn rename_params(value: &mut Value) {
+    match value {
+        Value::Object(obj) => {
+            if let Some(Value::String(name)) = obj.get_mut("$param") {
+                *name = format!("{}{}", RULE_PARAM_PREFIX, name);
+            } else if obj.get("type") == Some(&json!("param")) {
+                if let Some(Value::String(name)) = obj.get_mut("name") {
+                    *name = format!("{}{}", RULE_PARAM_PREFIX, name);
+                }
+            }
+            obj.values_mut().for_each(rename_params);
+        }
+        Value::Array(items) => items.iter_mut().for_each(rename_params),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::store::Change;
+
+    fn rule_of(condition: &str) -> SelectRule {
+        let mut permissions = Permissions::new();
+        permissions.define("a", condition).unwrap();
+        permissions.rule("a").unwrap().clone()
+    }
+
+    #[test]
+    fn select_conditions_compile_to_rules() {
+        let owned = rule_of("owner = $auth.id AND $access = 'account'");
+        let SelectRule::Where(predicate) = owned else {
+            panic!("expected a WHERE rule, got {:?}", owned);
+        };
+        let predicate = serde_json::to_value(predicate).unwrap();
+        assert_eq!(predicate["predicates"][0]["value"], json!({ "$param": "_00_auth.id" }));
+        assert_eq!(predicate["predicates"][1]["expr"]["left"]["name"], "_00_access");
+
+        assert!(matches!(rule_of("true"), SelectRule::Full));
+        assert!(matches!(rule_of("false"), SelectRule::None));
+    }
+
+    #[test]
+    fn rules_that_cannot_be_evaluated_deny_every_row() {
+        let mut permissions = Permissions::new();
+        let err = permissions
+            .define("thread", "$auth.id IN (SELECT VALUE in FROM member)")
+            .unwrap_err();
+        assert!(err.to_string().contains("thread"));
+        assert!(matches!(permissions.rule("thread"), Some(SelectRule::None)));
+    }
+
+    #[test]
+    fn non_object_params_do_not_escape_the_auth_context() {
+        for params in [json!([1]), json!(5), json!("x")] {
+            let bound = bind_auth(Some(params), Some(json!("user:a")), None).map(Sp00kyValue::from);
+            assert!(is_record_user(bound.as_ref()));
+        }
+    }
+
+    #[test]
+    fn rules_survive_serialization() {
+        let mut permissions = Permissions::new();
+        permissions.define("note", "owner = $auth.id").unwrap();
+        permissions.set("secret", SelectRule::None);
+        let restored: Permissions =
+            serde_json::from_value(serde_json::to_value(&permissions).unwrap()).unwrap();
+
+        let mut store = Store::new();
+        store.apply_change(&Change::create("note", "note:1", json!({ "owner": "user:a" })));
+        let as_user = |id: &str| bind_auth(None, Some(json!(id)), None).map(Sp00kyValue::from);
+        assert!(restored.allows(&store, "note:1", as_user("user:a").as_ref()));
+        assert!(!restored.allows(&store, "note:1", as_user("user:b").as_ref()));
+        assert!(matches!(restored.rule("secret"), Some(SelectRule::None)));
+    }
+
+    #[test]
+    fn rows_are_checked_against_the_bound_auth_context() {
+        let mut permissions = Permissions::new();
+        permissions.define("note", "owner = $auth.id").unwrap();
+        let mut store = Store::new();
+        store.apply_change(&Change::create("note", "note:1", json!({ "owner": "user:a" })));
+
+        let params = |auth: Option<Value>| {
+            bind_auth(Some(json!({ "_00_auth": { "id": "user:a" } })), auth, None).map(Sp00kyValue::from)
+        };
+        // Client params cannot claim an identity.
+        assert!(!is_record_user(params(None).as_ref()));
+        assert!(permissions.allows(&store, "note:1", params(Some(json!("user:a"))).as_ref()));
+        assert!(!permissions.allows(&store, "note:1", params(Some(json!({ "id": "user:b" }))).as_ref()));
+        assert!(permissions.allows(&store, "other:1", params(Some(json!("user:b"))).as_ref()));
+        // Views without an auth context are not restricted.
+        assert!(permissions.allows(&store, "note:1", None));
+    }
+}
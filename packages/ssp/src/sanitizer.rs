@@ -294,41 +294,6 @@ pub fn fix_surql_json(s: &str) -> String {
     sanitize_query(s).unwrap_or_else(|_| String::new())
 }
 
-pub fn normalize_record(record: Value) -> Value {
-    match record {
-        Value::String(s) => {
-            if (s.starts_with('{') && s.ends_with('}')) || (s.starts_with('[') && s.ends_with(']'))
-            {
-                if let Ok(parsed) = serde_json::from_str::<Value>(&s) {
-                    return normalize_record(parsed);
-                }
-            }
-            Value::String(s)
-        }
-        Value::Object(map) => {
-            if map.len() == 2 && map.contains_key("tb") && map.contains_key("id") {
-                let tb = map.get("tb").and_then(|v| v.as_str());
-                let id = map.get("id");
-                if let (Some(tb_str), Some(id_val)) = (tb, id) {
-                    let id_str = match id_val {
-                        Value::String(s) => s.clone(),
-                        Value::Number(n) => n.to_string(),
-                        _ => id_val.to_string(),
-                    };
-                    return Value::String(format!("{}:{}", tb_str, id_str));
-                }
-            }
-            let mut new_map = serde_json::Map::new();
-            for (k, v) in map {
-                new_map.insert(k, normalize_record(v));
-            }
-            Value::Object(new_map)
-        }
-        Value::Array(arr) => Value::Array(arr.into_iter().map(normalize_record).collect()),
-        _ => record,
-    }
-}
-
 pub fn parse_params(params: Value) -> Option<Value> {
     let s = match params {
         Value::String(s) => fix_surql_json(&s),
@@ -1,4 +1,4 @@
-use crate::{converter, sanitizer};
+use crate::{converter, permissions, sanitizer};
 use anyhow::{anyhow, Result};
 use serde_json::{json, Value};
 
@@ -11,6 +11,8 @@ pub mod view {
         pub safe_params: Option<Value>,
         pub metadata: Value,
         pub format: Option<crate::circuit::view::OutputFormat>,
+        /// Whether deltas should carry row content (`add_query_with_rows`).
+        pub rows: bool,
     }
 
     /// Prepares a view registration request using DBSP types.
@@ -64,18 +66,40 @@ pub mod view {
                 _ => None,
             });
 
-        let root_op_val = converter::convert_surql_to_dbsp(&surreal_ql)
-            .or_else(|_| {
-                serde_json::from_str::<Value>(&surreal_ql).map_err(anyhow::Error::from)
-            })
-            .map_err(|_| anyhow!("Invalid Query Plan"))?;
+        let rows = config.get("rows").and_then(|v| v.as_bool()).unwrap_or(false);
 
-        let root_op: OperatorPlan = serde_json::from_value(root_op_val)
-            .map_err(|e| anyhow!("Invalid Operator JSON: {}", e))?;
+        // A pre-built JSON plan is accepted as-is; anything else must parse
+        // as SurrealQL, and its diagnostic is returned to the caller.
+        let mut root_op_val = if surreal_ql.trim_start().starts_with('{') {
+            serde_json::from_str::<Value>(&surreal_ql)
+                .map_err(|e| anyhow!("Invalid query plan JSON: {}", e))?
+        } else {
+            converter::convert_surql_to_dbsp(&surreal_ql)
+                .map_err(|e| anyhow!("Invalid query: {}", e))?
+        };
+
+        // Parameters are named; anything else could not carry the auth
+        // context below.
+        let client_params = match sanitizer::parse_params(params.clone()) {
+            None | Some(Value::Null) => None,
+            Some(Value::Object(obj)) => Some(Value::Object(obj)),
+            Some(other) => return Err(anyhow!("Invalid 'params': expected an object, got {}", other)),
+        };
 
-        let safe_params = sanitizer::parse_params(params.clone());
+        // The registering user's identity, set by the database function that
+        // forwards the registration (not by the client), for row-level
+        // security rules.
+        let safe_params = permissions::bind_auth(
+            client_params,
+            config.get("auth").cloned(),
+            config.get("access").cloned(),
+        );
         let safe_params_val = safe_params.clone().unwrap_or(json!({}));
 
+        converter::bind_record_sources(&mut root_op_val, &safe_params_val)?;
+        let root_op: OperatorPlan = serde_json::from_value(root_op_val)
+            .map_err(|e| anyhow!("Invalid Operator JSON: {}", e))?;
+
         let plan = QueryPlan {
             id: id.clone(),
             root: root_op,
@@ -96,6 +120,7 @@ pub mod view {
             safe_params,
             metadata,
             format,
+            rows,
         })
     }
 }
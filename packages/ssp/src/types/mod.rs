@@ -4,4 +4,4 @@ pub mod value;
 
 pub use key::{make_key, parse_key, raw_id};
 pub use path::Path;
-pub use value::Sp00kyValue;
+pub use value::{Sp00kyValue, WireType};
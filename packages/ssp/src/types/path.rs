@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 /// A dot-separated field path for accessing nested values in Sp00kyValue.
 ///
 /// Example: `Path::new("address.city")` represents the path `["address", "city"]`.
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Path(pub Vec<String>);
 
 impl Path {
@@ -1,26 +1,63 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use ssp_protocol::snapshot_hash::{canonical_datetime, canonical_duration};
 use std::collections::HashMap;
+use std::time::Duration;
+use smol_str::SmolStr;
+use uuid::Uuid;
 
 /// Dynamic record value type.
 ///
 /// Represents the data content of a record in a collection.
-/// Uses standard `String` and `HashMap` instead of SmolStr/FxHasher.
+/// Strings and field names are `SmolStr`: short ones (most field names,
+/// ids, enum-like values) are stored inline without a heap allocation, and
+/// clones of longer ones share one buffer.
 ///
 /// Numbers are split into `Int` and `Float` so a JSON `5` round-trips back to
 /// `5` (not `5.0`). Hashing the same row through this type and through
 /// `serde_json::Value` directly must produce identical bytes — see
 /// `ssp_protocol::snapshot_hash::canonical_json` and the SSP/scheduler
-/// integrity check.
+/// integrity check. The SurrealDB types after `Object` therefore convert to
+/// JSON exactly as SurrealDB's SDK writes them (see `From<Sp00kyValue> for
+/// Value`).
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Sp00kyValue {
     Null,
     Bool(bool),
     Int(i64),
     Float(f64),
-    Str(String),
+    Str(SmolStr),
     Array(Vec<Sp00kyValue>),
-    Object(HashMap<String, Sp00kyValue>),
+    Object(HashMap<SmolStr, Sp00kyValue>),
+    // SurrealDB types JSON has no form for.
+    Datetime(DateTime<Utc>),
+    Duration(Duration),
+    /// Exact decimal, not rounded through `f64`.
+    Decimal(Decimal),
+    /// Record link, as its `table:key` text.
+    RecordId(SmolStr),
+    Uuid(Uuid),
+    Bytes(Vec<u8>),
+}
+
+/// SurrealDB types whose JSON `from_wire` cannot tell apart from a plain
+/// string or array. A field declared with one of them in the table schema
+/// is read back as that type (see `Sp00kyValue::from_wire_fields`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireType {
+    /// Written as its digits (`"12.50"`).
+    Decimal,
+    /// Written as SurrealQL duration text (`"1h30m"`).
+    Duration,
+    /// Written as an array of byte values.
+    Bytes,
+    /// Written as RFC 3339 text in UTC (`"2024-02-29T13:45:10Z"`).
+    Datetime,
+    /// Written as hyphenated lowercase text.
+    Uuid,
 }
 
 impl Default for Sp00kyValue {
@@ -30,9 +67,10 @@ impl Default for Sp00kyValue {
 }
 
 impl Sp00kyValue {
+    /// Text of a string, or the `table:key` of a record link.
     pub fn as_str(&self) -> Option<&str> {
         match self {
-            Sp00kyValue::Str(s) => Some(s.as_str()),
+            Sp00kyValue::Str(s) | Sp00kyValue::RecordId(s) => Some(s.as_str()),
             _ => None,
         }
     }
@@ -41,6 +79,7 @@ impl Sp00kyValue {
         match self {
             Sp00kyValue::Int(i) => Some(*i as f64),
             Sp00kyValue::Float(f) => Some(*f),
+            Sp00kyValue::Decimal(d) => d.to_f64(),
             _ => None,
         }
     }
@@ -60,7 +99,7 @@ impl Sp00kyValue {
         }
     }
 
-    pub fn as_object(&self) -> Option<&HashMap<String, Sp00kyValue>> {
+    pub fn as_object(&self) -> Option<&HashMap<SmolStr, Sp00kyValue>> {
         match self {
             Sp00kyValue::Object(map) => Some(map),
             _ => None,
@@ -81,6 +120,133 @@ impl Sp00kyValue {
     pub fn is_null(&self) -> bool {
         matches!(self, Sp00kyValue::Null)
     }
+
+    /// Table of a record link.
+    pub fn record_table(&self) -> Option<&str> {
+        match self {
+            Sp00kyValue::RecordId(id) => id.split_once(':').map(|(table, _)| table),
+            _ => None,
+        }
+    }
+
+    /// Convert JSON as SurrealDB sends it, recovering the types its JSON
+    /// flattens where that is unambiguous: `{ tb, id }` objects become
+    /// record links. Objects or arrays sent as JSON text are parsed.
+    /// Datetimes, UUIDs, decimals and durations arrive as plain strings and
+    /// bytes as arrays; `from_wire_fields` recovers them from the table
+    /// schema, so a `string` field holding datetime text stays a string.
+    pub fn from_wire(value: Value) -> Self {
+        match value {
+            Value::String(s) => {
+                let nested = (s.starts_with('{') && s.ends_with('}'))
+                    || (s.starts_with('[') && s.ends_with(']'));
+                if nested {
+                    if let Ok(parsed) = serde_json::from_str::<Value>(&s) {
+                        return Sp00kyValue::from_wire(parsed);
+                    }
+                }
+                Sp00kyValue::Str(s.into())
+            }
+            Value::Object(obj) => {
+                if obj.len() == 2 {
+                    if let (Some(Value::String(tb)), Some(id)) = (obj.get("tb"), obj.get("id")) {
+                        let id = match id {
+                            Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        };
+                        return Sp00kyValue::RecordId(format!("{tb}:{id}").into());
+                    }
+                }
+                Sp00kyValue::Object(
+                    obj.into_iter()
+                        .map(|(k, v)| (k.into(), Sp00kyValue::from_wire(v)))
+                        .collect(),
+                )
+            }
+            Value::Array(arr) => {
+                Sp00kyValue::Array(arr.into_iter().map(Sp00kyValue::from_wire).collect())
+            }
+            other => Sp00kyValue::from(other),
+        }
+    }
+
+    /// `from_wire` for a record whose top-level `fields` are declared with
+    /// a `WireType`, so it reads them as bootstrapping from SurrealDB
+    /// directly does. A value that would not convert back to the same JSON
+    /// is kept as `from_wire` reads it.
+    pub fn from_wire_fields(value: Value, fields: &HashMap<String, WireType>) -> Self {
+        let mut record = Sp00kyValue::from_wire(value);
+        if let Sp00kyValue::Object(map) = &mut record {
+            for (field, ty) in fields {
+                if let Some(value) = map.get_mut(field.as_str()) {
+                    *value = std::mem::take(value).into_wire_type(*ty);
+                }
+            }
+        }
+        record
+    }
+
+    fn into_wire_type(self, ty: WireType) -> Self {
+        let typed = match (&self, ty) {
+            (Sp00kyValue::Str(s), WireType::Decimal) => s
+                .parse::<Decimal>()
+                .ok()
+                .filter(|d| d.to_string() == s.as_str())
+                .map(Sp00kyValue::Decimal),
+            (Sp00kyValue::Str(s), WireType::Duration) => parse_duration(s)
+                .filter(|d| canonical_duration(*d) == s.as_str())
+                .map(Sp00kyValue::Duration),
+            (Sp00kyValue::Str(s), WireType::Datetime) => DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|t| t.to_utc())
+                .filter(|t| canonical_datetime(t) == s.as_str())
+                .map(Sp00kyValue::Datetime),
+            (Sp00kyValue::Str(s), WireType::Uuid) => Uuid::try_parse(s)
+                .ok()
+                .filter(|u| u.hyphenated().to_string() == s.as_str())
+                .map(Sp00kyValue::Uuid),
+            (Sp00kyValue::Array(items), WireType::Bytes) => items
+                .iter()
+                .map(|item| match item {
+                    Sp00kyValue::Int(b) => u8::try_from(*b).ok(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<u8>>>()
+                .map(Sp00kyValue::Bytes),
+            _ => None,
+        };
+        typed.unwrap_or(self)
+    }
+
+    /// Estimated bytes this value holds on the heap, not counting itself.
+    /// Strings shared between values are counted once per value.
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Sp00kyValue::Str(s) | Sp00kyValue::RecordId(s) => str_heap_size(s),
+            Sp00kyValue::Bytes(bytes) => bytes.capacity(),
+            Sp00kyValue::Array(items) => {
+                items.capacity() * std::mem::size_of::<Sp00kyValue>()
+                    + items.iter().map(Sp00kyValue::heap_size).sum::<usize>()
+            }
+            Sp00kyValue::Object(map) => {
+                map.capacity() * (std::mem::size_of::<(SmolStr, Sp00kyValue)>() + 1)
+                    + map
+                        .iter()
+                        .map(|(k, v)| str_heap_size(k) + v.heap_size())
+                        .sum::<usize>()
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Heap bytes of a `SmolStr`: nothing when stored inline.
+pub(crate) fn str_heap_size(s: &SmolStr) -> usize {
+    if s.is_heap_allocated() {
+        s.len()
+    } else {
+        0
+    }
 }
 
 impl From<Value> for Sp00kyValue {
@@ -96,19 +262,61 @@ impl From<Value> for Sp00kyValue {
                     Sp00kyValue::Float(n.as_f64().unwrap_or(0.0))
                 }
             }
-            Value::String(s) => Sp00kyValue::Str(s),
+            Value::String(s) => Sp00kyValue::Str(s.into()),
             Value::Array(arr) => {
                 Sp00kyValue::Array(arr.into_iter().map(Sp00kyValue::from).collect())
             }
             Value::Object(obj) => Sp00kyValue::Object(
                 obj.into_iter()
-                    .map(|(k, v)| (k, Sp00kyValue::from(v)))
+                    .map(|(k, v)| (k.into(), Sp00kyValue::from(v)))
                     .collect(),
             ),
         }
     }
 }
 
+impl From<Vec<Sp00kyValue>> for Sp00kyValue {
+    fn from(items: Vec<Sp00kyValue>) -> Self {
+        Sp00kyValue::Array(items)
+    }
+}
+
+impl From<String> for Sp00kyValue {
+    fn from(s: String) -> Self {
+        Sp00kyValue::Str(s.into())
+    }
+}
+
+/// Parse duration text in the units `canonical_duration` writes, exactly.
+pub(crate) fn parse_duration(text: &str) -> Option<Duration> {
+    if text.is_empty() {
+        return None;
+    }
+    const UNITS: [(&str, u64); 9] = [
+        ("y", 365 * 86_400_000_000_000),
+        ("w", 7 * 86_400_000_000_000),
+        ("d", 86_400_000_000_000),
+        ("h", 3_600_000_000_000),
+        ("ms", 1_000_000),
+        ("m", 60_000_000_000),
+        ("s", 1_000_000_000),
+        ("µs", 1_000),
+        ("ns", 1),
+    ];
+    let mut rest = text;
+    let mut nanos: u128 = 0;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let amount: u128 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let (unit, size) = UNITS.iter().find(|(unit, _)| rest.starts_with(unit))?;
+        nanos = nanos.checked_add(amount.checked_mul(*size as u128)?)?;
+        rest = &rest[unit.len()..];
+    }
+    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+    Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
 impl From<Sp00kyValue> for Value {
     fn from(val: Sp00kyValue) -> Self {
         match val {
@@ -116,13 +324,100 @@ impl From<Sp00kyValue> for Value {
             Sp00kyValue::Bool(b) => Value::Bool(b),
             Sp00kyValue::Int(i) => json!(i),
             Sp00kyValue::Float(f) => json!(f),
-            Sp00kyValue::Str(s) => Value::String(s),
+            Sp00kyValue::Str(s) => Value::String(s.into()),
             Sp00kyValue::Array(arr) => {
                 Value::Array(arr.into_iter().map(|v| v.into()).collect())
             }
             Sp00kyValue::Object(obj) => {
-                Value::Object(obj.into_iter().map(|(k, v)| (k, v.into())).collect())
+                Value::Object(obj.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
             }
+            Sp00kyValue::Datetime(t) => Value::String(canonical_datetime(&t)),
+            Sp00kyValue::Duration(d) => Value::String(canonical_duration(d)),
+            Sp00kyValue::Decimal(d) => Value::String(d.to_string()),
+            Sp00kyValue::RecordId(id) => Value::String(id.into()),
+            Sp00kyValue::Uuid(u) => Value::String(u.hyphenated().to_string()),
+            Sp00kyValue::Bytes(bytes) => Value::Array(bytes.into_iter().map(Value::from).collect()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_types_round_trip_to_the_same_json() {
+        let wire = json!({
+            "id": "event:1",
+            "at": "2024-02-29T13:45:10.500Z",
+            "token": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "author": { "tb": "user", "id": "ann" },
+            "price": "12.50",
+            "tags": ["a", "2024-01-01T00:00:00Z"]
+        });
+        let value = Sp00kyValue::from_wire(wire.clone());
+
+        assert_eq!(value.get("author"), Some(&Sp00kyValue::RecordId("user:ann".into())));
+        assert_eq!(value.get("author").and_then(Sp00kyValue::record_table), Some("user"));
+        // Without a declared type, strings stay strings whatever they hold.
+        assert!(matches!(value.get("at"), Some(Sp00kyValue::Str(_))));
+        assert!(matches!(value.get("token"), Some(Sp00kyValue::Str(_))));
+        assert!(matches!(value.get("price"), Some(Sp00kyValue::Str(_))));
+
+        let mut expected = wire;
+        expected["author"] = json!("user:ann");
+        assert_eq!(Value::from(value), expected);
+    }
+
+    #[test]
+    fn declared_field_types_are_read_back() {
+        let fields = HashMap::from([
+            ("price".to_string(), WireType::Decimal),
+            ("ttl".to_string(), WireType::Duration),
+            ("blob".to_string(), WireType::Bytes),
+            ("fine".to_string(), WireType::Duration),
+            ("odd".to_string(), WireType::Decimal),
+            ("at".to_string(), WireType::Datetime),
+            ("token".to_string(), WireType::Uuid),
+            ("local".to_string(), WireType::Datetime),
+        ]);
+        let wire = json!({
+            "price": "12.50",
+            "ttl": "1h30m",
+            "blob": [1, 2, 255],
+            "fine": "1m500µs",
+            "odd": "1e3",
+            "plain": "12.50",
+            "at": "2024-02-29T13:45:10.500Z",
+            "token": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "local": "2024-02-29T14:45:10+01:00",
+            "text": "2024-02-29T13:45:10Z"
+        });
+        let value = Sp00kyValue::from_wire_fields(wire.clone(), &fields);
+
+        assert_eq!(value.get("price"), Some(&Sp00kyValue::Decimal("12.50".parse().unwrap())));
+        assert_eq!(value.get("ttl"), Some(&Sp00kyValue::Duration(Duration::from_secs(5400))));
+        assert_eq!(value.get("blob"), Some(&Sp00kyValue::Bytes(vec![1, 2, 255])));
+        assert_eq!(
+            value.get("fine"),
+            Some(&Sp00kyValue::Duration(Duration::from_secs(60) + Duration::from_micros(500)))
+        );
+        assert!(matches!(value.get("at"), Some(Sp00kyValue::Datetime(_))));
+        assert!(matches!(value.get("token"), Some(Sp00kyValue::Uuid(_))));
+        // Not the value's own JSON, or not declared, so kept as written.
+        assert!(matches!(value.get("odd"), Some(Sp00kyValue::Str(_))));
+        assert!(matches!(value.get("plain"), Some(Sp00kyValue::Str(_))));
+        assert!(matches!(value.get("local"), Some(Sp00kyValue::Str(_))));
+        assert!(matches!(value.get("text"), Some(Sp00kyValue::Str(_))));
+        assert_eq!(Value::from(value), wire);
+    }
+
+    #[test]
+    fn typed_values_convert_to_surrealdb_json() {
+        let at = DateTime::parse_from_rfc3339("2024-02-29T13:45:10Z").unwrap().to_utc();
+        assert_eq!(Value::from(Sp00kyValue::Datetime(at)), json!("2024-02-29T13:45:10Z"));
+        assert_eq!(Value::from(Sp00kyValue::Duration(Duration::from_secs(90))), json!("1m30s"));
+        assert_eq!(Value::from(Sp00kyValue::Decimal("12.50".parse().unwrap())), json!("12.50"));
+        assert_eq!(Value::from(Sp00kyValue::Bytes(vec![1, 2])), json!([1, 2]));
+    }
+}
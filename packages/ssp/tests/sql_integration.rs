@@ -12,7 +12,7 @@ fn test_join_deserialization() {
     let op: OperatorPlan = serde_json::from_value(plan_json).expect("Deserialization to OperatorPlan failed!");
 
     // Verify it is a Join
-    if let OperatorPlan::Join { left, right, on } = op {
+    if let OperatorPlan::Join { left, right, on, .. } = op {
         println!("Successfully parsed Join!");
         println!("Left: {:?}", left);
         println!("Right: {:?}", right);
@@ -1,3 +1,4 @@
+use ssp_protocol::view_hash::SetHash;
 use surrealism::surrealism;
 
 /// XOR two BLAKE3 hex strings and return the result as a hex string
@@ -8,28 +9,13 @@ use surrealism::surrealism;
 ///
 /// # Returns
 /// * `Ok(String)` - XOR result as hex string
-/// * `Err(&'static str)` - Error message if decoding fails or lengths don't match
+/// * `Err(&'static str)` - Error message if either hash is not 32 bytes of hex
 #[surrealism]
 fn blake3_xor(hash1: String, hash2: String) -> Result<String, &'static str> {
-    // Decode hex strings to bytes
-    let bytes1 = hex::decode(&hash1)
-        .map_err(|_| "Failed to decode first hash from hex")?;
-    let bytes2 = hex::decode(&hash2)
-        .map_err(|_| "Failed to decode second hash from hex")?;
-
-    // Check if lengths match
-    if bytes1.len() != bytes2.len() {
-        return Err("Hash lengths do not match");
-    }
-
-    // Perform XOR operation
-    let result: Vec<u8> = bytes1.iter()
-        .zip(bytes2.iter())
-        .map(|(a, b)| a ^ b)
-        .collect();
-
-    // Encode result back to hex
-    Ok(hex::encode(result))
+    let mut acc = SetHash::from_hex(&hash1).ok_or("Failed to decode first hash from hex")?;
+    let other = SetHash::from_hex(&hash2).ok_or("Failed to decode second hash from hex")?;
+    acc.xor(&other);
+    Ok(acc.to_bare_hex())
 }
 
 /// XOR a list of BLAKE3 hex strings and return the accumulated result
@@ -39,29 +25,15 @@ fn blake3_xor(hash1: String, hash2: String) -> Result<String, &'static str> {
 ///
 /// # Returns
 /// * `Ok(String)` - Combined XOR result as hex string
-/// * `Err(&'static str)` - Error message if decoding fails, lengths don't match, or list is empty
+/// * `Err(&'static str)` - Error message if a hash is not 32 bytes of hex
 #[surrealism]
 fn blake3_xor_multi(hashes: Vec<String>) -> Result<String, &'static str> {
-    const HASH_SIZE: usize = 32;
-    let mut acc = vec![0u8; HASH_SIZE];
-
+    let mut acc = SetHash::default();
     for hash in hashes {
         if hash.is_empty() {
             continue; // Treat empty strings as identity (zeros)
         }
-
-        let bytes = hex::decode(&hash)
-            .map_err(|_| "Failed to decode hash from hex")?;
-        
-        if bytes.len() != HASH_SIZE {
-            // Optional: We could handle this more gracefully, but for now strict 32 bytes for valid hashes
-            return Err("Hash lengths do not match (expected 32 bytes)");
-        }
-
-        for (i, b) in bytes.iter().enumerate() {
-            acc[i] ^= b;
-        }
+        acc.xor(&SetHash::from_hex(&hash).ok_or("Failed to decode hash from hex (expected 32 bytes)")?);
     }
-
-    Ok(hex::encode(acc))
+    Ok(acc.to_bare_hex())
 }